@@ -0,0 +1,102 @@
+//! What one detection tick costs on this desktop
+//!
+//! `cargo bench` times the queries the engine makes on the foreground window
+//! every 100ms with criterion, then fails if a tick allocates, so a change
+//! that brings back a `String` or a monitor enumeration per tick shows up
+//! here. Run it with a game or a maximized window in front.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, Criterion};
+use smartlockcursor::system::{Win32System, WindowSystem};
+
+/// Ticks the allocation check runs
+const ROUNDS: usize = 10_000;
+const TOLERANCE: i32 = 5;
+
+/// Counts allocations, including growing ones
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// What every tick asks while a window is locked, whatever the config says
+fn tick(system: &Win32System) {
+    let foreground = system.foreground_window();
+    if system.task_switcher(foreground).is_none() {
+        black_box(system.fullscreen_rect(foreground, TOLERANCE));
+        black_box(system.is_minimized(foreground));
+        black_box(system.is_cloaked(foreground));
+        black_box(system.is_on_current_desktop(foreground));
+    }
+    black_box(system.cursor_clip());
+    black_box(system.virtual_screen_rect());
+}
+
+fn queries(c: &mut Criterion) {
+    let system = Win32System::default();
+    let hwnd = system.foreground_window();
+
+    c.bench_function("task_switcher", |b| b.iter(|| system.task_switcher(hwnd)));
+    c.bench_function("fullscreen_rect", |b| {
+        b.iter(|| system.fullscreen_rect(hwnd, TOLERANCE))
+    });
+    c.bench_function("window_monitor_rect", |b| {
+        b.iter(|| system.window_monitor_rect(hwnd))
+    });
+    c.bench_function("hidden checks", |b| {
+        b.iter(|| {
+            black_box(system.is_minimized(hwnd));
+            black_box(system.is_on_current_desktop(hwnd));
+            black_box(system.is_cloaked(hwnd));
+        })
+    });
+    c.bench_function("cursor_clip", |b| {
+        b.iter(|| {
+            black_box(system.cursor_clip());
+            black_box(system.virtual_screen_rect());
+        })
+    });
+    c.bench_function("monitors", |b| b.iter(|| system.monitors()));
+    c.bench_function("tick", |b| b.iter(|| tick(&system)));
+}
+
+criterion_group!(benches, queries);
+
+/// Criterion allocates while it measures, so the ticks are counted on their own
+fn assert_tick_does_not_allocate() {
+    let system = Win32System::default();
+    // The first call fills the caches, like the first tick does
+    tick(&system);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        tick(&system);
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(
+        allocations, 0,
+        "{} ticks allocated {} times, a tick should not allocate at all",
+        ROUNDS, allocations
+    );
+}
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    assert_tick_does_not_allocate();
+}
@@ -0,0 +1,812 @@
+//! Config file linting (`check-config`)
+//!
+//! Goes further than loading the file: unknown sections and rule keys, which
+//! serde skips without a word, title regexes that don't compile, rules that can
+//! never match or are always beaten by an earlier one, clashing hotkeys and
+//! `[[monitors]]` entries that match none of the connected monitors. Findings
+//! carry the line they are about where it can be found.
+
+use std::fmt;
+
+use regex::Regex;
+
+use crate::config::{Config, MonitorConfig, RuleAction, RuleConfig};
+use crate::hotkey::HotkeyAction;
+use crate::monitor::{hardware_id, MonitorBounds};
+use crate::rules::{glob, is_path_pattern};
+
+/// Top-level sections of [`Config`]
+const SECTIONS: &[&str] = &[
+    "startup",
+    "ui",
+    "engine",
+    "power",
+    "idle",
+    "gamepad",
+    "accessibility",
+    "presentation",
+    "mouse_sharing",
+    "conflicts",
+    "obs",
+    "hotkeys",
+    "keyboard",
+    "cursor",
+    "detection",
+    "learning",
+    "alt_tab",
+    "edge_escape",
+    "tray",
+    "event_log",
+    "system_log",
+    "commands",
+    "webhook",
+    "mqtt",
+    "discord",
+    "http",
+    "updates",
+    "crash_reports",
+    "monitors",
+    "rules",
+    "profiles",
+    "machines",
+];
+
+/// Keys of a `[[rules]]` entry, including the flattened lock overrides
+const RULE_KEYS: &[&str] = &[
+    "class",
+    "title",
+    "process",
+    "product",
+    "steam_app",
+    "match_children",
+    "action",
+    "backend",
+    "clip_to",
+    "center_on_lock",
+    "confine_to",
+    "pair",
+];
+
+const MONITOR_KEYS: &[&str] = &["index", "device", "id", "position", "lock"];
+
+const PROFILE_KEYS: &[&str] = &[
+    "name",
+    "hotkey",
+    "backend",
+    "margins",
+    "center_on_lock",
+    "relock_delay_ms",
+    "require_click",
+    "remember_timeout_ms",
+    "edge_escape",
+    "suppress_buttons",
+    "rules",
+];
+
+/// How bad a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting is dropped or can't work as written
+    Error,
+    /// The setting works, but probably not as intended
+    Warning,
+}
+
+/// One problem in the config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 1-based line number
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Checks the text of a config file against the connected `monitors`
+pub fn check_config(text: &str, monitors: &[MonitorBounds]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let config: Config = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            findings.push(Finding {
+                severity: Severity::Error,
+                line: e.span().map(|span| line_of(text, span.start)),
+                message: e.message().to_string(),
+            });
+            return findings;
+        }
+    };
+    // Parsing as typed config worked, so this can't fail
+    let raw: toml::Table = toml::from_str(text).unwrap_or_default();
+    let lines: Vec<&str> = text.lines().collect();
+
+    for key in raw.keys() {
+        if !SECTIONS.contains(&key.as_str()) {
+            findings.push(Finding {
+                severity: Severity::Error,
+                line: header_line(&lines, key, 0)
+                    .map(|header| header + 1)
+                    .or_else(|| key_line(&lines, 0, key)),
+                message: format!("unknown section [{}], it is ignored", key),
+            });
+        }
+    }
+
+    let machines = raw.get("machines").and_then(toml::Value::as_table);
+    for (machine, overrides) in machines.into_iter().flatten() {
+        // The first header of the machine, which may be one of its sections
+        let header = |key: Option<&str>| {
+            let name = format!("machines.{}", machine);
+            match key {
+                Some(key) => header_line(&lines, &format!("{}.{}", name, key), 0),
+                None => lines.iter().position(|line| {
+                    line.trim()
+                        .strip_prefix('[')
+                        .and_then(|rest| rest.strip_prefix(&name))
+                        .is_some_and(|rest| rest.starts_with(']') || rest.starts_with('.'))
+                }),
+            }
+            .map(|header| header + 1)
+        };
+        let Some(overrides) = overrides.as_table() else {
+            findings.push(Finding {
+                severity: Severity::Error,
+                line: None,
+                message: format!(
+                    "machines.{} must be a [machines.{}] table",
+                    machine, machine
+                ),
+            });
+            continue;
+        };
+        for key in overrides.keys() {
+            if key == "machines" || !SECTIONS.contains(&key.as_str()) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    line: header(Some(key)).or_else(|| header(None)),
+                    message: format!(
+                        "unknown section [{}] in [machines.{}], it is ignored",
+                        key, machine
+                    ),
+                });
+            }
+        }
+        if let Err(e) = Config::parse(text, Some(machine)) {
+            findings.push(Finding {
+                severity: Severity::Error,
+                line: header(None),
+                message: format!(
+                    "[machines.{}] makes the config invalid on that PC, which then uses the defaults: {}",
+                    machine,
+                    e.message()
+                ),
+            });
+        }
+    }
+
+    let entries = |name| {
+        raw.get(name)
+            .and_then(toml::Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+    check_unknown_keys(&mut findings, &lines, "rules", entries("rules"), RULE_KEYS);
+    check_unknown_keys(
+        &mut findings,
+        &lines,
+        "monitors",
+        entries("monitors"),
+        MONITOR_KEYS,
+    );
+    check_unknown_keys(
+        &mut findings,
+        &lines,
+        "profiles",
+        entries("profiles"),
+        PROFILE_KEYS,
+    );
+    check_rules(&mut findings, &lines, &config.rules);
+    check_monitors(&mut findings, &lines, &config.monitors, monitors);
+    check_pairs(&mut findings, &lines, &config.rules, monitors);
+
+    check_hotkeys(&mut findings, &lines, &config);
+    findings
+}
+
+fn check_hotkeys(findings: &mut Vec<Finding>, lines: &[&str], config: &Config) {
+    let bindings = config.hotkey_bindings();
+    for (i, (action, hotkey)) in bindings.iter().enumerate() {
+        let Some((first, _)) = bindings[..i].iter().find(|(_, other)| {
+            other.modifiers() == hotkey.modifiers() && other.key() == hotkey.key()
+        }) else {
+            continue;
+        };
+        let (section, n, key) = match *action {
+            HotkeyAction::Profile(index) => ("profiles", index, "hotkey"),
+            _ => ("hotkeys", 0, hotkey_key(*action)),
+        };
+        findings.push(Finding {
+            severity: Severity::Error,
+            line: header_line(lines, section, n)
+                .and_then(|header| key_line(lines, header + 1, key)),
+            message: format!(
+                "{} is used for both {} and {}, only the first can be registered",
+                hotkey,
+                describe_hotkey(config, *first),
+                describe_hotkey(config, *action)
+            ),
+        });
+    }
+}
+
+/// The `[hotkeys]` key of a fixed hotkey action
+fn hotkey_key(action: HotkeyAction) -> &'static str {
+    match action {
+        HotkeyAction::ManualLock => "manual_lock",
+        HotkeyAction::CycleMonitor => "cycle_monitor",
+        HotkeyAction::NextProfile => "next_profile",
+        HotkeyAction::Panic => "panic",
+        HotkeyAction::Snapshot => "snapshot",
+        HotkeyAction::Profile(_) => "hotkey",
+    }
+}
+
+fn describe_hotkey(config: &Config, action: HotkeyAction) -> String {
+    match action {
+        HotkeyAction::Profile(index) => format!("profile {}", config.profiles[index].name),
+        _ => hotkey_key(action).to_string(),
+    }
+}
+
+fn check_unknown_keys(
+    findings: &mut Vec<Finding>,
+    lines: &[&str],
+    section: &str,
+    entries: &[toml::Value],
+    known: &[&str],
+) {
+    for (i, entry) in entries.iter().enumerate() {
+        let Some(table) = entry.as_table() else {
+            continue;
+        };
+        let header = header_line(lines, section, i);
+        for key in table.keys() {
+            if !known.contains(&key.as_str()) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    line: header.and_then(|header| key_line(lines, header + 1, key)),
+                    message: format!(
+                        "unknown key \"{}\" in [[{}]] entry {}, it is ignored (expected one of {})",
+                        key,
+                        section,
+                        i + 1,
+                        known.join(", ")
+                    ),
+                });
+            }
+        }
+    }
+}
+
+fn check_rules(findings: &mut Vec<Finding>, lines: &[&str], rules: &[RuleConfig]) {
+    for (i, rule) in rules.iter().enumerate() {
+        let header = header_line(lines, "rules", i);
+        let at = |key: &str| {
+            header.and_then(|header| key_line(lines, header + 1, key).or(Some(header + 1)))
+        };
+        let mut push = |severity, line, message: String| {
+            findings.push(Finding {
+                severity,
+                line,
+                message: format!("rule {}: {}", i + 1, message),
+            })
+        };
+
+        if !has_matcher(rule) {
+            push(
+                Severity::Error,
+                header.map(|h| h + 1),
+                "has no class, title, process or steam_app, so it is skipped".to_string(),
+            );
+            continue;
+        }
+        if let Some(Err(e)) = rule.title.as_deref().map(Regex::new) {
+            push(
+                Severity::Error,
+                at("title"),
+                format!("invalid title regex, the rule is skipped: {}", e),
+            );
+            continue;
+        }
+        match (rule.action, rule.overrides.confine_to) {
+            (RuleAction::ConfineTo, None) => push(
+                Severity::Error,
+                at("action"),
+                "action \"confine_to\" needs a confine_to region, the rule locks as usual"
+                    .to_string(),
+            ),
+            (RuleAction::Lock | RuleAction::Ignore, Some(_)) => push(
+                Severity::Warning,
+                at("confine_to"),
+                "confine_to is ignored unless the action is \"confine_to\"".to_string(),
+            ),
+            _ => {}
+        }
+        match rule.process.as_deref() {
+            None if rule.match_children => push(
+                Severity::Warning,
+                at("match_children"),
+                "match_children has no effect without a process".to_string(),
+            ),
+            Some(process) if rule.match_children && is_path_pattern(process) => push(
+                Severity::Warning,
+                at("match_children"),
+                "match_children only follows executable names, not a process path".to_string(),
+            ),
+            _ => {}
+        }
+        if let Some((j, earlier)) = rules[..i]
+            .iter()
+            .enumerate()
+            .find(|(_, earlier)| is_valid(earlier) && covers(earlier, rule))
+        {
+            let ignores = |rule: &RuleConfig| rule.action == RuleAction::Ignore;
+            let conflict = if ignores(earlier) != ignores(rule) {
+                " with the opposite action"
+            } else {
+                ""
+            };
+            push(
+                Severity::Warning,
+                header.map(|h| h + 1),
+                format!(
+                    "never applies, rule {} matches every window it does first{}",
+                    j + 1,
+                    conflict
+                ),
+            );
+        }
+    }
+}
+
+/// Whether the rule makes it into the rule set at all
+fn is_valid(rule: &RuleConfig) -> bool {
+    has_matcher(rule)
+        && rule
+            .title
+            .as_deref()
+            .is_none_or(|title| Regex::new(title).is_ok())
+}
+
+fn has_matcher(rule: &RuleConfig) -> bool {
+    rule.class.is_some()
+        || rule.title.is_some()
+        || rule.process.is_some()
+        || rule.product.is_some()
+        || rule.steam_app.is_some()
+}
+
+/// Whether every window `later` matches is matched by `earlier` too
+fn covers(earlier: &RuleConfig, later: &RuleConfig) -> bool {
+    let same = |a: &Option<String>, b: &Option<String>| a.is_none() || a == b;
+    let same_glob = |a: &Option<String>, b: &Option<String>| {
+        a.as_ref().is_none_or(|a| {
+            b.as_ref().is_some_and(|b| {
+                a.eq_ignore_ascii_case(b) || !b.contains(['*', '?']) && glob(a).is_match(b)
+            })
+        })
+    };
+    same(&earlier.class, &later.class)
+        && same(&earlier.title, &later.title)
+        && same_glob(&earlier.product, &later.product)
+        && earlier
+            .steam_app
+            .is_none_or(|id| later.steam_app == Some(id))
+        && earlier.process.as_ref().is_none_or(|process| {
+            same_glob(&earlier.process, &later.process)
+                && is_path_pattern(process) == later.process.as_deref().is_some_and(is_path_pattern)
+                && (earlier.match_children || !later.match_children)
+        })
+}
+
+fn check_monitors(
+    findings: &mut Vec<Finding>,
+    lines: &[&str],
+    entries: &[MonitorConfig],
+    monitors: &[MonitorBounds],
+) {
+    for (i, entry) in entries.iter().enumerate() {
+        let header = header_line(lines, "monitors", i);
+        let at = |key: &str| {
+            header.and_then(|header| key_line(lines, header + 1, key).or(Some(header + 1)))
+        };
+        let mut push = |line, message: String| {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                line,
+                message: format!("monitor entry {}: {}", i + 1, message),
+            })
+        };
+
+        if entry.index.is_none()
+            && entry.device.is_none()
+            && entry.id.is_none()
+            && entry.position.is_none()
+        {
+            push(
+                header.map(|h| h + 1),
+                "has no index, device, id or position, so it applies to every monitor".to_string(),
+            );
+        }
+        match entry.index {
+            Some(0) => push(
+                at("index"),
+                "index 0 never matches, indexes start at 1".to_string(),
+            ),
+            Some(index) if index > monitors.len() => push(
+                at("index"),
+                format!(
+                    "index {} matches nothing, {} monitor(s) are connected",
+                    index,
+                    monitors.len()
+                ),
+            ),
+            _ => {}
+        }
+        if let Some(ref device) = entry.device {
+            if !monitors
+                .iter()
+                .any(|m| m.device.eq_ignore_ascii_case(device))
+            {
+                let devices: Vec<&str> = monitors.iter().map(|m| m.device.as_str()).collect();
+                push(
+                    at("device"),
+                    format!(
+                        "no connected monitor is named {} (connected: {})",
+                        device,
+                        devices.join(", ")
+                    ),
+                );
+            }
+        }
+        if let Some(ref id) = entry.id {
+            if !monitors.iter().any(|m| m.id_matches(id)) {
+                let ids: Vec<&str> = monitors
+                    .iter()
+                    .map(|m| hardware_id(&m.id).unwrap_or(&m.id))
+                    .collect();
+                push(
+                    at("id"),
+                    format!(
+                        "no connected monitor has an ID containing {} (connected: {})",
+                        id,
+                        ids.join(", ")
+                    ),
+                );
+            }
+        }
+        if let Some([x, y]) = entry.position {
+            if !monitors.iter().any(|m| m.rect.left == x && m.rect.top == y) {
+                push(
+                    at("position"),
+                    format!(
+                        "no connected monitor has its top-left corner at ({}, {})",
+                        x, y
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Warns about `pair` names that match no connected monitor, which leaves the
+/// rule's windows locked to their own monitor
+fn check_pairs(
+    findings: &mut Vec<Finding>,
+    lines: &[&str],
+    rules: &[RuleConfig],
+    monitors: &[MonitorBounds],
+) {
+    for (i, rule) in rules.iter().enumerate() {
+        let Some(ref pair) = rule.overrides.pair else {
+            continue;
+        };
+        for name in pair {
+            if monitors.iter().any(|m| m.is_named(name)) {
+                continue;
+            }
+            let header = header_line(lines, "rules", i);
+            findings.push(Finding {
+                severity: Severity::Warning,
+                line: header
+                    .and_then(|header| key_line(lines, header + 1, "pair").or(Some(header + 1))),
+                message: format!(
+                    "rule {}: no connected monitor is named {} or has it in its ID, the pair \
+                     is skipped",
+                    i + 1,
+                    name
+                ),
+            });
+        }
+    }
+}
+
+/// 1-based line of a byte offset
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// 0-based line of the `n`-th `[name]` or `[[name]]` header
+fn header_line(lines: &[&str], name: &str, n: usize) -> Option<usize> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            let inner = line
+                .strip_prefix("[[")
+                .and_then(|rest| rest.strip_suffix("]]"))
+                .or_else(|| {
+                    line.strip_prefix('[')
+                        .and_then(|rest| rest.strip_suffix(']'))
+                });
+            inner.is_some_and(|inner| inner.trim() == name)
+        })
+        .nth(n)
+        .map(|(i, _)| i)
+}
+
+/// 1-based line of `key = ...` from the 0-based line `start` up to the next
+/// header
+fn key_line(lines: &[&str], start: usize, key: &str) -> Option<usize> {
+    lines
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take_while(|(_, line)| !line.trim_start().starts_with('['))
+        .find(|(_, line)| {
+            line.trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+        .map(|(i, _)| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{is_conflict_copy, LockBackend, MonitorLock};
+    use crate::monitor::monitor_policy;
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::Graphics::Gdi::HMONITOR;
+
+    fn monitors() -> Vec<MonitorBounds> {
+        vec![MonitorBounds {
+            rect: RECT {
+                left: 0,
+                top: 0,
+                right: 1920,
+                bottom: 1080,
+            },
+            handle: HMONITOR::default(),
+            device: r"\\.\DISPLAY1".to_string(),
+            id: r"\\?\DISPLAY#DEL40F5#5&2b3c4d5&0&UID4353#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}"
+                .to_string(),
+        }]
+    }
+
+    fn lines(findings: &[Finding]) -> Vec<Option<usize>> {
+        findings.iter().map(|f| f.line).collect()
+    }
+
+    #[test]
+    fn a_clean_config_has_no_findings() {
+        let text = "[cursor]\nbackend = \"hook\"\n\n[[rules]]\nprocess = \"game.exe\"\n";
+        assert_eq!(check_config(text, &monitors()), []);
+    }
+
+    #[test]
+    fn reports_parse_errors_with_their_line() {
+        let text = "[cursor]\nbackend = \"rubber\"\n";
+        let findings = check_config(text, &monitors());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line, Some(2));
+    }
+
+    #[test]
+    fn reports_unknown_sections_and_rule_keys() {
+        let text =
+            "[curser]\nbackend = \"hook\"\n\n[[rules]]\nproces = \"game.exe\"\ntitle = \"Game\"\n";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(1), Some(5)]);
+    }
+
+    #[test]
+    fn reports_broken_and_shadowed_rules() {
+        let text = "\
+[[rules]]
+title = \"(unclosed\"
+
+[[rules]]
+process = \"Game.exe\"
+action = \"lock\"
+
+[[rules]]
+process = \"game.exe\"
+title = \"Menu\"
+action = \"ignore\"
+";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(2), Some(8)]);
+        assert!(findings[1]
+            .message
+            .starts_with("rule 3: never applies, rule 2"));
+        assert!(findings[1].message.contains("opposite action"));
+    }
+
+    #[test]
+    fn process_globs_shadow_the_names_they_match() {
+        let text = "\
+[[rules]]
+process = \"*.exe\"
+product = \"ELDEN*\"
+
+[[rules]]
+process = 'C:\\Games\\game.exe'
+product = \"ELDEN RING\"
+
+[[rules]]
+process = \"Game.exe\"
+product = \"elden ring\"
+";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(10)]);
+        assert!(findings[0]
+            .message
+            .starts_with("rule 3: never applies, rule 1"));
+    }
+
+    #[test]
+    fn checks_machine_specific_sections() {
+        let text = "\
+[cursor]
+backend = \"hook\"
+
+[machines.DESKTOP-1.cursor]
+backend = \"rubber\"
+
+[machines.LAPTOP.curser]
+backend = \"clip\"
+";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(4), Some(7)]);
+        assert!(findings[0].message.contains("[machines.DESKTOP-1]"));
+        assert!(findings[1].message.contains("unknown section [curser]"));
+    }
+
+    #[test]
+    fn machine_sections_apply_on_their_pc_only() {
+        let text = "\
+[cursor]
+backend = \"hook\"
+center_on_lock = true
+
+[[monitors]]
+index = 2
+lock = \"never\"
+
+[machines.laptop.cursor]
+backend = \"clip\"
+
+[[machines.laptop.monitors]]
+index = 1
+lock = \"always\"
+";
+        let desktop = Config::parse(text, Some("DESKTOP-1")).unwrap();
+        assert_eq!(desktop.cursor.backend, LockBackend::Hook);
+        assert_eq!(desktop.monitors[0].index, Some(2));
+
+        let laptop = Config::parse(text, Some("LAPTOP")).unwrap();
+        assert_eq!(laptop.cursor.backend, LockBackend::Clip);
+        assert!(laptop.cursor.center_on_lock);
+        assert_eq!(laptop.monitors.len(), 1);
+        assert_eq!(laptop.monitors[0].index, Some(1));
+    }
+
+    #[test]
+    fn spots_sync_conflict_copies() {
+        for copy in [
+            "config-DESKTOP-1.toml",
+            "config.sync-conflict-20260101-120000-ABCDEFG.toml",
+            "config (Me's conflicted copy 2026-01-01).toml",
+        ] {
+            assert!(is_conflict_copy("config.toml", copy), "{}", copy);
+        }
+        for other in [
+            "config.toml",
+            "config.toml.bak",
+            "stats.toml",
+            "configs.toml",
+        ] {
+            assert!(!is_conflict_copy("config.toml", other), "{}", other);
+        }
+    }
+
+    #[test]
+    fn reports_confine_to_without_a_region() {
+        let text = "\
+[[rules]]
+process = \"acc.exe\"
+action = \"confine_to\"
+
+[[rules]]
+process = \"other.exe\"
+confine_to = { width_percent = 50, height_percent = 50 }
+";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(3), Some(7)]);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn reports_clashing_hotkeys() {
+        let text = "\
+[hotkeys]
+manual_lock = \"Ctrl+Alt+L\"
+
+[[profiles]]
+name = \"Streaming\"
+hotkey = \"ctrl+alt+l\"
+";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(6)]);
+        assert!(findings[0]
+            .message
+            .contains("manual_lock and profile Streaming"));
+    }
+
+    #[test]
+    fn matches_monitors_by_their_stable_id() {
+        let text =
+            "[[monitors]]\nid = \"del40f5\"\nlock = \"never\"\n\n[[monitors]]\nid = \"GSM5B7F\"\n";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(6)]);
+        assert!(findings[0].message.contains("(connected: DEL40F5)"));
+
+        let config: Config = toml::from_str(text).unwrap();
+        let rect = monitors()[0].rect;
+        assert_eq!(
+            monitor_policy(&config.monitors, &monitors(), &rect),
+            MonitorLock::Never
+        );
+    }
+
+    #[test]
+    fn reports_monitor_entries_matching_nothing() {
+        let text = "[[monitors]]\nindex = 3\nlock = \"never\"\n\n[[monitors]]\ndevice = '\\\\.\\DISPLAY1'\n";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(2)]);
+    }
+
+    #[test]
+    fn reports_pairs_naming_unknown_monitors() {
+        let text = "[[rules]]\nprocess = \"sim.exe\"\npair = [\"DEL40F5\", '\\\\.\\DISPLAY2']\n";
+        let findings = check_config(text, &monitors());
+        assert_eq!(lines(&findings), [Some(3)]);
+        assert!(findings[0].message.contains(r"named \\.\DISPLAY2"));
+    }
+}
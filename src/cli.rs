@@ -0,0 +1,307 @@
+//! Command line parsing
+//!
+//! Without arguments the binary runs the lock engine. Everything else is a
+//! one-shot helper command.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::janitor::JANITOR_ARG;
+
+/// Usage text printed for `help` and after a parse error
+pub const USAGE: &str = "\
+Usage: smartlockcursor [COMMAND]
+
+Commands:
+  (none)                 Run the cursor lock engine
+  --stdio                Run the engine, taking pause/resume/toggle/status
+                         commands on stdin and writing JSON events to stdout
+  --quiet                Run the engine, printing warnings only
+  --tui                  Run the engine with a live dashboard of the foreground
+                         window, lock state, monitors and recent events
+  --only-known-games     Run the engine, locking only windows of known game
+                         engines and launchers (and lock rules)
+  --dry-run              Run the engine and log every lock and unlock, but
+                         never clip, hide or move the cursor
+  explain [--delay <s>]  Wait <s> seconds (default 3), then explain why the
+                         foreground window would or would not be locked
+  list-windows [--all]   List visible top-level windows (--all includes hidden
+                         ones) with their class, process, rect and verdict
+  list-mice              List the attached mice with the device paths
+                         [cursor] device can match
+  check-config [<path>]  Check the config file (default: the one the engine
+                         loads) and list problems with their line numbers
+  export-rules <path>    Write the [[rules]] and [[profiles]] of the config
+                         file to a rule pack
+  import-rules <path>    Merge a rule pack into the config file, keeping the
+                         rules and profiles already there
+  status                 Show the state and statistics of the running instance
+  replay <path> [--config <file>]
+                         Run the engine over a snapshot (or an array of
+                         them) and print what it decides, with the config
+                         recorded in the snapshot unless --config names one
+  snapshot               Save every window, the monitors, the clip and the
+                         engine state to a JSON file to attach to bug reports
+  lock                   Toggle a manual lock of the running instance on the
+                         monitor under the cursor
+  register-event-source  Register the Windows event log source named in
+                         [system_log], needs administrator rights
+  self-update            Download the latest release from GitHub, verify its
+                         SHA-256 and replace this executable with it
+  elevate                Relaunch with administrator rights, needed to lock
+                         games that run as administrator
+  help                   Show this message";
+
+/// How the engine presents itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunOptions {
+    /// Take commands on stdin and write JSON events to stdout
+    pub stdio: bool,
+    /// Print warnings only
+    pub quiet: bool,
+    /// Show the live dashboard instead of log lines
+    pub tui: bool,
+    /// Lock known games only, as if `only_known_games` were set
+    pub only_known_games: bool,
+    /// Log what would happen without ever touching the cursor
+    pub dry_run: bool,
+}
+
+/// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Run(RunOptions),
+    /// Diagnose the window that is in the foreground after `delay`
+    Explain {
+        delay: Duration,
+    },
+    /// Dump the top-level windows and how each would be classified
+    ListWindows {
+        include_hidden: bool,
+    },
+    /// Print the attached mice for `[cursor] device`
+    ListMice,
+    /// Lint the config file at `path`, or the default one
+    CheckConfig {
+        path: Option<PathBuf>,
+    },
+    /// Write the config's rules and profiles to a pack at `path`
+    ExportRules {
+        path: PathBuf,
+    },
+    /// Merge the pack at `path` into the config file
+    ImportRules {
+        path: PathBuf,
+    },
+    /// Print the running instance's state and statistics
+    Status,
+    /// Have the running instance (or this process) write a snapshot
+    Snapshot,
+    /// Run the engine over the snapshots at `path`
+    Replay {
+        path: PathBuf,
+        /// Config to replay with instead of the recorded one
+        config: Option<PathBuf>,
+    },
+    /// Toggle the running instance's manual lock
+    ToggleLock,
+    /// Register the `[system_log]` event source
+    RegisterEventSource,
+    /// Install the latest release over the running executable
+    SelfUpdate,
+    /// Relaunch the engine elevated
+    Elevate,
+    /// Internal: watch `parent_pid` and clear the clip if it crashes
+    Janitor {
+        parent_pid: u32,
+    },
+    Help,
+}
+
+/// Parses the command line, including the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().skip(1);
+
+    let Some(command) = args.next() else {
+        return Ok(Command::Run(RunOptions::default()));
+    };
+
+    let parsed = match command.as_str() {
+        "explain" => {
+            let mut delay = Duration::from_secs(3);
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--delay" => delay = Duration::from_secs(parse_value(&arg, args.next())?),
+                    _ => return Err(format!("Unknown option for explain: {}", arg)),
+                }
+            }
+            Command::Explain { delay }
+        }
+        "list-windows" | "--list-windows" => {
+            let mut include_hidden = false;
+            for arg in args.by_ref() {
+                match arg.as_str() {
+                    "--all" => include_hidden = true,
+                    _ => return Err(format!("Unknown option for list-windows: {}", arg)),
+                }
+            }
+            Command::ListWindows { include_hidden }
+        }
+        "list-mice" => Command::ListMice,
+        "check-config" => Command::CheckConfig {
+            path: args.next().map(PathBuf::from),
+        },
+        "export-rules" => Command::ExportRules {
+            path: PathBuf::from(
+                args.next()
+                    .ok_or("export-rules needs the path of the pack to write")?,
+            ),
+        },
+        "import-rules" => Command::ImportRules {
+            path: PathBuf::from(
+                args.next()
+                    .ok_or("import-rules needs the path of the pack to merge")?,
+            ),
+        },
+        JANITOR_ARG => Command::Janitor {
+            parent_pid: parse_value(JANITOR_ARG, args.next())?,
+        },
+        "--stdio" | "--quiet" | "--tui" | "--only-known-games" | "--dry-run" => {
+            let mut options = RunOptions::default();
+            for arg in std::iter::once(command.clone()).chain(args.by_ref()) {
+                match arg.as_str() {
+                    "--stdio" => options.stdio = true,
+                    "--quiet" => options.quiet = true,
+                    "--tui" => options.tui = true,
+                    "--only-known-games" => options.only_known_games = true,
+                    "--dry-run" => options.dry_run = true,
+                    _ => return Err(format!("Unknown option: {}", arg)),
+                }
+            }
+            if options.stdio && options.tui {
+                return Err("--tui can't be combined with --stdio".to_string());
+            }
+            Command::Run(options)
+        }
+        "status" => Command::Status,
+        "snapshot" => Command::Snapshot,
+        "replay" => {
+            let path = PathBuf::from(args.next().ok_or("replay needs the path of a snapshot")?);
+            let mut config = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--config" => {
+                        config = Some(PathBuf::from(args.next().ok_or("--config needs a value")?))
+                    }
+                    _ => return Err(format!("Unknown option for replay: {}", arg)),
+                }
+            }
+            Command::Replay { path, config }
+        }
+        "lock" => Command::ToggleLock,
+        "register-event-source" => Command::RegisterEventSource,
+        "self-update" => Command::SelfUpdate,
+        "elevate" | "--elevate" => Command::Elevate,
+        "help" | "--help" | "-h" => Command::Help,
+        _ => return Err(format!("Unknown command: {}", command)),
+    };
+
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument: {}", extra));
+    }
+    Ok(parsed)
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Command, String> {
+        parse(
+            std::iter::once("smartlockcursor")
+                .chain(line.split_whitespace())
+                .map(String::from),
+        )
+    }
+
+    #[test]
+    fn explain_waits_three_seconds_unless_told_otherwise() {
+        assert_eq!(
+            parse_line("explain"),
+            Ok(Command::Explain {
+                delay: Duration::from_secs(3)
+            })
+        );
+        assert_eq!(
+            parse_line("explain --delay 10"),
+            Ok(Command::Explain {
+                delay: Duration::from_secs(10)
+            })
+        );
+        assert_eq!(
+            parse_line("explain --delay"),
+            Err("--delay needs a value".to_string())
+        );
+        assert_eq!(
+            parse_line("explain --delay 1.5"),
+            Err("Invalid value for --delay: 1.5".to_string())
+        );
+        assert_eq!(
+            parse_line("explain --now"),
+            Err("Unknown option for explain: --now".to_string())
+        );
+    }
+
+    #[test]
+    fn list_windows_includes_hidden_ones_with_all() {
+        assert_eq!(
+            parse_line("list-windows"),
+            Ok(Command::ListWindows {
+                include_hidden: false
+            })
+        );
+        assert_eq!(
+            parse_line("list-windows --all"),
+            Ok(Command::ListWindows {
+                include_hidden: true
+            })
+        );
+        assert_eq!(
+            parse_line("--list-windows --all"),
+            Ok(Command::ListWindows {
+                include_hidden: true
+            })
+        );
+        assert_eq!(
+            parse_line("list-windows --hidden"),
+            Err("Unknown option for list-windows: --hidden".to_string())
+        );
+    }
+
+    #[test]
+    fn the_janitor_takes_the_pid_to_watch() {
+        assert_eq!(
+            parse_line("--janitor 4242"),
+            Ok(Command::Janitor { parent_pid: 4242 })
+        );
+        assert_eq!(
+            parse_line("--janitor"),
+            Err("--janitor needs a value".to_string())
+        );
+        assert_eq!(
+            parse_line("--janitor self"),
+            Err("Invalid value for --janitor: self".to_string())
+        );
+        assert_eq!(
+            parse_line("--janitor 4242 4243"),
+            Err("Unexpected argument: 4243".to_string())
+        );
+    }
+}
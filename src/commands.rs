@@ -0,0 +1,123 @@
+//! User commands run when the cursor gets locked or released
+//!
+//! `on_lock` and `on_unlock` under `[commands]` go through `cmd /C`, so they
+//! can be anything that works in a command prompt. They run in the background
+//! without a window and are never waited for. Details about the lock are
+//! passed in environment variables:
+//!
+//! | Variable | Value |
+//! |----------|-------|
+//! | `SLC_EVENT` | `lock` or `unlock` |
+//! | `SLC_PROCESS` | Executable of the locked window, empty for a manual lock |
+//! | `SLC_GAME` | Steam name of the game, or the executable without `.exe`; empty for a manual lock |
+//! | `SLC_HWND` | Locked window handle in hex, `0` for a manual lock |
+//! | `SLC_MONITOR` | Device name of the locked monitor, e.g. `\\.\DISPLAY1` |
+//! | `SLC_RECT` | The clip rect as `left,top,right,bottom` |
+
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+
+use windows::Win32::Foundation::RECT;
+
+use crate::{tr, warn};
+
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// What a lock command is told about the lock
+#[derive(Debug, Clone, Default)]
+pub struct LockDetails {
+    pub process: String,
+    /// Steam name of the game, or the executable name without `.exe`
+    pub game: String,
+    pub hwnd: isize,
+    pub monitor: String,
+    pub rect: RECT,
+}
+
+/// Starts `command` for `event` (`lock` or `unlock`) without waiting for it
+pub fn spawn(command: &str, event: &str, details: &LockDetails) {
+    let result = Command::new("cmd")
+        .arg("/C")
+        // Passed through untouched so the user's own quoting survives
+        .raw_arg(command)
+        .envs(environment(event, details))
+        .stdin(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn();
+
+    if let Err(e) = result {
+        warn!("{}", tr!("commands.failed", event, e));
+    }
+}
+
+/// The `SLC_*` variables of the table above
+fn environment(event: &str, details: &LockDetails) -> [(&'static str, String); 6] {
+    let rect = details.rect;
+    let hwnd = match details.hwnd {
+        0 => "0".to_string(),
+        hwnd => format!("{:#x}", hwnd),
+    };
+    [
+        ("SLC_EVENT", event.to_string()),
+        ("SLC_PROCESS", details.process.clone()),
+        ("SLC_GAME", details.game.clone()),
+        ("SLC_HWND", hwnd),
+        ("SLC_MONITOR", details.monitor.clone()),
+        (
+            "SLC_RECT",
+            format!("{},{},{},{}", rect.left, rect.top, rect.right, rect.bottom),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    #[test]
+    fn a_window_lock_describes_the_window() {
+        let details = LockDetails {
+            process: "eldenring.exe".to_string(),
+            game: "ELDEN RING".to_string(),
+            hwnd: 0x1a2b,
+            monitor: r"\\.\DISPLAY2".to_string(),
+            rect: RECT {
+                left: -1920,
+                ..MONITOR
+            },
+        };
+        assert_eq!(
+            environment("lock", &details),
+            [
+                ("SLC_EVENT", "lock".to_string()),
+                ("SLC_PROCESS", "eldenring.exe".to_string()),
+                ("SLC_GAME", "ELDEN RING".to_string()),
+                ("SLC_HWND", "0x1a2b".to_string()),
+                ("SLC_MONITOR", r"\\.\DISPLAY2".to_string()),
+                ("SLC_RECT", "-1920,0,1920,1080".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_manual_lock_has_no_window() {
+        let details = LockDetails {
+            monitor: r"\\.\DISPLAY1".to_string(),
+            rect: MONITOR,
+            ..Default::default()
+        };
+        let environment = environment("unlock", &details);
+        assert!(environment.contains(&("SLC_EVENT", "unlock".to_string())));
+        assert!(environment.contains(&("SLC_HWND", "0".to_string())));
+        assert!(environment.contains(&("SLC_PROCESS", String::new())));
+        assert!(environment.contains(&("SLC_GAME", String::new())));
+        assert!(environment.contains(&("SLC_RECT", "0,0,1920,1080".to_string())));
+    }
+}
@@ -0,0 +1,1403 @@
+//! Configuration file loading
+//!
+//! The configuration lives in `%APPDATA%\SmartLockCursor\config.toml`, or
+//! wherever `SMARTLOCKCURSOR_CONFIG` points, e.g. a folder OneDrive or Syncthing
+//! keeps in sync across PCs. Every setting is optional; a missing file simply
+//! means the defaults are used. Changes to the file are picked up while
+//! running. A `[machines.NAME]` table is laid over the rest of the file on the
+//! PC called `NAME`, for what differs between them, like the monitor layout.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use windows::Win32::Foundation::RECT;
+
+use crate::hotkey::{Hotkey, HotkeyAction};
+use crate::i18n::Language;
+use crate::machine::GRACE;
+use crate::{info, tr, warn};
+
+/// Environment variable naming a config file to use instead of the default one
+pub const CONFIG_ENV: &str = "SMARTLOCKCURSOR_CONFIG";
+
+/// What to do with the cursor when a lock is released
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleasePosition {
+    /// Leave the cursor wherever the clip left it
+    #[default]
+    Keep,
+    /// Move the cursor back to where it was before the lock engaged
+    Restore,
+    /// Move the cursor to the center of the monitor it was locked to
+    Center,
+}
+
+/// How the cursor is kept inside the locked monitor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockBackend {
+    /// `ClipCursor`, re-applied whenever another program clears it
+    #[default]
+    Clip,
+    /// A low-level mouse hook that stops the cursor at the edge itself, for
+    /// games that clear the clip every frame
+    Hook,
+    /// The mouse hook with a soft edge that gives way after `resistance_ms`
+    /// of sustained pushing
+    Soft,
+}
+
+/// How the clip backend keeps its clip in place once locked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reclip {
+    /// Call `ClipCursor` on every check, whatever the clip is
+    Always,
+    /// Check the clip and only re-apply it when another program changed it
+    #[default]
+    OnTheft,
+    /// Clip once when the lock engages and leave it at that
+    Never,
+}
+
+/// A mouse input the hook can swallow while locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    Middle,
+    /// The back thumb button
+    X1,
+    /// The forward thumb button
+    X2,
+    Wheel,
+}
+
+/// Per-edge insets applied to the clip rect (`[cursor.margins]` section)
+///
+/// Positive values keep the cursor that many pixels away from the monitor edge,
+/// negative values let it move past the edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ClipMargins {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl ClipMargins {
+    /// Shrinks (or grows) `rect` by the margins, never collapsing it below 1x1
+    pub fn apply(&self, rect: RECT) -> RECT {
+        let left = rect.left + self.left;
+        let top = rect.top + self.top;
+        RECT {
+            left,
+            top,
+            right: (rect.right - self.right).max(left + 1),
+            bottom: (rect.bottom - self.bottom).max(top + 1),
+        }
+    }
+}
+
+/// Cursor behavior settings (`[cursor]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CursorConfig {
+    pub on_release: ReleasePosition,
+    pub margins: ClipMargins,
+    pub backend: LockBackend,
+    /// With the soft backend, how long the edge must be pushed to cross it
+    pub resistance_ms: u32,
+    /// Make the cursor invisible while locked, unless the game hid it already
+    pub hide_while_locked: bool,
+    /// Move the cursor to the middle of the clip when a new lock engages
+    pub center_on_lock: bool,
+    /// Only hold the mouse whose Raw Input device path contains this,
+    /// case-insensitive; other pointing devices cross the edge freely
+    pub device: Option<String>,
+    /// Mouse inputs swallowed while locked, through the mouse hook
+    pub suppress_buttons: Vec<MouseButton>,
+    /// Move a game that keeps clearing the clip to the mouse hook for the rest
+    /// of the session, unless a rule picks its backend
+    pub escalate_to_hook: bool,
+    pub reclip: Reclip,
+    /// Least time between two clip checks, 0 for every tick
+    pub reclip_interval_ms: u64,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            on_release: ReleasePosition::Keep,
+            margins: ClipMargins::default(),
+            backend: LockBackend::Clip,
+            resistance_ms: 300,
+            hide_while_locked: false,
+            center_on_lock: false,
+            device: None,
+            suppress_buttons: Vec::new(),
+            escalate_to_hook: true,
+            reclip: Reclip::OnTheft,
+            reclip_interval_ms: 0,
+        }
+    }
+}
+
+/// How the main loop finds out about window changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineMode {
+    /// Check the foreground window every tick
+    #[default]
+    Poll,
+    /// Sleep until a WinEvent hook reports a change
+    Events,
+}
+
+/// Settings applied once at launch (`[startup]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Relaunch with admin rights through a UAC prompt when started without them
+    pub run_elevated: bool,
+    /// Bring back the pause, the profile and the hook escalations of the last run
+    pub restore_state: bool,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            run_elevated: false,
+            restore_state: true,
+        }
+    }
+}
+
+/// Console and dialog settings (`[ui]` section)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    pub language: Language,
+}
+
+/// Main loop settings (`[engine]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub mode: EngineMode,
+    /// In event mode, how often reality is cross-checked against the hook-derived state
+    pub watchdog_interval_ms: u64,
+    /// In poll mode, the check interval while nothing could be locked (0 = always 100ms)
+    pub idle_interval_ms: u64,
+    /// How long the main loop may hang before the supervisor releases the
+    /// cursor and restarts the program (0 = never, read at startup)
+    pub stall_timeout_ms: u64,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            mode: EngineMode::Poll,
+            watchdog_interval_ms: 2000,
+            idle_interval_ms: 750,
+            stall_timeout_ms: 15000,
+        }
+    }
+}
+
+/// When to use the low-power profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LowPowerMode {
+    /// While running on battery
+    #[default]
+    Auto,
+    /// Always, even when plugged in
+    Always,
+    Never,
+}
+
+/// Low-power profile settings (`[power]` section)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub low_power: LowPowerMode,
+    /// Tick interval while the low-power profile is active
+    pub low_power_interval_ms: u64,
+    /// How often the clip is checked for theft while the low-power profile is active
+    pub low_power_reclip_ms: u64,
+    /// Run the main thread with EcoQoS and below-normal priority
+    pub efficiency_mode: bool,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            low_power: LowPowerMode::Auto,
+            low_power_interval_ms: 250,
+            low_power_reclip_ms: 1000,
+            efficiency_mode: true,
+        }
+    }
+}
+
+/// Idle detection settings (`[idle]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Release the lock after no keyboard or mouse input for `timeout_s`
+    pub enabled: bool,
+    pub timeout_s: u64,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_s: 300,
+        }
+    }
+}
+
+/// Controller settings (`[gamepad]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    /// Leave the cursor free while a controller is in use
+    pub enabled: bool,
+    /// How long after the last controller input it still counts as in use
+    pub timeout_s: u64,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_s: 10,
+        }
+    }
+}
+
+/// Assistive technology settings (`[accessibility]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Leave the cursor free while Magnifier, a screen reader or the
+    /// on-screen keyboard is running
+    pub pause_for_tools: bool,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            pause_for_tools: true,
+        }
+    }
+}
+
+/// Presentation settings (`[presentation]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PresentationConfig {
+    /// Leave the cursor free while Windows presentation mode is on or the
+    /// displays are duplicated
+    pub pause: bool,
+}
+
+impl Default for PresentationConfig {
+    fn default() -> Self {
+        Self { pause: true }
+    }
+}
+
+/// Mouse sharing settings (`[mouse_sharing]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MouseSharingConfig {
+    /// Leave the cursor free while one of `processes` has the foreground,
+    /// which is how they look while the pointer is on another computer
+    pub pause: bool,
+    /// Executables of Mouse Without Borders, Barrier, Input Leap, Synergy,
+    /// Logitech Flow and the like
+    pub processes: Vec<String>,
+}
+
+impl Default for MouseSharingConfig {
+    fn default() -> Self {
+        Self {
+            pause: true,
+            processes: [
+                "MouseWithoutBorders.exe",
+                "MouseWithoutBordersHelper.exe",
+                "PowerToys.MouseWithoutBorders.exe",
+                "PowerToys.MouseWithoutBordersHelper.exe",
+                "barrier.exe",
+                "barriers.exe",
+                "input-leap.exe",
+                "input-leaps.exe",
+                "synergy.exe",
+                "synergys.exe",
+                "synergy-core.exe",
+                "deskflow.exe",
+                "LogiOptionsMgr.exe",
+                "logioptionsplus_agent.exe",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// Other cursor confinement tools (`[conflicts]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConflictsConfig {
+    /// Executables of tools that clip the cursor themselves, warned about
+    /// when found running
+    pub processes: Vec<String>,
+    /// While one of them runs, re-apply the clip only once it was released
+    /// instead of whenever it differs
+    pub cooperate: bool,
+}
+
+impl Default for ConflictsConfig {
+    fn default() -> Self {
+        Self {
+            processes: [
+                "DualMonitorTools.exe",
+                "CursorLock.exe",
+                "LockCursor.exe",
+                "CursorLocker.exe",
+                "DisplayFusion.exe",
+            ]
+            .map(String::from)
+            .to_vec(),
+            cooperate: true,
+        }
+    }
+}
+
+/// OBS Studio settings (`[obs]` section); `websocket` is used by builds with
+/// the `obs` feature
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ObsConfig {
+    /// Executables of OBS Studio and its forks
+    pub processes: Vec<String>,
+    /// Modules game capture injects into the captured game, which show it is
+    /// being recorded even by an OBS under another name
+    pub hook_modules: Vec<String>,
+    /// `[[profiles]]` entry to switch to while OBS is running
+    pub profile: Option<String>,
+    /// Keep the lock while an OBS projector window takes the foreground
+    pub hold_for_projectors: bool,
+    /// Words in the title of projector windows, matched ignoring case
+    pub projector_titles: Vec<String>,
+    /// Broadcast lock state changes as obs-websocket custom events
+    pub websocket: bool,
+    pub host: String,
+    pub port: u16,
+    /// The server password from Tools > WebSocket Server Settings
+    pub password: Option<String>,
+}
+
+impl Default for ObsConfig {
+    fn default() -> Self {
+        Self {
+            processes: ["obs64.exe", "obs32.exe", "obs.exe", "Streamlabs OBS.exe"]
+                .map(String::from)
+                .to_vec(),
+            hook_modules: ["graphics-hook64.dll", "graphics-hook32.dll"]
+                .map(String::from)
+                .to_vec(),
+            profile: None,
+            hold_for_projectors: true,
+            projector_titles: vec!["Projector".to_string()],
+            websocket: false,
+            host: "127.0.0.1".to_string(),
+            port: 4455,
+            password: None,
+        }
+    }
+}
+
+/// Global hotkey settings (`[hotkeys]` section)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HotkeysConfig {
+    /// Toggle a lock on the monitor under the cursor, for windowed games the
+    /// detector never locks
+    pub manual_lock: Option<Hotkey>,
+    /// Move the manual lock to the next monitor in enumeration order
+    pub cycle_monitor: Option<Hotkey>,
+    /// Switch to the next `[[profiles]]` entry, and from the last one back to
+    /// the main config
+    pub next_profile: Option<Hotkey>,
+    /// Write a snapshot of every window, the monitors and the engine's state
+    /// to attach to a bug report
+    pub snapshot: Option<Hotkey>,
+    /// Whether cycling also moves the cursor to the center of the next monitor,
+    /// rather than letting it snap to the nearest edge
+    pub cycle_moves_cursor: bool,
+    /// Emergency release, registered before every other hotkey and also when
+    /// the config fails to load
+    pub panic: Hotkey,
+    /// How long locking stays paused after the panic hotkey
+    pub panic_cooldown_ms: u64,
+}
+
+impl Default for HotkeysConfig {
+    fn default() -> Self {
+        Self {
+            manual_lock: None,
+            cycle_monitor: None,
+            next_profile: None,
+            snapshot: None,
+            cycle_moves_cursor: true,
+            panic: "Ctrl+Alt+End".parse().expect("valid default hotkey"),
+            panic_cooldown_ms: 60_000,
+        }
+    }
+}
+
+/// Key blocking while locked (`[keyboard]` section)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    /// Swallow both Windows keys, and with them every Win+ shortcut but Win+L
+    pub block_win_key: bool,
+    /// Other combinations to swallow, written like hotkeys (`"Alt+Esc"`)
+    pub block: Vec<Hotkey>,
+}
+
+impl KeyboardConfig {
+    pub fn blocks_anything(&self) -> bool {
+        self.block_win_key || !self.block.is_empty()
+    }
+}
+
+/// Lock and unlock commands (`[commands]` section)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandsConfig {
+    /// Run whenever the cursor gets locked, including after Alt+Tab
+    pub on_lock: Option<String>,
+    /// Run whenever the cursor is released again
+    pub on_unlock: Option<String>,
+}
+
+/// HTTP callback settings (`[webhook]` section), used by builds with the
+/// `webhook` feature
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// `http://host[:port]/path` to POST each event to as JSON
+    pub url: Option<String>,
+    /// Only send these event kinds, e.g. `locked`; all of them when empty
+    pub events: Vec<String>,
+    /// Connect and response timeout per attempt
+    pub timeout_ms: u64,
+    /// Further attempts after a failed one
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            events: Vec::new(),
+            timeout_ms: 2000,
+            retries: 2,
+            retry_delay_ms: 1000,
+        }
+    }
+}
+
+/// MQTT state publishing settings (`[mqtt]` section), used by builds with the
+/// `mqtt` feature
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker host name or address; publishing is off without one
+    pub host: Option<String>,
+    pub port: u16,
+    pub topic: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Publish the state as a retained message, so new subscribers see it
+    pub retain: bool,
+    pub keepalive_s: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            topic: "smartlockcursor/state".to_string(),
+            client_id: "smartlockcursor".to_string(),
+            username: None,
+            password: None,
+            retain: true,
+            keepalive_s: 60,
+        }
+    }
+}
+
+/// Discord Rich Presence settings (`[discord]` section), used by builds with
+/// the `discord` feature
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    /// Application ID from the Discord developer portal; the presence is off
+    /// without one
+    pub client_id: Option<String>,
+    /// Show "Cursor free" with the time since startup while nothing is locked,
+    /// instead of clearing the presence
+    pub show_unlocked: bool,
+    /// Name the locked game
+    pub show_game: bool,
+    /// Art asset of the application shown next to the status
+    pub large_image: Option<String>,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            show_unlocked: true,
+            show_game: true,
+            large_image: None,
+        }
+    }
+}
+
+/// Local status endpoint settings (`[http]` section), used by builds with the
+/// `http` feature
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Port on 127.0.0.1 serving `/status` and `/metrics`; off without one
+    pub port: Option<u16>,
+}
+
+/// Update check settings (`[updates]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    /// Look for a newer release on GitHub at startup
+    pub check: bool,
+    /// `owner/name` of the GitHub repository releases come from
+    pub repository: String,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        Self {
+            check: false,
+            repository: "TheArmagan/smartlockcursor".to_string(),
+        }
+    }
+}
+
+/// Crash report settings (`[crash_reports]` section), used by builds with the
+/// `crash-reports` feature
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CrashReportsConfig {
+    /// Consent to write a report when SmartLockCursor crashes
+    pub enabled: bool,
+    /// `https://` URL to POST reports to, after asking at the next start
+    pub upload_url: Option<String>,
+}
+
+/// Rule learning settings (`[learning]` section)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LearningConfig {
+    /// After locking an app no rule mentions, ask whether to always or never
+    /// lock it and save the answer as a rule
+    pub prompt: bool,
+}
+
+/// How much it takes for a fullscreen window to be locked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionLevel {
+    /// Lock a fullscreen window in front, whatever shows over it
+    #[default]
+    Casual,
+    /// Also require that no other window shows over it on its monitor, since a
+    /// borderless window can be in front without being what's on screen
+    Strict,
+}
+
+/// Fullscreen detection settings (`[detection]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    pub level: DetectionLevel,
+    /// Only lock windows matched by a `lock` rule
+    pub whitelist_only: bool,
+    /// Allowed difference in pixels between the window and monitor rects
+    pub tolerance: i32,
+    /// Share of its monitor, from 0 to 1, a window covering at least that much
+    /// of it counts as fullscreen with, whatever `tolerance` says
+    pub min_coverage: Option<f64>,
+    /// Executables (case-insensitive) of windows that take the foreground for
+    /// a moment, like toasts and shell flyouts, and are ignored while they do
+    pub transient_processes: Vec<String>,
+    /// Exact window classes treated the same way
+    pub transient_classes: Vec<String>,
+    /// Never lock the windows of `browsers` unless a rule says so, since
+    /// fullscreen video is watched with the other monitor in reach
+    pub ignore_browsers: bool,
+    /// Executables (case-insensitive) of web browsers
+    pub browsers: Vec<String>,
+    /// Only lock known games: windows of a built-in or listed engine class,
+    /// or started by a known launcher, plus whatever a `lock` rule matches
+    pub only_known_games: bool,
+    /// Tolerance in pixels used instead of `tolerance` for known games
+    pub known_game_tolerance: i32,
+    /// Window classes added to the built-in engine table
+    pub game_classes: Vec<String>,
+    /// Executables (case-insensitive) added to the built-in launcher table
+    pub game_launchers: Vec<String>,
+    /// How long a window has to be fullscreen and focused before it is locked
+    pub lock_delay_ms: u64,
+    /// How long a lock survives while its window is out of front
+    pub grace_ms: u64,
+    /// Rhai script deciding whether a window may be locked, relative to the
+    /// config folder unless absolute; needs the `rhai` cargo feature
+    pub script: Option<PathBuf>,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            level: DetectionLevel::Casual,
+            whitelist_only: false,
+            tolerance: 5,
+            min_coverage: None,
+            // Toasts, Action Center, Snap Assist and snap layouts
+            transient_processes: vec!["ShellExperienceHost.exe".to_string()],
+            // The Windows 10 volume and brightness OSD
+            transient_classes: vec!["NativeHWNDHost".to_string()],
+            ignore_browsers: true,
+            browsers: [
+                "chrome.exe",
+                "msedge.exe",
+                "firefox.exe",
+                "brave.exe",
+                "opera.exe",
+                "vivaldi.exe",
+                "chromium.exe",
+                "librewolf.exe",
+                "waterfox.exe",
+                "iexplore.exe",
+            ]
+            .map(String::from)
+            .to_vec(),
+            only_known_games: false,
+            known_game_tolerance: 16,
+            game_classes: Vec::new(),
+            game_launchers: Vec::new(),
+            lock_delay_ms: 0,
+            grace_ms: GRACE.as_millis() as u64,
+            script: None,
+        }
+    }
+}
+
+/// Alt+Tab behavior settings (`[alt_tab]` section)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AltTabConfig {
+    /// Wait this long after Alt+Tab before the clip is re-applied
+    pub relock_delay_ms: u64,
+    /// After switching away, only re-lock once the fullscreen window is clicked,
+    /// not when it merely regains focus
+    pub require_click: bool,
+    /// Forget the fullscreen window after being away this long (0 = never)
+    pub remember_timeout_ms: u64,
+}
+
+/// Edge-escape gesture settings (`[edge_escape]` section)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EdgeEscapeConfig {
+    /// Hitting the clip edge twice releases the cursor for a while
+    pub enabled: bool,
+    /// Both hits must land within this long
+    pub double_hit_ms: u64,
+    /// How long the cursor stays free afterwards
+    pub release_ms: u64,
+}
+
+impl Default for EdgeEscapeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            double_hit_ms: 500,
+            release_ms: 5000,
+        }
+    }
+}
+
+/// Notification area icon settings (`[tray]` section)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Show the icon, whose menu can unlock the cursor for a while
+    pub enabled: bool,
+    /// Show a notification on every lock; clicking it unlocks too
+    pub lock_notifications: bool,
+    /// How long "Unlock for 30s" frees the cursor
+    pub unlock_for_ms: u64,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lock_notifications: false,
+            unlock_for_ms: 30_000,
+        }
+    }
+}
+
+/// File format of the event log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One JSON object per line
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+/// Event history settings (`[event_log]` section)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct EventLogConfig {
+    pub enabled: bool,
+    pub format: LogFormat,
+    /// Defaults to `events.jsonl` / `events.csv` next to the config file
+    pub path: Option<PathBuf>,
+    /// Rotate once the file grows past this size (0 = never)
+    pub max_size_kb: u64,
+    /// How many rotated files to keep
+    pub keep: u32,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: LogFormat::Jsonl,
+            path: None,
+            max_size_kb: 1024,
+            keep: 3,
+        }
+    }
+}
+
+/// Windows Application event log settings (`[system_log]` section)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SystemLogConfig {
+    pub enabled: bool,
+    /// Event source name, as registered with `register-event-source`
+    pub source: String,
+    /// Event kinds to write; startup and errors are always written
+    pub events: Vec<String>,
+}
+
+impl Default for SystemLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: "SmartLockCursor".to_string(),
+            events: [
+                "locked",
+                "unlocked",
+                "clip_stolen",
+                "manual_locked",
+                "manual_unlocked",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// Whether fullscreen windows on a monitor may be locked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorLock {
+    /// Decided by the rules and detection settings as usual
+    #[default]
+    Auto,
+    /// Never lock windows on this monitor
+    Never,
+    /// Lock fullscreen windows here even when the rules would ignore them
+    Always,
+}
+
+/// A `[[monitors]]` entry; every field given must match the monitor
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// 1-based position in the startup monitor list
+    pub index: Option<usize>,
+    /// GDI device name such as `\\.\DISPLAY3`
+    pub device: Option<String>,
+    /// Part of the monitor's device ID, e.g. its EDID code `DEL40F5`, which,
+    /// unlike the index and device name, survives docking and cable swaps
+    pub id: Option<String>,
+    /// Top-left corner in virtual screen coordinates
+    pub position: Option<[i32; 2]>,
+    pub lock: MonitorLock,
+}
+
+/// What a matching rule does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    #[default]
+    Lock,
+    Ignore,
+    /// Lock like `lock`, but only to the rule's `confine_to` region
+    ConfineTo,
+}
+
+/// Part of the screen a `confine_to` rule keeps the cursor in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ConfineRegion {
+    /// Desktop coordinates in pixels
+    Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    },
+    /// The middle of what the window would be locked to, in percent of its
+    /// width and height, e.g. the center display of a triple-screen setup
+    Center {
+        width_percent: u32,
+        height_percent: u32,
+    },
+}
+
+impl ConfineRegion {
+    /// The region within `rect`, the monitor (or window) the lock would cover
+    pub fn apply(&self, rect: RECT) -> RECT {
+        match *self {
+            ConfineRegion::Rect {
+                left,
+                top,
+                right,
+                bottom,
+            } => RECT {
+                left,
+                top,
+                right: right.max(left + 1),
+                bottom: bottom.max(top + 1),
+            },
+            ConfineRegion::Center {
+                width_percent,
+                height_percent,
+            } => {
+                let shrink = |low: i32, high: i32, percent: u32| {
+                    let size = (high - low) as i64;
+                    let kept = (size * percent.clamp(1, 100) as i64 / 100).max(1);
+                    let start = low + ((size - kept) / 2) as i32;
+                    (start, start + kept as i32)
+                };
+                let (left, right) = shrink(rect.left, rect.right, width_percent);
+                let (top, bottom) = shrink(rect.top, rect.bottom, height_percent);
+                RECT {
+                    left,
+                    top,
+                    right,
+                    bottom,
+                }
+            }
+        }
+    }
+}
+
+/// What the cursor is confined to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipTarget {
+    /// The monitor of a fullscreen window
+    #[default]
+    Monitor,
+    /// The window's client area, following it when it moves or resizes; the
+    /// window is locked whenever it has focus, fullscreen or not
+    Window,
+    /// The monitor minus the taskbar, for pseudo-fullscreen windows that keep
+    /// the taskbar visible; windows filling just the work area count as fullscreen
+    WorkArea,
+}
+
+/// Lock settings a rule can override for the windows it matches
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LockOverrides {
+    /// Overrides `[cursor] backend`
+    pub backend: Option<LockBackend>,
+    pub clip_to: Option<ClipTarget>,
+    /// Overrides `[cursor] center_on_lock`
+    pub center_on_lock: Option<bool>,
+    /// Where `action = "confine_to"` keeps the cursor, within what `clip_to`
+    /// locks to
+    pub confine_to: Option<ConfineRegion>,
+    /// Two monitors, each by device name or part of its ID, locked to as one
+    /// when the window is fullscreen on either of them
+    pub pair: Option<[String; 2]>,
+}
+
+/// A single `[[rules]]` entry
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    /// Exact window class name
+    pub class: Option<String>,
+    /// Regex matched against the window title, re-checked whenever the title changes
+    pub title: Option<String>,
+    /// Executable name of the window's process (e.g. `game.exe`), or with a
+    /// `\` its full path (e.g. `C:\Games\**\*.exe`); a case-insensitive glob
+    pub process: Option<String>,
+    /// Product name from the executable's version info, a case-insensitive glob
+    pub product: Option<String>,
+    /// Steam AppID of the game the window's process belongs to, which stays
+    /// the same across installs and executable renames
+    pub steam_app: Option<u32>,
+    /// Also match windows of processes started (directly or not) by `process`
+    pub match_children: bool,
+    pub action: RuleAction,
+    #[serde(flatten)]
+    pub overrides: LockOverrides,
+}
+
+/// A `[[profiles]]` entry: a named set of rules and lock settings applied on
+/// top of the rest of the file while it is active
+///
+/// Settings a profile leaves out keep their value from the main config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub name: String,
+    /// Switches to this profile, or back to the main config if it is active
+    pub hotkey: Option<Hotkey>,
+    /// Overrides `[cursor] backend`
+    pub backend: Option<LockBackend>,
+    /// Overrides `[cursor.margins]`
+    pub margins: Option<ClipMargins>,
+    /// Overrides `[cursor] center_on_lock`
+    pub center_on_lock: Option<bool>,
+    /// Overrides `[alt_tab] relock_delay_ms`
+    pub relock_delay_ms: Option<u64>,
+    /// Overrides `[alt_tab] require_click`
+    pub require_click: Option<bool>,
+    /// Overrides `[alt_tab] remember_timeout_ms`
+    pub remember_timeout_ms: Option<u64>,
+    /// Overrides `[edge_escape] enabled`
+    pub edge_escape: Option<bool>,
+    /// Overrides `[cursor] suppress_buttons`
+    pub suppress_buttons: Option<Vec<MouseButton>>,
+    /// Checked before the main `[[rules]]`
+    pub rules: Vec<RuleConfig>,
+}
+
+/// Top-level configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub startup: StartupConfig,
+    pub ui: UiConfig,
+    pub engine: EngineConfig,
+    pub power: PowerConfig,
+    pub idle: IdleConfig,
+    pub gamepad: GamepadConfig,
+    pub accessibility: AccessibilityConfig,
+    pub presentation: PresentationConfig,
+    pub mouse_sharing: MouseSharingConfig,
+    pub conflicts: ConflictsConfig,
+    pub obs: ObsConfig,
+    pub hotkeys: HotkeysConfig,
+    pub keyboard: KeyboardConfig,
+    pub cursor: CursorConfig,
+    pub detection: DetectionConfig,
+    pub learning: LearningConfig,
+    pub alt_tab: AltTabConfig,
+    pub edge_escape: EdgeEscapeConfig,
+    pub tray: TrayConfig,
+    pub event_log: EventLogConfig,
+    pub system_log: SystemLogConfig,
+    pub commands: CommandsConfig,
+    pub webhook: WebhookConfig,
+    pub mqtt: MqttConfig,
+    pub discord: DiscordConfig,
+    pub http: HttpConfig,
+    pub updates: UpdatesConfig,
+    pub crash_reports: CrashReportsConfig,
+    pub monitors: Vec<MonitorConfig>,
+    pub rules: Vec<RuleConfig>,
+    pub profiles: Vec<ProfileConfig>,
+}
+
+impl Config {
+    /// Directory holding the config file and other per-user data
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("SmartLockCursor"))
+    }
+
+    /// Location of the config file, `SMARTLOCKCURSOR_CONFIG` if set
+    ///
+    /// Statistics, logs and other per-user data stay in [`Config::default_dir`],
+    /// so a synced config doesn't drag them along.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        Self::default_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Name of this PC, which picks its `[machines.NAME]` table
+    pub fn machine_name() -> Option<String> {
+        std::env::var("COMPUTERNAME")
+            .ok()
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Index of the profile called `name`, ignoring case
+    pub fn profile_index(&self, name: &str) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// This config with the profile at `index` applied on top
+    pub fn with_profile(&self, index: usize) -> Config {
+        let mut config = self.clone();
+        let Some(profile) = self.profiles.get(index) else {
+            return config;
+        };
+        if let Some(backend) = profile.backend {
+            config.cursor.backend = backend;
+        }
+        if let Some(margins) = profile.margins {
+            config.cursor.margins = margins;
+        }
+        if let Some(center_on_lock) = profile.center_on_lock {
+            config.cursor.center_on_lock = center_on_lock;
+        }
+        if let Some(relock_delay_ms) = profile.relock_delay_ms {
+            config.alt_tab.relock_delay_ms = relock_delay_ms;
+        }
+        if let Some(require_click) = profile.require_click {
+            config.alt_tab.require_click = require_click;
+        }
+        if let Some(remember_timeout_ms) = profile.remember_timeout_ms {
+            config.alt_tab.remember_timeout_ms = remember_timeout_ms;
+        }
+        if let Some(edge_escape) = profile.edge_escape {
+            config.edge_escape.enabled = edge_escape;
+        }
+        if let Some(ref buttons) = profile.suppress_buttons {
+            config.cursor.suppress_buttons.clone_from(buttons);
+        }
+        config.rules = profile.rules.iter().chain(&self.rules).cloned().collect();
+        config
+    }
+
+    /// Every configured global hotkey with what it does, the panic hotkey first
+    pub fn hotkey_bindings(&self) -> Vec<(HotkeyAction, Hotkey)> {
+        let hotkeys = &self.hotkeys;
+        let fixed = [
+            (HotkeyAction::ManualLock, &hotkeys.manual_lock),
+            (HotkeyAction::CycleMonitor, &hotkeys.cycle_monitor),
+            (HotkeyAction::NextProfile, &hotkeys.next_profile),
+            (HotkeyAction::Snapshot, &hotkeys.snapshot),
+        ];
+        let profiles = self
+            .profiles
+            .iter()
+            .enumerate()
+            .map(|(i, profile)| (HotkeyAction::Profile(i), &profile.hotkey));
+        let configured = fixed
+            .into_iter()
+            .chain(profiles)
+            .filter_map(|(action, hotkey)| Some((action, hotkey.clone()?)));
+        std::iter::once((HotkeyAction::Panic, hotkeys.panic.clone()))
+            .chain(configured)
+            .collect()
+    }
+
+    /// Parses config text, with the `[machines.NAME]` table of `machine` laid
+    /// over the rest
+    ///
+    /// Tables in the machine's table merge key by key; anything else, lists
+    /// like `[[monitors]]` included, replaces what the file says.
+    pub fn parse(text: &str, machine: Option<&str>) -> Result<Self, toml::de::Error> {
+        let mut raw: toml::Table = toml::from_str(text)?;
+        let overrides = match (raw.remove("machines"), machine) {
+            (Some(toml::Value::Table(machines)), Some(machine)) => machines
+                .into_iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(machine))
+                .map(|(_, overrides)| overrides),
+            _ => None,
+        };
+        let Some(toml::Value::Table(overrides)) = overrides else {
+            // Straight from the text, so errors keep their line
+            return toml::from_str(text);
+        };
+        overlay(&mut raw, overrides);
+        toml::Value::Table(raw).try_into()
+    }
+
+    /// Reads and parses a config file; a missing file yields the defaults
+    pub fn read(path: &Path) -> Result<Self, toml::de::Error> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, Self::machine_name().as_deref()),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::default_path().filter(|p| p.exists()) else {
+            return Self::default();
+        };
+
+        warn_conflict_copies(&path);
+        match Self::read(&path) {
+            Ok(config) => {
+                info!("{}", tr!("config.loaded", path.display()));
+                config
+            }
+            Err(e) => {
+                warn!("{}", tr!("config.load_failed", path.display(), e));
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Lays the tables and values of `overrides` over `base`
+fn overlay(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match value {
+            toml::Value::Table(value) if base.get(&key).is_some_and(toml::Value::is_table) => {
+                if let Some(toml::Value::Table(base)) = base.get_mut(&key) {
+                    overlay(base, value);
+                }
+            }
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether `candidate` looks like a copy a sync client made of the config file
+/// `config` after conflicting edits on two PCs
+///
+/// OneDrive appends the PC name (`config-DESKTOP-1.toml`), Syncthing a
+/// `.sync-conflict-...` stamp and Dropbox `(... conflicted copy ...)`.
+pub fn is_conflict_copy(config: &str, candidate: &str) -> bool {
+    let (Some((stem, ext)), Some((other, other_ext))) =
+        (config.rsplit_once('.'), candidate.rsplit_once('.'))
+    else {
+        return false;
+    };
+    if !ext.eq_ignore_ascii_case(other_ext) || other.len() <= stem.len() {
+        return false;
+    }
+    let (start, rest) = other.split_at(stem.len());
+    start.eq_ignore_ascii_case(stem)
+        && (rest.starts_with('-')
+            || rest.starts_with(".sync-conflict-")
+            || (rest.starts_with(" (") && rest.contains("conflicted copy")))
+}
+
+/// Warns about sync conflict copies next to the config file, whose changes
+/// would otherwise go unnoticed
+pub fn warn_conflict_copies(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let name = name.to_string_lossy();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let candidate = entry.file_name();
+        if is_conflict_copy(&name, &candidate.to_string_lossy()) {
+            warn!(
+                "{}",
+                tr!("config.conflict_copy", entry.path().display(), name)
+            );
+        }
+    }
+}
+
+/// Appends a `[[rules]]` entry for an executable to the config file, creating
+/// the file if needed
+///
+/// The text is appended as-is, so comments and formatting elsewhere in the
+/// file survive.
+pub fn append_rule(path: &Path, process: &str, action: RuleAction) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(rule_entry(process, action).as_bytes())
+}
+
+/// The text [`append_rule`] adds to the end of a config file
+fn rule_entry(process: &str, action: RuleAction) -> String {
+    let action = match action {
+        RuleAction::Lock => "lock",
+        RuleAction::Ignore => "ignore",
+        RuleAction::ConfineTo => "confine_to",
+    };
+    format!(
+        "\n# Added from the \"lock this app in the future?\" prompt\n\
+         [[rules]]\nprocess = {}\naction = \"{}\"\n",
+        toml::Value::String(process.to_string()),
+        action
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    #[test]
+    fn margins_are_read_per_edge() {
+        let config = Config::parse("[cursor.margins]\nleft = 10\nbottom = -5\n", None).unwrap();
+        assert_eq!(
+            config.cursor.margins,
+            ClipMargins {
+                left: 10,
+                top: 0,
+                right: 0,
+                bottom: -5,
+            }
+        );
+    }
+
+    #[test]
+    fn margins_shrink_or_grow_the_clip() {
+        let margins = ClipMargins {
+            left: 10,
+            top: 20,
+            right: 30,
+            bottom: -40,
+        };
+        assert_eq!(
+            margins.apply(MONITOR),
+            RECT {
+                left: 10,
+                top: 20,
+                right: 1890,
+                bottom: 1120,
+            }
+        );
+        assert_eq!(ClipMargins::default().apply(MONITOR), MONITOR);
+    }
+
+    #[test]
+    fn margins_never_collapse_the_clip_below_one_pixel() {
+        let margins = ClipMargins {
+            left: 1500,
+            top: 0,
+            right: 1500,
+            bottom: 2000,
+        };
+        assert_eq!(
+            margins.apply(MONITOR),
+            RECT {
+                left: 1500,
+                top: 0,
+                right: 1501,
+                bottom: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn a_prompt_answer_is_appended_as_a_rule() {
+        let entry = rule_entry(r#"Odd "Game" \ 2.exe"#, RuleAction::Ignore);
+        // A blank line and a comment set it apart from what the user wrote
+        assert!(entry.starts_with(
+            "\n# Added from the \"lock this app in the future?\" prompt\n[[rules]]\n"
+        ));
+        assert!(entry.ends_with("\naction = \"ignore\"\n"));
+        let config = Config::parse(&entry, None).unwrap();
+        assert_eq!(
+            config.rules[0].process.as_deref(),
+            Some(r#"Odd "Game" \ 2.exe"#)
+        );
+        assert_eq!(config.rules[0].action, RuleAction::Ignore);
+    }
+
+    #[test]
+    fn appending_a_rule_keeps_the_rest_of_the_file() {
+        let dir = std::env::temp_dir().join(format!("slc-append-rule-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        // Comments, odd spacing and a table left open with no final newline
+        let existing = "# My setup\n\
+                        [[rules]]\n\
+                        process   =  'launcher.exe'   # the launcher\n\
+                        action = \"ignore\"\n\
+                        \n\
+                        [cursor]\n\
+                        resistance_ms = 450";
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, existing).unwrap();
+
+        append_rule(&path, "game.exe", RuleAction::Lock).unwrap();
+        append_rule(&path, "editor.exe", RuleAction::Ignore).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(text.starts_with(existing));
+        let config = Config::parse(&text, None).unwrap();
+        assert_eq!(config.cursor.resistance_ms, 450);
+        let rules: Vec<_> = config
+            .rules
+            .iter()
+            .map(|rule| (rule.process.as_deref().unwrap(), rule.action))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("launcher.exe", RuleAction::Ignore),
+                ("game.exe", RuleAction::Lock),
+                ("editor.exe", RuleAction::Ignore),
+            ]
+        );
+    }
+}
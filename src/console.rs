@@ -0,0 +1,254 @@
+//! Console status output
+//!
+//! All `[INFO]` and `[WARN]` lines go through [`info!`](crate::info) and
+//! [`warn!`](crate::warn). Consecutive identical lines collapse into one with a
+//! repeat counter, updated in place on a real console, so a game that keeps
+//! stealing the clip shows up as a single "re-applying (x240)" line instead of
+//! hundreds. Tags are colored when the console supports it, and `--quiet`
+//! silences everything but warnings. While the `--tui` dashboard owns the
+//! screen, lines are kept in memory for it instead of being printed.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use windows::Win32::System::Console::{
+    GetConsoleMode, GetStdHandle, SetConsoleMode, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+    STD_OUTPUT_HANDLE,
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static LAST: Mutex<Option<LastLine>> = Mutex::new(None);
+/// Recent lines and how many to keep, while captured
+static CAPTURED: Mutex<Option<(VecDeque<String>, usize)>> = Mutex::new(None);
+
+/// How important a line is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+}
+
+impl Level {
+    fn tag(self) -> &'static str {
+        match self {
+            Level::Info => "[INFO]",
+            Level::Warn => "[WARN]",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Level::Info => "\x1b[36m",
+            Level::Warn => "\x1b[33m",
+        }
+    }
+}
+
+/// The line printed last and how often it came in
+struct LastLine {
+    level: Level,
+    text: String,
+    count: u32,
+}
+
+/// Sets up the output; colors need a console with virtual terminal support
+pub fn init(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+    if std::env::var_os("NO_COLOR").is_some() {
+        return;
+    }
+    let enabled = unsafe {
+        GetStdHandle(STD_OUTPUT_HANDLE).is_ok_and(|handle| {
+            let mut mode = Default::default();
+            GetConsoleMode(handle, &mut mode).is_ok()
+                && SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING).is_ok()
+        })
+    };
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Keeps the last `limit` lines for [`recent`] instead of printing them
+pub fn capture(limit: usize) {
+    *CAPTURED.lock().unwrap_or_else(|e| e.into_inner()) = Some((VecDeque::new(), limit));
+}
+
+/// The captured lines, oldest first
+pub fn recent() -> Vec<String> {
+    CAPTURED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|(lines, _)| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Prints a tagged line, collapsing it into the previous one if it is the same
+pub fn log(level: Level, args: fmt::Arguments<'_>) {
+    if level == Level::Info && QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let text = args.to_string();
+    let color = COLOR.load(Ordering::Relaxed);
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((ref mut lines, limit)) = *CAPTURED.lock().unwrap_or_else(|e| e.into_inner()) {
+        keep(lines, limit, &mut last, level, text);
+        return;
+    }
+    print(&mut io::stdout().lock(), &mut last, level, text, color);
+}
+
+/// Adds a line to the captured ones, or bumps the count of the last one if
+/// it is the same
+fn keep(
+    lines: &mut VecDeque<String>,
+    limit: usize,
+    last: &mut Option<LastLine>,
+    level: Level,
+    text: String,
+) {
+    let line = match *last {
+        Some(ref mut previous) if previous.level == level && previous.text == text => {
+            previous.count += 1;
+            lines.pop_back();
+            format!("{} (x{})", tagged(level, &text, false), previous.count)
+        }
+        _ => {
+            let line = tagged(level, &text, false);
+            *last = Some(LastLine {
+                level,
+                text,
+                count: 1,
+            });
+            line
+        }
+    };
+    lines.push_back(line);
+    while lines.len() > limit {
+        lines.pop_front();
+    }
+}
+
+/// Writes a line to `out`, or bumps the count of the last one if it is the
+/// same: in place with colors, or as a "repeated" line once another comes in
+fn print(
+    out: &mut impl Write,
+    last: &mut Option<LastLine>,
+    level: Level,
+    text: String,
+    color: bool,
+) {
+    if let Some(ref mut previous) = *last {
+        if previous.level == level && previous.text == text {
+            previous.count += 1;
+            if color {
+                // Rewrite the line above with the new count
+                let _ = write!(out, "\x1b[1A\x1b[2K");
+                let _ = writeln!(out, "{} (x{})", tagged(level, &text, color), previous.count);
+            }
+            return;
+        }
+        if previous.count > 1 && !color {
+            let _ = writeln!(
+                out,
+                "{} (repeated {} times)",
+                tagged(previous.level, &previous.text, false),
+                previous.count
+            );
+        }
+    }
+
+    let _ = writeln!(out, "{}", tagged(level, &text, color));
+    *last = Some(LastLine {
+        level,
+        text,
+        count: 1,
+    });
+}
+
+fn tagged(level: Level, text: &str, color: bool) -> String {
+    if color {
+        format!("{}{}\x1b[0m {}", level.color(), level.tag(), text)
+    } else {
+        format!("{} {}", level.tag(), text)
+    }
+}
+
+/// Prints an `[INFO]` line, unless running with `--quiet`
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::console::log($crate::console::Level::Info, format_args!($($arg)*))
+    };
+}
+
+/// Prints a `[WARN]` line
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::console::log($crate::console::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(lines: &[(Level, &str)], color: bool) -> String {
+        let mut out = Vec::new();
+        let mut last = None;
+        for (level, text) in lines {
+            print(&mut out, &mut last, *level, text.to_string(), color);
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn repeats_collapse_into_one_line_without_color() {
+        let out = printed(
+            &[
+                (Level::Info, "re-applying"),
+                (Level::Info, "re-applying"),
+                (Level::Info, "re-applying"),
+                (Level::Warn, "re-applying"),
+                (Level::Info, "locked"),
+            ],
+            false,
+        );
+        assert_eq!(
+            out,
+            "[INFO] re-applying\n\
+             [INFO] re-applying (repeated 3 times)\n\
+             [WARN] re-applying\n\
+             [INFO] locked\n"
+        );
+        assert!(!out.contains('\x1b'));
+    }
+
+    #[test]
+    fn repeats_count_up_in_place_with_color() {
+        let out = printed(&[(Level::Warn, "stolen"), (Level::Warn, "stolen")], true);
+        assert_eq!(
+            out,
+            "\x1b[33m[WARN]\x1b[0m stolen\n\
+             \x1b[1A\x1b[2K\x1b[33m[WARN]\x1b[0m stolen (x2)\n"
+        );
+    }
+
+    #[test]
+    fn captured_repeats_replace_the_last_line() {
+        let mut lines = VecDeque::new();
+        let mut last = None;
+        for text in ["a", "b", "b", "b", "c"] {
+            keep(&mut lines, 3, &mut last, Level::Info, text.to_string());
+        }
+        assert_eq!(lines, ["[INFO] a", "[INFO] b (x3)", "[INFO] c"]);
+
+        keep(&mut lines, 3, &mut last, Level::Info, "d".to_string());
+        assert_eq!(lines, ["[INFO] b (x3)", "[INFO] c", "[INFO] d"]);
+    }
+}
@@ -0,0 +1,315 @@
+//! Hidden control window
+//!
+//! A never-shown top-level window that receives [`protocol`](crate::protocol)
+//! requests from other tools, global hotkeys and system notifications such as
+//! session changes, sleep/resume, display changes and logoff/shutdown. It
+//! belongs to the main thread, so the main loop pumps its messages in [`wait`]
+//! between ticks.
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::time::{Duration, Instant};
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{HANDLE, HWND, LPARAM, LRESULT, WAIT_OBJECT_0, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Power::{
+    RegisterSuspendResumeNotification, UnregisterSuspendResumeNotification, HPOWERNOTIFY,
+};
+use windows::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, WTSUnRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{RegisterHotKey, UnregisterHotKey};
+use windows::Win32::UI::WindowsAndMessaging::{
+    ChangeWindowMessageFilterEx, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW,
+    MsgWaitForMultipleObjects, PeekMessageW, RegisterClassW, RegisterWindowMessageW,
+    TranslateMessage, DEVICE_NOTIFY_WINDOW_HANDLE, HMENU, MSG, MSGFLT_ALLOW, PM_REMOVE,
+    QS_ALLINPUT, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DISPLAYCHANGE, WM_ENDSESSION, WM_HOTKEY,
+    WM_POWERBROADCAST, WM_WTSSESSION_CHANGE, WNDCLASSW,
+};
+
+use crate::hooks::take_pending_event;
+use crate::hotkey::{Hotkey, HotkeyAction};
+use crate::monitor::invalidate_monitors;
+use crate::protocol::{status_path, Reply, Request, MESSAGE_NAME, WINDOW_CLASS};
+use crate::tray::TrayCommand;
+
+thread_local! {
+    static HANDOFF_MESSAGE: Cell<u32> = const { Cell::new(0) };
+    static STATUS: Cell<Reply> = const { Cell::new(Reply::Unlocked) };
+    static PENDING: RefCell<Vec<ControlMessage>> = const { RefCell::new(Vec::new()) };
+    static REPORT: RefCell<Option<Box<dyn Fn() -> String>>> = const { RefCell::new(None) };
+    static SESSION_END: RefCell<Option<Box<dyn Fn()>>> = const { RefCell::new(None) };
+}
+
+/// Something the control window received that the main loop has to act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// A handoff request and the sender's process ID
+    Handoff(Request, u32),
+    /// A `WM_WTSSESSION_CHANGE` notification with its `WTS_*` code
+    SessionChange(u32),
+    /// The input desktop changed (UAC secure desktop, lock screen, ...)
+    DesktopSwitch,
+    /// A `WM_POWERBROADCAST` notification with its `PBT_*` code
+    Power(u32),
+    /// A registered hotkey was pressed
+    Hotkey(HotkeyAction),
+    /// Something was picked from the tray icon's menu or balloon
+    Tray(TrayCommand),
+}
+
+pub(crate) fn push_message(message: ControlMessage) {
+    PENDING.with(|p| p.borrow_mut().push(message));
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg != 0 && msg == HANDOFF_MESSAGE.with(Cell::get) {
+        let reply = match Request::from_wparam(wparam.0) {
+            // Answered right away so the sender never waits on the main loop
+            Some(Request::Query) => STATUS.with(Cell::get),
+            // Also answered in place, once the file is complete
+            Some(Request::WriteStatus) => write_status(),
+            Some(request) => {
+                push_message(ControlMessage::Handoff(request, lparam.0 as u32));
+                Reply::Ack
+            }
+            None => Reply::Unsupported,
+        };
+        return LRESULT(reply as isize);
+    }
+
+    if msg == WM_WTSSESSION_CHANGE {
+        push_message(ControlMessage::SessionChange(wparam.0 as u32));
+        return LRESULT(0);
+    }
+
+    if msg == WM_HOTKEY {
+        if let Some(action) = HotkeyAction::from_id(wparam.0 as i32) {
+            push_message(ControlMessage::Hotkey(action));
+        }
+        return LRESULT(0);
+    }
+
+    if msg == WM_DISPLAYCHANGE {
+        // Monitors were added, removed, moved or changed resolution
+        invalidate_monitors();
+        return LRESULT(0);
+    }
+
+    if msg == WM_ENDSESSION {
+        // Windows may end the process any time after this returns, so the
+        // main loop wouldn't get to it
+        if wparam.0 != 0 {
+            SESSION_END.with(|s| {
+                if let Some(handler) = s.borrow().as_ref() {
+                    handler();
+                }
+            });
+        }
+        return LRESULT(0);
+    }
+
+    if msg == WM_POWERBROADCAST {
+        push_message(ControlMessage::Power(wparam.0 as u32));
+        return LRESULT(1);
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+fn write_status() -> Reply {
+    let Some(report) = REPORT.with(|r| r.borrow().as_ref().map(|report| report())) else {
+        return Reply::Unsupported;
+    };
+    match status_path().map(|path| fs::write(path, report)) {
+        Some(Ok(())) => Reply::Ack,
+        _ => Reply::Unsupported,
+    }
+}
+
+/// The hidden window receiving handoff requests and notifications
+pub struct ControlWindow {
+    hwnd: HWND,
+    power_notify: Option<HPOWERNOTIFY>,
+    hotkeys: RefCell<Vec<HotkeyAction>>,
+}
+
+impl ControlWindow {
+    /// Registers the window class and creates the window on the current thread
+    pub fn create() -> windows::core::Result<Self> {
+        unsafe {
+            let instance = GetModuleHandleW(PCWSTR::null())?;
+            let class_name = HSTRING::from(WINDOW_CLASS);
+
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                &class_name,
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND::default(),
+                HMENU::default(),
+                instance,
+                None,
+            )?;
+
+            let message = RegisterWindowMessageW(&HSTRING::from(MESSAGE_NAME));
+            HANDOFF_MESSAGE.with(|m| m.set(message));
+
+            // Let non-elevated tools talk to an elevated instance
+            let _ = ChangeWindowMessageFilterEx(hwnd, message, MSGFLT_ALLOW, None);
+
+            // Remote desktop connects/disconnects, workstation lock, ...
+            let _ = WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION);
+
+            // Top-level windows get the classic sleep broadcasts anyway; this
+            // also delivers them on modern standby systems
+            let power_notify =
+                RegisterSuspendResumeNotification(HANDLE(hwnd.0), DEVICE_NOTIFY_WINDOW_HANDLE).ok();
+
+            Ok(Self {
+                hwnd,
+                power_notify,
+                hotkeys: RefCell::new(Vec::new()),
+            })
+        }
+    }
+
+    /// Sets the state reported to [`Request::Query`]
+    pub fn set_status(&self, status: Reply) {
+        STATUS.with(|s| s.set(status));
+    }
+
+    /// Sets what [`Request::WriteStatus`] writes to the status file
+    pub fn set_report(&self, report: impl Fn() -> String + 'static) {
+        REPORT.with(|r| *r.borrow_mut() = Some(Box::new(report)));
+    }
+
+    /// Sets what runs when the user logs off or Windows shuts down, right
+    /// inside the `WM_ENDSESSION` notification
+    pub fn set_session_end(&self, handler: impl Fn() + 'static) {
+        SESSION_END.with(|s| *s.borrow_mut() = Some(Box::new(handler)));
+    }
+
+    /// Registers a global hotkey for `action`, replacing any earlier one
+    pub fn register_hotkey(
+        &self,
+        action: HotkeyAction,
+        hotkey: &Hotkey,
+    ) -> windows::core::Result<()> {
+        self.unregister_hotkey(action);
+        unsafe { RegisterHotKey(self.hwnd, action.id(), hotkey.modifiers(), hotkey.key())? };
+        self.hotkeys.borrow_mut().push(action);
+        Ok(())
+    }
+
+    pub fn unregister_hotkey(&self, action: HotkeyAction) {
+        self.hotkeys.borrow_mut().retain(|&a| a != action);
+        unsafe {
+            let _ = UnregisterHotKey(self.hwnd, action.id());
+        }
+    }
+
+    /// The actions that currently have a hotkey registered
+    pub fn registered_hotkeys(&self) -> Vec<HotkeyAction> {
+        self.hotkeys.borrow().clone()
+    }
+
+    /// Takes the messages received since the last call
+    pub fn take_messages(&self) -> Vec<ControlMessage> {
+        PENDING.with(|p| std::mem::take(&mut *p.borrow_mut()))
+    }
+}
+
+impl Drop for ControlWindow {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(notify) = self.power_notify.take() {
+                let _ = UnregisterSuspendResumeNotification(notify);
+            }
+            for action in self.hotkeys.get_mut().drain(..) {
+                let _ = UnregisterHotKey(self.hwnd, action.id());
+            }
+            let _ = WTSUnRegisterSessionNotification(self.hwnd);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+/// Why [`wait`] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wake {
+    /// The timeout elapsed
+    Timeout,
+    /// A message is waiting in [`ControlWindow::take_messages`]
+    Message,
+    /// A WinEvent hook reported a relevant change
+    Event,
+    /// The handle at this index was signaled
+    Handle(usize),
+}
+
+/// Waits up to `timeout` while dispatching window messages, returning early
+/// when a control message or hook event arrives or one of `handles` is signaled
+pub fn wait(timeout: Duration, handles: &[HANDLE]) -> Wake {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Wake::Timeout;
+        }
+
+        let result = unsafe {
+            MsgWaitForMultipleObjects(
+                Some(handles),
+                false,
+                remaining.as_millis() as u32,
+                QS_ALLINPUT,
+            )
+        };
+
+        let index = result.0.wrapping_sub(WAIT_OBJECT_0.0) as usize;
+        if index < handles.len() {
+            return Wake::Handle(index);
+        }
+
+        pump_messages();
+
+        if PENDING.with(|p| !p.borrow().is_empty()) {
+            return Wake::Message;
+        }
+
+        if take_pending_event() {
+            return Wake::Event;
+        }
+    }
+}
+
+/// Dispatches all queued messages for the current thread
+fn pump_messages() {
+    unsafe {
+        let mut msg = MSG::default();
+        while PeekMessageW(&mut msg, HWND::default(), 0, 0, PM_REMOVE).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
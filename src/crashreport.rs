@@ -0,0 +1,249 @@
+//! Opt-in crash reports
+//!
+//! Bugs in the hooks or the control pipe tend to show up once a week on
+//! someone else's machine. With `[crash_reports] enabled = true` in a build
+//! with the `crash-reports` feature, a panic on any thread writes a report with
+//! the message, where it happened and a backtrace to [`reports_dir`]. Nothing
+//! leaves the machine on its own: with an `upload_url`, the next start asks
+//! before sending the reports it hasn't asked about yet.
+
+use std::backtrace::Backtrace;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, IDYES, MB_ICONWARNING, MB_YESNO};
+
+use crate::config::{Config, CrashReportsConfig};
+use crate::{info, tr, update, warn};
+
+/// Reports kept before the oldest ones are deleted
+pub const MAX_REPORTS: usize = 20;
+
+/// Suffix of reports that wait for the upload question
+const PENDING: &str = ".new.txt";
+
+/// Where reports are written
+pub fn reports_dir() -> Option<PathBuf> {
+    Config::default_dir().map(|dir| dir.join("crashes"))
+}
+
+/// Writes a report for every panic from now on, after the panic hook set
+/// before has released the cursor; does nothing without consent
+pub fn install(config: &CrashReportsConfig) {
+    let Some(dir) = reports_dir().filter(|_| config.enabled) else {
+        return;
+    };
+    let pending = config.upload_url.is_some();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |panic| {
+        previous(panic);
+        let thread = thread::current();
+        let report = format_report(
+            &message(panic),
+            panic.location().map(ToString::to_string).as_deref(),
+            thread.name(),
+            &Backtrace::force_capture(),
+        );
+        // Not through the console, whose lock the panicking thread may hold
+        match write_report(&dir, &report, pending) {
+            Ok(path) => eprintln!("{}", tr!("crash.written", path.display())),
+            Err(e) => eprintln!("{}", tr!("crash.write_failed", e)),
+        }
+    }));
+}
+
+/// Asks on a background thread whether to send the reports written since the
+/// last time it asked, if `upload_url` is set
+pub fn offer_upload(config: &CrashReportsConfig) {
+    let Some(url) = config.upload_url.clone().filter(|_| config.enabled) else {
+        return;
+    };
+    let Some(dir) = reports_dir() else {
+        return;
+    };
+    let reports = pending(&dir);
+    if reports.is_empty() {
+        return;
+    }
+
+    let spawned = thread::Builder::new()
+        .name("crash-upload".to_string())
+        .spawn(move || {
+            let host = url
+                .split_once("://")
+                .map_or(url.as_str(), |(_, rest)| rest)
+                .split('/')
+                .next()
+                .unwrap_or_default();
+            let text = tr!("crash.prompt_text", reports.len(), dir.display(), host);
+            let answer = unsafe {
+                MessageBoxW(
+                    HWND::default(),
+                    &HSTRING::from(text),
+                    &HSTRING::from(tr!("crash.prompt_title")),
+                    MB_YESNO | MB_ICONWARNING,
+                )
+            };
+            for report in reports {
+                if answer == IDYES {
+                    if let Err(e) = upload(&url, &report) {
+                        // Asked again next time
+                        warn!("{}", tr!("crash.send_failed", report.display(), e));
+                        continue;
+                    }
+                    info!("{}", tr!("crash.sent", report.display()));
+                }
+                let _ = fs::rename(&report, reviewed(&report));
+            }
+        });
+
+    if let Err(e) = spawned {
+        warn!("{}", tr!("crash.offer_failed", e));
+    }
+}
+
+fn upload(url: &str, report: &Path) -> Result<(), String> {
+    let body = fs::read(report).map_err(|e| e.to_string())?;
+    update::post(url, "text/plain; charset=utf-8", &body).map(|_| ())
+}
+
+/// The text of a report
+pub fn format_report(
+    message: &str,
+    location: Option<&str>,
+    thread: Option<&str>,
+    backtrace: &Backtrace,
+) -> String {
+    format!(
+        "SmartLockCursor {} crashed\n\
+         time: {}\n\
+         os: {} {}\n\
+         thread: {}\n\
+         panic: {}\n\
+         at: {}\n\
+         \n\
+         {}\n",
+        env!("CARGO_PKG_VERSION"),
+        unix_time(),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        thread.unwrap_or("unnamed"),
+        message,
+        location.unwrap_or("unknown"),
+        backtrace
+    )
+}
+
+fn message(panic: &PanicHookInfo) -> String {
+    let payload = panic.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(no message)".to_string())
+}
+
+/// Writes `report` to a new file in `dir`, marked for the upload question if
+/// `pending`, and deletes the oldest reports beyond [`MAX_REPORTS`]
+pub fn write_report(dir: &Path, report: &str, pending: bool) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let suffix = if pending { PENDING } else { ".txt" };
+    let time = unix_time();
+    // Two threads can panic within the same second
+    let (path, mut file) = (0..)
+        .map(|n| match n {
+            0 => dir.join(format!("crash-{}{}", time, suffix)),
+            n => dir.join(format!("crash-{}-{}{}", time, n, suffix)),
+        })
+        .find_map(
+            |path| match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => Some(Ok((path, file))),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(e)),
+            },
+        )
+        .expect("an unused file name")?;
+    file.write_all(report.as_bytes())?;
+
+    let mut reports = reports(dir);
+    if reports.len() > MAX_REPORTS {
+        reports.sort();
+        for old in &reports[..reports.len() - MAX_REPORTS] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(path)
+}
+
+/// Reports nobody was asked about yet
+pub fn pending(dir: &Path) -> Vec<PathBuf> {
+    reports(dir)
+        .into_iter()
+        .filter(|path| path.to_string_lossy().ends_with(PENDING))
+        .collect()
+}
+
+fn reports(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".txt"))
+        })
+        .collect()
+}
+
+/// `crash-1.new.txt` once it was asked about: `crash-1.txt`
+fn reviewed(report: &Path) -> PathBuf {
+    let name = report.to_string_lossy();
+    PathBuf::from(format!("{}.txt", name.trim_end_matches(PENDING)))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_reports_and_keeps_the_newest() {
+        let dir = std::env::temp_dir().join(format!("slc-crashes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let report = format_report(
+            "index out of bounds",
+            Some("src/hooks.rs:12:5"),
+            Some("control"),
+            &Backtrace::disabled(),
+        );
+        assert!(
+            report.contains("thread: control\npanic: index out of bounds\nat: src/hooks.rs:12:5")
+        );
+
+        let first = write_report(&dir, &report, true).unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), report);
+        assert_eq!(pending(&dir), std::slice::from_ref(&first));
+        fs::rename(&first, reviewed(&first)).unwrap();
+        assert!(pending(&dir).is_empty());
+        assert!(reviewed(&first).to_string_lossy().ends_with(".txt"));
+
+        for _ in 0..MAX_REPORTS + 3 {
+            write_report(&dir, &report, false).unwrap();
+        }
+        assert_eq!(reports(&dir).len(), MAX_REPORTS);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,176 @@
+//! Cursor clipping and positioning
+
+use std::mem::{size_of, zeroed};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, HINSTANCE, POINT, RECT, WIN32_ERROR};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, VIRTUAL_KEY, VK_LBUTTON, VK_MBUTTON, VK_RBUTTON,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    ClipCursor, CreateCursor, GetClipCursor, GetCursorInfo, GetCursorPos, SetCursorPos,
+    SetSystemCursor, SystemParametersInfoW, CURSORINFO, CURSOR_SHOWING, OCR_APPSTARTING, OCR_CROSS,
+    OCR_HAND, OCR_HELP, OCR_IBEAM, OCR_NO, OCR_NORMAL, OCR_SIZEALL, OCR_SIZENESW, OCR_SIZENS,
+    OCR_SIZENWSE, OCR_SIZEWE, OCR_UP, OCR_WAIT, SPI_SETCURSORS, SYSTEM_CURSOR_ID,
+    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+};
+
+use crate::monitor::{clamp_rect, rects_equal, virtual_screen_rect};
+
+/// Set while the system cursors are swapped for transparent ones
+static CURSORS_HIDDEN: AtomicBool = AtomicBool::new(false);
+
+/// Every standard cursor a window might show over the game
+const SYSTEM_CURSORS: [SYSTEM_CURSOR_ID; 14] = [
+    OCR_NORMAL,
+    OCR_IBEAM,
+    OCR_WAIT,
+    OCR_CROSS,
+    OCR_UP,
+    OCR_SIZENWSE,
+    OCR_SIZENESW,
+    OCR_SIZEWE,
+    OCR_SIZENS,
+    OCR_SIZEALL,
+    OCR_NO,
+    OCR_HAND,
+    OCR_APPSTARTING,
+    OCR_HELP,
+];
+
+/// Why the cursor isn't confined where asked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipError {
+    /// `ERROR_ACCESS_DENIED`: UIPI or the desktop keeps us away from the input
+    AccessDenied,
+    /// Accepted, but Windows reports the clip elsewhere, as DPI virtualization
+    /// does between processes of different DPI awareness
+    Moved(RECT),
+    /// Any other Win32 error code
+    Other(u32),
+}
+
+impl std::fmt::Display for ClipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AccessDenied => write!(f, "access denied"),
+            Self::Moved(r) => write!(
+                f,
+                "Windows moved the clip to ({}, {}) - ({}, {})",
+                r.left, r.top, r.right, r.bottom
+            ),
+            Self::Other(code) => write!(f, "error {}", code),
+        }
+    }
+}
+
+/// Clips the cursor to the specified rectangle, checking it landed there
+pub fn clip_cursor_to_rect(rect: &RECT) -> Result<(), ClipError> {
+    if let Err(e) = unsafe { ClipCursor(Some(rect)) } {
+        return Err(match WIN32_ERROR::from_error(&e) {
+            Some(ERROR_ACCESS_DENIED) => ClipError::AccessDenied,
+            Some(code) => ClipError::Other(code.0),
+            None => ClipError::Other(e.code().0 as u32),
+        });
+    }
+    // Windows clamps the clip to the screen, but moves it no further
+    let expected = clamp_rect(rect, &virtual_screen_rect());
+    match get_cursor_clip() {
+        Some(clip) if !rects_equal(&clip, &expected) => Err(ClipError::Moved(clip)),
+        _ => Ok(()),
+    }
+}
+
+/// Releases the cursor clip
+pub fn release_cursor_clip() -> bool {
+    unsafe { ClipCursor(None).is_ok() }
+}
+
+/// Gets the current clip rectangle (the whole virtual screen when nothing is clipped)
+pub fn get_cursor_clip() -> Option<RECT> {
+    unsafe {
+        let mut rect: RECT = zeroed();
+        GetClipCursor(&mut rect).ok().map(|_| rect)
+    }
+}
+
+/// Gets the current cursor position
+pub fn get_cursor_pos() -> Option<POINT> {
+    unsafe {
+        let mut point: POINT = zeroed();
+        GetCursorPos(&mut point).ok().map(|_| point)
+    }
+}
+
+/// Moves the cursor to the specified screen coordinates
+pub fn set_cursor_pos(x: i32, y: i32) -> bool {
+    unsafe { SetCursorPos(x, y).is_ok() }
+}
+
+/// Whether the cursor is currently drawn, i.e. the foreground app has not hidden it
+pub fn is_cursor_showing() -> bool {
+    unsafe {
+        let mut info = CURSORINFO {
+            cbSize: size_of::<CURSORINFO>() as u32,
+            ..zeroed()
+        };
+        GetCursorInfo(&mut info).is_ok() && info.flags.0 & CURSOR_SHOWING.0 != 0
+    }
+}
+
+/// Replaces the system cursors with transparent ones, hiding the cursor in
+/// every app until [`restore_system_cursors`]
+pub fn hide_system_cursors() -> bool {
+    // 32x32 monochrome: an all-ones AND mask and an all-zero XOR mask draw nothing
+    let and_mask = [0xFFu8; 32 * 4];
+    let xor_mask = [0u8; 32 * 4];
+    let mut hidden = false;
+    for id in SYSTEM_CURSORS {
+        unsafe {
+            let Ok(cursor) = CreateCursor(
+                HINSTANCE::default(),
+                0,
+                0,
+                32,
+                32,
+                and_mask.as_ptr().cast(),
+                xor_mask.as_ptr().cast(),
+            ) else {
+                continue;
+            };
+            // The system takes ownership of the cursor
+            hidden |= SetSystemCursor(cursor, id).is_ok();
+        }
+    }
+    if hidden {
+        CURSORS_HIDDEN.store(true, Ordering::SeqCst);
+    }
+    hidden
+}
+
+/// Reloads the user's cursor scheme if [`hide_system_cursors`] replaced it;
+/// safe to call from the panic hook and the Ctrl+C handler
+pub fn restore_system_cursors() {
+    if CURSORS_HIDDEN.swap(false, Ordering::SeqCst) {
+        reload_system_cursors();
+    }
+}
+
+/// Reloads the user's cursor scheme from the registry, undoing any replaced cursors
+pub fn reload_system_cursors() {
+    unsafe {
+        let _ = SystemParametersInfoW(
+            SPI_SETCURSORS,
+            0,
+            None,
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        );
+    }
+}
+
+/// Checks if a mouse button is held down or was pressed since the last call
+pub fn mouse_button_pressed() -> bool {
+    [VK_LBUTTON, VK_RBUTTON, VK_MBUTTON]
+        .iter()
+        .any(|&VIRTUAL_KEY(key)| unsafe { GetAsyncKeyState(key as i32) } as u16 & 0x8001 != 0)
+}
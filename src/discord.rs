@@ -0,0 +1,288 @@
+//! Discord Rich Presence of the lock state
+//!
+//! Shows "Cursor locked" with the game and the time since the lock engaged on
+//! the user's Discord profile, and "Cursor free" with the time since startup
+//! while nothing is locked, which doubles as a visible sign that SmartLockCursor
+//! is running. A background thread talks to the local Discord client over its
+//! IPC pipe (`\\.\pipe\discord-ipc-N`), retries while Discord is closed and
+//! re-sends the presence every minute so a restarted client picks it up again.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::commands::LockDetails;
+use crate::config::DiscordConfig;
+use crate::json;
+use crate::{info, tr, warn};
+
+/// How often the presence is sent again, and Discord looked for while closed
+const REFRESH: Duration = Duration::from_secs(60);
+/// Largest frame accepted from Discord
+const MAX_FRAME: u32 = 64 * 1024;
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+const OP_PING: u32 = 3;
+const OP_PONG: u32 = 4;
+
+/// A running presence updater
+#[derive(Debug)]
+pub struct DiscordPresence {
+    config: DiscordConfig,
+    /// When SmartLockCursor started, in Unix seconds
+    started: u64,
+    updates: Sender<Option<String>>,
+}
+
+impl DiscordPresence {
+    /// Starts the client thread for `[discord]`, or returns `Ok(None)` if no
+    /// application ID is configured
+    pub fn start(config: &DiscordConfig) -> Result<Option<Self>, String> {
+        let Some(client_id) = config.client_id.clone() else {
+            return Ok(None);
+        };
+        let (updates, pending) = mpsc::channel();
+        let presence = Self {
+            config: config.clone(),
+            started: unix_now(),
+            updates,
+        };
+        let initial = presence.activity(None);
+        thread::Builder::new()
+            .name("discord".to_string())
+            .spawn(move || run(client_id, initial, pending))
+            .map_err(|e| e.to_string())?;
+        Ok(Some(presence))
+    }
+
+    /// Shows the lock state; `details` is `None` once the cursor is free
+    pub fn publish_state(&self, details: Option<&LockDetails>) {
+        let _ = self.updates.send(self.activity(details));
+    }
+
+    /// The `activity` of a `SET_ACTIVITY` command, `None` to clear it
+    fn activity(&self, details: Option<&LockDetails>) -> Option<String> {
+        let (text, game, start) = match details {
+            Some(details) => (
+                tr!("discord.locked"),
+                Some(details.game.as_str())
+                    .filter(|game| self.config.show_game && !game.is_empty()),
+                unix_now(),
+            ),
+            None if self.config.show_unlocked => (tr!("discord.unlocked"), None, self.started),
+            None => return None,
+        };
+        let mut activity = format!(
+            "{{\"details\":{},\"timestamps\":{{\"start\":{}}}",
+            json::string(text),
+            start
+        );
+        if let Some(game) = game {
+            activity.push_str(&format!(",\"state\":{}", json::string(game)));
+        }
+        if let Some(ref image) = self.config.large_image {
+            activity.push_str(&format!(
+                ",\"assets\":{{\"large_image\":{},\"large_text\":\"SmartLockCursor\"}}",
+                json::string(image)
+            ));
+        }
+        activity.push('}');
+        Some(activity)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_secs())
+}
+
+/// Keeps the presence up to date until the updater is dropped
+fn run(client_id: String, mut activity: Option<String>, updates: Receiver<Option<String>>) {
+    let mut pipe: Option<File> = None;
+    let mut nonce = 0u64;
+    let mut warned = false;
+
+    loop {
+        if pipe.is_none() {
+            match connect(&client_id) {
+                Ok(connected) => {
+                    info!("{}", tr!("discord.connected"));
+                    pipe = Some(connected);
+                    warned = false;
+                }
+                Err(e) if !warned => {
+                    warn!("{}", tr!("discord.connect_failed", REFRESH.as_secs(), e));
+                    warned = true;
+                }
+                Err(_) => {}
+            }
+        }
+        if let Some(ref mut connected) = pipe {
+            nonce += 1;
+            if let Err(e) = set_activity(connected, activity.as_deref(), nonce) {
+                warn!("{}", tr!("discord.connection_lost", e));
+                pipe = None;
+            }
+        }
+
+        match updates.recv_timeout(REFRESH) {
+            Ok(update) => activity = update,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if let Some(ref mut connected) = pipe {
+                    let _ = set_activity(connected, None, nonce + 1);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Opens the first Discord IPC pipe that answers the handshake
+fn connect(client_id: &str) -> io::Result<File> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "Discord is not running");
+    for n in 0..10 {
+        let path = format!(r"\\.\pipe\discord-ipc-{}", n);
+        let Ok(mut pipe) = OpenOptions::new().read(true).write(true).open(path) else {
+            continue;
+        };
+        let handshake = format!("{{\"v\":1,\"client_id\":{}}}", json::string(client_id));
+        write_frame(&mut pipe, OP_HANDSHAKE, &handshake)?;
+        match read_reply(&mut pipe) {
+            Ok(reply) if reply.get("evt").and_then(json::Value::as_str) == Some("READY") => {
+                return Ok(pipe);
+            }
+            Ok(_) => last_error = io::Error::other("unexpected handshake reply"),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn set_activity(pipe: &mut File, activity: Option<&str>, nonce: u64) -> io::Result<()> {
+    let activity = activity.map_or(String::new(), |a| format!(",\"activity\":{}", a));
+    let command = format!(
+        "{{\"cmd\":\"SET_ACTIVITY\",\"args\":{{\"pid\":{}{}}},\"nonce\":\"{}\"}}",
+        std::process::id(),
+        activity,
+        nonce
+    );
+    write_frame(pipe, OP_FRAME, &command)?;
+    let reply = read_reply(pipe)?;
+    if reply.get("evt").and_then(json::Value::as_str) == Some("ERROR") {
+        let message = reply
+            .get("data")
+            .and_then(|data| data.get("message"))
+            .and_then(json::Value::as_str)
+            .unwrap_or(tr!("discord.unknown_error"));
+        warn!("{}", tr!("discord.rejected", message));
+    }
+    Ok(())
+}
+
+/// Reads frames until a reply arrives, answering pings on the way
+fn read_reply(pipe: &mut File) -> io::Result<json::Value> {
+    loop {
+        let (op, payload) = read_frame(pipe)?;
+        match op {
+            OP_FRAME => return json::parse(&payload).map_err(io::Error::other),
+            OP_PING => write_frame(pipe, OP_PONG, &payload)?,
+            OP_CLOSE => {
+                let message = json::parse(&payload)
+                    .ok()
+                    .and_then(|close| {
+                        close
+                            .get("message")
+                            .and_then(json::Value::as_str)
+                            .map(str::to_string)
+                    })
+                    .unwrap_or(payload);
+                return Err(io::Error::other(format!(
+                    "Discord closed the connection: {}",
+                    message
+                )));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn read_frame(pipe: &mut impl Read) -> io::Result<(u32, String)> {
+    let mut header = [0u8; 8];
+    pipe.read_exact(&mut header)?;
+    let op = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if len > MAX_FRAME {
+        return Err(io::Error::other("oversized frame"));
+    }
+    let mut payload = vec![0u8; len as usize];
+    pipe.read_exact(&mut payload)?;
+    Ok((op, String::from_utf8_lossy(&payload).into_owned()))
+}
+
+/// Writes a frame in one call, as Discord expects
+fn write_frame(pipe: &mut impl Write, op: u32, payload: &str) -> io::Result<()> {
+    pipe.write_all(&frame(op, payload))
+}
+
+fn frame(op: u32, payload: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + payload.len());
+    out.extend_from_slice(&op.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload.as_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presence(config: DiscordConfig) -> DiscordPresence {
+        DiscordPresence {
+            config,
+            started: 1000,
+            updates: mpsc::channel().0,
+        }
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let encoded = frame(OP_FRAME, "{}");
+        assert_eq!(encoded[..8], [1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(
+            read_frame(&mut encoded.as_slice()).unwrap(),
+            (OP_FRAME, "{}".to_string())
+        );
+    }
+
+    #[test]
+    fn names_the_locked_game() {
+        let presence = presence(DiscordConfig::default());
+        let details = LockDetails {
+            game: "ELDEN RING".to_string(),
+            ..Default::default()
+        };
+        let activity = presence.activity(Some(&details)).unwrap();
+        let activity = json::parse(&activity).unwrap();
+        assert_eq!(
+            activity.get("details").and_then(json::Value::as_str),
+            Some("Cursor locked")
+        );
+        assert_eq!(
+            activity.get("state").and_then(json::Value::as_str),
+            Some("ELDEN RING")
+        );
+
+        assert!(presence.activity(None).unwrap().contains("\"start\":1000"));
+        let hidden = self::presence(DiscordConfig {
+            show_unlocked: false,
+            ..Default::default()
+        });
+        assert_eq!(hidden.activity(None), None);
+    }
+}
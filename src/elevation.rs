@@ -0,0 +1,78 @@
+//! Administrator rights
+//!
+//! A non-elevated process can't reliably clip the cursor for, or query, an
+//! elevated game because of UIPI. This module detects the mismatch and can
+//! relaunch the executable with admin rights.
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND};
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+/// Checks if the current process runs with admin rights
+pub fn is_elevated() -> bool {
+    unsafe { token_elevated(GetCurrentProcess()) }.unwrap_or(false)
+}
+
+/// Checks if another process runs with admin rights
+///
+/// Returns `None` if its token can't be inspected, which from a non-elevated
+/// process usually means it is elevated (or protected).
+pub fn is_process_elevated(pid: u32) -> Option<bool> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let elevated = token_elevated(process);
+        let _ = CloseHandle(process);
+        elevated
+    }
+}
+
+unsafe fn token_elevated(process: HANDLE) -> Option<bool> {
+    let mut token = HANDLE::default();
+    OpenProcessToken(process, TOKEN_QUERY, &mut token).ok()?;
+
+    let mut elevation = TOKEN_ELEVATION::default();
+    let mut len = 0u32;
+    let result = GetTokenInformation(
+        token,
+        TokenElevation,
+        Some(&mut elevation as *mut _ as *mut _),
+        std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+        &mut len,
+    );
+    let _ = CloseHandle(token);
+    result.ok()?;
+    Some(elevation.TokenIsElevated != 0)
+}
+
+/// Starts the executable again with admin rights through the UAC prompt,
+/// passing `args` along
+///
+/// Returns false if the relaunch failed or the user declined the prompt.
+pub fn relaunch_elevated(args: &[String]) -> bool {
+    let Ok(exe) = std::env::current_exe() else {
+        return false;
+    };
+    let params = args
+        .iter()
+        .map(|arg| format!("\"{}\"", arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let result = unsafe {
+        ShellExecuteW(
+            HWND::default(),
+            &HSTRING::from("runas"),
+            &HSTRING::from(exe.as_os_str()),
+            &HSTRING::from(params),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes
+    result.0 as usize > 32
+}
@@ -0,0 +1,309 @@
+//! Persistent event history
+//!
+//! Appends every [`Event`] to a JSON Lines or CSV file with a timestamp and the
+//! window, process and monitor involved. The file is rotated once it grows past
+//! the configured size, keeping a few older generations next to it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, EventLogConfig, LogFormat};
+use crate::events::Event;
+use crate::json;
+
+/// One line of the log
+#[derive(Debug)]
+pub struct Record<'a> {
+    pub event: &'a Event,
+    pub hwnd: isize,
+    pub process: Option<String>,
+    pub monitor: Option<String>,
+}
+
+/// An open, size-limited event log file
+#[derive(Debug)]
+pub struct EventLog {
+    path: PathBuf,
+    format: LogFormat,
+    max_bytes: u64,
+    keep: u32,
+    file: File,
+    size: u64,
+}
+
+impl EventLog {
+    /// Opens (or creates) the log file configured in `[event_log]`
+    pub fn open(config: &EventLogConfig) -> io::Result<Self> {
+        let path = match config.path {
+            Some(ref path) => path.clone(),
+            None => Config::default_dir()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?
+                .join(match config.format {
+                    LogFormat::Jsonl => "events.jsonl",
+                    LogFormat::Csv => "events.csv",
+                }),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = open_append(&path)?;
+        let mut log = Self {
+            size: file.metadata()?.len(),
+            path,
+            format: config.format,
+            max_bytes: config.max_size_kb.saturating_mul(1024),
+            keep: config.keep,
+            file,
+        };
+        if log.size == 0 {
+            log.write_header()?;
+        }
+        Ok(log)
+    }
+
+    /// Where the log is written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a record, rotating the file first if it would grow too large
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let line = match self.format {
+            LogFormat::Jsonl => format_json(record),
+            LogFormat::Csv => format_csv(record),
+        };
+
+        if self.max_bytes > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if self.format == LogFormat::Csv {
+            let header = "time,kind,hwnd,process,monitor,message\n";
+            self.file.write_all(header.as_bytes())?;
+            self.size += header.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Shifts `events.jsonl` to `events.1.jsonl` and so on, dropping the oldest
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep > 0 {
+            let _ = fs::remove_file(self.generation(self.keep));
+            for i in (1..self.keep).rev() {
+                let _ = fs::rename(self.generation(i), self.generation(i + 1));
+            }
+            fs::rename(&self.path, self.generation(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.write_header()
+    }
+
+    fn generation(&self, i: u32) -> PathBuf {
+        let ext = self
+            .path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path.with_extension(format!("{}.{}", i, ext))
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Record<'_> {
+    /// The record as a single-line JSON object, as written to JSON Lines logs
+    pub fn to_json(&self) -> String {
+        let mut line = format_json(self);
+        line.pop();
+        line
+    }
+}
+
+fn format_json(record: &Record) -> String {
+    let mut line = format!(
+        "{{\"time\":\"{}\",\"kind\":\"{}\",\"hwnd\":\"{:#x}\"",
+        timestamp(),
+        record.event.kind(),
+        record.hwnd
+    );
+    if let Some(ref process) = record.process {
+        line.push_str(&format!(",\"process\":{}", json::string(process)));
+    }
+    if let Some(ref monitor) = record.monitor {
+        line.push_str(&format!(",\"monitor\":{}", json::string(monitor)));
+    }
+    line.push_str(&format!(
+        ",\"message\":{}}}\n",
+        json::string(&record.event.to_string())
+    ));
+    line
+}
+
+fn format_csv(record: &Record) -> String {
+    format!(
+        "{},{},{:#x},{},{},{}\n",
+        timestamp(),
+        record.event.kind(),
+        record.hwnd,
+        csv_field(record.process.as_deref().unwrap_or("")),
+        csv_field(record.monitor.as_deref().unwrap_or("")),
+        csv_field(&record.event.to_string())
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Current UTC time as ISO 8601 with milliseconds
+pub(crate) fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stolen() -> Event {
+        Event::ClipStolen {
+            hwnd: 0x100,
+            culprit: "overlay.exe".to_string(),
+        }
+    }
+
+    fn record(event: &Event) -> Record<'_> {
+        Record {
+            event,
+            hwnd: 0x100,
+            process: Some("game.exe".to_string()),
+            monitor: Some(r"\\.\DISPLAY1".to_string()),
+        }
+    }
+
+    fn open(dir: &Path, format: LogFormat, keep: u32) -> EventLog {
+        let _ = fs::remove_dir_all(dir);
+        EventLog::open(&EventLogConfig {
+            enabled: true,
+            format,
+            path: Some(dir.join(match format {
+                LogFormat::Jsonl => "events.jsonl",
+                LogFormat::Csv => "events.csv",
+            })),
+            max_size_kb: 1,
+            keep,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest_generations() {
+        let dir = std::env::temp_dir().join(format!("slc-eventlog-{}", std::process::id()));
+        let event = stolen();
+        let mut log = open(&dir, LogFormat::Jsonl, 2);
+        for _ in 0..60 {
+            log.write(&record(&event)).unwrap();
+        }
+        for name in ["events.jsonl", "events.1.jsonl", "events.2.jsonl"] {
+            let size = fs::metadata(dir.join(name)).unwrap().len();
+            assert!(size > 0 && size <= 1024, "{} is {} bytes", name, size);
+        }
+        assert!(!dir.join("events.3.jsonl").exists());
+        drop(log);
+
+        // Without generations to keep the full file is just started over
+        let mut log = open(&dir, LogFormat::Csv, 0);
+        for _ in 0..60 {
+            log.write(&record(&event)).unwrap();
+        }
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["events.csv"]);
+        let text = fs::read_to_string(dir.join("events.csv")).unwrap();
+        assert!(text.starts_with("time,kind,hwnd,process,monitor,message\n"));
+        assert!(text.len() <= 1024);
+        drop(log);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        for (value, field) in [
+            ("game.exe", "game.exe"),
+            ("", ""),
+            ("Game, Deluxe Edition", "\"Game, Deluxe Edition\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("two\nlines", "\"two\nlines\""),
+            ("carriage\rreturn", "\"carriage\rreturn\""),
+        ] {
+            assert_eq!(csv_field(value), field, "{:?}", value);
+        }
+
+        let event = stolen();
+        let mut record = record(&event);
+        record.process = Some("a,b.exe".to_string());
+        let line = format_csv(&record);
+        assert!(line.ends_with('\n'));
+        assert!(line.contains(r#",0x100,"a,b.exe",\\.\DISPLAY1,"#));
+    }
+
+    #[test]
+    fn writes_json_records_on_one_line() {
+        let event = stolen();
+        let line = record(&event).to_json();
+        assert!(!line.contains('\n'));
+        assert!(line.contains(r#","process":"game.exe","monitor":"\\\\.\\DISPLAY1","#));
+
+        let value = json::parse(&line).unwrap();
+        assert_eq!(
+            value.get("monitor").and_then(json::Value::as_str),
+            Some(r"\\.\DISPLAY1")
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Structured events emitted by the lock engine
+
+use std::fmt;
+use std::time::Duration;
+
+use windows::Win32::Foundation::RECT;
+
+use crate::config::{ClipTarget, LockBackend};
+use crate::steam::SteamApp;
+use crate::tr;
+
+/// Which shell switcher took the foreground
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Switcher {
+    /// The Alt+Tab switcher
+    #[default]
+    AltTab,
+    /// Task View (Win+Tab or the taskbar button), which stays up while the
+    /// mouse is used and can switch virtual desktops
+    TaskView,
+}
+
+/// How an Alt+Tab session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AltTabOutcome {
+    /// The user picked the fullscreen window that was locked before
+    Returned,
+    /// The user picked a different window, so the cursor stays free
+    SwitchedAway,
+    /// Nothing was locked or remembered when Alt+Tab started
+    NotLocked,
+}
+
+/// What brought the lock back after Alt+Tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelockTrigger {
+    /// Alt+Tab ended on the remembered fullscreen window
+    Returned,
+    /// The fullscreen window became the foreground window again
+    Activated,
+    /// The user clicked the fullscreen window (`require_click`)
+    Clicked,
+}
+
+/// Why a lock ended for good
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockReason {
+    /// The grace period ran out with no foreground window
+    NoForeground,
+    /// The grace period ran out with a non-fullscreen window in front
+    FullscreenExited,
+    /// A title change made the rules ignore the locked window
+    RulesChanged,
+    /// The locked window was minimized
+    Minimized,
+    /// The user switched to a virtual desktop without the locked window, or
+    /// moved the window to another one
+    OtherDesktop,
+    /// The locked window was cloaked, e.g. hidden by its app
+    Cloaked,
+    /// The locked window was hidden with `ShowWindow`
+    Hidden,
+    /// The locked window was destroyed, and its handle may soon belong to
+    /// another window
+    Closed,
+}
+
+/// Something noteworthy that happened in the lock engine
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Locked {
+        hwnd: isize,
+        rect: RECT,
+        target: ClipTarget,
+        backend: LockBackend,
+        /// The Steam game the window belongs to, if it was installed by Steam
+        steam_app: Option<SteamApp>,
+    },
+    Unlocked {
+        hwnd: isize,
+        reason: UnlockReason,
+    },
+    /// Another program changed or cleared the clip and it was re-applied
+    ClipStolen {
+        hwnd: isize,
+        culprit: String,
+    },
+    /// The locked game kept clearing the clip and was moved to the mouse hook
+    BackendEscalated {
+        process: String,
+        thefts: usize,
+        within: Duration,
+    },
+    /// Alt+Tab or Task View came up
+    AltTabStarted {
+        was_locked: bool,
+        switcher: Switcher,
+    },
+    AltTabEnded {
+        switcher: Switcher,
+        /// How long the switcher was up
+        duration: Duration,
+        chosen_hwnd: isize,
+        chosen_process: Option<String>,
+        outcome: AltTabOutcome,
+    },
+    Relocked {
+        trigger: RelockTrigger,
+        /// Time since Alt+Tab ended
+        after: Duration,
+    },
+    /// `remember_timeout_ms` passed without the user coming back
+    RememberedWindowExpired {
+        after: Duration,
+    },
+    /// The edge-escape gesture released the cursor for `release`
+    EdgeEscaped {
+        release: Duration,
+    },
+    /// The edge-escape release ran out
+    EdgeEscapeEnded,
+    /// "Unlock for 30s" in the tray freed the cursor for `release`
+    TimedUnlock {
+        release: Duration,
+    },
+    /// The tray unlock ran out
+    TimedUnlockEnded,
+    /// The manual lock hotkey or `lock` command confined the cursor to a monitor
+    ManualLocked {
+        rect: RECT,
+    },
+    /// The cycle hotkey moved the manual lock to another monitor
+    ManualLockMoved {
+        rect: RECT,
+    },
+    ManualUnlocked,
+}
+
+impl Event {
+    /// Stable snake_case name, used in the event log
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::Locked { .. } => "locked",
+            Event::Unlocked { .. } => "unlocked",
+            Event::ClipStolen { .. } => "clip_stolen",
+            Event::BackendEscalated { .. } => "backend_escalated",
+            Event::AltTabStarted { .. } => "alt_tab_started",
+            Event::AltTabEnded { .. } => "alt_tab_ended",
+            Event::Relocked { .. } => "relocked",
+            Event::RememberedWindowExpired { .. } => "remembered_window_expired",
+            Event::EdgeEscaped { .. } => "edge_escaped",
+            Event::EdgeEscapeEnded => "edge_escape_ended",
+            Event::TimedUnlock { .. } => "timed_unlock",
+            Event::TimedUnlockEnded => "timed_unlock_ended",
+            Event::ManualLocked { .. } => "manual_locked",
+            Event::ManualLockMoved { .. } => "manual_lock_moved",
+            Event::ManualUnlocked => "manual_unlocked",
+        }
+    }
+
+    /// The window the event is about, if it names one
+    pub fn hwnd(&self) -> Option<isize> {
+        match self {
+            Event::Locked { hwnd, .. }
+            | Event::Unlocked { hwnd, .. }
+            | Event::ClipStolen { hwnd, .. } => Some(*hwnd),
+            Event::AltTabEnded { chosen_hwnd, .. } => Some(*chosen_hwnd),
+            _ => None,
+        }
+    }
+
+    /// Whether the event is logged as a warning
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            Event::ClipStolen { .. } | Event::BackendEscalated { .. }
+        )
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Locked {
+                rect,
+                target,
+                backend,
+                steam_app,
+                ..
+            } => {
+                let target = match target {
+                    ClipTarget::Monitor => tr!("target.monitor"),
+                    ClipTarget::Window => tr!("target.window"),
+                    ClipTarget::WorkArea => tr!("target.work_area"),
+                };
+                let backend = match backend {
+                    LockBackend::Clip => "",
+                    LockBackend::Hook => tr!("backend.hook"),
+                    LockBackend::Soft => tr!("backend.soft"),
+                };
+                f.write_str(&tr!("event.locked", target, span(rect), backend))?;
+                match steam_app {
+                    Some(app) => f.write_str(&tr!("event.locked.steam_app", app.name, app.id)),
+                    None => Ok(()),
+                }
+            }
+            Event::Unlocked { reason, .. } => f.write_str(match reason {
+                UnlockReason::NoForeground => tr!("event.unlocked.no_foreground"),
+                UnlockReason::FullscreenExited => tr!("event.unlocked.fullscreen_exited"),
+                UnlockReason::RulesChanged => tr!("event.unlocked.rules_changed"),
+                UnlockReason::Minimized => tr!("event.unlocked.minimized"),
+                UnlockReason::OtherDesktop => tr!("event.unlocked.other_desktop"),
+                UnlockReason::Cloaked => tr!("event.unlocked.cloaked"),
+                UnlockReason::Hidden => tr!("event.unlocked.hidden"),
+                UnlockReason::Closed => tr!("event.unlocked.closed"),
+            }),
+            Event::ClipStolen { culprit, .. } => f.write_str(&tr!("event.clip_stolen", culprit)),
+            Event::BackendEscalated {
+                process,
+                thefts,
+                within,
+            } => f.write_str(&tr!(
+                "event.backend_escalated",
+                process,
+                thefts,
+                seconds(*within)
+            )),
+            Event::AltTabStarted {
+                was_locked,
+                switcher,
+            } => f.write_str(match (switcher, was_locked) {
+                (Switcher::AltTab, true) => tr!("event.alt_tab_started.locked"),
+                (Switcher::AltTab, false) => tr!("event.alt_tab_started"),
+                (Switcher::TaskView, true) => tr!("event.task_view_started.locked"),
+                (Switcher::TaskView, false) => tr!("event.task_view_started"),
+            }),
+            Event::AltTabEnded {
+                switcher,
+                duration,
+                chosen_hwnd,
+                chosen_process,
+                outcome,
+            } => {
+                let process = chosen_process
+                    .as_deref()
+                    .unwrap_or(tr!("event.unknown_process"));
+                let ended = match switcher {
+                    Switcher::AltTab => "event.alt_tab_ended",
+                    Switcher::TaskView => "event.task_view_ended",
+                };
+                f.write_str(&tr!(ended, seconds(*duration)))?;
+                match outcome {
+                    AltTabOutcome::Returned => {
+                        f.write_str(&tr!("event.alt_tab_ended.returned", process))
+                    }
+                    AltTabOutcome::SwitchedAway => f.write_str(&tr!(
+                        "event.alt_tab_ended.switched_away",
+                        process,
+                        format!("{:#x}", chosen_hwnd)
+                    )),
+                    AltTabOutcome::NotLocked => Ok(()),
+                }
+            }
+            Event::Relocked { trigger, after } => {
+                let cause = match trigger {
+                    RelockTrigger::Returned => tr!("relock.returned"),
+                    RelockTrigger::Activated => tr!("relock.activated"),
+                    RelockTrigger::Clicked => tr!("relock.clicked"),
+                };
+                f.write_str(&tr!("event.relocked", seconds(*after), cause))
+            }
+            Event::RememberedWindowExpired { after } => {
+                f.write_str(&tr!("event.remembered_window_expired", seconds(*after)))
+            }
+            Event::EdgeEscaped { release } => {
+                f.write_str(&tr!("event.edge_escaped", seconds(*release)))
+            }
+            Event::EdgeEscapeEnded => f.write_str(tr!("event.edge_escape_ended")),
+            Event::TimedUnlock { release } => {
+                f.write_str(&tr!("event.timed_unlock", seconds(*release)))
+            }
+            Event::TimedUnlockEnded => f.write_str(tr!("event.timed_unlock_ended")),
+            Event::ManualLocked { rect } => f.write_str(&tr!("event.manual_locked", span(rect))),
+            Event::ManualLockMoved { rect } => {
+                f.write_str(&tr!("event.manual_lock_moved", span(rect)))
+            }
+            Event::ManualUnlocked => f.write_str(tr!("event.manual_unlocked")),
+        }
+    }
+}
+
+/// `(left, top) - (right, bottom)`
+fn span(rect: &RECT) -> String {
+    format!(
+        "({}, {}) - ({}, {})",
+        rect.left, rect.top, rect.right, rect.bottom
+    )
+}
+
+/// Seconds with one decimal
+fn seconds(duration: Duration) -> String {
+    format!("{:.1}", duration.as_secs_f32())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{set_language, Language};
+
+    fn ended(outcome: AltTabOutcome, chosen_process: Option<&str>) -> Event {
+        Event::AltTabEnded {
+            switcher: Switcher::AltTab,
+            duration: Duration::from_millis(1300),
+            chosen_hwnd: 0x200,
+            chosen_process: chosen_process.map(String::from),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn describes_how_alt_tab_ended() {
+        set_language(Language::En);
+        assert_eq!(
+            ended(AltTabOutcome::Returned, Some("Game.exe")).to_string(),
+            "Alt+Tab ended after 1.3s - returned to fullscreen window (Game.exe)"
+        );
+        assert_eq!(
+            ended(AltTabOutcome::SwitchedAway, None).to_string(),
+            "Alt+Tab ended after 1.3s - switched to unknown process (hwnd 0x200), cursor stays free"
+        );
+        assert_eq!(
+            ended(AltTabOutcome::NotLocked, Some("chrome.exe")).to_string(),
+            "Alt+Tab ended after 1.3s"
+        );
+        let relocked = Event::Relocked {
+            trigger: RelockTrigger::Activated,
+            after: Duration::from_millis(300),
+        };
+        assert_eq!(
+            relocked.to_string(),
+            "Cursor re-locked 0.3s after Alt+Tab (fullscreen window activated)"
+        );
+    }
+
+    #[test]
+    fn names_alt_tab_events_and_the_window_chosen() {
+        let event = ended(AltTabOutcome::SwitchedAway, Some("chrome.exe"));
+        assert_eq!(event.kind(), "alt_tab_ended");
+        assert_eq!(event.hwnd(), Some(0x200));
+        assert!(!event.is_warning());
+        let started = Event::AltTabStarted {
+            was_locked: true,
+            switcher: Switcher::TaskView,
+        };
+        assert_eq!(started.kind(), "alt_tab_started");
+        assert_eq!(started.hwnd(), None);
+    }
+}
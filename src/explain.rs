@@ -0,0 +1,325 @@
+//! "Why not locked?" diagnostics
+//!
+//! Walks through the same checks the lock engine makes for a window and
+//! reports which one decided the outcome, either for a single window or as a
+//! table of all top-level windows.
+
+use std::fmt::Write;
+
+use windows::Win32::Foundation::{HWND, RECT};
+
+use crate::config::{ClipTarget, Config, MonitorLock};
+use crate::events::Switcher;
+use crate::monitor::{
+    coverage, covers_share, get_all_monitors, get_window_monitor_rect, monitor_policy,
+    FRAME_OVERHANG,
+};
+use crate::process::{get_process_name, get_process_path};
+use crate::rules::{RuleSet, Verdict, WindowInfo};
+use crate::window::{
+    analyze_fullscreen, check_fullscreen, check_work_area, enumerate_windows, get_class_name,
+    get_task_switcher, get_window_process_id, get_window_title, resolve_hosted_window,
+};
+
+/// Builds a human readable report for `hwnd`
+pub fn explain(hwnd: HWND, config: &Config) -> String {
+    let mut out = String::new();
+
+    if hwnd.0.is_null() {
+        out.push_str("No foreground window, nothing would be locked\n");
+        return out;
+    }
+
+    let class = get_class_name(hwnd).unwrap_or_default();
+    let title = get_window_title(hwnd);
+    let pid = get_window_process_id(hwnd);
+    let process = get_process_path(pid)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let _ = writeln!(out, "Window:   {:#x}", hwnd.0 as isize);
+    let _ = writeln!(out, "Class:    {}", class);
+    let _ = writeln!(out, "Title:    \"{}\"", title);
+    let _ = writeln!(out, "Process:  {} (pid {})", process, pid);
+    let hosted = resolve_hosted_window(hwnd);
+    if hosted != hwnd {
+        let _ = writeln!(
+            out,
+            "Hosted:   {:#x} (UWP core window, used for the checks below)",
+            hosted.0 as isize
+        );
+    }
+
+    match get_task_switcher(hwnd) {
+        Some(Switcher::AltTab) => {
+            out.push_str("\nThis is the Alt+Tab task switcher, which always releases the cursor\n");
+            return out;
+        }
+        Some(Switcher::TaskView) => {
+            out.push_str("\nThis is Task View, which always releases the cursor\n");
+            return out;
+        }
+        None => {}
+    }
+
+    let rules = RuleSet::from_config(config);
+    let info = WindowInfo::query(hwnd, &rules);
+    if !info.ancestors.is_empty() {
+        let _ = writeln!(out, "Parents:  {}", info.ancestors.join(" <- "));
+    }
+    if let Some(ref product) = info.product {
+        let _ = writeln!(out, "Product:  {}", product);
+    }
+    if rules.is_projector(&info) {
+        out.push_str(
+            "\nThis is an OBS projector, which neither starts nor ends a lock ([obs] hold_for_projectors)\n",
+        );
+        return out;
+    }
+    if rules.is_transient(&info) {
+        out.push_str(
+            "\nThis is a transient window from [detection], which neither starts nor ends a lock\n",
+        );
+        return out;
+    }
+
+    let policy = get_window_monitor_rect(hwnd).map_or(MonitorLock::Auto, |rect| {
+        monitor_policy(&config.monitors, &get_all_monitors(), &rect)
+    });
+    let verdict = match policy {
+        MonitorLock::Auto => rules.evaluate(&info),
+        MonitorLock::Never => {
+            out.push_str("\nResult: not locked, a [[monitors]] entry sets lock = \"never\" for its monitor\n");
+            return out;
+        }
+        MonitorLock::Always => {
+            out.push_str(
+                "\nA [[monitors]] entry sets lock = \"always\", so the rules are skipped\n",
+            );
+            Verdict::Lock
+        }
+    };
+    if verdict == Verdict::Lock && rules.overrides(&info).clip_to == Some(ClipTarget::Window) {
+        out.push_str(
+            "\nResult: locked to the window's client area, a rule sets clip_to = \"window\"\n",
+        );
+        return out;
+    }
+
+    let known_game = rules.is_known_game(&info);
+    if known_game {
+        out.push_str("Game:     known engine class or launcher, looser tolerance\n");
+    }
+    let tolerance = if known_game {
+        config
+            .detection
+            .tolerance
+            .max(config.detection.known_game_tolerance)
+    } else {
+        config.detection.tolerance
+    };
+    if verdict == Verdict::Lock && rules.overrides(&info).clip_to == Some(ClipTarget::WorkArea) {
+        match check_work_area(hwnd, tolerance) {
+            Some(work) => {
+                let _ = writeln!(
+                    out,
+                    "\nResult: locked to the monitor work area {}, a rule sets clip_to = \"work_area\"",
+                    format_rect(&work)
+                );
+            }
+            None => out.push_str(
+                "\nResult: not locked, a rule sets clip_to = \"work_area\" but the window does not fill the work area\n",
+            ),
+        }
+        return out;
+    }
+    let Some(check) = analyze_fullscreen(hwnd, tolerance) else {
+        out.push_str("\nCould not query the window or monitor geometry\n");
+        return out;
+    };
+
+    let _ = writeln!(out, "Window:   {}", format_rect(&check.window_rect));
+    let _ = writeln!(out, "Monitor:  {}", format_rect(&check.monitor_rect));
+
+    let window_width = check.window_rect.right - check.window_rect.left;
+    let window_height = check.window_rect.bottom - check.window_rect.top;
+    let monitor_width = check.monitor_rect.right - check.monitor_rect.left;
+    let monitor_height = check.monitor_rect.bottom - check.monitor_rect.top;
+
+    let _ = writeln!(out, "\nFullscreen conditions (tolerance {}px):", tolerance);
+    let _ = writeln!(out, "  Exact match:");
+    let _ = writeln!(
+        out,
+        "    {} width   {} vs {}",
+        mark(check.width_match),
+        window_width,
+        monitor_width
+    );
+    let _ = writeln!(
+        out,
+        "    {} height  {} vs {}",
+        mark(check.height_match),
+        window_height,
+        monitor_height
+    );
+    let _ = writeln!(
+        out,
+        "    {} left    {} vs {}",
+        mark(check.left_match),
+        check.window_rect.left,
+        check.monitor_rect.left
+    );
+    let _ = writeln!(
+        out,
+        "    {} top     {} vs {}",
+        mark(check.top_match),
+        check.window_rect.top,
+        check.monitor_rect.top
+    );
+    let _ = writeln!(out, "  Or:");
+    let _ = writeln!(
+        out,
+        "    {} window covers every monitor edge, and no other monitor",
+        mark(check.covers_monitor)
+    );
+    let _ = writeln!(out, "  Or:");
+    match check.spanned_rect {
+        Some(rect) => {
+            let _ = writeln!(
+                out,
+                "    {} window spans several monitors: {}",
+                mark(true),
+                format_rect(&rect)
+            );
+        }
+        None => {
+            let _ = writeln!(
+                out,
+                "    {} window spans several monitors exactly",
+                mark(false)
+            );
+        }
+    }
+
+    let covered = config.detection.min_coverage.is_some_and(|min_coverage| {
+        let share = coverage(&check.window_rect, &check.monitor_rect);
+        let covered = covers_share(
+            &get_all_monitors(),
+            &check.monitor_rect,
+            &check.window_rect,
+            min_coverage,
+            tolerance.max(FRAME_OVERHANG),
+        );
+        let _ = writeln!(out, "  Or:");
+        let _ = writeln!(
+            out,
+            "    {} window covers {:.1}% of the monitor, min_coverage is {:.1}%",
+            mark(covered),
+            share * 100.0,
+            min_coverage * 100.0
+        );
+        covered
+    });
+
+    if !check.is_fullscreen() && !covered {
+        out.push_str("\nResult: not locked, the window is not fullscreen\n");
+        return out;
+    }
+
+    match verdict {
+        Verdict::Lock if check.spanned_rect.is_some() => out.push_str(
+            "\nResult: locked to all spanned monitors, the window is fullscreen across them\n",
+        ),
+        Verdict::Lock => out.push_str("\nResult: locked, the window is fullscreen\n"),
+        Verdict::Ignore if !rules.has_match(&info) && rules.script_verdict(&info).is_some() => {
+            out.push_str("\nResult: not locked, the detection script ignores this window\n")
+        }
+        Verdict::Ignore if config.detection.whitelist_only => out.push_str(
+            "\nResult: not locked, no rule allows this window and whitelist_only is set\n",
+        ),
+        Verdict::Ignore if rules.is_browser(&info) && !rules.has_match(&info) => out.push_str(
+            "\nResult: not locked, browsers are ignored unless a rule locks them (see ignore_browsers)\n",
+        ),
+        Verdict::Ignore if config.detection.only_known_games && !rules.has_match(&info) => out
+            .push_str(
+                "\nResult: not locked, only_known_games is set and this is not a known game\n",
+            ),
+        Verdict::Ignore => out.push_str("\nResult: not locked, a rule ignores this window\n"),
+    }
+    out
+}
+
+/// Builds a table of the top-level windows and how each would be classified
+pub fn list_windows(include_hidden: bool, config: &Config) -> String {
+    let rules = RuleSet::from_config(config);
+    let tolerance = config.detection.tolerance;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{:<10} {:<5} {:<6} {:<26} {:<20} {:<32} TITLE",
+        "HWND", "FULL", "RULE", "RECT", "PROCESS", "CLASS"
+    );
+
+    for hwnd in enumerate_windows(include_hidden) {
+        let class = get_class_name(hwnd).unwrap_or_default();
+        let title = get_window_title(hwnd);
+        let info = WindowInfo::query(hwnd, &rules);
+        let process =
+            get_process_name(get_window_process_id(hwnd)).unwrap_or_else(|| "?".to_string());
+        let rect = analyze_fullscreen(hwnd, tolerance)
+            .map(|check| {
+                let r = check.window_rect;
+                format!(
+                    "{},{} {}x{}",
+                    r.left,
+                    r.top,
+                    r.right - r.left,
+                    r.bottom - r.top
+                )
+            })
+            .unwrap_or_else(|| "?".to_string());
+        let fullscreen = if check_fullscreen(hwnd, tolerance).is_some() {
+            "yes"
+        } else {
+            "no"
+        };
+        let verdict = match rules.evaluate(&info) {
+            Verdict::Lock => "lock",
+            Verdict::Ignore => "ignore",
+        };
+
+        let _ = writeln!(
+            out,
+            "{:<10} {:<5} {:<6} {:<26} {:<20} {:<32} {}",
+            format!("{:#x}", hwnd.0 as isize),
+            fullscreen,
+            verdict,
+            rect,
+            process,
+            class,
+            title
+        );
+    }
+    out
+}
+
+fn mark(ok: bool) -> &'static str {
+    if ok {
+        "[x]"
+    } else {
+        "[ ]"
+    }
+}
+
+fn format_rect(rect: &RECT) -> String {
+    format!(
+        "({}, {}) - ({}, {}), {}x{}",
+        rect.left,
+        rect.top,
+        rect.right,
+        rect.bottom,
+        rect.right - rect.left,
+        rect.bottom - rect.top
+    )
+}
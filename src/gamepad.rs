@@ -0,0 +1,89 @@
+//! Controller activity detection
+//!
+//! Polls XInput controllers and remembers when one was last actually used, so
+//! the lock can stay out of the way while playing with a gamepad. Polling a
+//! slot with no controller is surprisingly slow, so empty slots are only
+//! re-checked every few seconds.
+
+use std::time::{Duration, Instant};
+
+use windows::Win32::UI::Input::XboxController::{
+    XInputGetState, XINPUT_GAMEPAD, XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE,
+    XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE, XINPUT_GAMEPAD_TRIGGER_THRESHOLD, XINPUT_STATE,
+};
+
+const SLOTS: usize = 4;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const SCAN_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Tracks the connected controllers and their last real input
+#[derive(Debug, Default)]
+pub struct GamepadMonitor {
+    /// Last packet number per slot, `None` for empty slots
+    packets: [Option<u32>; SLOTS],
+    last_poll: Option<Instant>,
+    last_scan: Option<Instant>,
+    last_input: Option<Instant>,
+}
+
+impl GamepadMonitor {
+    /// Polls the controllers (at most every 250ms) and returns when one was
+    /// last used, ignoring stick drift inside the dead zones
+    pub fn poll(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .is_some_and(|last| now - last < POLL_INTERVAL)
+        {
+            return self.last_input;
+        }
+        self.last_poll = Some(now);
+
+        let scan = self
+            .last_scan
+            .is_none_or(|last| now - last >= SCAN_INTERVAL);
+        if scan {
+            self.last_scan = Some(now);
+        }
+
+        for (slot, packet) in self.packets.iter_mut().enumerate() {
+            if packet.is_none() && !scan {
+                continue;
+            }
+
+            let mut state = XINPUT_STATE::default();
+            if unsafe { XInputGetState(slot as u32, &mut state) } != 0 {
+                *packet = None;
+                continue;
+            }
+
+            // A new packet only means something changed; count it if the
+            // controller is actually being held in a non-resting position
+            let changed = packet.is_some_and(|p| p != state.dwPacketNumber);
+            *packet = Some(state.dwPacketNumber);
+            if changed && is_active(&state.Gamepad) {
+                self.last_input = Some(now);
+            }
+        }
+        self.last_input
+    }
+}
+
+fn is_active(pad: &XINPUT_GAMEPAD) -> bool {
+    let outside = |x: i16, y: i16, deadzone: u16| {
+        i32::from(x).abs() > i32::from(deadzone) || i32::from(y).abs() > i32::from(deadzone)
+    };
+    pad.wButtons.0 != 0
+        || pad.bLeftTrigger > XINPUT_GAMEPAD_TRIGGER_THRESHOLD.0 as u8
+        || pad.bRightTrigger > XINPUT_GAMEPAD_TRIGGER_THRESHOLD.0 as u8
+        || outside(
+            pad.sThumbLX,
+            pad.sThumbLY,
+            XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE.0,
+        )
+        || outside(
+            pad.sThumbRX,
+            pad.sThumbRY,
+            XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE.0,
+        )
+}
@@ -0,0 +1,43 @@
+//! Built-in table of known games
+//!
+//! Window classes of common game engines and the launchers games are started
+//! from. A window matching either is a game with much more certainty than one
+//! that merely fills a monitor: it gets a looser fullscreen tolerance, and with
+//! `only_known_games` nothing else is locked. `[detection]` can add entries.
+
+/// Exact window classes registered by game engines
+pub const ENGINE_CLASSES: &[&str] = &[
+    // Unreal Engine 3 to 5
+    "UnrealWindow",
+    "LaunchUnrealUWindowsClient",
+    "UnityWndClass",
+    // Also used by Ren'Py, FNA and most SDL2 ports
+    "SDL_app",
+    "CryENGINE",
+    // RPG Maker XP, VX and VX Ace
+    "RGSS Player",
+    // Source engine
+    "Valve001",
+    // GLFW and LWJGL (Minecraft Java Edition)
+    "GLFW30",
+    "LWJGL",
+    "YYGameMakerYY",
+    "OgreD3D11Wnd",
+    "Respawn001",
+    "Riot Window Class",
+    "POEWindowClass",
+];
+
+/// Executables (lowercased) whose child processes are games
+pub const LAUNCHERS: &[&str] = &[
+    "steam.exe",
+    "epicgameslauncher.exe",
+    "galaxyclient.exe",
+    "battle.net.exe",
+    "eadesktop.exe",
+    "origin.exe",
+    "upc.exe",
+    "riotclientservices.exe",
+    "playnite.desktopapp.exe",
+    "playnite.fullscreenapp.exe",
+];
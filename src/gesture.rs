@@ -0,0 +1,117 @@
+//! Edge-escape gesture
+//!
+//! Hitting the edge of the clip rect twice in quick succession temporarily
+//! breaks out of the lock. The cursor position is sampled every tick, so a
+//! "hit" is a sample at the edge following one that was not.
+
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::{POINT, RECT};
+
+/// Tracks edge hits between ticks
+#[derive(Debug, Default)]
+pub struct EdgeEscape {
+    was_at_edge: bool,
+    last_hit: Option<Instant>,
+}
+
+impl EdgeEscape {
+    /// Feeds a cursor sample and returns true when the gesture completed
+    pub fn sample(&mut self, pos: POINT, clip: &RECT, now: Instant, double_hit: Duration) -> bool {
+        let at_edge = pos.x <= clip.left
+            || pos.x >= clip.right - 1
+            || pos.y <= clip.top
+            || pos.y >= clip.bottom - 1;
+        let hit = at_edge && !self.was_at_edge;
+        self.was_at_edge = at_edge;
+
+        if !hit {
+            return false;
+        }
+
+        match self.last_hit {
+            Some(last) if now - last <= double_hit => {
+                self.reset();
+                true
+            }
+            _ => {
+                self.last_hit = Some(now);
+                false
+            }
+        }
+    }
+
+    /// Forgets any partial gesture
+    pub fn reset(&mut self) {
+        self.was_at_edge = false;
+        self.last_hit = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIP: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+    const DOUBLE_HIT: Duration = Duration::from_millis(500);
+    const MIDDLE: POINT = POINT { x: 960, y: 540 };
+    const LEFT: POINT = POINT { x: 0, y: 540 };
+    const BOTTOM: POINT = POINT { x: 960, y: 1079 };
+
+    /// Feeds `samples` 100ms apart and returns what each completed
+    fn run(escape: &mut EdgeEscape, start: Instant, samples: &[POINT]) -> Vec<bool> {
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, &pos)| {
+                let now = start + Duration::from_millis(100 * i as u64);
+                escape.sample(pos, &CLIP, now, DOUBLE_HIT)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn two_quick_hits_on_any_edge_complete_the_gesture() {
+        let mut escape = EdgeEscape::default();
+        assert_eq!(
+            run(&mut escape, Instant::now(), &[MIDDLE, LEFT, MIDDLE, BOTTOM]),
+            [false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn resting_at_the_edge_is_a_single_hit() {
+        let mut escape = EdgeEscape::default();
+        assert_eq!(
+            run(&mut escape, Instant::now(), &[LEFT, LEFT, LEFT, BOTTOM]),
+            [false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn hits_too_far_apart_start_over() {
+        let mut escape = EdgeEscape::default();
+        let start = Instant::now();
+        assert!(!escape.sample(LEFT, &CLIP, start, DOUBLE_HIT));
+        assert!(!escape.sample(MIDDLE, &CLIP, start, DOUBLE_HIT));
+        let late = start + Duration::from_millis(600);
+        assert!(!escape.sample(LEFT, &CLIP, late, DOUBLE_HIT));
+        // The late hit counts as the first of a new gesture
+        assert!(!escape.sample(MIDDLE, &CLIP, late, DOUBLE_HIT));
+        assert!(escape.sample(LEFT, &CLIP, late + DOUBLE_HIT, DOUBLE_HIT));
+    }
+
+    #[test]
+    fn a_reset_forgets_the_first_hit() {
+        let mut escape = EdgeEscape::default();
+        let start = Instant::now();
+        assert_eq!(run(&mut escape, start, &[LEFT, MIDDLE]), [false, false]);
+        escape.reset();
+        assert!(!escape.sample(LEFT, &CLIP, start, DOUBLE_HIT));
+    }
+}
@@ -0,0 +1,192 @@
+//! WinEvent hooks for event-driven mode
+//!
+//! Instead of polling the foreground window every tick, the main loop can sleep
+//! until Windows reports a focus change, a move/resize or a title change of the
+//! foreground window. The hooks are out-of-context, so their callbacks run on
+//! the installing thread while it pumps messages in [`control::wait`](crate::control::wait).
+
+use std::cell::Cell;
+
+use crate::control::{push_message, ControlMessage};
+
+use windows::Win32::Foundation::{HMODULE, HWND};
+use windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CHILDID_SELF, EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE, EVENT_SYSTEM_DESKTOPSWITCH,
+    EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZESTART, OBJID_WINDOW,
+    WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+};
+
+thread_local! {
+    static EVENT_PENDING: Cell<bool> = const { Cell::new(false) };
+    static LAST_FOREGROUND: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    let hwnd_value = hwnd.0 as isize;
+
+    if event == EVENT_SYSTEM_FOREGROUND {
+        LAST_FOREGROUND.with(|f| f.set(hwnd_value));
+        EVENT_PENDING.with(|p| p.set(true));
+        return;
+    }
+
+    // Everything else only matters for the foreground window itself, not for
+    // its children or the cursor (which reports a location change on every move)
+    if id_object == OBJID_WINDOW.0
+        && id_child == CHILDID_SELF as i32
+        && hwnd_value == LAST_FOREGROUND.with(Cell::get)
+    {
+        EVENT_PENDING.with(|p| p.set(true));
+    }
+}
+
+/// The installed hooks, removed on drop
+pub struct EventHooks {
+    hooks: Vec<HWINEVENTHOOK>,
+}
+
+impl EventHooks {
+    /// Installs the hooks on the current thread, which must pump messages
+    pub fn install(foreground: HWND) -> Option<Self> {
+        let ranges = [
+            (EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_FOREGROUND),
+            (EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_MINIMIZEEND),
+            (EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE),
+        ];
+
+        let mut hooks = Vec::new();
+        for (min, max) in ranges {
+            let hook = unsafe {
+                SetWinEventHook(
+                    min,
+                    max,
+                    HMODULE::default(),
+                    Some(win_event_proc),
+                    0,
+                    0,
+                    WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+                )
+            };
+            if hook.is_invalid() {
+                // Dropping the partial set unhooks what was installed so far
+                drop(Self { hooks });
+                return None;
+            }
+            hooks.push(hook);
+        }
+
+        LAST_FOREGROUND.with(|f| f.set(foreground.0 as isize));
+        Some(Self { hooks })
+    }
+
+    /// Foreground window according to the last focus event
+    pub fn last_foreground(&self) -> isize {
+        LAST_FOREGROUND.with(Cell::get)
+    }
+
+    /// Corrects the tracked foreground window after a missed focus event
+    pub fn resync(&self, foreground: HWND) {
+        LAST_FOREGROUND.with(|f| f.set(foreground.0 as isize));
+    }
+}
+
+impl Drop for EventHooks {
+    fn drop(&mut self) {
+        for hook in self.hooks.drain(..) {
+            unsafe {
+                let _ = UnhookWinEvent(hook);
+            }
+        }
+    }
+}
+
+/// Wakes [`control::wait`](crate::control::wait) on foreground changes only,
+/// so poll mode decides within milliseconds of a focus change rather than at
+/// the next tick, and can slow down while nothing could be locked
+pub struct ForegroundHook {
+    hook: HWINEVENTHOOK,
+}
+
+impl ForegroundHook {
+    /// Installs the hook on the current thread, which must pump messages
+    pub fn install() -> Option<Self> {
+        let hook = unsafe {
+            SetWinEventHook(
+                EVENT_SYSTEM_FOREGROUND,
+                EVENT_SYSTEM_FOREGROUND,
+                HMODULE::default(),
+                Some(win_event_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            )
+        };
+        (!hook.is_invalid()).then_some(Self { hook })
+    }
+}
+
+impl Drop for ForegroundHook {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = UnhookWinEvent(self.hook);
+        }
+    }
+}
+
+unsafe extern "system" fn desktop_switch_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    push_message(ControlMessage::DesktopSwitch);
+}
+
+/// Reports input desktop switches as [`ControlMessage::DesktopSwitch`],
+/// independently of the engine mode
+pub struct DesktopSwitchHook {
+    hook: HWINEVENTHOOK,
+}
+
+impl DesktopSwitchHook {
+    /// Installs the hook on the current thread, which must pump messages
+    pub fn install() -> Option<Self> {
+        let hook = unsafe {
+            SetWinEventHook(
+                EVENT_SYSTEM_DESKTOPSWITCH,
+                EVENT_SYSTEM_DESKTOPSWITCH,
+                HMODULE::default(),
+                Some(desktop_switch_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT,
+            )
+        };
+        (!hook.is_invalid()).then_some(Self { hook })
+    }
+}
+
+impl Drop for DesktopSwitchHook {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = UnhookWinEvent(self.hook);
+        }
+    }
+}
+
+/// Checks whether an event arrived since the last call, clearing the flag
+pub fn take_pending_event() -> bool {
+    EVENT_PENDING.with(|p| p.replace(false))
+}
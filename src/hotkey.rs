@@ -0,0 +1,227 @@
+//! Global hotkeys
+//!
+//! Hotkeys are written like `"Ctrl+Alt+L"` in the `[hotkeys]` section and
+//! registered with `RegisterHotKey` on the control window, which reports them as
+//! [`ControlMessage::Hotkey`](crate::control::ControlMessage::Hotkey).
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, VIRTUAL_KEY,
+    VK_BACK, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_F1, VK_HOME, VK_INSERT, VK_LEFT, VK_NEXT,
+    VK_NUMPAD0, VK_PAUSE, VK_PRIOR, VK_RETURN, VK_RIGHT, VK_SCROLL, VK_SNAPSHOT, VK_SPACE, VK_TAB,
+    VK_UP,
+};
+
+/// `RegisterHotKey` ID of the first profile hotkey
+const PROFILE_ID_BASE: i32 = 0x100;
+
+/// What a registered hotkey does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// Toggle the manual lock on the monitor under the cursor
+    ManualLock,
+    /// Move the manual lock to the next monitor
+    CycleMonitor,
+    /// Switch to the next profile
+    NextProfile,
+    /// Switch to (or back from) the profile at this index
+    Profile(usize),
+    /// Release the cursor whatever holds it and pause locking for a while
+    Panic,
+    /// Write a snapshot of the desktop and the engine for a bug report
+    Snapshot,
+}
+
+impl HotkeyAction {
+    /// The `RegisterHotKey` ID
+    pub fn id(self) -> i32 {
+        match self {
+            HotkeyAction::ManualLock => 1,
+            HotkeyAction::CycleMonitor => 2,
+            HotkeyAction::NextProfile => 3,
+            HotkeyAction::Panic => 4,
+            HotkeyAction::Snapshot => 5,
+            HotkeyAction::Profile(index) => PROFILE_ID_BASE + index as i32,
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            1 => Some(HotkeyAction::ManualLock),
+            2 => Some(HotkeyAction::CycleMonitor),
+            3 => Some(HotkeyAction::NextProfile),
+            4 => Some(HotkeyAction::Panic),
+            5 => Some(HotkeyAction::Snapshot),
+            id if id >= PROFILE_ID_BASE => {
+                Some(HotkeyAction::Profile((id - PROFILE_ID_BASE) as usize))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A key combination such as `Ctrl+Alt+L`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Hotkey {
+    modifiers: HOT_KEY_MODIFIERS,
+    key: VIRTUAL_KEY,
+    text: String,
+}
+
+impl Hotkey {
+    /// Modifiers for `RegisterHotKey`, with auto-repeat suppressed
+    pub fn modifiers(&self) -> HOT_KEY_MODIFIERS {
+        self.modifiers | MOD_NOREPEAT
+    }
+
+    pub fn key(&self) -> u32 {
+        self.key.0 as u32
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|k| !k.is_empty());
+        let Some(key) = key_name.and_then(key_code) else {
+            return Err(format!("Unknown key in hotkey \"{}\"", text));
+        };
+
+        let mut modifiers = HOT_KEY_MODIFIERS(0);
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => MOD_CONTROL,
+                "alt" => MOD_ALT,
+                "shift" => MOD_SHIFT,
+                "win" => MOD_WIN,
+                _ => {
+                    return Err(format!(
+                        "Unknown modifier \"{}\" in hotkey \"{}\"",
+                        part, text
+                    ))
+                }
+            };
+        }
+        if modifiers.0 == 0 {
+            return Err(format!(
+                "Hotkey \"{}\" needs at least one of Ctrl, Alt, Shift or Win",
+                text
+            ));
+        }
+
+        Ok(Self {
+            modifiers,
+            key,
+            text: text.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Maps a key name (`L`, `F5`, `PageUp`, `Numpad3`, ...) to its virtual-key code
+fn key_code(name: &str) -> Option<VIRTUAL_KEY> {
+    let lower = name.to_ascii_lowercase();
+
+    if let [c] = lower.as_bytes() {
+        return c
+            .is_ascii_alphanumeric()
+            .then(|| VIRTUAL_KEY(c.to_ascii_uppercase() as u16));
+    }
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return (1..=24).contains(&n).then(|| VIRTUAL_KEY(VK_F1.0 + n - 1));
+    }
+    if let Some(n) = lower
+        .strip_prefix("numpad")
+        .and_then(|n| n.parse::<u16>().ok())
+    {
+        return (n <= 9).then(|| VIRTUAL_KEY(VK_NUMPAD0.0 + n));
+    }
+
+    let key = match lower.as_str() {
+        "left" => VK_LEFT,
+        "right" => VK_RIGHT,
+        "up" => VK_UP,
+        "down" => VK_DOWN,
+        "home" => VK_HOME,
+        "end" => VK_END,
+        "pageup" | "pgup" => VK_PRIOR,
+        "pagedown" | "pgdn" => VK_NEXT,
+        "insert" | "ins" => VK_INSERT,
+        "delete" | "del" => VK_DELETE,
+        "space" => VK_SPACE,
+        "tab" => VK_TAB,
+        "enter" | "return" => VK_RETURN,
+        "escape" | "esc" => VK_ESCAPE,
+        "backspace" => VK_BACK,
+        "pause" => VK_PAUSE,
+        "scrolllock" => VK_SCROLL,
+        "printscreen" => VK_SNAPSHOT,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modifiers_and_key() {
+        let hotkey: Hotkey = "Ctrl+Alt+L".parse().unwrap();
+        assert_eq!(hotkey.modifiers(), MOD_CONTROL | MOD_ALT | MOD_NOREPEAT);
+        assert_eq!(hotkey.key(), 'L' as u32);
+        assert_eq!(hotkey.to_string(), "Ctrl+Alt+L");
+    }
+
+    #[test]
+    fn parses_named_keys_case_insensitively() {
+        let hotkey: Hotkey = "shift + win + pageup".parse().unwrap();
+        assert_eq!(hotkey.modifiers(), MOD_SHIFT | MOD_WIN | MOD_NOREPEAT);
+        assert_eq!(hotkey.key(), VK_PRIOR.0 as u32);
+        assert_eq!(
+            "Alt+F12".parse::<Hotkey>().unwrap().key(),
+            VK_F1.0 as u32 + 11
+        );
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        for action in [
+            HotkeyAction::ManualLock,
+            HotkeyAction::NextProfile,
+            HotkeyAction::Panic,
+            HotkeyAction::Profile(0),
+            HotkeyAction::Profile(7),
+        ] {
+            assert_eq!(HotkeyAction::from_id(action.id()), Some(action));
+        }
+        assert_eq!(HotkeyAction::from_id(0), None);
+    }
+
+    #[test]
+    fn rejects_bad_hotkeys() {
+        assert!("L".parse::<Hotkey>().is_err());
+        assert!("Ctrl+".parse::<Hotkey>().is_err());
+        assert!("Ctrl+F25".parse::<Hotkey>().is_err());
+        assert!("Hyper+L".parse::<Hotkey>().is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Local HTTP status endpoint
+//!
+//! Serves `GET /status` (JSON) and `GET /metrics` (Prometheus text format) on
+//! `127.0.0.1`, for dashboards, stream overlays and monitoring scrapers. The
+//! endpoint is read-only and answers from [`Stats`], which the main loop keeps
+//! up to date, plus the monitor layout at the time of the request. Connections are handled one at a time on a background thread;
+//! the responses are tiny, so there is nothing to gain from more.
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::monitor::{monitor_details, monitors_json};
+use crate::stats::Stats;
+
+/// Longest request head we read before giving up on a client
+const MAX_REQUEST: usize = 8192;
+
+/// A running status endpoint, stopped when dropped
+#[derive(Debug)]
+pub struct StatusServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    /// Listens on `127.0.0.1:port` and starts answering requests
+    pub fn start(port: u16, stats: Arc<Mutex<Stats>>) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let thread = thread::Builder::new()
+            .name("http-status".to_string())
+            .spawn(move || serve(listener, stats, stopping))?;
+        Ok(Self {
+            addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the blocking accept so the thread sees the flag
+        let _ = TcpStream::connect_timeout(&self.addr, Duration::from_millis(200));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve(listener: TcpListener, stats: Arc<Mutex<Stats>>, stop: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(stream) = stream {
+            let _ = respond(stream, &stats);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, stats: &Mutex<Stats>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(2)))?;
+
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let request_line = head.lines().next().unwrap_or_default();
+
+    let (status, content_type, body) = route(request_line, stats);
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Picks the response for a request line such as `GET /status HTTP/1.1`
+fn route(request_line: &str, stats: &Mutex<Stats>) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    // Ignore any query string
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    const TEXT: &str = "text/plain; charset=utf-8";
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            TEXT,
+            "Only GET is supported\n".into(),
+        );
+    }
+    let stats = || stats.lock().unwrap_or_else(|e| e.into_inner());
+    match path {
+        "/status" => {
+            let mut json = stats().to_json();
+            // Inside the object, before its closing brace
+            json.pop();
+            json.push_str(&format!(
+                ",\"monitors\":{}}}",
+                monitors_json(&monitor_details())
+            ));
+            ("200 OK", "application/json", json)
+        }
+        "/metrics" => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            stats().to_metrics(),
+        ),
+        _ => ("404 Not Found", TEXT, "Try /status or /metrics\n".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_status_and_metrics() {
+        let stats = Mutex::new(Stats::default());
+        let (status, kind, body) = route("GET /status HTTP/1.1", &stats);
+        assert_eq!((status, kind), ("200 OK", "application/json"));
+        assert!(body.starts_with("{\"state\":\"unlocked\""));
+        assert!(body.contains(",\"monitors\":["));
+        assert!(body.ends_with("]}"));
+
+        let (status, _, body) = route("GET /metrics?x=1 HTTP/1.1", &stats);
+        assert_eq!(status, "200 OK");
+        assert!(body.contains("smartlockcursor_locked 0\n"));
+    }
+
+    #[test]
+    fn rejects_other_requests() {
+        let stats = Mutex::new(Stats::default());
+        assert_eq!(route("GET / HTTP/1.1", &stats).0, "404 Not Found");
+        assert_eq!(
+            route("POST /status HTTP/1.1", &stats).0,
+            "405 Method Not Allowed"
+        );
+    }
+}
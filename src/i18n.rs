@@ -0,0 +1,651 @@
+//! Translated user-facing messages
+//!
+//! Messages are looked up by key in a per-language table, with `{}`
+//! placeholders filled in order by [`tr!`](crate::tr). Keys missing from a
+//! translation fall back to English, so a partial table is never an error.
+//! The language comes from `[ui] language`, or from the Windows display
+//! language when that is `auto`.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::Deserialize;
+use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+/// Primary language ID of Turkish in a Windows `LANGID`
+const LANG_TURKISH: u16 = 0x1F;
+
+static CURRENT: AtomicU8 = AtomicU8::new(Language::En as u8);
+
+/// A language for console and dialog messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Language {
+    /// Whatever Windows is displayed in, if there is a translation for it
+    #[default]
+    Auto,
+    En,
+    Tr,
+}
+
+impl Language {
+    /// Resolves [`Language::Auto`] from the user's display language
+    fn resolve(self) -> Self {
+        if self != Language::Auto {
+            return self;
+        }
+        match unsafe { GetUserDefaultUILanguage() } & 0x3FF {
+            LANG_TURKISH => Language::Tr,
+            _ => Language::En,
+        }
+    }
+
+    fn table(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::Tr => TR,
+            Language::En | Language::Auto => EN,
+        }
+    }
+}
+
+/// Switches all further messages to `language`
+pub fn set_language(language: Language) {
+    CURRENT.store(language.resolve() as u8, Ordering::Relaxed);
+}
+
+fn current() -> Language {
+    match CURRENT.load(Ordering::Relaxed) {
+        x if x == Language::Tr as u8 => Language::Tr,
+        _ => Language::En,
+    }
+}
+
+fn lookup(table: &[(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+/// The message for `key` in the current language
+pub fn text(key: &'static str) -> &'static str {
+    lookup(current().table(), key)
+        .or_else(|| lookup(EN, key))
+        .unwrap_or(key)
+}
+
+/// The message for `key` with its `{}` placeholders replaced by `args`
+pub fn format(key: &'static str, args: &[&dyn fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = text(key).split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// Looks up a message by key and fills in its placeholders
+#[macro_export]
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::i18n::text($key)
+    };
+    ($key:expr, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::format($key, &[$(&$arg),+])
+    };
+}
+
+const EN: &[(&str, &str)] = &[
+    ("target.monitor", "monitor"),
+    ("target.window", "window"),
+    ("target.work_area", "work area"),
+    ("backend.hook", " using the mouse hook"),
+    ("backend.soft", " with a soft edge"),
+    ("event.locked", "Cursor locked to {}: {}{}"),
+    ("event.locked.steam_app", " for {} ({})"),
+    ("event.unlocked.no_foreground", "No foreground window, cursor released"),
+    ("event.unlocked.fullscreen_exited", "Fullscreen exited, cursor released"),
+    ("event.unlocked.rules_changed", "Locked window no longer matches the rules, cursor released"),
+    ("event.unlocked.minimized", "Locked window was minimized, cursor released"),
+    ("event.unlocked.other_desktop", "Locked window is on another virtual desktop, cursor released"),
+    ("event.unlocked.cloaked", "Locked window was hidden, cursor released"),
+    ("event.unlocked.hidden", "Locked window was hidden, cursor released"),
+    ("event.unlocked.closed", "Locked window was closed, cursor released"),
+    ("event.clip_stolen", "Cursor clip was changed by another program (likely {}), re-applying"),
+    ("event.backend_escalated", "{} cleared the clip {} times in {}s, switching it to the mouse hook backend (a rule with backend = \"hook\" makes this permanent)"),
+    ("event.alt_tab_started.locked", "Alt+Tab detected, cursor temporarily released"),
+    ("event.alt_tab_started", "Alt+Tab detected"),
+    ("event.alt_tab_ended", "Alt+Tab ended after {}s"),
+    ("event.task_view_started.locked", "Task View opened, cursor temporarily released"),
+    ("event.task_view_started", "Task View opened"),
+    ("event.task_view_ended", "Task View closed after {}s"),
+    ("event.alt_tab_ended.returned", " - returned to fullscreen window ({})"),
+    ("event.alt_tab_ended.switched_away", " - switched to {} (hwnd {}), cursor stays free"),
+    ("event.unknown_process", "unknown process"),
+    ("event.relocked", "Cursor re-locked {}s after Alt+Tab ({})"),
+    ("relock.returned", "returned to fullscreen window"),
+    ("relock.activated", "fullscreen window activated"),
+    ("relock.clicked", "user clicked fullscreen window"),
+    ("event.remembered_window_expired", "Forgot the fullscreen window after {}s away, next lock starts fresh"),
+    ("event.edge_escaped", "Edge escape gesture, cursor released for {}s"),
+    ("event.edge_escape_ended", "Edge escape over, re-locking"),
+    ("event.timed_unlock", "Unlocked from the tray for {}s"),
+    ("event.timed_unlock_ended", "Tray unlock over, re-locking"),
+    ("event.manual_locked", "Manual lock on, cursor locked to monitor: {}"),
+    ("event.manual_lock_moved", "Manual lock moved to monitor: {}"),
+    ("event.manual_unlocked", "Manual lock off, cursor released"),
+    ("paused.remote_session", "Remote desktop session detected, cursor locking paused"),
+    ("paused.session_locked", "Workstation locked, cursor released"),
+    ("paused.session_disconnected", "Session switched away, cursor released"),
+    ("paused.secure_desktop", "Secure desktop active (UAC prompt), cursor released"),
+    ("paused.sleep", "System is going to sleep, cursor released"),
+    ("paused.idle", "No input for a while, cursor released until you're back"),
+    ("paused.gamepad", "Controller in use, cursor released"),
+    ("paused.magnifier", "Magnifier is running, cursor locking paused"),
+    ("paused.screen_reader", "A screen reader is running, cursor locking paused"),
+    ("paused.on_screen_keyboard", "On-screen keyboard is open, cursor locking paused"),
+    ("paused.presenting", "Presentation mode or duplicated displays, cursor locking paused"),
+    ("paused.mouse_sharing", "Pointer is on another computer, cursor locking paused"),
+    ("paused.paused", "Paused, cursor released until resumed"),
+    ("paused.panic", "Panic hotkey pressed, cursor released and locking paused for a while"),
+    ("resumed.remote_session", "Remote desktop session ended, cursor locking resumed"),
+    ("resumed.session_locked", "Workstation unlocked, re-evaluating"),
+    ("resumed.session_disconnected", "Session reconnected, re-evaluating"),
+    ("resumed.secure_desktop", "Back on the normal desktop, re-evaluating"),
+    ("resumed.sleep", "Resumed from sleep, re-evaluating"),
+    ("resumed.idle", "Input detected, re-evaluating"),
+    ("resumed.gamepad", "Controller put down, re-evaluating"),
+    ("resumed.assistive_tool", "Assistive tool closed, re-evaluating"),
+    ("resumed.presenting", "Presentation over, re-evaluating"),
+    ("resumed.mouse_sharing", "Pointer is back from the other computer, re-evaluating"),
+    ("resumed.paused", "Resumed, re-evaluating"),
+    ("resumed.panic", "Panic cool-down over, re-evaluating"),
+    ("prompt.title", "SmartLockCursor - lock this app in the future?"),
+    ("prompt.text", "SmartLockCursor just locked the cursor to {}.\n\nYes - always lock it\nNo - never lock it\nCancel - ask again next time"),
+    ("main.monitoring", "Monitoring for fullscreen windows..."),
+    ("main.dry_run", "Dry run: locks are logged but the cursor is never clipped"),
+    ("profile.switched", "Switched to profile {}"),
+    ("profile.cleared", "Profile off, back to the main config"),
+    ("discord.locked", "Cursor locked"),
+    ("discord.unlocked", "Cursor free"),
+    ("tray.unlock", "Unlock for {}s"),
+    ("tray.exit", "Exit"),
+    ("tray.locked_title", "Cursor locked"),
+    ("tray.locked_hint", "Click here to free the cursor for {}s"),
+    ("clip.failing_title", "Cursor lock is failing"),
+    ("clip.failing_elevated", "Windows refuses to confine the cursor to {}, which runs as administrator. Run `smartlockcursor elevate` or set run_elevated = true under [startup]"),
+    ("clip.failing", "Windows refuses to confine the cursor. Try backend = \"hook\" under [cursor] or run SmartLockCursor as administrator"),
+    ("crash.prompt_title", "SmartLockCursor crashed"),
+    ("crash.prompt_text", "SmartLockCursor crashed and wrote {} crash report(s) to {}. They hold the error, where it happened and a backtrace.\n\nSend them to {}?"),
+    ("crash.written", "Crash report written to {}"),
+    ("crash.write_failed", "Failed to write a crash report: {}"),
+    ("crash.send_failed", "Failed to send {}: {}"),
+    ("crash.sent", "Sent crash report {}"),
+    ("crash.offer_failed", "Failed to offer the crash reports: {}"),
+    ("main.shutting_down", "Shutting down, releasing cursor..."),
+    ("main.console_closed", "Console closed, releasing cursor..."),
+    ("main.session_ending", "Windows is logging off or shutting down, releasing cursor..."),
+    ("event_log.opened", "Logging events to {}"),
+    ("event_log.open_failed", "Failed to open the event log: {}"),
+    ("event_log.write_failed", "Failed to write to the event log {}, disabling it: {}"),
+    ("system_log.open_failed", "Failed to open the Windows event log source {}: {}"),
+    ("mqtt.disabled", "MQTT publishing disabled: {}"),
+    ("mqtt.unsupported", "[mqtt] is set, but this build has no MQTT support (cargo feature \"mqtt\")"),
+    ("discord.disabled", "Discord presence disabled: {}"),
+    ("discord.unsupported", "[discord] is set, but this build has no Discord support (cargo feature \"discord\")"),
+    ("discord.connected", "Connected to Discord"),
+    ("discord.connect_failed", "Could not reach Discord, retrying every {}s: {}"),
+    ("discord.connection_lost", "Lost the Discord connection: {}"),
+    ("discord.rejected", "Discord rejected the presence: {}"),
+    ("discord.unknown_error", "unknown error"),
+    ("obs.disabled", "obs-websocket broadcasting disabled: {}"),
+    ("obs.unsupported", "[obs] websocket is set, but this build has no obs-websocket support (cargo feature \"obs\")"),
+    ("obs.running", "OBS is running ({})"),
+    ("obs.closed", "OBS was closed"),
+    ("obs.game_capture", "game capture"),
+    ("obs.connected", "Connected to obs-websocket"),
+    ("obs.connect_failed", "Could not connect to obs-websocket, retrying while OBS is closed: {}"),
+    ("obs.connection_lost", "Lost the obs-websocket connection, reconnecting: {}"),
+    ("obs.unknown_profile", "[obs] profile {} is not a [[profiles]] entry"),
+    ("http.serving", "Serving status on http://{}/status"),
+    ("http.failed", "Failed to serve status on port {}: {}"),
+    ("http.unsupported", "[http] is set, but this build has no status endpoint (cargo feature \"http\")"),
+    ("webhook.sending", "Sending events to the webhook"),
+    ("webhook.disabled", "Webhook disabled: {}"),
+    ("webhook.unsupported", "[webhook] is set, but this build has no webhook support (cargo feature \"webhook\")"),
+    ("crash.unsupported", "[crash_reports] is enabled, but this build has no crash report support (cargo feature \"crash-reports\")"),
+    ("state.save_failed", "Failed to save the state to {}: {}"),
+    ("hook.watch_failed", "Failed to watch mouse input, confining every pointing device: {}"),
+    ("hook.install_failed", "Failed to install the mouse hook, falling back to ClipCursor"),
+    ("clip.works_again", "The cursor clip works again after {} failed attempts"),
+    ("clip.refused", "Windows refused to confine the cursor, trying again once another app is in front. {}"),
+    ("clip.dpi_escalated", "{}, which DPI scaling does to {}; using the mouse hook for it"),
+    ("clip.failed_repeatedly", "Failed to confine the cursor {} times in a row ({}). {}"),
+    ("clip.follows", "Locked window moved, clip follows: ({}, {}) - ({}, {})"),
+    ("power.low_power_on", "Low-power profile on: checking every {}ms, clip theft every {}ms"),
+    ("power.low_power_off", "Low-power profile off"),
+    ("power.efficiency_failed", "Failed to switch to efficiency mode"),
+    ("handoff.yielded", "Yielded cursor clip to process {}"),
+    ("handoff.returned", "Process {} handed the cursor clip back"),
+    ("handoff.reclaimed", "Process {} exited without handing the clip back, reclaiming"),
+    ("snapshot.written", "Snapshot written to {}"),
+    ("snapshot.failed", "Failed to write the snapshot: {}"),
+    ("snapshot.no_directory", "No config directory to write the snapshot to"),
+    ("manual.no_monitor", "Could not find the monitor under the cursor, not locking"),
+    ("manual.not_locked", "Not locked manually, nothing to move to the next monitor"),
+    ("window.now_locked", "now eligible for locking"),
+    ("window.now_ignored", "now ignored"),
+    ("window.title_changed", "Window title changed to \"{}\", {}"),
+    ("window.moved", "Window moved to {}, {}"),
+    ("window.recreated", "Locked window was recreated ({} -> {}), keeping the lock"),
+    ("conflict.mouse_sharing", "Mouse sharing tool {} is running, locking pauses while it has the pointer"),
+    ("conflict.cooperating", "{} also confines the cursor, re-applying the clip only when it is released"),
+    ("conflict.fighting", "{} also confines the cursor, the two may fight over the clip and make the cursor stutter"),
+    ("elevation.process", "process {}"),
+    ("elevation.game_elevated", "{} runs as administrator but SmartLockCursor does not, so the lock may not hold. Run `smartlockcursor elevate` or set `run_elevated = true` under [startup]"),
+    ("elevation.stdio", "run_elevated is ignored with --stdio, the relaunched instance would lose stdin"),
+    ("elevation.relaunched", "Relaunched with administrator rights, this window can be closed"),
+    ("elevation.relaunch_failed", "Could not relaunch with administrator rights, continuing without"),
+    ("explain.waiting", "Switch to the window to diagnose, checking in {}s..."),
+    ("main.monitors", "Detected {} monitor(s):"),
+    ("main.janitor_failed", "Failed to start janitor process, a crash may leave the cursor clipped: {}"),
+    ("main.control_failed", "Failed to create control window, clip handoff disabled: {}"),
+    ("main.supervisor_failed", "Failed to start the supervisor, a hang may leave the cursor clipped: {}"),
+    ("main.tray_exit", "Exit chosen from the tray, shutting down"),
+    ("main.stdin_closed", "stdin closed, shutting down"),
+    ("main.tagline", "Automatically locks cursor to fullscreen windows"),
+    ("main.exit_hint", "Press Ctrl+C to exit"),
+    ("main.stdio_failed", "Failed to set up the stdio channel: {}"),
+    ("main.dashboard_failed", "Failed to start the dashboard: {}"),
+    ("process.unknown", "unknown"),
+    ("process.unknown_culprit", "an unknown process"),
+    ("watchdog.missed_foreground", "missed a foreground change"),
+    ("watchdog.missed_window", "missed a window change"),
+    ("cli.source_registered", "Registered event source {}"),
+    ("cli.source_failed", "Failed to register event source {} (run this from an administrator prompt): {}"),
+    ("cli.update_checking", "Checking {} for a newer release..."),
+    ("cli.update_installed", "Installed SmartLockCursor {}, restart it to use the new version"),
+    ("cli.update_latest", "SmartLockCursor {} is the latest version"),
+    ("cli.update_failed", "Self-update failed: {}"),
+    ("cli.elevate_failed", "Failed to relaunch with administrator rights"),
+    ("cli.no_mice", "No mice found"),
+    ("cli.mouse_matches", "* matches [cursor] device = \"{}\""),
+    ("cli.mouse_hint", "Set [cursor] device to part of a path, e.g. its VID_xxxx&PID_xxxx, to confine only that mouse"),
+    ("cli.no_config_dir", "No config directory"),
+    ("cli.no_config_dir_to_check", "No config directory, pass the path of the file to check"),
+    ("cli.read_failed", "Failed to read {}: {}"),
+    ("cli.write_failed", "Failed to write {}: {}"),
+    ("cli.check_summary", "{}: {} error(s), {} warning(s)"),
+    ("cli.export_failed", "Failed to export the rules of {}: {}"),
+    ("cli.exported", "Exported the rules and profiles of {} to {}"),
+    ("cli.import_failed", "Failed to import {}: {}"),
+    ("cli.imported", "Imported {} rule(s) into {}, {} already there"),
+    ("cli.profiles_added", "Added profiles: {}"),
+    ("cli.profiles_kept", "Kept the existing profiles: {}"),
+    ("cli.not_running", "SmartLockCursor is not running"),
+    ("cli.running", "SmartLockCursor is running, cursor {}"),
+    ("cli.state_locked", "locked"),
+    ("cli.state_unlocked", "not locked"),
+    ("cli.state_yielded", "yielded to another tool"),
+    ("cli.no_statistics", "The running instance did not provide statistics"),
+    ("cli.monitors", "Monitors:"),
+    ("cli.snapshot_missing", "The running instance did not write a snapshot"),
+    ("cli.snapshot_offline", "SmartLockCursor is not running, the snapshot has no engine state"),
+    ("cli.config_failed", "Failed to load the config: {}"),
+    ("cli.toggled", "Toggled the manual lock"),
+    ("hotkey.registered", "Registered hotkey {} ({})"),
+    ("hotkey.failed", "Failed to register hotkey {}, another program may be using it: {}"),
+    ("engine.event_driven", "Event-driven mode enabled, watchdog every {}ms"),
+    ("engine.hooks_failed", "Failed to install WinEvent hooks, falling back to polling"),
+    ("engine.polling", "Polling mode enabled"),
+    ("tray.failed", "Failed to add the tray icon: {}"),
+    ("supervisor.stalled", "The main loop stopped responding ({}s overdue), released the cursor"),
+    ("supervisor.restarted", "Restarted SmartLockCursor"),
+    ("supervisor.restart_failed", "Failed to restart SmartLockCursor: {}"),
+    ("watchdog.resync", "Watchdog resync #{}: hooks {}"),
+    ("config.missing", "{} is missing or empty, keeping the previous config until it is back"),
+    ("config.reloaded", "Reloaded config from {}"),
+    ("config.parse_failed", "Failed to parse {}, keeping the previous config: {}"),
+    ("config.loaded", "Loaded config from {}"),
+    ("config.load_failed", "Failed to parse {}, using defaults: {}"),
+    ("config.conflict_copy", "{} looks like a sync conflict copy of {}, merge its changes by hand and delete it"),
+    ("update.available", "SmartLockCursor {} is available (this is {}), run `smartlockcursor.exe self-update` to install it"),
+    ("update.check_failed", "Update check failed: {}"),
+    ("update.spawn_failed", "Failed to start the update check: {}"),
+    ("commands.failed", "Failed to run the on_{} command: {}"),
+    ("rules.script_unsupported", "[detection] script is set, but this build has no script support (cargo feature \"rhai\")"),
+    ("rules.no_matcher", "Rule {} has no class, title, process, product or steam_app, skipping"),
+    ("rules.children_without_process", "Rule {} sets match_children without a process, ignoring it"),
+    ("rules.invalid_title", "Rule {} has an invalid title regex, skipping: {}"),
+    ("script.load_failed", "Can't load the detection script {}: {}"),
+    ("script.print", "Detection script: {}"),
+    ("script.failed", "Detection script failed on \"{}\": {}"),
+    ("script.unexpected", "Detection script returned \"{}\", expected \"lock\" or \"ignore\""),
+    ("script.wrong_type", "Detection script returned a {}, expected \"lock\" or \"ignore\""),
+    ("prompt.added_lock", "Added a rule to {}: always lock {}"),
+    ("prompt.added_ignore", "Added a rule to {}: never lock {}"),
+    ("prompt.add_failed", "Failed to add a rule for {} to {}: {}"),
+    ("prompt.failed", "Failed to show the rule prompt: {}"),
+    ("mqtt.connected", "Connected to the MQTT broker"),
+    ("mqtt.connect_failed", "MQTT connection failed, retrying in {}s: {}"),
+    ("mqtt.connection_lost", "Lost the MQTT connection, reconnecting: {}"),
+    ("webhook.behind", "Webhook endpoint is not keeping up, dropping an event"),
+    ("webhook.failed", "Webhook to {}:{}{} failed: {}"),
+];
+
+const TR: &[(&str, &str)] = &[
+    ("target.monitor", "monitöre"),
+    ("target.window", "pencereye"),
+    ("target.work_area", "çalışma alanına"),
+    ("backend.hook", " (fare kancasıyla)"),
+    ("backend.soft", " (yumuşak kenarla)"),
+    ("event.locked", "İmleç {} kilitlendi: {}{}"),
+    ("event.locked.steam_app", " - {} ({})"),
+    ("event.unlocked.no_foreground", "Ön planda pencere yok, imleç serbest bırakıldı"),
+    ("event.unlocked.fullscreen_exited", "Tam ekrandan çıkıldı, imleç serbest bırakıldı"),
+    ("event.unlocked.rules_changed", "Kilitli pencere artık kurallara uymuyor, imleç serbest bırakıldı"),
+    ("event.unlocked.minimized", "Kilitli pencere simge durumuna küçültüldü, imleç serbest bırakıldı"),
+    ("event.unlocked.other_desktop", "Kilitli pencere başka bir sanal masaüstünde, imleç serbest bırakıldı"),
+    ("event.unlocked.cloaked", "Kilitli pencere gizlendi, imleç serbest bırakıldı"),
+    ("event.unlocked.hidden", "Kilitli pencere gizlendi, imleç serbest bırakıldı"),
+    ("event.unlocked.closed", "Kilitli pencere kapatıldı, imleç serbest bırakıldı"),
+    ("event.clip_stolen", "İmleç sınırı başka bir program tarafından değiştirildi (muhtemelen {}), yeniden uygulanıyor"),
+    ("event.backend_escalated", "{} imleç sınırını {} kez {} sn içinde kaldırdı, fare kancası yöntemine geçiliyor (backend = \"hook\" içeren bir kural bunu kalıcı yapar)"),
+    ("event.alt_tab_started.locked", "Alt+Tab algılandı, imleç geçici olarak serbest bırakıldı"),
+    ("event.alt_tab_started", "Alt+Tab algılandı"),
+    ("event.alt_tab_ended", "Alt+Tab {} sn sonra bitti"),
+    ("event.task_view_started.locked", "Görev Görünümü açıldı, imleç geçici olarak serbest bırakıldı"),
+    ("event.task_view_started", "Görev Görünümü açıldı"),
+    ("event.task_view_ended", "Görev Görünümü {} sn sonra kapandı"),
+    ("event.alt_tab_ended.returned", " - tam ekran pencereye dönüldü ({})"),
+    ("event.alt_tab_ended.switched_away", " - {} (hwnd {}) penceresine geçildi, imleç serbest kalıyor"),
+    ("event.unknown_process", "bilinmeyen işlem"),
+    ("event.relocked", "İmleç Alt+Tab'dan {} sn sonra yeniden kilitlendi ({})"),
+    ("relock.returned", "tam ekran pencereye dönüldü"),
+    ("relock.activated", "tam ekran pencere etkinleşti"),
+    ("relock.clicked", "kullanıcı tam ekran pencereye tıkladı"),
+    ("event.remembered_window_expired", "Tam ekran pencere {} sn uzakta kalındıktan sonra unutuldu, sonraki kilit sıfırdan başlar"),
+    ("event.edge_escaped", "Kenardan kaçış hareketi, imleç {} sn serbest"),
+    ("event.edge_escape_ended", "Kenardan kaçış bitti, yeniden kilitleniyor"),
+    ("event.timed_unlock", "Bildirim alanından {} sn serbest bırakıldı"),
+    ("event.timed_unlock_ended", "Bildirim alanından serbest bırakma bitti, yeniden kilitleniyor"),
+    ("event.manual_locked", "Elle kilit açık, imleç monitöre kilitlendi: {}"),
+    ("event.manual_lock_moved", "Elle kilit başka monitöre taşındı: {}"),
+    ("event.manual_unlocked", "Elle kilit kapalı, imleç serbest bırakıldı"),
+    ("paused.remote_session", "Uzak masaüstü oturumu algılandı, imleç kilitleme duraklatıldı"),
+    ("paused.session_locked", "Bilgisayar kilitlendi, imleç serbest bırakıldı"),
+    ("paused.session_disconnected", "Oturumdan çıkıldı, imleç serbest bırakıldı"),
+    ("paused.secure_desktop", "Güvenli masaüstü etkin (UAC istemi), imleç serbest bırakıldı"),
+    ("paused.sleep", "Sistem uykuya geçiyor, imleç serbest bırakıldı"),
+    ("paused.idle", "Bir süredir giriş yok, dönene kadar imleç serbest"),
+    ("paused.gamepad", "Oyun kumandası kullanılıyor, imleç serbest bırakıldı"),
+    ("paused.magnifier", "Büyüteç çalışıyor, imleç kilitleme duraklatıldı"),
+    ("paused.screen_reader", "Bir ekran okuyucu çalışıyor, imleç kilitleme duraklatıldı"),
+    ("paused.on_screen_keyboard", "Ekran klavyesi açık, imleç kilitleme duraklatıldı"),
+    ("paused.presenting", "Sunum modu ya da yansıtılan ekranlar, imleç kilitleme duraklatıldı"),
+    ("paused.mouse_sharing", "İmleç başka bir bilgisayarda, imleç kilitleme duraklatıldı"),
+    ("paused.paused", "Duraklatıldı, devam edilene kadar imleç serbest"),
+    ("paused.panic", "Acil durum kısayoluna basıldı, imleç serbest ve kilitleme bir süre duraklatıldı"),
+    ("resumed.remote_session", "Uzak masaüstü oturumu bitti, imleç kilitleme devam ediyor"),
+    ("resumed.session_locked", "Bilgisayarın kilidi açıldı, yeniden değerlendiriliyor"),
+    ("resumed.session_disconnected", "Oturuma yeniden bağlanıldı, yeniden değerlendiriliyor"),
+    ("resumed.secure_desktop", "Normal masaüstüne dönüldü, yeniden değerlendiriliyor"),
+    ("resumed.sleep", "Uykudan uyanıldı, yeniden değerlendiriliyor"),
+    ("resumed.idle", "Giriş algılandı, yeniden değerlendiriliyor"),
+    ("resumed.gamepad", "Oyun kumandası bırakıldı, yeniden değerlendiriliyor"),
+    ("resumed.assistive_tool", "Yardımcı araç kapandı, yeniden değerlendiriliyor"),
+    ("resumed.presenting", "Sunum bitti, yeniden değerlendiriliyor"),
+    ("resumed.mouse_sharing", "İmleç diğer bilgisayardan döndü, yeniden değerlendiriliyor"),
+    ("resumed.paused", "Devam ediliyor, yeniden değerlendiriliyor"),
+    ("resumed.panic", "Acil durum bekleme süresi bitti, yeniden değerlendiriliyor"),
+    ("prompt.title", "SmartLockCursor - bu uygulama ileride de kilitlensin mi?"),
+    ("prompt.text", "SmartLockCursor imleci az önce {} uygulamasına kilitledi.\n\nEvet - her zaman kilitle\nHayır - asla kilitleme\nİptal - bir dahaki sefere yine sor"),
+    ("main.monitoring", "Tam ekran pencereler izleniyor..."),
+    ("main.dry_run", "Deneme modu: kilitler kaydedilir ama imleç asla kısıtlanmaz"),
+    ("profile.switched", "{} profiline geçildi"),
+    ("profile.cleared", "Profil kapatıldı, ana yapılandırmaya dönüldü"),
+    ("discord.locked", "İmleç kilitli"),
+    ("discord.unlocked", "İmleç serbest"),
+    ("tray.unlock", "{} sn serbest bırak"),
+    ("tray.exit", "Çıkış"),
+    ("tray.locked_title", "İmleç kilitli"),
+    ("tray.locked_hint", "İmleci {} sn serbest bırakmak için buraya tıklayın"),
+    ("clip.failing_title", "İmleç kilidi başarısız oluyor"),
+    ("clip.failing_elevated", "Windows imleci yönetici olarak çalışan {} içine kilitlemeyi reddediyor. `smartlockcursor elevate` çalıştırın veya [startup] altında run_elevated = true ayarlayın"),
+    ("clip.failing", "Windows imleci kilitlemeyi reddediyor. [cursor] altında backend = \"hook\" deneyin veya SmartLockCursor'ı yönetici olarak çalıştırın"),
+    ("crash.prompt_title", "SmartLockCursor çöktü"),
+    ("crash.prompt_text", "SmartLockCursor çöktü ve {} çökme raporunu {} klasörüne yazdı. Raporlar hatayı, nerede olduğunu ve bir geri izlemeyi içerir.\n\n{} adresine gönderilsin mi?"),
+    ("crash.written", "Çökme raporu {} dosyasına yazıldı"),
+    ("crash.write_failed", "Çökme raporu yazılamadı: {}"),
+    ("crash.send_failed", "{} gönderilemedi: {}"),
+    ("crash.sent", "{} çökme raporu gönderildi"),
+    ("crash.offer_failed", "Çökme raporları sunulamadı: {}"),
+    ("main.shutting_down", "Kapatılıyor, imleç serbest bırakılıyor..."),
+    ("main.console_closed", "Konsol kapatıldı, imleç serbest bırakılıyor..."),
+    ("main.session_ending", "Windows oturumu kapatıyor veya kapanıyor, imleç serbest bırakılıyor..."),
+    ("event_log.opened", "Olaylar {} dosyasına kaydediliyor"),
+    ("event_log.open_failed", "Olay günlüğü açılamadı: {}"),
+    ("event_log.write_failed", "Olay günlüğüne ({}) yazılamadı, kapatılıyor: {}"),
+    ("system_log.open_failed", "Windows olay günlüğü kaynağı {} açılamadı: {}"),
+    ("mqtt.disabled", "MQTT yayını kapatıldı: {}"),
+    ("mqtt.unsupported", "[mqtt] ayarlı, ama bu derlemede MQTT desteği yok (cargo özelliği \"mqtt\")"),
+    ("discord.disabled", "Discord durumu kapatıldı: {}"),
+    ("discord.unsupported", "[discord] ayarlı, ama bu derlemede Discord desteği yok (cargo özelliği \"discord\")"),
+    ("discord.connected", "Discord'a bağlanıldı"),
+    ("discord.connect_failed", "Discord'a ulaşılamadı, her {} sn'de bir yeniden deneniyor: {}"),
+    ("discord.connection_lost", "Discord bağlantısı koptu: {}"),
+    ("discord.rejected", "Discord durumu reddetti: {}"),
+    ("discord.unknown_error", "bilinmeyen hata"),
+    ("obs.disabled", "obs-websocket yayını kapatıldı: {}"),
+    ("obs.unsupported", "[obs] websocket ayarlı, ama bu derlemede obs-websocket desteği yok (cargo özelliği \"obs\")"),
+    ("obs.running", "OBS çalışıyor ({})"),
+    ("obs.closed", "OBS kapatıldı"),
+    ("obs.game_capture", "oyun yakalama"),
+    ("obs.connected", "obs-websocket'e bağlanıldı"),
+    ("obs.connect_failed", "obs-websocket'e bağlanılamadı, OBS kapalıyken yeniden deneniyor: {}"),
+    ("obs.connection_lost", "obs-websocket bağlantısı koptu, yeniden bağlanılıyor: {}"),
+    ("obs.unknown_profile", "[obs] profile {} bir [[profiles]] girdisi değil"),
+    ("http.serving", "Durum http://{}/status adresinden sunuluyor"),
+    ("http.failed", "Durum {} portundan sunulamadı: {}"),
+    ("http.unsupported", "[http] ayarlı, ama bu derlemede durum uç noktası yok (cargo özelliği \"http\")"),
+    ("webhook.sending", "Olaylar webhook'a gönderiliyor"),
+    ("webhook.disabled", "Webhook kapatıldı: {}"),
+    ("webhook.unsupported", "[webhook] ayarlı, ama bu derlemede webhook desteği yok (cargo özelliği \"webhook\")"),
+    ("crash.unsupported", "[crash_reports] açık, ama bu derlemede çökme raporu desteği yok (cargo özelliği \"crash-reports\")"),
+    ("state.save_failed", "Durum {} dosyasına kaydedilemedi: {}"),
+    ("hook.watch_failed", "Fare girişi izlenemedi, tüm işaretleme aygıtları kısıtlanıyor: {}"),
+    ("hook.install_failed", "Fare kancası kurulamadı, ClipCursor'a geri dönülüyor"),
+    ("clip.works_again", "İmleç kısıtlaması {} başarısız denemeden sonra yeniden çalışıyor"),
+    ("clip.refused", "Windows imleci kısıtlamayı reddetti, başka bir uygulama öne geçince yeniden denenecek. {}"),
+    ("clip.dpi_escalated", "{}; DPI ölçeklemesi {} için bunu yapıyor, onun için fare kancası kullanılıyor"),
+    ("clip.failed_repeatedly", "İmleç art arda {} kez kısıtlanamadı ({}). {}"),
+    ("clip.follows", "Kilitli pencere taşındı, kısıtlama onu izliyor: ({}, {}) - ({}, {})"),
+    ("power.low_power_on", "Düşük güç profili açık: her {} ms'de bir denetleniyor, kısıtlama kaybı her {} ms'de bir"),
+    ("power.low_power_off", "Düşük güç profili kapalı"),
+    ("power.efficiency_failed", "Verimlilik moduna geçilemedi"),
+    ("handoff.yielded", "İmleç kısıtlaması {} işlemine bırakıldı"),
+    ("handoff.returned", "{} işlemi imleç kısıtlamasını geri verdi"),
+    ("handoff.reclaimed", "{} işlemi kısıtlamayı geri vermeden kapandı, geri alınıyor"),
+    ("snapshot.written", "Anlık görüntü {} dosyasına yazıldı"),
+    ("snapshot.failed", "Anlık görüntü yazılamadı: {}"),
+    ("snapshot.no_directory", "Anlık görüntünün yazılacağı yapılandırma klasörü yok"),
+    ("manual.no_monitor", "İmlecin altındaki monitör bulunamadı, kilitlenmiyor"),
+    ("manual.not_locked", "Elle kilit yok, sonraki monitöre taşınacak bir şey yok"),
+    ("window.now_locked", "artık kilitlenebilir"),
+    ("window.now_ignored", "artık yok sayılıyor"),
+    ("window.title_changed", "Pencere başlığı \"{}\" oldu, {}"),
+    ("window.moved", "Pencere {} konumuna taşındı, {}"),
+    ("window.recreated", "Kilitli pencere yeniden oluşturuldu ({} -> {}), kilit korunuyor"),
+    ("conflict.mouse_sharing", "Fare paylaşım aracı {} çalışıyor, imleç ondayken kilitleme duraklar"),
+    ("conflict.cooperating", "{} de imleci kısıtlıyor, kısıtlama yalnızca o bıraktığında yeniden uygulanacak"),
+    ("conflict.fighting", "{} de imleci kısıtlıyor, ikisi kısıtlama için çekişip imlecin takılmasına yol açabilir"),
+    ("elevation.process", "{} numaralı işlem"),
+    ("elevation.game_elevated", "{} yönetici olarak çalışıyor ama SmartLockCursor çalışmıyor, kilit tutmayabilir. `smartlockcursor elevate` çalıştırın veya [startup] altında `run_elevated = true` ayarlayın"),
+    ("elevation.stdio", "--stdio ile run_elevated yok sayılır, yeniden başlatılan kopya stdin'i kaybederdi"),
+    ("elevation.relaunched", "Yönetici haklarıyla yeniden başlatıldı, bu pencere kapatılabilir"),
+    ("elevation.relaunch_failed", "Yönetici haklarıyla yeniden başlatılamadı, onlarsız devam ediliyor"),
+    ("explain.waiting", "İncelenecek pencereye geçin, {} sn sonra bakılacak..."),
+    ("main.monitors", "{} monitör algılandı:"),
+    ("main.janitor_failed", "Temizlik işlemi başlatılamadı, bir çökme imleci kısıtlı bırakabilir: {}"),
+    ("main.control_failed", "Denetim penceresi oluşturulamadı, kısıtlama devri kapalı: {}"),
+    ("main.supervisor_failed", "Gözetmen başlatılamadı, bir donma imleci kısıtlı bırakabilir: {}"),
+    ("main.tray_exit", "Bildirim alanından çıkış seçildi, kapatılıyor"),
+    ("main.stdin_closed", "stdin kapandı, kapatılıyor"),
+    ("main.tagline", "İmleci tam ekran pencerelere otomatik kısıtlar"),
+    ("main.exit_hint", "Çıkmak için Ctrl+C'ye basın"),
+    ("main.stdio_failed", "stdio kanalı kurulamadı: {}"),
+    ("main.dashboard_failed", "Pano başlatılamadı: {}"),
+    ("process.unknown", "bilinmiyor"),
+    ("process.unknown_culprit", "bilinmeyen bir işlem"),
+    ("watchdog.missed_foreground", "bir ön plan değişikliğini kaçırdı"),
+    ("watchdog.missed_window", "bir pencere değişikliğini kaçırdı"),
+    ("cli.source_registered", "{} olay kaynağı kaydedildi"),
+    ("cli.source_failed", "{} olay kaynağı kaydedilemedi (yönetici komut isteminden çalıştırın): {}"),
+    ("cli.update_checking", "{} daha yeni bir sürüm için denetleniyor..."),
+    ("cli.update_installed", "SmartLockCursor {} kuruldu, yeni sürümü kullanmak için yeniden başlatın"),
+    ("cli.update_latest", "SmartLockCursor {} en son sürüm"),
+    ("cli.update_failed", "Kendi kendini güncelleme başarısız: {}"),
+    ("cli.elevate_failed", "Yönetici haklarıyla yeniden başlatılamadı"),
+    ("cli.no_mice", "Fare bulunamadı"),
+    ("cli.mouse_matches", "* [cursor] device = \"{}\" ile eşleşiyor"),
+    ("cli.mouse_hint", "Yalnızca bir fareyi kısıtlamak için [cursor] device değerini yolunun bir parçasına, örneğin VID_xxxx&PID_xxxx kısmına ayarlayın"),
+    ("cli.no_config_dir", "Yapılandırma klasörü yok"),
+    ("cli.no_config_dir_to_check", "Yapılandırma klasörü yok, denetlenecek dosyanın yolunu verin"),
+    ("cli.read_failed", "{} okunamadı: {}"),
+    ("cli.write_failed", "{} yazılamadı: {}"),
+    ("cli.check_summary", "{}: {} hata, {} uyarı"),
+    ("cli.export_failed", "{} kuralları dışa aktarılamadı: {}"),
+    ("cli.exported", "{} kuralları ve profilleri {} dosyasına aktarıldı"),
+    ("cli.import_failed", "{} içe aktarılamadı: {}"),
+    ("cli.imported", "{} kural {} dosyasına aktarıldı, {} tanesi zaten vardı"),
+    ("cli.profiles_added", "Eklenen profiller: {}"),
+    ("cli.profiles_kept", "Var olan profiller korundu: {}"),
+    ("cli.not_running", "SmartLockCursor çalışmıyor"),
+    ("cli.running", "SmartLockCursor çalışıyor, imleç {}"),
+    ("cli.state_locked", "kısıtlı"),
+    ("cli.state_unlocked", "serbest"),
+    ("cli.state_yielded", "başka bir araca bırakıldı"),
+    ("cli.no_statistics", "Çalışan örnek istatistik vermedi"),
+    ("cli.monitors", "Monitörler:"),
+    ("cli.snapshot_missing", "Çalışan örnek anlık görüntü yazmadı"),
+    ("cli.snapshot_offline", "SmartLockCursor çalışmıyor, anlık görüntüde motor durumu yok"),
+    ("cli.config_failed", "Yapılandırma yüklenemedi: {}"),
+    ("cli.toggled", "Elle kısıtlama değiştirildi"),
+    ("hotkey.registered", "{} kısayolu kaydedildi ({})"),
+    ("hotkey.failed", "{} kısayolu kaydedilemedi, başka bir program kullanıyor olabilir: {}"),
+    ("engine.event_driven", "Olay güdümlü mod açık, gözcü her {} ms'de bir"),
+    ("engine.hooks_failed", "WinEvent kancaları kurulamadı, yoklamaya geri dönülüyor"),
+    ("engine.polling", "Yoklama modu açık"),
+    ("tray.failed", "Bildirim alanı simgesi eklenemedi: {}"),
+    ("supervisor.stalled", "Ana döngü yanıt vermiyor ({} sn gecikti), imleç serbest bırakıldı"),
+    ("supervisor.restarted", "SmartLockCursor yeniden başlatıldı"),
+    ("supervisor.restart_failed", "SmartLockCursor yeniden başlatılamadı: {}"),
+    ("watchdog.resync", "Gözcü eşitlemesi #{}: kancalar {}"),
+    ("config.missing", "{} eksik veya boş, geri gelene kadar önceki yapılandırma korunuyor"),
+    ("config.reloaded", "Yapılandırma {} dosyasından yeniden yüklendi"),
+    ("config.parse_failed", "{} ayrıştırılamadı, önceki yapılandırma korunuyor: {}"),
+    ("config.loaded", "Yapılandırma {} dosyasından yüklendi"),
+    ("config.load_failed", "{} ayrıştırılamadı, varsayılanlar kullanılıyor: {}"),
+    ("config.conflict_copy", "{}, {} dosyasının bir eşitleme çakışması kopyasına benziyor, değişikliklerini elle birleştirip silin"),
+    ("update.available", "SmartLockCursor {} yayında (bu sürüm {}), kurmak için `smartlockcursor.exe self-update` çalıştırın"),
+    ("update.check_failed", "Güncelleme denetimi başarısız: {}"),
+    ("update.spawn_failed", "Güncelleme denetimi başlatılamadı: {}"),
+    ("commands.failed", "on_{} komutu çalıştırılamadı: {}"),
+    ("rules.script_unsupported", "[detection] script ayarlı, ama bu derlemede betik desteği yok (cargo özelliği \"rhai\")"),
+    ("rules.no_matcher", "{}. kuralda class, title, process, product veya steam_app yok, atlanıyor"),
+    ("rules.children_without_process", "{}. kural process olmadan match_children ayarlıyor, yok sayılıyor"),
+    ("rules.invalid_title", "{}. kuralın title düzenli ifadesi geçersiz, atlanıyor: {}"),
+    ("script.load_failed", "Algılama betiği {} yüklenemedi: {}"),
+    ("script.print", "Algılama betiği: {}"),
+    ("script.failed", "Algılama betiği \"{}\" için başarısız oldu: {}"),
+    ("script.unexpected", "Algılama betiği \"{}\" döndürdü, \"lock\" veya \"ignore\" bekleniyordu"),
+    ("script.wrong_type", "Algılama betiği {} türünde bir değer döndürdü, \"lock\" veya \"ignore\" bekleniyordu"),
+    ("prompt.added_lock", "{} dosyasına kural eklendi: {} her zaman kısıtlanacak"),
+    ("prompt.added_ignore", "{} dosyasına kural eklendi: {} hiç kısıtlanmayacak"),
+    ("prompt.add_failed", "{} için kural {} dosyasına eklenemedi: {}"),
+    ("prompt.failed", "Kural sorusu gösterilemedi: {}"),
+    ("mqtt.connected", "MQTT aracısına bağlanıldı"),
+    ("mqtt.connect_failed", "MQTT bağlantısı kurulamadı, {} sn sonra yeniden denenecek: {}"),
+    ("mqtt.connection_lost", "MQTT bağlantısı koptu, yeniden bağlanılıyor: {}"),
+    ("webhook.behind", "Webhook uç noktası yetişemiyor, bir olay atlanıyor"),
+    ("webhook.failed", "{}:{}{} adresine webhook başarısız: {}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_match_english_keys_and_placeholders() {
+        for (key, text) in TR {
+            let english = lookup(EN, key).unwrap_or_else(|| panic!("{} is not in EN", key));
+            assert_eq!(
+                text.matches("{}").count(),
+                english.matches("{}").count(),
+                "placeholders of {}",
+                key
+            );
+        }
+    }
+
+    /// Every module that logs with `info!` or `warn!`
+    const LOGGING_MODULES: &[(&str, &str)] = &[
+        ("commands.rs", include_str!("commands.rs")),
+        ("config.rs", include_str!("config.rs")),
+        ("crashreport.rs", include_str!("crashreport.rs")),
+        ("discord.rs", include_str!("discord.rs")),
+        ("main.rs", include_str!("main.rs")),
+        ("mqtt.rs", include_str!("mqtt.rs")),
+        ("obs.rs", include_str!("obs.rs")),
+        ("prompt.rs", include_str!("prompt.rs")),
+        ("rules.rs", include_str!("rules.rs")),
+        ("script.rs", include_str!("script.rs")),
+        ("update.rs", include_str!("update.rs")),
+        ("webhook.rs", include_str!("webhook.rs")),
+    ];
+
+    #[test]
+    fn every_message_of_the_engine_is_translated() {
+        let mut keys = Vec::new();
+        for (name, source) in LOGGING_MODULES {
+            keys.extend(
+                source
+                    .split("tr!(")
+                    .skip(1)
+                    .filter_map(|rest| rest.trim_start().strip_prefix('"')?.split('"').next()),
+            );
+            // Log lines only ever print a translated message
+            let calls = ["info!(", "warn!("]
+                .iter()
+                .flat_map(|logger| source.split(logger).skip(1));
+            for call in calls {
+                assert!(
+                    call.trim_start().starts_with("\"{}\","),
+                    "untranslated log line in {}: {}",
+                    name,
+                    call.lines().next().unwrap_or_default()
+                );
+            }
+        }
+        assert!(keys.len() > 150, "found only {} tr! keys", keys.len());
+        for key in keys {
+            assert!(lookup(EN, key).is_some(), "{} is not in EN", key);
+            assert!(lookup(TR, key).is_some(), "{} is not in TR", key);
+        }
+    }
+
+    #[test]
+    fn fills_placeholders_in_order() {
+        set_language(Language::En);
+        assert_eq!(
+            crate::tr!("event.relocked", "1.5", "x"),
+            "Cursor re-locked 1.5s after Alt+Tab (x)"
+        );
+        assert_eq!(crate::tr!("no.such.key"), "no.such.key");
+    }
+}
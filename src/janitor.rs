@@ -0,0 +1,43 @@
+//! Crash-safe janitor process
+//!
+//! The panic hook and the Ctrl+C handler release the clip on the way out, but
+//! nothing runs when the process is killed from Task Manager or crashes with an
+//! access violation, leaving the mouse trapped. The janitor is a second, windowless
+//! copy of the executable that waits for the main process and releases the clip
+//! if it exited abnormally. `ClipCursor` is global, so any process can clear it,
+//! and the same goes for the system cursors `hide_while_locked` swaps out.
+
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+
+use crate::cursor::{release_cursor_clip, reload_system_cursors};
+use crate::process::wait_for_exit;
+
+/// Hidden command line flag that turns the executable into a janitor for the
+/// process ID that follows it
+pub const JANITOR_ARG: &str = "--janitor";
+
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// Starts a janitor watching the current process
+pub fn spawn() -> io::Result<Child> {
+    Command::new(std::env::current_exe()?)
+        .arg(JANITOR_ARG)
+        .arg(std::process::id().to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()
+}
+
+/// Waits for `parent_pid` to exit and releases the clip unless it shut down cleanly
+pub fn run(parent_pid: u32) {
+    // A clean exit already released the clip, and clearing it again could undo
+    // a clip set by a game in the meantime
+    if wait_for_exit(parent_pid) != Some(0) {
+        release_cursor_clip();
+        reload_system_cursors();
+    }
+}
@@ -0,0 +1,169 @@
+//! Keyboard blocking while locked
+//!
+//! A `WH_KEYBOARD_LL` hook that swallows the Windows keys and the combinations
+//! in `[keyboard] block` (Alt+Esc, Ctrl+Esc, ...) while the cursor is locked to
+//! a game, so a slipped finger doesn't throw the user out to the desktop. Like
+//! the mouse hook it runs on the installing thread, which must keep pumping
+//! messages. Ctrl+Alt+Del and Win+L can't be blocked and stay available.
+
+use std::cell::RefCell;
+
+use windows::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+    VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, SetWindowsHookExW, UnhookWindowsHookEx, HC_ACTION, HHOOK, KBDLLHOOKSTRUCT,
+    WH_KEYBOARD_LL, WM_KEYDOWN, WM_SYSKEYDOWN,
+};
+
+use crate::config::KeyboardConfig;
+
+/// The keys to swallow
+#[derive(Debug, Clone, Default)]
+struct Blocklist {
+    win_key: bool,
+    /// Modifiers (without `MOD_NOREPEAT`) and virtual-key code of each combination
+    combos: Vec<(HOT_KEY_MODIFIERS, u32)>,
+    /// Keys whose press was swallowed, so their release is too
+    held: Vec<u32>,
+}
+
+impl Blocklist {
+    fn from_config(config: &KeyboardConfig) -> Self {
+        Self {
+            win_key: config.block_win_key,
+            combos: config
+                .block
+                .iter()
+                .map(|combo| (combo.modifiers() & !MOD_NOREPEAT, combo.key()))
+                .collect(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Whether a key event is swallowed, given the modifiers held right now
+    ///
+    /// A release is swallowed only if its press was: one let through before
+    /// the hook went in has to reach Windows, or it thinks the key is still down.
+    fn swallows(&mut self, vk: u32, down: bool, modifiers: HOT_KEY_MODIFIERS) -> bool {
+        if !down {
+            let held = self.held.len();
+            self.held.retain(|&key| key != vk);
+            return self.held.len() != held;
+        }
+        let blocked = (self.win_key && (vk == VK_LWIN.0 as u32 || vk == VK_RWIN.0 as u32))
+            || self
+                .combos
+                .iter()
+                .any(|&(combo, key)| key == vk && combo == modifiers);
+        if blocked && !self.held.contains(&vk) {
+            self.held.push(vk);
+        }
+        blocked
+    }
+}
+
+thread_local! {
+    static BLOCKLIST: RefCell<Option<Blocklist>> = const { RefCell::new(None) };
+}
+
+fn held_modifiers() -> HOT_KEY_MODIFIERS {
+    let pressed = |vk: u16| unsafe { GetAsyncKeyState(vk as i32) } < 0;
+    let mut modifiers = HOT_KEY_MODIFIERS(0);
+    for (vk, modifier) in [
+        (VK_CONTROL.0, MOD_CONTROL),
+        (VK_MENU.0, MOD_ALT),
+        (VK_SHIFT.0, MOD_SHIFT),
+        (VK_LWIN.0, MOD_WIN),
+        (VK_RWIN.0, MOD_WIN),
+    ] {
+        if pressed(vk) {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let message = wparam.0 as u32;
+        let down = message == WM_KEYDOWN || message == WM_SYSKEYDOWN;
+        let swallowed = BLOCKLIST.with(|b| {
+            b.borrow_mut()
+                .as_mut()
+                .is_some_and(|blocklist| blocklist.swallows(info.vkCode, down, held_modifiers()))
+        });
+        if swallowed {
+            return LRESULT(1);
+        }
+    }
+
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// An installed keyboard hook, removed on drop
+pub struct KeyboardHook {
+    hook: HHOOK,
+}
+
+impl KeyboardHook {
+    /// Installs the hook on the current thread if `[keyboard]` blocks anything
+    pub fn install(config: &KeyboardConfig) -> Option<Self> {
+        if !config.blocks_anything() {
+            return None;
+        }
+        BLOCKLIST.with(|b| *b.borrow_mut() = Some(Blocklist::from_config(config)));
+        let hook = unsafe { SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), None, 0) };
+        match hook {
+            Ok(hook) => Some(Self { hook }),
+            Err(_) => {
+                BLOCKLIST.with(|b| *b.borrow_mut() = None);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for KeyboardHook {
+    fn drop(&mut self) {
+        BLOCKLIST.with(|b| *b.borrow_mut() = None);
+        unsafe {
+            let _ = UnhookWindowsHookEx(self.hook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{VK_ESCAPE, VK_TAB};
+
+    #[test]
+    fn swallows_win_keys_and_listed_combos_with_their_release() {
+        let config = KeyboardConfig {
+            block_win_key: true,
+            block: vec!["Alt+Esc".parse().unwrap()],
+        };
+        let mut blocklist = Blocklist::from_config(&config);
+        let esc = VK_ESCAPE.0 as u32;
+        let none = HOT_KEY_MODIFIERS(0);
+
+        let (left_win, right_win) = (VK_LWIN.0 as u32, VK_RWIN.0 as u32);
+        assert!(blocklist.swallows(left_win, true, none));
+        // Auto-repeat keeps coming while it is held
+        assert!(blocklist.swallows(left_win, true, MOD_WIN));
+        assert!(blocklist.swallows(left_win, false, none));
+        // Pressed before the hook, so Windows has to see it go up
+        assert!(!blocklist.swallows(right_win, false, none));
+        assert!(!blocklist.swallows(esc, true, none));
+        assert!(!blocklist.swallows(VK_TAB.0 as u32, true, MOD_ALT));
+
+        assert!(blocklist.swallows(esc, true, MOD_ALT));
+        // Released after Alt, the key still belongs to the swallowed press
+        assert!(blocklist.swallows(esc, false, none));
+        assert!(!blocklist.swallows(esc, false, none));
+    }
+}
@@ -0,0 +1,64 @@
+//! SmartLockCursor library
+//!
+//! Building blocks shared by the `smartlockcursor` binary. Tools that want to
+//! cooperate with a running instance should look at [`protocol`].
+
+pub mod check;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod console;
+pub mod control;
+#[cfg(feature = "crash-reports")]
+pub mod crashreport;
+pub mod cursor;
+#[cfg(feature = "discord")]
+pub mod discord;
+pub mod elevation;
+pub mod eventlog;
+pub mod events;
+pub mod explain;
+pub mod gamepad;
+pub mod games;
+pub mod gesture;
+pub mod hooks;
+pub mod hotkey;
+#[cfg(feature = "http")]
+pub mod httpstatus;
+pub mod i18n;
+pub mod janitor;
+pub mod json;
+pub mod keyhook;
+pub mod machine;
+pub mod monitor;
+pub mod mousehook;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "obs")]
+pub mod obs;
+pub mod persist;
+pub mod power;
+pub mod process;
+pub mod prompt;
+pub mod protocol;
+pub mod rawinput;
+pub mod replay;
+pub mod rulepack;
+pub mod rules;
+#[cfg(feature = "rhai")]
+pub mod script;
+pub mod session;
+pub mod snapshot;
+pub mod stats;
+pub mod stdio;
+pub mod steam;
+pub mod supervisor;
+pub mod system;
+pub mod systemlog;
+pub mod tray;
+pub mod tui;
+pub mod update;
+pub mod watcher;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub mod window;
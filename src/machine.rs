@@ -0,0 +1,1114 @@
+//! The lock state machine
+//!
+//! Decides when the cursor is locked, held through a grace period, released for
+//! Alt+Tab or Task View or kept free after the user switched away. It only sees what the
+//! foreground window is on every tick ([`Input`]) and answers with what should
+//! happen ([`Action`]); actually clipping the cursor, logging and everything
+//! Win32 is up to the caller. Time is passed in, so every path can be tested.
+
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::RECT;
+
+use crate::config::{AltTabConfig, Config};
+use crate::events::{AltTabOutcome, RelockTrigger, Switcher, UnlockReason};
+use crate::monitor::rects_equal;
+
+/// How long a lock survives without its fullscreen window in front by default
+pub const GRACE: Duration = Duration::from_secs(5);
+
+/// What the foreground looks like on this tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// There is no foreground window
+    NoForeground,
+    /// The Alt+Tab switcher or Task View is up
+    TaskSwitcher(Switcher),
+    /// A transient window like a toast or Snap Assist has the foreground for a
+    /// moment; the lock is held as it is, without counting down the grace period
+    Transient,
+    /// A regular window is in front
+    Window {
+        hwnd: isize,
+        /// Where the window would be locked to, `None` if it isn't eligible
+        lock_rect: Option<RECT>,
+        /// Whether a mouse button went down, for `require_click`
+        clicked: bool,
+    },
+}
+
+/// Where the lock currently stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Nothing is locked
+    Idle,
+    /// The cursor is locked to `hwnd`, which is in front
+    Locked { hwnd: isize, rect: RECT },
+    /// The locked window lost the foreground; the lock ends once `remaining`
+    /// (as of `as_of`) has passed
+    GracePeriod {
+        hwnd: isize,
+        rect: RECT,
+        remaining: Duration,
+        as_of: Instant,
+    },
+    /// The Alt+Tab switcher or Task View is up
+    AltTab {
+        /// The switcher that came up first, kept if the other one follows
+        switcher: Switcher,
+        since: Instant,
+        /// The window that was locked (or left) before
+        remembered: Option<isize>,
+        /// When the user first switched away, if this Alt+Tab started from there
+        away_since: Option<Instant>,
+    },
+    /// The user picked another window after Alt+Tab; stay free until a
+    /// fullscreen window is back in front
+    SwitchedAway { since: Instant, remembered: isize },
+}
+
+/// What the caller should do after a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Lock the cursor to `rect`; `relock` is set when this ends an Alt+Tab,
+    /// with what triggered it and how long after Alt+Tab
+    Lock {
+        hwnd: isize,
+        rect: RECT,
+        relock: Option<(RelockTrigger, Duration)>,
+    },
+    /// The locked window moved or resized, move the clip along
+    Follow { rect: RECT },
+    /// The locked window is still in front, keep the clip as it is
+    Refresh,
+    /// The grace period is running, keep the clip as it is
+    Hold,
+    /// The lock ended for good
+    Unlock { hwnd: isize, reason: UnlockReason },
+    /// Alt+Tab or Task View came up; release the clip but remember the lock
+    AltTabStarted {
+        was_locked: bool,
+        switcher: Switcher,
+    },
+    AltTabEnded {
+        switcher: Switcher,
+        duration: Duration,
+        chosen_hwnd: isize,
+        outcome: AltTabOutcome,
+    },
+    /// `remember_timeout_ms` passed while switched away
+    Forgot { after: Duration },
+    /// A hold from [`LockMachine::hold_free`] ran out
+    HoldEnded,
+}
+
+/// The settings the machine times its decisions by
+///
+/// Engaging and releasing have separate thresholds: `lock_delay` keeps a new
+/// window from locking until it has settled, `grace` keeps a lock alive while
+/// its window is briefly out of front. Both are wall-clock time, so they don't
+/// depend on how often the machine is stepped.
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub alt_tab: AltTabConfig,
+    /// How long a new fullscreen window has to stay in front before it is
+    /// locked, so launch splash screens don't lock and unlock in a burst
+    pub lock_delay: Duration,
+    /// How long a lock survives without its window in front
+    pub grace: Duration,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            alt_tab: AltTabConfig::default(),
+            lock_delay: Duration::ZERO,
+            grace: GRACE,
+        }
+    }
+}
+
+impl Timing {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            alt_tab: config.alt_tab.clone(),
+            lock_delay: Duration::from_millis(config.detection.lock_delay_ms),
+            grace: Duration::from_millis(config.detection.grace_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PendingRelock {
+    trigger: RelockTrigger,
+    since: Instant,
+    due: Instant,
+}
+
+/// The lock state plus the timers that outlive a single state
+#[derive(Debug, Clone)]
+pub struct LockMachine {
+    state: State,
+    relock: Option<PendingRelock>,
+    free_until: Option<Instant>,
+    /// A fullscreen window waiting out the lock delay, and since when
+    warming_up: Option<(isize, Instant)>,
+    /// When the locked window was last seen in front, where the grace period
+    /// starts from
+    in_front: Option<Instant>,
+}
+
+impl Default for LockMachine {
+    fn default() -> Self {
+        Self {
+            state: State::Idle,
+            relock: None,
+            free_until: None,
+            warming_up: None,
+            in_front: None,
+        }
+    }
+}
+
+impl LockMachine {
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Whether the cursor should currently be confined
+    pub fn is_locked(&self) -> bool {
+        matches!(self.state, State::Locked { .. } | State::GracePeriod { .. })
+    }
+
+    /// The locked window, including during the grace period
+    pub fn locked_hwnd(&self) -> Option<isize> {
+        match self.state {
+            State::Locked { hwnd, .. } | State::GracePeriod { hwnd, .. } => Some(hwnd),
+            _ => None,
+        }
+    }
+
+    /// The rect the lock was made for, before margins
+    pub fn locked_rect(&self) -> Option<RECT> {
+        match self.state {
+            State::Locked { rect, .. } | State::GracePeriod { rect, .. } => Some(rect),
+            _ => None,
+        }
+    }
+
+    /// The window Alt+Tab or switching away left behind
+    pub fn remembered(&self) -> Option<isize> {
+        match self.state {
+            State::AltTab { remembered, .. } => remembered,
+            State::SwitchedAway { remembered, .. } => Some(remembered),
+            _ => None,
+        }
+    }
+
+    /// Whether a timer is running, so the caller has to keep ticking even
+    /// without window events
+    pub fn needs_ticks(&self) -> bool {
+        self.state != State::Idle
+            || self.relock.is_some()
+            || self.free_until.is_some()
+            || self.warming_up.is_some()
+    }
+
+    /// Forgets the lock without an [`Action::Unlock`], e.g. when the clip was
+    /// handed to another program
+    pub fn unlock(&mut self) {
+        if self.is_locked() {
+            self.state = State::Idle;
+        }
+    }
+
+    /// Forgets everything, including Alt+Tab and pending timers
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Moves the lock to a window that replaced the locked one
+    pub fn adopt(&mut self, new_hwnd: isize) {
+        if let State::Locked { ref mut hwnd, .. } | State::GracePeriod { ref mut hwnd, .. } =
+            self.state
+        {
+            *hwnd = new_hwnd;
+        }
+    }
+
+    /// Ends the lock and keeps fullscreen windows from re-locking until `until`
+    pub fn hold_free(&mut self, until: Instant) {
+        self.state = State::Idle;
+        self.free_until = Some(until);
+    }
+
+    /// Advances the machine by one tick
+    pub fn step(&mut self, input: Input, now: Instant, timing: &Timing) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let alt_tab = &timing.alt_tab;
+        // Only kept while the same window stays fullscreen in front
+        let warming_up = self.warming_up.take();
+        // A hold ends on time whatever is in front, or nothing would let the
+        // caller stop ticking
+        if self.free_until.is_some_and(|until| now >= until) {
+            self.free_until = None;
+            actions.push(Action::HoldEnded);
+        }
+
+        let (hwnd, lock_rect, clicked) = match input {
+            Input::NoForeground => {
+                self.tick_grace(UnlockReason::NoForeground, now, timing, &mut actions);
+                return actions;
+            }
+            Input::TaskSwitcher(switcher) => {
+                self.start_alt_tab(switcher, now, &mut actions);
+                return actions;
+            }
+            Input::Transient => {
+                // As if the locked window was still in front, so no grace runs out
+                match self.state {
+                    State::Locked { .. } => self.in_front = Some(now),
+                    State::GracePeriod { ref mut as_of, .. } => *as_of = now,
+                    _ => return actions,
+                }
+                actions.push(Action::Hold);
+                return actions;
+            }
+            Input::Window {
+                hwnd,
+                lock_rect,
+                clicked,
+            } => (hwnd, lock_rect, clicked),
+        };
+
+        if let State::AltTab {
+            switcher,
+            since,
+            remembered,
+            away_since,
+        } = self.state
+        {
+            let outcome = match remembered {
+                None => {
+                    self.state = State::Idle;
+                    AltTabOutcome::NotLocked
+                }
+                Some(remembered) if remembered == hwnd => {
+                    self.state = State::Idle;
+                    self.schedule_relock(RelockTrigger::Returned, now, now, alt_tab);
+                    AltTabOutcome::Returned
+                }
+                Some(remembered) => {
+                    self.state = State::SwitchedAway {
+                        since: away_since.unwrap_or(now),
+                        remembered,
+                    };
+                    AltTabOutcome::SwitchedAway
+                }
+            };
+            actions.push(Action::AltTabEnded {
+                switcher,
+                duration: now - since,
+                chosen_hwnd: hwnd,
+                outcome,
+            });
+        }
+
+        if let State::SwitchedAway { since, .. } = self.state {
+            let timeout = Duration::from_millis(alt_tab.remember_timeout_ms);
+            if !timeout.is_zero() && now - since >= timeout {
+                self.state = State::Idle;
+                actions.push(Action::Forgot { after: now - since });
+            }
+        }
+
+        let Some(rect) = lock_rect else {
+            // The user went on to something that can't be locked, so the next
+            // fullscreen window is a new one rather than the return relocked
+            self.relock = None;
+            self.tick_grace(UnlockReason::FullscreenExited, now, timing, &mut actions);
+            return actions;
+        };
+
+        if self.free_until.is_some() {
+            return actions;
+        }
+
+        // Coming back from switching away, only re-lock once a fullscreen
+        // window is in front (and clicked, if configured)
+        if let State::SwitchedAway { since, .. } = self.state {
+            let trigger = if alt_tab.require_click {
+                if !clicked {
+                    return actions;
+                }
+                RelockTrigger::Clicked
+            } else {
+                RelockTrigger::Activated
+            };
+            self.state = State::Idle;
+            self.schedule_relock(trigger, since, now, alt_tab);
+        }
+
+        // Hold the clip back until the re-lock delay has passed
+        if self.relock.is_some_and(|relock| now < relock.due) {
+            return actions;
+        }
+
+        match self.state {
+            State::Locked {
+                hwnd: locked,
+                rect: locked_rect,
+            }
+            | State::GracePeriod {
+                hwnd: locked,
+                rect: locked_rect,
+                ..
+            } if locked == hwnd => {
+                self.state = State::Locked { hwnd, rect };
+                self.in_front = Some(now);
+                if rects_equal(&rect, &locked_rect) {
+                    actions.push(Action::Refresh);
+                } else {
+                    actions.push(Action::Follow { rect });
+                }
+            }
+            _ => {
+                // Relocks after Alt+Tab return to a window that was settled already
+                if self.relock.is_none() && !timing.lock_delay.is_zero() {
+                    let since = match warming_up {
+                        Some((warming, since)) if warming == hwnd => since,
+                        _ => now,
+                    };
+                    if now - since < timing.lock_delay {
+                        self.warming_up = Some((hwnd, since));
+                        // Not locked yet, so a lock it replaces runs out as usual
+                        self.tick_grace(UnlockReason::FullscreenExited, now, timing, &mut actions);
+                        return actions;
+                    }
+                }
+                // A new fullscreen window, possibly replacing the locked one
+                let relock = self
+                    .relock
+                    .take()
+                    .map(|relock| (relock.trigger, now - relock.since));
+                self.state = State::Locked { hwnd, rect };
+                self.in_front = Some(now);
+                actions.push(Action::Lock { hwnd, rect, relock });
+            }
+        }
+        actions
+    }
+
+    fn schedule_relock(
+        &mut self,
+        trigger: RelockTrigger,
+        since: Instant,
+        now: Instant,
+        alt_tab: &AltTabConfig,
+    ) {
+        self.relock = Some(PendingRelock {
+            trigger,
+            since,
+            due: now + Duration::from_millis(alt_tab.relock_delay_ms),
+        });
+    }
+
+    fn start_alt_tab(&mut self, switcher: Switcher, now: Instant, actions: &mut Vec<Action>) {
+        let (remembered, away_since, was_locked) = match self.state {
+            State::AltTab { .. } => return,
+            State::Locked { hwnd, .. } | State::GracePeriod { hwnd, .. } => {
+                (Some(hwnd), None, true)
+            }
+            State::SwitchedAway { since, remembered } => (Some(remembered), Some(since), false),
+            State::Idle => (None, None, false),
+        };
+
+        self.state = State::AltTab {
+            switcher,
+            since: now,
+            remembered,
+            away_since,
+        };
+        self.relock = None;
+        actions.push(Action::AltTabStarted {
+            was_locked,
+            switcher,
+        });
+    }
+
+    /// Counts down the grace period while the locked window isn't in front
+    fn tick_grace(
+        &mut self,
+        reason: UnlockReason,
+        now: Instant,
+        timing: &Timing,
+        actions: &mut Vec<Action>,
+    ) {
+        let (hwnd, rect, remaining, as_of) = match self.state {
+            State::Locked { hwnd, rect } => {
+                (hwnd, rect, timing.grace, self.in_front.unwrap_or(now))
+            }
+            State::GracePeriod {
+                hwnd,
+                rect,
+                remaining,
+                as_of,
+            } => (hwnd, rect, remaining, as_of),
+            _ => return,
+        };
+
+        let remaining = remaining.saturating_sub(now.saturating_duration_since(as_of));
+        if remaining.is_zero() {
+            self.state = State::Idle;
+            actions.push(Action::Unlock { hwnd, reason });
+        } else {
+            self.state = State::GracePeriod {
+                hwnd,
+                rect,
+                remaining,
+                as_of: now,
+            };
+            actions.push(Action::Hold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+    const GAME: isize = 0x100;
+    const BROWSER: isize = 0x200;
+    const ALT_TAB: Input = Input::TaskSwitcher(Switcher::AltTab);
+    /// Time between harness steps
+    const TICK: Duration = Duration::from_millis(100);
+    /// Harness steps in the default grace period
+    const GRACE_TICKS: u32 = 50;
+    const TASK_VIEW: Input = Input::TaskSwitcher(Switcher::TaskView);
+
+    fn fullscreen(hwnd: isize) -> Input {
+        Input::Window {
+            hwnd,
+            lock_rect: Some(MONITOR),
+            clicked: false,
+        }
+    }
+
+    fn windowed(hwnd: isize) -> Input {
+        Input::Window {
+            hwnd,
+            lock_rect: None,
+            clicked: false,
+        }
+    }
+
+    fn clicked(hwnd: isize) -> Input {
+        Input::Window {
+            hwnd,
+            lock_rect: Some(MONITOR),
+            clicked: true,
+        }
+    }
+
+    struct Harness {
+        machine: LockMachine,
+        timing: Timing,
+        now: Instant,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                machine: LockMachine::default(),
+                timing: Timing::default(),
+                now: Instant::now(),
+            }
+        }
+
+        fn step(&mut self, input: Input) -> Vec<Action> {
+            self.now += TICK;
+            self.machine.step(input, self.now, &self.timing)
+        }
+
+        fn wait(&mut self, duration: Duration) {
+            self.now += duration;
+        }
+
+        fn locked(&mut self) {
+            let actions = self.step(fullscreen(GAME));
+            assert!(matches!(actions[..], [Action::Lock { hwnd: GAME, .. }]));
+        }
+    }
+
+    #[test]
+    fn locks_fullscreen_window() {
+        let mut h = Harness::new();
+        assert_eq!(
+            h.step(fullscreen(GAME)),
+            [Action::Lock {
+                hwnd: GAME,
+                rect: MONITOR,
+                relock: None
+            }]
+        );
+        assert_eq!(
+            h.machine.state(),
+            State::Locked {
+                hwnd: GAME,
+                rect: MONITOR
+            }
+        );
+        assert_eq!(h.step(fullscreen(GAME)), [Action::Refresh]);
+    }
+
+    #[test]
+    fn windowed_apps_stay_idle() {
+        let mut h = Harness::new();
+        assert!(h.step(windowed(BROWSER)).is_empty());
+        assert!(h.step(Input::NoForeground).is_empty());
+        assert_eq!(h.machine.state(), State::Idle);
+        assert!(!h.machine.needs_ticks());
+    }
+
+    #[test]
+    fn follows_moved_window() {
+        let mut h = Harness::new();
+        h.locked();
+        let moved = RECT {
+            left: 1920,
+            right: 3840,
+            ..MONITOR
+        };
+        let actions = h.step(Input::Window {
+            hwnd: GAME,
+            lock_rect: Some(moved),
+            clicked: false,
+        });
+        assert_eq!(actions, [Action::Follow { rect: moved }]);
+        assert!(h
+            .machine
+            .locked_rect()
+            .is_some_and(|r| rects_equal(&r, &moved)));
+    }
+
+    #[test]
+    fn grace_period_runs_out() {
+        let mut h = Harness::new();
+        h.locked();
+        for i in 1..GRACE_TICKS {
+            assert_eq!(h.step(windowed(BROWSER)), [Action::Hold]);
+            assert_eq!(
+                h.machine.state(),
+                State::GracePeriod {
+                    hwnd: GAME,
+                    rect: MONITOR,
+                    remaining: GRACE - TICK * i,
+                    as_of: h.now,
+                }
+            );
+        }
+        assert_eq!(
+            h.step(windowed(BROWSER)),
+            [Action::Unlock {
+                hwnd: GAME,
+                reason: UnlockReason::FullscreenExited
+            }]
+        );
+        assert_eq!(h.machine.state(), State::Idle);
+    }
+
+    #[test]
+    fn grace_period_is_wall_clock_time() {
+        let mut h = Harness::new();
+        h.locked();
+        // Stepped once a second instead of every 100ms
+        for _ in 0..4 {
+            h.wait(Duration::from_millis(900));
+            assert_eq!(h.step(windowed(BROWSER)), [Action::Hold]);
+        }
+        h.wait(Duration::from_millis(900));
+        assert!(matches!(
+            h.step(windowed(BROWSER))[..],
+            [Action::Unlock { hwnd: GAME, .. }]
+        ));
+    }
+
+    #[test]
+    fn grace_period_resets_when_window_returns() {
+        let mut h = Harness::new();
+        h.locked();
+        for _ in 0..GRACE_TICKS - 1 {
+            h.step(windowed(BROWSER));
+        }
+        assert_eq!(h.step(fullscreen(GAME)), [Action::Refresh]);
+        assert_eq!(h.step(windowed(BROWSER)), [Action::Hold]);
+        assert!(h.machine.is_locked());
+    }
+
+    #[test]
+    fn no_foreground_unlocks_with_its_own_reason() {
+        let mut h = Harness::new();
+        h.locked();
+        for _ in 0..GRACE_TICKS - 1 {
+            assert_eq!(h.step(Input::NoForeground), [Action::Hold]);
+        }
+        assert_eq!(
+            h.step(Input::NoForeground),
+            [Action::Unlock {
+                hwnd: GAME,
+                reason: UnlockReason::NoForeground
+            }]
+        );
+    }
+
+    #[test]
+    fn transient_windows_hold_the_lock_without_using_up_the_grace_period() {
+        let mut h = Harness::new();
+        h.locked();
+        h.step(windowed(BROWSER));
+        for _ in 0..GRACE_TICKS * 2 {
+            assert_eq!(h.step(Input::Transient), [Action::Hold]);
+        }
+        assert!(matches!(
+            h.machine.state(),
+            State::GracePeriod { remaining, .. } if remaining == GRACE - TICK
+        ));
+        assert_eq!(h.step(fullscreen(GAME)), [Action::Refresh]);
+
+        h.machine.reset();
+        assert!(h.step(Input::Transient).is_empty());
+        assert_eq!(h.machine.state(), State::Idle);
+    }
+
+    #[test]
+    fn another_fullscreen_window_takes_over_the_lock() {
+        let mut h = Harness::new();
+        h.locked();
+        let actions = h.step(fullscreen(BROWSER));
+        assert!(matches!(
+            actions[..],
+            [Action::Lock {
+                hwnd: BROWSER,
+                relock: None,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn adopt_keeps_the_lock() {
+        let mut h = Harness::new();
+        h.locked();
+        h.machine.adopt(0x101);
+        assert_eq!(h.step(fullscreen(0x101)), [Action::Refresh]);
+    }
+
+    #[test]
+    fn alt_tab_and_return_relocks() {
+        let mut h = Harness::new();
+        h.locked();
+        assert_eq!(
+            h.step(ALT_TAB),
+            [Action::AltTabStarted {
+                was_locked: true,
+                switcher: Switcher::AltTab
+            }]
+        );
+        assert!(!h.machine.is_locked());
+        assert_eq!(h.machine.remembered(), Some(GAME));
+        // Staying in the switcher doesn't repeat the start
+        assert!(h.step(ALT_TAB).is_empty());
+        assert!(h.step(Input::NoForeground).is_empty());
+
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [
+                Action::AltTabEnded {
+                    chosen_hwnd: GAME,
+                    outcome: AltTabOutcome::Returned,
+                    ..
+                },
+                Action::Lock {
+                    hwnd: GAME,
+                    relock: Some((RelockTrigger::Returned, _)),
+                    ..
+                }
+            ]
+        ));
+        assert!(h.machine.is_locked());
+    }
+
+    #[test]
+    fn alt_tab_without_lock() {
+        let mut h = Harness::new();
+        h.step(windowed(BROWSER));
+        assert_eq!(
+            h.step(ALT_TAB),
+            [Action::AltTabStarted {
+                was_locked: false,
+                switcher: Switcher::AltTab
+            }]
+        );
+        let actions = h.step(windowed(BROWSER));
+        assert!(matches!(
+            actions[..],
+            [Action::AltTabEnded {
+                outcome: AltTabOutcome::NotLocked,
+                ..
+            }]
+        ));
+        assert_eq!(h.machine.state(), State::Idle);
+    }
+
+    #[test]
+    fn task_view_and_return_relocks() {
+        let mut h = Harness::new();
+        h.locked();
+        assert_eq!(
+            h.step(TASK_VIEW),
+            [Action::AltTabStarted {
+                was_locked: true,
+                switcher: Switcher::TaskView
+            }]
+        );
+        assert!(!h.machine.is_locked());
+        // Task View stays up while the user looks around, without a grace period
+        for _ in 0..GRACE_TICKS * 2 {
+            assert!(h.step(TASK_VIEW).is_empty());
+        }
+
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [
+                Action::AltTabEnded {
+                    switcher: Switcher::TaskView,
+                    chosen_hwnd: GAME,
+                    outcome: AltTabOutcome::Returned,
+                    ..
+                },
+                Action::Lock {
+                    hwnd: GAME,
+                    relock: Some((RelockTrigger::Returned, _)),
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn task_view_switching_away_keeps_the_cursor_free() {
+        let mut h = Harness::new();
+        h.locked();
+        h.step(TASK_VIEW);
+        let actions = h.step(windowed(BROWSER));
+        assert!(matches!(
+            actions[..],
+            [Action::AltTabEnded {
+                switcher: Switcher::TaskView,
+                outcome: AltTabOutcome::SwitchedAway,
+                ..
+            }]
+        ));
+        assert_eq!(h.machine.remembered(), Some(GAME));
+
+        // Alt+Tab back to the game from there behaves as usual
+        assert_eq!(
+            h.step(ALT_TAB),
+            [Action::AltTabStarted {
+                was_locked: false,
+                switcher: Switcher::AltTab
+            }]
+        );
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [
+                Action::AltTabEnded {
+                    switcher: Switcher::AltTab,
+                    outcome: AltTabOutcome::Returned,
+                    ..
+                },
+                Action::Lock { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn alt_tab_turning_into_task_view_is_one_session() {
+        let mut h = Harness::new();
+        h.locked();
+        h.step(ALT_TAB);
+        assert!(h.step(TASK_VIEW).is_empty());
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [
+                Action::AltTabEnded {
+                    switcher: Switcher::AltTab,
+                    outcome: AltTabOutcome::Returned,
+                    ..
+                },
+                Action::Lock { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn relock_delay_holds_the_clip_back() {
+        let mut h = Harness::new();
+        h.timing.alt_tab.relock_delay_ms = 500;
+        h.locked();
+        h.step(ALT_TAB);
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(actions[..], [Action::AltTabEnded { .. }]));
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(h.machine.needs_ticks());
+
+        h.wait(Duration::from_millis(400));
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [Action::Lock {
+                relock: Some((RelockTrigger::Returned, _)),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn switching_away_keeps_the_cursor_free() {
+        let mut h = Harness::new();
+        h.locked();
+        h.step(ALT_TAB);
+        let actions = h.step(windowed(BROWSER));
+        assert!(matches!(
+            actions[..],
+            [Action::AltTabEnded {
+                chosen_hwnd: BROWSER,
+                outcome: AltTabOutcome::SwitchedAway,
+                ..
+            }]
+        ));
+        assert!(matches!(
+            h.machine.state(),
+            State::SwitchedAway {
+                remembered: GAME,
+                ..
+            }
+        ));
+        // No grace period and no unlock while away
+        for _ in 0..GRACE_TICKS * 2 {
+            assert!(h.step(windowed(BROWSER)).is_empty());
+        }
+
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [Action::Lock {
+                relock: Some((RelockTrigger::Activated, _)),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn require_click_waits_for_a_click() {
+        let mut h = Harness::new();
+        h.timing.alt_tab.require_click = true;
+        h.locked();
+        h.step(ALT_TAB);
+        h.step(windowed(BROWSER));
+
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(!h.machine.is_locked());
+        let actions = h.step(clicked(GAME));
+        assert!(matches!(
+            actions[..],
+            [Action::Lock {
+                relock: Some((RelockTrigger::Clicked, _)),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn second_alt_tab_while_away_keeps_the_remembered_window() {
+        let mut h = Harness::new();
+        h.locked();
+        h.step(ALT_TAB);
+        h.step(windowed(BROWSER));
+        let away = match h.machine.state() {
+            State::SwitchedAway { since, .. } => since,
+            state => panic!("unexpected state {:?}", state),
+        };
+
+        assert_eq!(
+            h.step(ALT_TAB),
+            [Action::AltTabStarted {
+                was_locked: false,
+                switcher: Switcher::AltTab
+            }]
+        );
+        h.step(windowed(0x300));
+        assert_eq!(
+            h.machine.state(),
+            State::SwitchedAway {
+                since: away,
+                remembered: GAME
+            }
+        );
+
+        h.step(ALT_TAB);
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [
+                Action::AltTabEnded {
+                    outcome: AltTabOutcome::Returned,
+                    ..
+                },
+                Action::Lock { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn remembered_window_expires() {
+        let mut h = Harness::new();
+        h.timing.alt_tab.remember_timeout_ms = 10_000;
+        h.timing.alt_tab.require_click = true;
+        h.locked();
+        h.step(ALT_TAB);
+        h.step(windowed(BROWSER));
+
+        h.wait(Duration::from_secs(10));
+        let actions = h.step(windowed(BROWSER));
+        assert!(matches!(actions[..], [Action::Forgot { .. }]));
+        assert_eq!(h.machine.state(), State::Idle);
+
+        // A fresh lock, no click needed and not reported as a re-lock
+        assert!(matches!(
+            h.step(fullscreen(GAME))[..],
+            [Action::Lock { relock: None, .. }]
+        ));
+    }
+
+    #[test]
+    fn grace_is_configurable() {
+        let mut h = Harness::new();
+        h.timing.grace = TICK * 3;
+        h.locked();
+        assert_eq!(h.step(windowed(BROWSER)), [Action::Hold]);
+        assert_eq!(h.step(windowed(BROWSER)), [Action::Hold]);
+        assert!(matches!(
+            h.step(windowed(BROWSER))[..],
+            [Action::Unlock { hwnd: GAME, .. }]
+        ));
+    }
+
+    #[test]
+    fn lock_delay_waits_for_a_settled_window() {
+        let mut h = Harness::new();
+        h.timing.lock_delay = Duration::from_millis(300);
+        // A splash screen flashing fullscreen and back never locks
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(h.step(windowed(GAME)).is_empty());
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(h.step(fullscreen(GAME)).is_empty());
+        assert!(h.machine.needs_ticks());
+        assert!(matches!(
+            h.step(fullscreen(GAME))[..],
+            [Action::Lock { hwnd: GAME, .. }]
+        ));
+
+        // Coming back from Alt+Tab is no new window
+        h.step(ALT_TAB);
+        assert!(matches!(
+            h.step(fullscreen(GAME))[..],
+            [Action::AltTabEnded { .. }, Action::Lock { .. }]
+        ));
+    }
+
+    #[test]
+    fn hold_free_blocks_relocking_until_it_ends() {
+        let mut h = Harness::new();
+        h.locked();
+        h.machine.hold_free(h.now + Duration::from_secs(5));
+        assert!(!h.machine.is_locked());
+        assert!(h.step(fullscreen(GAME)).is_empty());
+
+        h.wait(Duration::from_secs(5));
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(
+            actions[..],
+            [Action::HoldEnded, Action::Lock { relock: None, .. }]
+        ));
+    }
+
+    #[test]
+    fn a_hold_ends_without_a_fullscreen_window() {
+        let mut h = Harness::new();
+        h.locked();
+        h.machine.hold_free(h.now + Duration::from_secs(5));
+        assert!(h.step(windowed(BROWSER)).is_empty());
+        assert!(h.machine.needs_ticks());
+
+        h.wait(Duration::from_secs(5));
+        assert_eq!(h.step(windowed(BROWSER)), [Action::HoldEnded]);
+        assert!(!h.machine.needs_ticks());
+    }
+
+    #[test]
+    fn an_abandoned_relock_leaves_the_next_window_to_the_lock_delay() {
+        const OTHER_GAME: isize = 0x300;
+        let mut h = Harness::new();
+        h.timing.alt_tab.relock_delay_ms = 500;
+        h.timing.lock_delay = Duration::from_secs(1);
+        h.step(fullscreen(GAME));
+        h.wait(Duration::from_secs(1));
+        h.locked();
+        h.step(ALT_TAB);
+        let actions = h.step(fullscreen(GAME));
+        assert!(matches!(actions[..], [Action::AltTabEnded { .. }]));
+
+        // Before the relock is due, something windowed comes to the front
+        assert!(h.step(windowed(BROWSER)).is_empty());
+        assert!(!h.machine.needs_ticks());
+
+        // Another game going fullscreen is a new lock, not the relock
+        h.wait(Duration::from_millis(500));
+        assert!(h.step(fullscreen(OTHER_GAME)).is_empty());
+        h.wait(Duration::from_secs(1));
+        let actions = h.step(fullscreen(OTHER_GAME));
+        assert!(matches!(
+            actions[..],
+            [Action::Lock {
+                hwnd: OTHER_GAME,
+                relock: None,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        let mut h = Harness::new();
+        h.timing.alt_tab.relock_delay_ms = 1000;
+        h.locked();
+        h.step(ALT_TAB);
+        h.step(fullscreen(GAME));
+        assert!(h.machine.needs_ticks());
+        h.machine.reset();
+        assert!(!h.machine.needs_ticks());
+        assert_eq!(h.machine.state(), State::Idle);
+    }
+}
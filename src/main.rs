@@ -3,405 +3,5057 @@
 //! This utility detects when a window goes fullscreen and clips the mouse cursor
 //! to the bounds of the display containing that window.
 
-use std::mem::zeroed;
-use std::ptr::null_mut;
-use std::thread;
-use std::time::Duration;
-
-use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT, TRUE};
-use windows::Win32::Graphics::Gdi::{
-    EnumDisplayMonitors, GetMonitorInfoW, MonitorFromWindow, HDC, HMONITOR, MONITORINFO,
-    MONITOR_DEFAULTTONEAREST,
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::{BOOL, FALSE, HANDLE, HWND, POINT, RECT, TRUE};
+use windows::Win32::System::Console::{
+    SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
 };
 use windows::Win32::UI::WindowsAndMessaging::{
-    ClipCursor, GetClassNameW, GetForegroundWindow, GetWindowRect,
+    ClipCursor, GetForegroundWindow, PBT_APMPOWERSTATUSCHANGE, PBT_APMRESUMEAUTOMATIC,
+    PBT_APMRESUMESUSPEND, PBT_APMSUSPEND, WTS_CONSOLE_CONNECT, WTS_CONSOLE_DISCONNECT,
+    WTS_REMOTE_CONNECT, WTS_REMOTE_DISCONNECT, WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
 };
 
-/// Represents a monitor's information
-#[derive(Debug, Clone)]
-struct MonitorBounds {
-    rect: RECT,
-    #[allow(dead_code)]
-    handle: HMONITOR,
-}
-
-/// Collects all monitor bounds in the system
-fn get_all_monitors() -> Vec<MonitorBounds> {
-    let mut monitors: Vec<MonitorBounds> = Vec::new();
-
-    unsafe extern "system" fn enum_monitor_proc(
-        hmonitor: HMONITOR,
-        _hdc: HDC,
-        _lprect: *mut RECT,
-        lparam: LPARAM,
-    ) -> BOOL {
-        let monitors = &mut *(lparam.0 as *mut Vec<MonitorBounds>);
-
-        let mut monitor_info: MONITORINFO = zeroed();
-        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
-
-        if GetMonitorInfoW(hmonitor, &mut monitor_info).as_bool() {
-            monitors.push(MonitorBounds {
-                rect: monitor_info.rcMonitor,
-                handle: hmonitor,
-            });
-        }
+use smartlockcursor::check::{self, Severity};
+use smartlockcursor::cli::{self, Command, RunOptions};
+use smartlockcursor::commands::{self, LockDetails};
+use smartlockcursor::config::{
+    warn_conflict_copies, ClipTarget, Config, DetectionLevel, EngineConfig, EngineMode,
+    LockBackend, LockOverrides, LowPowerMode, MonitorLock, ProfileConfig, Reclip, ReleasePosition,
+    TrayConfig,
+};
+use smartlockcursor::console::{self, Level};
+use smartlockcursor::control::{self, ControlMessage, ControlWindow, Wake};
+#[cfg(feature = "crash-reports")]
+use smartlockcursor::crashreport;
+use smartlockcursor::cursor::{release_cursor_clip, restore_system_cursors, ClipError};
+#[cfg(feature = "discord")]
+use smartlockcursor::discord::DiscordPresence;
+use smartlockcursor::elevation::{is_elevated, relaunch_elevated};
+use smartlockcursor::eventlog::{EventLog, Record};
+use smartlockcursor::events::{AltTabOutcome, Event, UnlockReason};
+use smartlockcursor::explain::{explain, list_windows};
+use smartlockcursor::gesture::EdgeEscape;
+use smartlockcursor::hooks::{DesktopSwitchHook, EventHooks, ForegroundHook};
+use smartlockcursor::hotkey::HotkeyAction;
+#[cfg(feature = "http")]
+use smartlockcursor::httpstatus::StatusServer;
+use smartlockcursor::i18n;
+use smartlockcursor::janitor;
+use smartlockcursor::keyhook::KeyboardHook;
+use smartlockcursor::machine::{Action, Input, LockMachine, State, Timing};
+use smartlockcursor::monitor::{
+    clamp_rect, covers_share, describe_rect, get_all_monitors, monitor_at, monitor_details,
+    monitor_policy, pair_rect, rect_contains, rects_equal, rects_overlap, FRAME_OVERHANG,
+};
+use smartlockcursor::mousehook::MouseHook;
+#[cfg(feature = "mqtt")]
+use smartlockcursor::mqtt::MqttPublisher;
+#[cfg(feature = "obs")]
+use smartlockcursor::obs::ObsBroadcaster;
+use smartlockcursor::persist::{self, SavedState};
+use smartlockcursor::power::{is_on_battery, set_efficiency_mode};
+use smartlockcursor::prompt;
+use smartlockcursor::protocol::{send_request, status_path, Reply, Request};
+use smartlockcursor::rawinput::{self, MouseWatch};
+use smartlockcursor::replay::{self, Recording};
+use smartlockcursor::rulepack;
+use smartlockcursor::rules::{RuleSet, Verdict, WindowInfo};
+use smartlockcursor::session::AssistiveTool;
+use smartlockcursor::snapshot::{self, snapshot_path, EngineState};
+use smartlockcursor::stats::Stats;
+use smartlockcursor::stdio::{StdioChannel, StdioCommand};
+use smartlockcursor::supervisor::{self, Heartbeat};
+use smartlockcursor::system::{MockSystem, Win32System, WindowSystem};
+use smartlockcursor::systemlog::{self, SystemLog};
+use smartlockcursor::tray::{Balloon, TrayCommand, TrayIcon};
+use smartlockcursor::tui::{self, Dashboard};
+use smartlockcursor::update;
+use smartlockcursor::watcher::ConfigWatcher;
+#[cfg(feature = "webhook")]
+use smartlockcursor::webhook::Webhook;
+use smartlockcursor::window::is_shell_class;
+use smartlockcursor::{info, tr, warn};
+
+/// How often presentation mode is checked; Windows sends no notification for it
+const PRESENTATION_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the process list is scanned for mouse sharing and clipping tools
+const PROCESS_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+/// This many clip thefts within the window mean the game clears the clip by
+/// itself, and the clip backend can't win against it
+const THEFTS_TO_ESCALATE: usize = 5;
+const THEFT_WINDOW: Duration = Duration::from_secs(2);
+
+/// Failed clips in a row before saying so; the console hears again after
+/// twice as many, the tray only once per run
+const CLIP_FAILURES_TO_WARN: u32 = 10;
+
+/// What keeps `ClipCursor` from confining the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClipFailure {
+    /// The foreground process runs as administrator and we don't, so UIPI
+    /// shields it until another app is in front
+    Elevated(u32),
+    /// A UAC prompt or the lock screen has the input, which passes by itself
+    SecureDesktop,
+    /// DPI virtualization put the clip somewhere else
+    Dpi,
+    Other(ClipError),
+}
+
+/// Why cursor locking is paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suspension {
+    RemoteSession,
+    SessionLocked,
+    SessionDisconnected,
+    SecureDesktop,
+    Sleep,
+    Idle,
+    Gamepad,
+    AssistiveTool(AssistiveTool),
+    Presenting,
+    MouseSharing,
+    Paused,
+    Panic,
+}
 
-        TRUE
+impl Suspension {
+    fn paused_message(self) -> &'static str {
+        match self {
+            Suspension::RemoteSession => tr!("paused.remote_session"),
+            Suspension::SessionLocked => tr!("paused.session_locked"),
+            Suspension::SessionDisconnected => tr!("paused.session_disconnected"),
+            Suspension::SecureDesktop => tr!("paused.secure_desktop"),
+            Suspension::Sleep => tr!("paused.sleep"),
+            Suspension::Idle => tr!("paused.idle"),
+            Suspension::Gamepad => tr!("paused.gamepad"),
+            Suspension::AssistiveTool(AssistiveTool::Magnifier) => tr!("paused.magnifier"),
+            Suspension::AssistiveTool(AssistiveTool::ScreenReader) => tr!("paused.screen_reader"),
+            Suspension::AssistiveTool(AssistiveTool::OnScreenKeyboard) => {
+                tr!("paused.on_screen_keyboard")
+            }
+            Suspension::Presenting => tr!("paused.presenting"),
+            Suspension::MouseSharing => tr!("paused.mouse_sharing"),
+            Suspension::Paused => tr!("paused.paused"),
+            Suspension::Panic => tr!("paused.panic"),
+        }
     }
 
-    unsafe {
-        let _ = EnumDisplayMonitors(
-            HDC::default(),
-            None,
-            Some(enum_monitor_proc),
-            LPARAM(&mut monitors as *mut _ as isize),
-        );
+    fn resumed_message(self) -> &'static str {
+        match self {
+            Suspension::RemoteSession => tr!("resumed.remote_session"),
+            Suspension::SessionLocked => tr!("resumed.session_locked"),
+            Suspension::SessionDisconnected => tr!("resumed.session_disconnected"),
+            Suspension::SecureDesktop => tr!("resumed.secure_desktop"),
+            Suspension::Sleep => tr!("resumed.sleep"),
+            Suspension::Idle => tr!("resumed.idle"),
+            Suspension::Gamepad => tr!("resumed.gamepad"),
+            Suspension::AssistiveTool(_) => tr!("resumed.assistive_tool"),
+            Suspension::Presenting => tr!("resumed.presenting"),
+            Suspension::MouseSharing => tr!("resumed.mouse_sharing"),
+            Suspension::Paused => tr!("resumed.paused"),
+            Suspension::Panic => tr!("resumed.panic"),
+        }
     }
+}
 
-    monitors
+/// Main application state
+struct AppState<S: WindowSystem = Win32System> {
+    system: S,
+    // Lock, grace period and Alt+Tab state
+    machine: LockMachine,
+    // What the machine times its decisions by, from the config
+    timing: Timing,
+    // Process and class of the locked window, to recognize it when the game recreates it
+    locked_pid: u32,
+    locked_class: String,
+    // Cursor position captured right before the lock engaged
+    saved_cursor_pos: Option<POINT>,
+    config: Config,
+    // The config as loaded, before the active profile is applied
+    base_config: Config,
+    // Index of the active `[[profiles]]` entry
+    profile: Option<usize>,
+    rules: RuleSet,
+    // Window whose rule verdict is cached, re-evaluated when its title changes,
+    // and its process, which tells it from a later window under the same handle
+    tracked_hwnd: isize,
+    tracked_pid: u32,
+    tracked: WindowInfo,
+    tracked_verdict: Verdict,
+    tracked_overrides: LockOverrides,
+    // Confinement hook while locked with one of the hook backends
+    mouse_hook: Option<MouseHook>,
+    // Swallows the Windows key and `[keyboard] block` while locked
+    key_hook: Option<KeyboardHook>,
+    confined_with: LockBackend,
+    // Whether we made the cursor invisible for the lock (`hide_while_locked`)
+    cursor_hidden: bool,
+    // What the on_lock command was told, kept for on_unlock while confined
+    lock_details: Option<LockDetails>,
+    // Process that asked us to yield the clip via the handoff protocol
+    yielded_to: Option<u32>,
+    // Active reasons for not locking at all
+    suspensions: Vec<Suspension>,
+    // When the pause after the panic hotkey ends
+    panic_until: Option<Instant>,
+    // Whether we run as administrator, and the last elevated process we warned about
+    elevated: bool,
+    warned_elevated_pid: Option<u32>,
+    // ClipCursor calls that failed in a row, and the advice for the tray
+    // balloon once they are too many
+    clip_failures: u32,
+    // Elevated process that refused the clip, left alone while it is in front
+    clip_refused_by: Option<u32>,
+    clip_alert: Option<String>,
+    clip_alerted: bool,
+    // Monitor locked by the manual lock hotkey or `lock` command, and whether
+    // its clip is applied right now (not while suspended or yielded)
+    manual_lock: Option<RECT>,
+    manual_confined: bool,
+    // App no rule mentions that was locked, to ask about once the lock ends,
+    // and the apps already asked about this run
+    learn_candidate: Option<String>,
+    prompted: HashSet<String>,
+    // Edge-escape gesture progress
+    edge_escape: EdgeEscape,
+    // Whether the current hold comes from the tray's "Unlock for 30s" rather
+    // than the edge-escape gesture
+    timed_unlock: bool,
+    // When presentation mode and display duplication were last checked
+    last_presentation_check: Option<Instant>,
+    // Mouse sharing and conflicting clipping tools found running, and when the
+    // process list was last scanned for them
+    mouse_sharing_tool: Option<String>,
+    conflicting_tool: Option<String>,
+    // OBS process found running, or "game capture" when only its hook is
+    obs_running: Option<String>,
+    last_process_scan: Option<Instant>,
+    // Whether the foreground window could be locked at all, for adaptive polling
+    candidate: bool,
+    // Low-power profile state, and when the clip was last checked for theft under it
+    low_power: bool,
+    last_clip_check: Option<Instant>,
+    // Recent clip thefts during this lock, and the apps moved to the mouse
+    // hook for clearing the clip over and over
+    clip_thefts: Vec<Instant>,
+    escalated: HashSet<String>,
+    // Where pausing, the profile and the escalations are kept for the next
+    // run, `None` when they aren't
+    state_file: Option<PathBuf>,
+    // Whether the main thread currently runs in efficiency mode
+    efficient: bool,
+    // `--dry-run`: the system only pretends to clip, and the hooks stay out
+    dry_run: bool,
+    // Shared with the Ctrl+C handler and the status report
+    stats: Arc<Mutex<Stats>>,
+    event_log: Option<EventLog>,
+    system_log: Option<SystemLog>,
+    // Command channel of `--stdio`, which also gets every event as JSON
+    stdio: Option<StdioChannel>,
+    #[cfg(feature = "webhook")]
+    webhook: Option<Webhook>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<MqttPublisher>,
+    #[cfg(feature = "discord")]
+    discord: Option<DiscordPresence>,
+    #[cfg(feature = "obs")]
+    obs: Option<ObsBroadcaster>,
+    #[cfg(feature = "http")]
+    status_server: Option<StatusServer>,
 }
 
-/// Gets monitor rect for a specific monitor handle
-fn get_monitor_rect(hmonitor: HMONITOR) -> Option<RECT> {
-    unsafe {
-        let mut monitor_info: MONITORINFO = zeroed();
-        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+impl<S: WindowSystem> AppState<S> {
+    fn new(config: Config, system: S) -> Self {
+        let timing = Timing::from_config(&config);
+        let mut stats = Stats::default();
+        stats.set_timing(&timing);
+        Self {
+            system,
+            machine: LockMachine::default(),
+            timing,
+            locked_pid: 0,
+            locked_class: String::new(),
+            saved_cursor_pos: None,
+            rules: RuleSet::from_config(&config),
+            base_config: config.clone(),
+            profile: None,
+            config,
+            tracked_hwnd: 0,
+            tracked_pid: 0,
+            tracked: WindowInfo::default(),
+            tracked_verdict: Verdict::Lock,
+            tracked_overrides: LockOverrides::default(),
+            mouse_hook: None,
+            key_hook: None,
+            confined_with: LockBackend::Clip,
+            cursor_hidden: false,
+            lock_details: None,
+            yielded_to: None,
+            suspensions: Vec::new(),
+            panic_until: None,
+            elevated: is_elevated(),
+            warned_elevated_pid: None,
+            clip_failures: 0,
+            clip_refused_by: None,
+            clip_alert: None,
+            clip_alerted: false,
+            manual_lock: None,
+            manual_confined: false,
+            learn_candidate: None,
+            prompted: HashSet::new(),
+            edge_escape: EdgeEscape::default(),
+            timed_unlock: false,
+            last_presentation_check: None,
+            mouse_sharing_tool: None,
+            conflicting_tool: None,
+            obs_running: None,
+            last_process_scan: None,
+            candidate: true,
+            low_power: false,
+            last_clip_check: None,
+            clip_thefts: Vec::new(),
+            escalated: HashSet::new(),
+            state_file: None,
+            efficient: false,
+            dry_run: false,
+            stats: Arc::new(Mutex::new(stats)),
+            event_log: None,
+            system_log: None,
+            stdio: None,
+            #[cfg(feature = "webhook")]
+            webhook: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            #[cfg(feature = "discord")]
+            discord: None,
+            #[cfg(feature = "obs")]
+            obs: None,
+            #[cfg(feature = "http")]
+            status_server: None,
+        }
+    }
+
+    fn stats(&self) -> MutexGuard<'_, Stats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-        if GetMonitorInfoW(hmonitor, &mut monitor_info).as_bool() {
-            Some(monitor_info.rcMonitor)
+    /// Prints an event and appends it to the event log
+    fn emit(&mut self, event: Event) {
+        let level = if event.is_warning() {
+            Level::Warn
         } else {
-            None
-        }
+            Level::Info
+        };
+        console::log(level, format_args!("{}", event));
+        self.record(&event);
     }
-}
 
-/// Checks if a window is in fullscreen mode and returns the monitor rect if so
-fn check_fullscreen(hwnd: HWND) -> Option<RECT> {
-    if hwnd.0 == null_mut() {
-        return None;
+    /// Prints a warning and writes it to the Windows event log
+    fn warn(&self, message: &str) {
+        warn!("{}", message);
+        if let Some(ref log) = self.system_log {
+            log.error(message);
+        }
     }
 
-    unsafe {
-        // Get window rect
-        let mut window_rect: RECT = zeroed();
-        if GetWindowRect(hwnd, &mut window_rect).is_err() {
-            return None;
+    /// Appends an event to the event logs and sends it to the webhook and the
+    /// stdio channel, without printing it
+    fn record(&mut self, event: &Event) {
+        #[cfg(feature = "webhook")]
+        let webhook = self.webhook.as_ref().filter(|w| w.wants(event.kind()));
+        #[cfg(feature = "webhook")]
+        let sending = webhook.is_some() || self.stdio.is_some();
+        #[cfg(not(feature = "webhook"))]
+        let sending = self.stdio.is_some();
+        if self.event_log.is_none() && self.system_log.is_none() && !sending {
+            return;
+        }
+
+        let hwnd = event
+            .hwnd()
+            .filter(|&hwnd| hwnd != 0)
+            .or(self.machine.locked_hwnd())
+            .or(self.machine.remembered())
+            .unwrap_or(0);
+        let window = HWND(hwnd as *mut _);
+        let record = Record {
+            event,
+            hwnd,
+            process: (hwnd != 0)
+                .then(|| {
+                    self.system
+                        .process_name(self.system.window_process_id(window))
+                })
+                .flatten(),
+            monitor: (hwnd != 0)
+                .then(|| self.system.window_monitor_rect(window))
+                .flatten()
+                .map(|rect| describe_rect(&rect)),
+        };
+
+        #[cfg(feature = "webhook")]
+        if let Some(webhook) = webhook {
+            webhook.send(record.to_json());
+        }
+        if let Some(ref mut stdio) = self.stdio {
+            stdio.send(&record.to_json());
+        }
+        if let Some(ref log) = self.system_log {
+            let mut text = event.to_string();
+            if let Some(ref process) = record.process {
+                text.push_str(&format!("\nProcess: {}", process));
+            }
+            if let Some(ref monitor) = record.monitor {
+                text.push_str(&format!("\nMonitor: {}", monitor));
+            }
+            log.event(event, &text);
+        }
+
+        let Some(ref mut log) = self.event_log else {
+            return;
+        };
+        if let Err(e) = log.write(&record) {
+            let message = tr!("event_log.write_failed", log.path().display(), e);
+            self.event_log = None;
+            self.warn(&message);
         }
+    }
 
-        // Get the monitor this window is primarily on
-        let hmonitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
-        let monitor_rect = get_monitor_rect(hmonitor)?;
+    /// Opens the Windows event log source if `[system_log]` enables it
+    fn open_system_log(&mut self) {
+        self.system_log = None;
+        match SystemLog::open(&self.config.system_log) {
+            Ok(log) => self.system_log = log,
+            Err(e) => warn!(
+                "{}",
+                tr!("system_log.open_failed", self.config.system_log.source, e)
+            ),
+        }
+    }
 
-        // Calculate dimensions
-        let window_width = window_rect.right - window_rect.left;
-        let window_height = window_rect.bottom - window_rect.top;
-        let monitor_width = monitor_rect.right - monitor_rect.left;
-        let monitor_height = monitor_rect.bottom - monitor_rect.top;
+    /// Connects to the MQTT broker if `[mqtt]` names one, dropping any previous connection
+    fn start_mqtt(&mut self) {
+        #[cfg(feature = "mqtt")]
+        {
+            self.mqtt = None;
+            match MqttPublisher::start(&self.config.mqtt) {
+                Ok(Some(mqtt)) => {
+                    if let Some(ref details) = self.lock_details {
+                        mqtt.publish_state(Some(details));
+                    }
+                    self.mqtt = Some(mqtt);
+                }
+                Ok(None) => {}
+                Err(e) => self.warn(&tr!("mqtt.disabled", e)),
+            }
+        }
+        #[cfg(not(feature = "mqtt"))]
+        if self.config.mqtt.host.is_some() {
+            warn!("{}", tr!("mqtt.unsupported"));
+        }
+    }
 
-        // Allow small tolerance (some apps have slight differences)
-        let tolerance = 5;
+    /// Shows the lock state on Discord if `[discord]` names an application,
+    /// dropping any previous connection
+    fn start_discord(&mut self) {
+        #[cfg(feature = "discord")]
+        {
+            self.discord = None;
+            match DiscordPresence::start(&self.config.discord) {
+                Ok(Some(discord)) => {
+                    if let Some(ref details) = self.lock_details {
+                        discord.publish_state(Some(details));
+                    }
+                    self.discord = Some(discord);
+                }
+                Ok(None) => {}
+                Err(e) => self.warn(&tr!("discord.disabled", e)),
+            }
+        }
+        #[cfg(not(feature = "discord"))]
+        if self.config.discord.client_id.is_some() {
+            warn!("{}", tr!("discord.unsupported"));
+        }
+    }
 
-        // Check if window size matches monitor size (with tolerance)
-        let width_match = (window_width - monitor_width).abs() <= tolerance;
-        let height_match = (window_height - monitor_height).abs() <= tolerance;
+    /// Connects to obs-websocket if `[obs]` asks for it, dropping any previous connection
+    fn start_obs(&mut self) {
+        #[cfg(feature = "obs")]
+        {
+            self.obs = None;
+            match ObsBroadcaster::start(&self.config.obs) {
+                Ok(Some(obs)) => {
+                    if let Some(ref details) = self.lock_details {
+                        obs.publish_state(Some(details));
+                    }
+                    self.obs = Some(obs);
+                }
+                Ok(None) => {}
+                Err(e) => self.warn(&tr!("obs.disabled", e)),
+            }
+        }
+        #[cfg(not(feature = "obs"))]
+        if self.config.obs.websocket {
+            warn!("{}", tr!("obs.unsupported"));
+        }
+    }
 
-        // Check if window position matches monitor position (with tolerance)
-        let left_match = (window_rect.left - monitor_rect.left).abs() <= tolerance;
-        let top_match = (window_rect.top - monitor_rect.top).abs() <= tolerance;
+    /// Serves `/status` and `/metrics` if `[http]` sets a port, stopping any previous server
+    fn start_status_server(&mut self) {
+        #[cfg(feature = "http")]
+        {
+            self.status_server = None;
+            if let Some(port) = self.config.http.port {
+                match StatusServer::start(port, Arc::clone(&self.stats)) {
+                    Ok(server) => {
+                        info!("{}", tr!("http.serving", server.addr()));
+                        self.status_server = Some(server);
+                    }
+                    Err(e) => self.warn(&tr!("http.failed", port, e)),
+                }
+            }
+        }
+        #[cfg(not(feature = "http"))]
+        if self.config.http.port.is_some() {
+            warn!("{}", tr!("http.unsupported"));
+        }
+    }
 
-        if width_match && height_match && left_match && top_match {
-            return Some(monitor_rect);
+    /// Name of the current state for the status endpoint
+    fn state_name(&self) -> &'static str {
+        match self.status() {
+            Reply::Yielded => "yielded",
+            Reply::Locked => "locked",
+            _ if self.suspensions.contains(&Suspension::Paused) => "paused",
+            _ if !self.suspensions.is_empty() => "suspended",
+            _ => "unlocked",
         }
+    }
 
-        // Alternative: window completely covers or exceeds monitor bounds
-        if window_rect.left <= monitor_rect.left + tolerance
-            && window_rect.top <= monitor_rect.top + tolerance
-            && window_rect.right >= monitor_rect.right - tolerance
-            && window_rect.bottom >= monitor_rect.bottom - tolerance
-            && window_width >= monitor_width - tolerance
-            && window_height >= monitor_height - tolerance
+    /// Starts the webhook sender if `[webhook]` sets a URL, stopping any previous one
+    fn start_webhook(&mut self) {
+        #[cfg(feature = "webhook")]
         {
-            return Some(monitor_rect);
+            self.webhook = None;
+            match Webhook::start(&self.config.webhook) {
+                Ok(Some(webhook)) => {
+                    info!("{}", tr!("webhook.sending"));
+                    self.webhook = Some(webhook);
+                }
+                Ok(None) => {}
+                Err(e) => self.warn(&tr!("webhook.disabled", e)),
+            }
         }
+        #[cfg(not(feature = "webhook"))]
+        if self.config.webhook.url.is_some() {
+            warn!("{}", tr!("webhook.unsupported"));
+        }
+    }
 
-        None
+    /// Switches to a freshly loaded config, keeping the current lock if it
+    /// still applies and the active profile if the new file still has one of
+    /// that name
+    fn apply_config(&mut self, config: Config) {
+        let profile = self
+            .active_profile()
+            .and_then(|profile| config.profile_index(&profile.name));
+        self.base_config = config;
+        self.profile = profile;
+        let config = match profile {
+            Some(index) => self.base_config.with_profile(index),
+            None => self.base_config.clone(),
+        };
+        self.use_config(config);
     }
-}
 
-/// Clips the cursor to the specified rectangle
-fn clip_cursor_to_rect(rect: &RECT) -> bool {
-    unsafe { ClipCursor(Some(rect)).is_ok() }
-}
+    fn active_profile(&self) -> Option<&ProfileConfig> {
+        self.profile
+            .and_then(|index| self.base_config.profiles.get(index))
+    }
 
-/// Releases the cursor clip
-fn release_cursor_clip() -> bool {
-    unsafe { ClipCursor(None).is_ok() }
-}
+    /// Activates the profile at `index`, or goes back to the main config
+    fn switch_profile(&mut self, index: Option<usize>) {
+        self.profile = index.filter(|&index| index < self.base_config.profiles.len());
+        let config = match self.profile {
+            Some(index) => self.base_config.with_profile(index),
+            None => self.base_config.clone(),
+        };
+        match self.active_profile() {
+            Some(profile) => info!("{}", tr!("profile.switched", profile.name)),
+            None => info!("{}", tr!("profile.cleared")),
+        }
+        self.use_config(config);
+        self.save_state();
+    }
 
-/// Compare two RECTs for equality
-fn rects_equal(a: &RECT, b: &RECT) -> bool {
-    a.left == b.left && a.top == b.top && a.right == b.right && a.bottom == b.bottom
-}
+    /// What the next run picks up with `[startup] restore_state`
+    fn saved_state(&self) -> SavedState {
+        let mut escalated: Vec<String> = self.escalated.iter().cloned().collect();
+        escalated.sort();
+        SavedState {
+            paused: self.suspensions.contains(&Suspension::Paused),
+            profile: self.active_profile().map(|profile| profile.name.clone()),
+            escalated,
+        }
+    }
 
-/// Check if the current foreground window is the Alt+Tab task switcher
-fn is_task_switcher(hwnd: HWND) -> bool {
-    if hwnd.0 == null_mut() {
-        return false;
+    /// Writes the state file, if there is one
+    fn save_state(&self) {
+        let Some(ref path) = self.state_file else {
+            return;
+        };
+        // Turned off since the launch
+        if !self.config.startup.restore_state {
+            return;
+        }
+        if let Err(e) = self.saved_state().save(path) {
+            warn!("{}", tr!("state.save_failed", path.display(), e));
+        }
     }
 
-    unsafe {
-        let mut class_name = [0u16; 256];
-        let len = GetClassNameW(hwnd, &mut class_name);
-        if len == 0 {
+    /// Carries on where the last run left off
+    fn restore_state(&mut self, state: SavedState) {
+        self.escalated.extend(state.escalated);
+        let profile = state
+            .profile
+            .as_deref()
+            .and_then(|name| self.base_config.profile_index(name));
+        if profile.is_some() {
+            self.switch_profile(profile);
+        }
+        if state.paused {
+            self.suspend(Suspension::Paused);
+        }
+    }
+
+    /// Puts `config` into effect, restarting whatever depends on changed settings
+    fn use_config(&mut self, config: Config) {
+        let confinement_changed = config.cursor.margins != self.config.cursor.margins
+            || config.cursor.resistance_ms != self.config.cursor.resistance_ms
+            || config.cursor.device != self.config.cursor.device
+            || config.cursor.suppress_buttons != self.config.cursor.suppress_buttons;
+        let event_log_changed = config.event_log != self.config.event_log;
+        let system_log_changed = config.system_log != self.config.system_log;
+        let webhook_changed = config.webhook != self.config.webhook;
+        let mqtt_changed = config.mqtt != self.config.mqtt;
+        let discord_changed = config.discord != self.config.discord;
+        let obs_changed = config.obs != self.config.obs;
+        let http_changed = config.http != self.config.http;
+        let power_changed = config.power != self.config.power;
+        let keyboard_changed = config.keyboard != self.config.keyboard;
+        i18n::set_language(config.ui.language);
+        self.rules = RuleSet::from_config(&config);
+        self.timing = Timing::from_config(&config);
+        self.stats().set_timing(&self.timing);
+        self.config = config;
+        // Force the foreground window to be classified again under the new rules
+        self.tracked_hwnd = 0;
+
+        if event_log_changed {
+            self.open_event_log();
+        }
+        if system_log_changed {
+            self.open_system_log();
+        }
+        if webhook_changed {
+            self.start_webhook();
+        }
+        if mqtt_changed {
+            self.start_mqtt();
+        }
+        if discord_changed {
+            self.start_discord();
+        }
+        if obs_changed {
+            self.start_obs();
+        }
+        if http_changed {
+            self.start_status_server();
+        }
+        if power_changed {
+            self.check_power_source();
+            self.sync_efficiency_mode();
+        }
+        if !self.config.cursor.hide_while_locked {
+            self.show_cursor();
+        }
+        if keyboard_changed {
+            self.key_hook = None;
+            if self.clip_rect().is_some() && !self.dry_run {
+                self.key_hook = KeyboardHook::install(&self.config.keyboard);
+            }
+        }
+
+        // Apply new margins, resistance, device or buttons right away instead of mistaking the old clip for theft
+        if confinement_changed {
+            if let Some(clip) = self.clip_rect() {
+                let _ = self.confine(&clip, self.confined_with);
+            }
+        }
+    }
+
+    /// Opens the event log if `[event_log]` enables it, closing any previous one
+    fn open_event_log(&mut self) {
+        self.event_log = None;
+        if !self.config.event_log.enabled {
+            return;
+        }
+
+        match EventLog::open(&self.config.event_log) {
+            Ok(log) => {
+                info!("{}", tr!("event_log.opened", log.path().display()));
+                self.event_log = Some(log);
+            }
+            Err(e) => self.warn(&tr!("event_log.open_failed", e)),
+        }
+    }
+
+    /// Confines the cursor to `rect` with the given backend, running the
+    /// `on_lock` command if it was free until now
+    fn confine(&mut self, rect: &RECT, backend: LockBackend) -> bool {
+        if !self.install_confinement(rect, backend) {
             return false;
         }
+        // Blocking keys for real has no place in a dry run either
+        if self.key_hook.is_none() && !self.dry_run {
+            self.key_hook = KeyboardHook::install(&self.config.keyboard);
+        }
+        // Only when the game still shows a cursor of its own; one that hid it
+        // needs no help
+        if self.config.cursor.hide_while_locked
+            && !self.cursor_hidden
+            && self.system.cursor_showing()
+        {
+            self.cursor_hidden = self.system.hide_cursor();
+        }
 
-        let class_str = String::from_utf16_lossy(&class_name[..len as usize]);
+        if self.lock_details.is_none() && self.reports_locks() {
+            let details = self.lock_details(rect);
+            if let Some(ref command) = self.config.commands.on_lock {
+                commands::spawn(command, "lock", &details);
+            }
+            #[cfg(feature = "mqtt")]
+            if let Some(ref mqtt) = self.mqtt {
+                mqtt.publish_state(Some(&details));
+            }
+            #[cfg(feature = "discord")]
+            if let Some(ref discord) = self.discord {
+                discord.publish_state(Some(&details));
+            }
+            #[cfg(feature = "obs")]
+            if let Some(ref obs) = self.obs {
+                obs.publish_state(Some(&details));
+            }
+            self.lock_details = Some(details);
+        }
+        true
+    }
 
-        // Windows Alt+Tab switcher class names
-        // "MultitaskingViewFrame" - Windows 10/11 Alt+Tab
-        // "TaskSwitcherWnd" - Older Windows Alt+Tab
-        // "XamlExplorerHostIslandWindow" - Windows 11 Alt+Tab variant
-        // "Windows.UI.Core.CoreWindow" - Can be task view
-        class_str.contains("MultitaskingView")
-            || class_str.contains("TaskSwitcher")
-            || class_str.contains("XamlExplorerHostIslandWindow")
-            || class_str == "ForegroundStaging"
+    /// Whether anything wants to hear about the cursor getting locked and released
+    fn reports_locks(&self) -> bool {
+        #[cfg(feature = "mqtt")]
+        if self.mqtt.is_some() {
+            return true;
+        }
+        #[cfg(feature = "discord")]
+        if self.discord.is_some() {
+            return true;
+        }
+        #[cfg(feature = "obs")]
+        if self.obs.is_some() {
+            return true;
+        }
+        let commands = &self.config.commands;
+        commands.on_lock.is_some() || commands.on_unlock.is_some()
     }
-}
 
-/// Main application state
-struct AppState {
-    is_cursor_locked: bool,
-    locked_to_hwnd: isize,
-    current_monitor_rect: Option<RECT>,
-    // Counter for grace period - prevents immediate unlock on transient focus changes
-    stable_count: u32,
-    // Track if we're in Alt+Tab mode
-    alt_tab_active: bool,
-    // Track if user switched away after Alt+Tab (don't re-lock until they click fullscreen window)
-    user_switched_away: bool,
-    // Remember the fullscreen window we were locked to
-    remembered_fullscreen_hwnd: isize,
-}
-
-impl AppState {
-    fn new() -> Self {
-        Self {
-            is_cursor_locked: false,
-            locked_to_hwnd: 0,
-            current_monitor_rect: None,
-            stable_count: 0,
-            alt_tab_active: false,
-            user_switched_away: false,
-            remembered_fullscreen_hwnd: 0,
+    /// What the lock commands are told about a lock to `rect`
+    fn lock_details(&self, rect: &RECT) -> LockDetails {
+        let hwnd = self.machine.locked_hwnd().unwrap_or(0);
+        let pid = if hwnd != 0 {
+            self.system.window_process_id(HWND(hwnd as *mut _))
+        } else {
+            0
+        };
+        let process = (pid != 0)
+            .then(|| self.system.process_name(pid))
+            .flatten()
+            .unwrap_or_default();
+        let game = (pid != 0)
+            .then(|| self.system.steam_app(pid))
+            .flatten()
+            .map(|app| app.name)
+            .or_else(|| {
+                Path::new(&process)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+        let center = POINT {
+            x: rect.left + (rect.right - rect.left) / 2,
+            y: rect.top + (rect.bottom - rect.top) / 2,
+        };
+        let monitor = monitor_at(&self.system.monitors(), center)
+            .map(|monitor| monitor.device.clone())
+            .unwrap_or_default();
+        LockDetails {
+            process,
+            game,
+            hwnd,
+            monitor,
+            rect: *rect,
         }
     }
 
-    fn update(&mut self) {
-        unsafe {
-            let foreground = GetForegroundWindow();
-
-            // Handle case when no foreground window
-            if foreground.0 == null_mut() {
-                if self.is_cursor_locked {
-                    self.stable_count = self.stable_count.saturating_sub(1);
-                    if self.stable_count == 0 {
-                        release_cursor_clip();
-                        self.is_cursor_locked = false;
-                        self.locked_to_hwnd = 0;
-                        self.current_monitor_rect = None;
-                        println!("[INFO] No foreground window, cursor released");
-                    } else {
-                        // Keep re-applying clip during grace period
-                        if let Some(ref rect) = self.current_monitor_rect {
-                            let _ = clip_cursor_to_rect(rect);
-                        }
-                    }
+    fn install_confinement(&mut self, rect: &RECT, backend: LockBackend) -> bool {
+        let device = self.config.cursor.device.clone();
+        let suppressed = &self.config.cursor.suppress_buttons;
+        let resistance = match backend {
+            // The hook would hold the cursor for real
+            _ if self.dry_run => None,
+            // Only the hook can tell mice apart or swallow buttons
+            LockBackend::Clip if device.is_some() || !suppressed.is_empty() => Some(None),
+            LockBackend::Clip => None,
+            LockBackend::Hook => Some(None),
+            LockBackend::Soft => Some(Some(self.config.cursor.resistance_ms)),
+        };
+
+        if let Some(resistance) = resistance {
+            let backend = match backend {
+                LockBackend::Clip => LockBackend::Hook,
+                backend => backend,
+            };
+            match self.mouse_hook {
+                Some(ref hook) if hook.device() == device.as_deref() => {
+                    hook.set_rect(rect, resistance);
+                    hook.set_suppressed(&self.config.cursor.suppress_buttons);
+                    self.confined_with = backend;
+                    return true;
                 }
-                return;
+                // Watching another device, the old hook goes first
+                _ => self.mouse_hook = None,
+            }
+            let watch = device.and_then(|device| match MouseWatch::start(&device) {
+                Ok(watch) => Some(watch),
+                Err(e) => {
+                    self.warn(&tr!("hook.watch_failed", e));
+                    None
+                }
+            });
+            self.mouse_hook = MouseHook::install(rect, resistance, watch);
+            if let Some(ref hook) = self.mouse_hook {
+                hook.set_suppressed(&self.config.cursor.suppress_buttons);
+                // Drop a clip left over from a window using the clip backend
+                self.system.release_clip();
+                self.confined_with = backend;
+                self.sync_efficiency_mode();
+                return true;
             }
+            self.warn(tr!("hook.install_failed"));
+        }
 
-            // Check if Alt+Tab task switcher is active
-            if is_task_switcher(foreground) {
-                if !self.alt_tab_active {
-                    self.alt_tab_active = true;
-                    // Remember which fullscreen window we were locked to
-                    if self.is_cursor_locked {
-                        self.remembered_fullscreen_hwnd = self.locked_to_hwnd;
-                    }
-                    // Temporarily release cursor for Alt+Tab navigation
-                    release_cursor_clip();
-                    println!("[INFO] Alt+Tab detected, cursor temporarily released");
+        self.mouse_hook = None;
+        self.confined_with = LockBackend::Clip;
+        self.sync_efficiency_mode();
+        self.clip_cursor(rect)
+    }
+
+    /// Clips the cursor, and deals with Windows refusing by its cause instead
+    /// of retrying in silence
+    fn clip_cursor(&mut self, rect: &RECT) -> bool {
+        let pid = self
+            .system
+            .window_process_id(self.system.foreground_window());
+        if self.clip_refused_by == Some(pid) {
+            return false;
+        }
+        let error = match self.system.clip_cursor(rect) {
+            Ok(()) => {
+                if self.clip_failures >= CLIP_FAILURES_TO_WARN {
+                    info!("{}", tr!("clip.works_again", self.clip_failures));
                 }
-                // Don't do anything else while in Alt+Tab
-                return;
+                self.clip_failures = 0;
+                return true;
             }
+            Err(error) => error,
+        };
 
-            // If we were in Alt+Tab and now we're not
-            if self.alt_tab_active {
-                self.alt_tab_active = false;
-                let hwnd_value = foreground.0 as isize;
-
-                // Check if user switched to a different window than the fullscreen one
-                if self.remembered_fullscreen_hwnd != 0
-                    && hwnd_value != self.remembered_fullscreen_hwnd
-                {
-                    // User switched to a different window after Alt+Tab
-                    self.user_switched_away = true;
-                    self.is_cursor_locked = false;
-                    self.locked_to_hwnd = 0;
-                    self.current_monitor_rect = None;
-                    self.stable_count = 0;
-                    println!(
-                        "[INFO] Alt+Tab ended - switched to different window, cursor stays free"
-                    );
-                } else if self.remembered_fullscreen_hwnd != 0
-                    && hwnd_value == self.remembered_fullscreen_hwnd
-                {
-                    // User returned to the same fullscreen window
-                    self.user_switched_away = false;
-                    println!("[INFO] Alt+Tab ended - returned to fullscreen window");
-                } else {
-                    println!("[INFO] Alt+Tab ended");
+        let error = match self.classify_clip_error(error, pid) {
+            // Trying again on the next tick is all there is to do
+            ClipFailure::SecureDesktop => return false,
+            ClipFailure::Elevated(pid) => {
+                self.clip_refused_by = Some(pid);
+                let advice = self.clip_failure_advice();
+                self.warn(&tr!("clip.refused", advice));
+                self.alert_clip_failure(advice);
+                return false;
+            }
+            ClipFailure::Dpi => {
+                // The wrong clip could trap the cursor on the wrong monitor
+                self.system.release_clip();
+                let process = self.system.process_name(pid);
+                if let Some(process) = process.filter(|_| self.can_escalate()) {
+                    if self.escalated.insert(process.clone()) {
+                        self.save_state();
+                        self.warn(&tr!("clip.dpi_escalated", error, process));
+                        return self.confine(rect, LockBackend::Hook);
+                    }
                 }
-                self.remembered_fullscreen_hwnd = 0;
+                error
             }
+            ClipFailure::Other(error) => error,
+        };
 
-            let hwnd_value = foreground.0 as isize;
+        self.clip_failures += 1;
+        let failures = self.clip_failures;
+        if !failures.is_multiple_of(CLIP_FAILURES_TO_WARN)
+            || !(failures / CLIP_FAILURES_TO_WARN).is_power_of_two()
+        {
+            return false;
+        }
+        let advice = self.clip_failure_advice();
+        self.warn(&tr!("clip.failed_repeatedly", failures, error, advice));
+        self.alert_clip_failure(advice);
+        false
+    }
 
-            // Check if current window is fullscreen
-            if let Some(monitor_rect) = check_fullscreen(foreground) {
-                // Window is fullscreen
+    /// Tells the tray about failing clips, once per run
+    fn alert_clip_failure(&mut self, advice: String) {
+        if !self.clip_alerted {
+            self.clip_alerted = true;
+            self.clip_alert = Some(advice);
+        }
+    }
 
-                // If user switched away after Alt+Tab, only re-lock if they click the fullscreen window
-                if self.user_switched_away {
-                    // User clicked on a fullscreen window - clear the switched_away flag and lock
-                    self.user_switched_away = false;
-                    println!("[INFO] User clicked fullscreen window, re-enabling lock");
-                }
+    fn classify_clip_error(&self, error: ClipError, pid: u32) -> ClipFailure {
+        match error {
+            ClipError::AccessDenied if !self.system.is_input_desktop_default() => {
+                ClipFailure::SecureDesktop
+            }
+            // An unreadable token almost always means the process is elevated
+            ClipError::AccessDenied
+                if !self.elevated
+                    && pid != 0
+                    && self.system.is_process_elevated(pid) != Some(false) =>
+            {
+                ClipFailure::Elevated(pid)
+            }
+            ClipError::Moved(_) => ClipFailure::Dpi,
+            error => ClipFailure::Other(error),
+        }
+    }
 
-                let is_new_lock = !self.is_cursor_locked;
-                let is_different_window = self.locked_to_hwnd != hwnd_value;
-                let is_different_monitor = self
-                    .current_monitor_rect
-                    .map_or(true, |r| !rects_equal(&r, &monitor_rect));
-
-                if is_new_lock || is_different_window || is_different_monitor {
-                    // New fullscreen detected
-                    if clip_cursor_to_rect(&monitor_rect) {
-                        self.is_cursor_locked = true;
-                        self.locked_to_hwnd = hwnd_value;
-                        self.current_monitor_rect = Some(monitor_rect);
-                        self.stable_count = 50; // 5 second grace period (50 * 100ms)
-                        println!(
-                            "[INFO] Cursor locked to monitor: ({}, {}) - ({}, {})",
-                            monitor_rect.left,
-                            monitor_rect.top,
-                            monitor_rect.right,
-                            monitor_rect.bottom
-                        );
-                    }
-                } else {
-                    // Same fullscreen window - refresh the clip and reset grace period
-                    self.stable_count = 50;
-                    // Re-apply clip periodically (some apps/overlays can steal it)
-                    if let Some(ref rect) = self.current_monitor_rect {
-                        let _ = clip_cursor_to_rect(rect);
-                    }
-                }
-            } else {
-                // Window is NOT fullscreen
+    /// What to do about failing clips; an elevated foreground app is the usual
+    /// reason, as Windows doesn't let us confine the cursor to it
+    fn clip_failure_advice(&self) -> String {
+        let pid = self
+            .system
+            .window_process_id(self.system.foreground_window());
+        // An unreadable token almost always means the process is elevated
+        if self.elevated || pid == 0 || self.system.is_process_elevated(pid) == Some(false) {
+            return tr!("clip.failing").to_string();
+        }
+        let name = self
+            .system
+            .process_name(pid)
+            .unwrap_or_else(|| format!("process {}", pid));
+        tr!("clip.failing_elevated", name)
+    }
 
-                // If user switched away, don't apply any lock logic
-                if self.user_switched_away {
-                    // User is on a non-fullscreen window after Alt+Tab, do nothing
-                    return;
-                }
+    /// Lifts the confinement of either backend
+    fn unconfine(&mut self) {
+        self.mouse_hook = None;
+        self.key_hook = None;
+        self.clip_thefts.clear();
+        self.manual_confined = false;
+        self.sync_efficiency_mode();
+        self.system.release_clip();
+        self.show_cursor();
+        self.stats().lock_ended();
+
+        if let Some(details) = self.lock_details.take() {
+            if let Some(ref command) = self.config.commands.on_unlock {
+                commands::spawn(command, "unlock", &details);
+            }
+            #[cfg(feature = "mqtt")]
+            if let Some(ref mqtt) = self.mqtt {
+                mqtt.publish_state(None);
+            }
+            #[cfg(feature = "discord")]
+            if let Some(ref discord) = self.discord {
+                discord.publish_state(None);
+            }
+            #[cfg(feature = "obs")]
+            if let Some(ref obs) = self.obs {
+                obs.publish_state(None);
+            }
+        }
+    }
 
-                if self.is_cursor_locked {
-                    self.stable_count = self.stable_count.saturating_sub(1);
+    /// Brings back a cursor hidden for the lock
+    fn show_cursor(&mut self) {
+        if self.cursor_hidden {
+            self.system.show_cursor();
+            self.cursor_hidden = false;
+        }
+    }
 
-                    if self.stable_count == 0 {
-                        // Grace period expired, release cursor
-                        release_cursor_clip();
-                        self.is_cursor_locked = false;
-                        self.locked_to_hwnd = 0;
-                        self.current_monitor_rect = None;
-                        println!("[INFO] Fullscreen exited, cursor released");
-                    } else {
-                        // Still in grace period - keep clip active
-                        // This handles transient overlays, notifications, etc.
-                        if let Some(ref rect) = self.current_monitor_rect {
-                            let _ = clip_cursor_to_rect(rect);
-                        }
-                    }
+    /// The rect the cursor is clipped to: the locked monitor minus the margins
+    fn clip_rect(&self) -> Option<RECT> {
+        self.manual_lock
+            .or_else(|| self.machine.locked_rect())
+            .map(|rect| self.config.cursor.margins.apply(rect))
+    }
+
+    /// Whether the state needs regular ticks (clip re-assertion, grace period,
+    /// Alt+Tab timers) rather than just reacting to window events
+    fn needs_ticks(&self) -> bool {
+        self.machine.needs_ticks()
+            || self.manual_lock.is_some()
+            || self.yielded_to.is_some()
+            || self.panic_until.is_some()
+            // Watching for the user to come back or put the controller down
+            || self.suspensions.contains(&Suspension::Idle)
+            || self.suspensions.contains(&Suspension::Gamepad)
+    }
+
+    /// How long to wait between ticks while something needs them
+    fn tick_interval(&self) -> Duration {
+        if self.low_power {
+            Duration::from_millis(self.config.power.low_power_interval_ms)
+        } else {
+            Duration::from_millis(100)
+        }
+    }
+
+    /// Switches the low-power profile on or off to match the power source and config
+    fn check_power_source(&mut self) {
+        let low_power = match self.config.power.low_power {
+            LowPowerMode::Auto => is_on_battery(),
+            LowPowerMode::Always => true,
+            LowPowerMode::Never => false,
+        };
+        if low_power == self.low_power {
+            return;
+        }
+
+        self.low_power = low_power;
+        self.last_clip_check = None;
+        if low_power {
+            info!(
+                "{}",
+                tr!(
+                    "power.low_power_on",
+                    self.config.power.low_power_interval_ms,
+                    self.config.power.low_power_reclip_ms
+                )
+            );
+        } else {
+            info!("{}", tr!("power.low_power_off"));
+        }
+    }
+
+    /// Puts the main thread in efficiency mode if configured, except while it
+    /// hosts the mouse hook, which has to answer every mouse move promptly
+    fn sync_efficiency_mode(&mut self) {
+        let wanted = self.config.power.efficiency_mode && self.mouse_hook.is_none();
+        if wanted == self.efficient {
+            return;
+        }
+        if !set_efficiency_mode(wanted) && wanted {
+            warn!("{}", tr!("power.efficiency_failed"));
+        }
+        self.efficient = wanted;
+    }
+
+    /// Whether polling may slow down: nothing is locked or pending and the
+    /// foreground window is the desktop or one the rules ignore
+    fn can_back_off(&self) -> bool {
+        !self.candidate && !self.needs_ticks()
+    }
+
+    /// State reported to handoff protocol queries
+    fn status(&self) -> Reply {
+        if self.yielded_to.is_some() {
+            Reply::Yielded
+        } else if self.machine.is_locked() || self.manual_confined {
+            Reply::Locked
+        } else {
+            Reply::Unlocked
+        }
+    }
+
+    /// Applies a handoff protocol request from another process
+    fn handle_request(&mut self, request: Request, pid: u32) {
+        match request {
+            Request::Yield => {
+                // Hand the clip over as-is, without moving the cursor
+                self.drop_lock();
+                self.yielded_to = Some(pid);
+                info!("{}", tr!("handoff.yielded", pid));
+            }
+            Request::TakeOver => {
+                if self.yielded_to.take().is_some() {
+                    info!("{}", tr!("handoff.returned", pid));
                 }
             }
+            Request::ToggleManualLock => self.toggle_manual_lock(),
+            Request::WriteSnapshot => self.write_snapshot(),
+            Request::Query | Request::WriteStatus => {}
         }
     }
-}
 
-fn print_banner() {
-    println!("╔═══════════════════════════════════════════════════════════╗");
-    println!("║              SmartLockCursor v0.1.0                       ║");
-    println!("║  Automatically locks cursor to fullscreen windows         ║");
-    println!("╠═══════════════════════════════════════════════════════════╣");
-    println!("║  Press Ctrl+C to exit                                     ║");
-    println!("╚═══════════════════════════════════════════════════════════╝");
-    println!();
-}
+    /// Carries out a command from the stdio channel and answers with the state
+    fn handle_stdio(&mut self, command: StdioCommand) {
+        let paused = self.suspensions.contains(&Suspension::Paused);
+        match command {
+            StdioCommand::Pause => self.suspend(Suspension::Paused),
+            StdioCommand::Resume => self.resume(Suspension::Paused),
+            StdioCommand::Toggle if paused => self.resume(Suspension::Paused),
+            StdioCommand::Toggle => self.suspend(Suspension::Paused),
+            StdioCommand::Status | StdioCommand::Quit => {}
+        }
 
-fn print_monitor_info() {
-    let monitors = get_all_monitors();
-    println!("[INFO] Detected {} monitor(s):", monitors.len());
-    for (i, monitor) in monitors.iter().enumerate() {
-        let width = monitor.rect.right - monitor.rect.left;
-        let height = monitor.rect.bottom - monitor.rect.top;
-        println!(
-            "  Monitor {}: {}x{} at ({}, {})",
-            i + 1,
-            width,
-            height,
-            monitor.rect.left,
-            monitor.rect.top
+        let clip = self.clip_rect();
+        let status = {
+            let mut stats = self.stats();
+            stats.set_state(self.state_name(), clip);
+            stats.to_json_of_kind("status")
+        };
+        if let Some(ref mut stdio) = self.stdio {
+            stdio.send(&status);
+        }
+    }
+
+    fn handle_hotkey(&mut self, action: HotkeyAction) {
+        match action {
+            HotkeyAction::ManualLock => self.toggle_manual_lock(),
+            HotkeyAction::CycleMonitor => self.cycle_manual_lock(),
+            HotkeyAction::NextProfile => {
+                let next = self.profile.map_or(0, |index| index + 1);
+                self.switch_profile(Some(next));
+            }
+            HotkeyAction::Profile(index) if self.profile == Some(index) => {
+                self.switch_profile(None)
+            }
+            HotkeyAction::Profile(index) => self.switch_profile(Some(index)),
+            HotkeyAction::Panic => self.panic(),
+            HotkeyAction::Snapshot => self.write_snapshot(),
+        }
+    }
+
+    /// What the engine adds to a snapshot
+    fn engine_state(&self) -> EngineState {
+        EngineState {
+            state: self.state_name().to_string(),
+            machine: format!("{:?}", self.machine.state()),
+            profile: self.active_profile().map(|profile| profile.name.clone()),
+            suspensions: self
+                .suspensions
+                .iter()
+                .map(|suspension| format!("{:?}", suspension))
+                .collect(),
+            manual_lock: self.manual_lock,
+            yielded_to: self.yielded_to,
+            tracked_hwnd: self.tracked_hwnd,
+        }
+    }
+
+    /// Writes a snapshot of the desktop, the engine and the config file to
+    /// [`snapshot_path`]
+    fn write_snapshot(&self) {
+        let config = Config::default_path().and_then(|path| std::fs::read_to_string(path).ok());
+        let snapshot = snapshot::capture(
+            &self.system,
+            &self.rules,
+            self.tolerance(),
+            Some(&self.engine_state()),
+            config.as_deref(),
         );
+        match snapshot_path().map(|path| std::fs::write(&path, snapshot).map(|()| path)) {
+            Some(Ok(path)) => info!("{}", tr!("snapshot.written", path.display())),
+            Some(Err(e)) => self.warn(&tr!("snapshot.failed", e)),
+            None => self.warn(tr!("snapshot.no_directory")),
+        }
     }
-    println!();
-}
 
-fn main() {
-    print_banner();
-    print_monitor_info();
+    /// Emergency release for when detection traps the cursor: lifts every
+    /// lock, manual or not, and keeps locking off for `panic_cooldown_ms`.
+    /// Pressing it again restarts the cool-down.
+    fn panic(&mut self) {
+        self.manual_lock = None;
+        self.suspend(Suspension::Panic);
+        // Also a clip left over from a lock we no longer track
+        self.system.release_clip();
+        let cooldown = Duration::from_millis(self.config.hotkeys.panic_cooldown_ms);
+        self.panic_until = Some(self.system.now() + cooldown);
+    }
 
-    println!("[INFO] Monitoring for fullscreen windows...");
-    println!();
+    /// Resumes locking once the panic cool-down is over
+    fn check_panic(&mut self) {
+        if self
+            .panic_until
+            .is_some_and(|until| self.system.now() >= until)
+        {
+            self.panic_until = None;
+            self.resume(Suspension::Panic);
+        }
+    }
 
-    let mut state = AppState::new();
+    /// Locks the cursor to the monitor it is on regardless of fullscreen
+    /// detection, or lifts that lock again
+    fn toggle_manual_lock(&mut self) {
+        if self.manual_lock.take().is_some() {
+            self.unconfine();
+            self.emit(Event::ManualUnlocked);
+            return;
+        }
 
-    // Set up Ctrl+C handler to release cursor on exit
-    ctrlc_handler();
+        let monitors = self.system.monitors();
+        let Some(monitor) = self
+            .system
+            .cursor_pos()
+            .and_then(|pos| monitor_at(&monitors, pos))
+        else {
+            warn!("{}", tr!("manual.no_monitor"));
+            return;
+        };
 
-    // Main loop - check every 100ms
-    loop {
-        state.update();
-        thread::sleep(Duration::from_millis(100));
+        // The manual lock replaces whatever was locked automatically
+        self.drop_lock();
+        self.machine.reset();
+        self.manual_lock = Some(monitor.rect);
+        // Applied by the next update, unless locking is suspended or yielded
+        self.emit(Event::ManualLocked { rect: monitor.rect });
     }
-}
 
-/// Sets up a handler to release cursor clip on Ctrl+C
-fn ctrlc_handler() {
-    std::panic::set_hook(Box::new(|_| unsafe {
-        let _ = ClipCursor(None);
-    }));
+    /// Moves the manual lock to the next monitor in enumeration order
+    fn cycle_manual_lock(&mut self) {
+        let Some(current) = self.manual_lock else {
+            info!("{}", tr!("manual.not_locked"));
+            return;
+        };
+        let monitors = self.system.monitors();
+        if monitors.len() < 2 {
+            return;
+        }
+        // A monitor that went away starts the cycle over at the first one
+        let next = monitors
+            .iter()
+            .position(|m| rects_equal(&m.rect, &current))
+            .map_or(0, |i| (i + 1) % monitors.len());
+        let rect = monitors[next].rect;
+        self.manual_lock = Some(rect);
 
-    // Handle Ctrl+C
-    let _ = ctrlc::set_handler(move || {
-        println!("\n[INFO] Shutting down, releasing cursor...");
-        unsafe {
-            let _ = ClipCursor(None);
+        // Otherwise the next update applies it, once no longer suspended or yielded
+        if self.manual_confined {
+            let clip = self.config.cursor.margins.apply(rect);
+            if self.confine(&clip, self.confined_with) {
+                self.stats()
+                    .lock_started("manual lock".to_string(), describe_rect(&rect));
+                if self.config.hotkeys.cycle_moves_cursor {
+                    self.system.set_cursor_pos(
+                        rect.left + (rect.right - rect.left) / 2,
+                        rect.top + (rect.bottom - rect.top) / 2,
+                    );
+                }
+            } else {
+                self.manual_confined = false;
+            }
         }
-        std::process::exit(0);
-    });
+        self.emit(Event::ManualLockMoved { rect });
+    }
+
+    /// Keeps the manual lock's clip applied
+    fn hold_manual_lock(&mut self, rect: RECT) {
+        if self.manual_confined {
+            self.reassert_clip(self.system.foreground_window());
+            return;
+        }
+
+        let clip = self.config.cursor.margins.apply(rect);
+        if self.confine(&clip, self.config.cursor.backend) {
+            self.manual_confined = true;
+            self.stats()
+                .lock_started("manual lock".to_string(), describe_rect(&rect));
+        }
+    }
+
+    /// Evaluates the rules for a window, re-checking only when the window or its title changes
+    fn classify(&mut self, hwnd: HWND) -> Verdict {
+        let hwnd_value = hwnd.0 as isize;
+        let pid = self.system.window_process_id(hwnd);
+        let same_window = hwnd_value == self.tracked_hwnd && pid == self.tracked_pid;
+
+        let mut title_changed = false;
+        if same_window {
+            if self.rules.uses_titles() {
+                let title = self.system.window_title(hwnd);
+                title_changed = title != self.tracked.title;
+                self.tracked.title = title;
+            }
+            // A detection script may look at where the window is, which
+            // changes when it goes fullscreen or moves to another monitor
+            let mut moved = false;
+            if self.rules.uses_script() {
+                let rect = self.system.window_rect(hwnd);
+                let monitor = self.system.window_monitor_rect(hwnd);
+                moved = rect != self.tracked.rect || monitor != self.tracked.monitor;
+                self.tracked.rect = rect;
+                self.tracked.monitor = monitor;
+            }
+            if !title_changed && !moved {
+                return self.tracked_verdict;
+            }
+        } else {
+            // Class and process never change, so they are only looked up once per window
+            self.tracked_hwnd = hwnd_value;
+            self.tracked_pid = pid;
+            self.tracked = self.system.window_info(hwnd, &self.rules);
+        }
+
+        let verdict = self.rules.evaluate(&self.tracked);
+        self.tracked_overrides = self.rules.overrides(&self.tracked);
+        if same_window && verdict != self.tracked_verdict {
+            let status = match verdict {
+                Verdict::Lock => tr!("window.now_locked"),
+                Verdict::Ignore => tr!("window.now_ignored"),
+            };
+            if title_changed {
+                info!(
+                    "{}",
+                    tr!("window.title_changed", self.tracked.title, status)
+                );
+            } else {
+                info!(
+                    "{}",
+                    tr!(
+                        "window.moved",
+                        self.tracked
+                            .rect
+                            .map(|rect| describe_rect(&rect))
+                            .unwrap_or_default(),
+                        status
+                    )
+                );
+            }
+        }
+
+        self.tracked_verdict = verdict;
+        verdict
+    }
+
+    /// Pauses all locking until every suspension has been lifted
+    fn suspend(&mut self, reason: Suspension) {
+        if self.suspensions.contains(&reason) {
+            return;
+        }
+        self.suspensions.push(reason);
+        self.drop_lock();
+        // Start from a clean slate once the suspension ends
+        self.machine.reset();
+        self.tracked_hwnd = 0;
+        info!("{}", reason.paused_message());
+        if reason == Suspension::Paused {
+            self.save_state();
+        }
+    }
+
+    /// Lifts a suspension; locking resumes once none are left
+    fn resume(&mut self, reason: Suspension) {
+        if !self.suspensions.contains(&reason) {
+            return;
+        }
+        self.suspensions.retain(|&s| s != reason);
+        info!("{}", reason.resumed_message());
+        if reason == Suspension::Paused {
+            self.save_state();
+        }
+    }
+
+    /// Pauses locking while the session is being used over remote desktop, where
+    /// clipping causes bizarre pointer behavior for the remote user
+    fn check_remote_session(&mut self) {
+        if self.system.is_remote_session() {
+            self.suspend(Suspension::RemoteSession);
+        } else {
+            self.resume(Suspension::RemoteSession);
+        }
+    }
+
+    /// Reacts to a `WM_WTSSESSION_CHANGE` notification
+    fn handle_session_change(&mut self, code: u32) {
+        match code {
+            WTS_SESSION_LOCK => self.suspend(Suspension::SessionLocked),
+            WTS_SESSION_UNLOCK => self.resume(Suspension::SessionLocked),
+            WTS_CONSOLE_DISCONNECT | WTS_REMOTE_DISCONNECT => {
+                // Fast user switching or a dropped remote connection
+                self.suspend(Suspension::SessionDisconnected);
+                self.check_remote_session();
+            }
+            WTS_CONSOLE_CONNECT | WTS_REMOTE_CONNECT => {
+                self.resume(Suspension::SessionDisconnected);
+                self.check_remote_session();
+            }
+            _ => {}
+        }
+    }
+
+    /// Pauses locking while the UAC secure desktop or lock screen has the input
+    fn check_input_desktop(&mut self) {
+        if self.system.is_input_desktop_default() {
+            self.resume(Suspension::SecureDesktop);
+        } else {
+            self.suspend(Suspension::SecureDesktop);
+        }
+    }
+
+    /// Releases the lock while the user is away (AFK, a long cutscene) and
+    /// brings it back on the next input
+    fn check_idle(&mut self) {
+        let idle = self.system.input_idle_time();
+        let timeout = Duration::from_secs(self.config.idle.timeout_s);
+        if self.suspensions.contains(&Suspension::Idle) {
+            if !self.config.idle.enabled || idle < timeout {
+                self.resume(Suspension::Idle);
+            }
+        } else if self.config.idle.enabled
+            && idle >= timeout
+            && (self.machine.is_locked() || self.manual_lock.is_some())
+        {
+            self.suspend(Suspension::Idle);
+        }
+    }
+
+    /// Leaves the cursor free while a controller is being used, e.g. to keep a
+    /// browser on the second screen reachable
+    fn check_gamepad(&mut self) {
+        let in_use = self.config.gamepad.enabled
+            && self
+                .system
+                .gamepad_idle_time()
+                .is_some_and(|idle| idle < Duration::from_secs(self.config.gamepad.timeout_s));
+        if in_use {
+            self.suspend(Suspension::Gamepad);
+        } else {
+            self.resume(Suspension::Gamepad);
+        }
+    }
+
+    /// Stays out of the way of Magnifier, screen readers and the on-screen
+    /// keyboard, which all need the cursor to reach the whole desktop
+    fn check_assistive_tools(&mut self) {
+        let running = self
+            .config
+            .accessibility
+            .pause_for_tools
+            .then(|| self.system.assistive_tool())
+            .flatten();
+        let suspended = self.suspensions.iter().find_map(|s| match *s {
+            Suspension::AssistiveTool(tool) => Some(tool),
+            _ => None,
+        });
+        if suspended == running {
+            return;
+        }
+        if let Some(tool) = suspended {
+            self.resume(Suspension::AssistiveTool(tool));
+        }
+        if let Some(tool) = running {
+            self.suspend(Suspension::AssistiveTool(tool));
+        }
+    }
+
+    /// Leaves the cursor free while presenting or mirroring to a projector,
+    /// where a trapped cursor shows up in front of the audience
+    fn check_presentation(&mut self) {
+        let now = self.system.now();
+        if self
+            .last_presentation_check
+            .is_some_and(|last| now - last < PRESENTATION_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_presentation_check = Some(now);
+        if self.config.presentation.pause && self.system.is_presenting() {
+            self.suspend(Suspension::Presenting);
+        } else {
+            self.resume(Suspension::Presenting);
+        }
+    }
+
+    /// Looks for mouse sharing tools and other cursor confinement tools every
+    /// few seconds, starting with the first update
+    fn scan_processes(&mut self) {
+        let now = self.system.now();
+        if self
+            .last_process_scan
+            .is_some_and(|last| now - last < PROCESS_SCAN_INTERVAL)
+        {
+            return;
+        }
+        self.last_process_scan = Some(now);
+
+        let tool = self
+            .config
+            .mouse_sharing
+            .pause
+            .then(|| {
+                self.system
+                    .find_process(&self.config.mouse_sharing.processes)
+            })
+            .flatten();
+        if tool != self.mouse_sharing_tool {
+            if let Some(ref name) = tool {
+                info!("{}", tr!("conflict.mouse_sharing", name));
+            }
+            self.mouse_sharing_tool = tool;
+        }
+
+        let tool = self.system.find_process(&self.config.conflicts.processes);
+        if tool != self.conflicting_tool {
+            match (&tool, self.config.conflicts.cooperate) {
+                (Some(name), true) => warn!("{}", tr!("conflict.cooperating", name)),
+                (Some(name), false) => warn!("{}", tr!("conflict.fighting", name)),
+                (None, _) => {}
+            }
+            self.conflicting_tool = tool;
+        }
+
+        let obs = &self.config.obs;
+        let running = self.system.find_process(&obs.processes).or_else(|| {
+            // Game capture gives away an OBS under any name
+            (self.locked_pid != 0 && self.system.has_module(self.locked_pid, &obs.hook_modules))
+                .then(|| tr!("obs.game_capture").to_string())
+        });
+        if running.is_some() != self.obs_running.is_some() {
+            match running {
+                Some(ref name) => info!("{}", tr!("obs.running", name)),
+                None => info!("{}", tr!("obs.closed")),
+            }
+            self.obs_running = running;
+            self.sync_streaming_profile();
+        }
+    }
+
+    /// Switches to the `[obs] profile` while OBS runs, and back once it is
+    /// closed, leaving a profile picked by hand alone
+    fn sync_streaming_profile(&mut self) {
+        let Some(ref name) = self.config.obs.profile else {
+            return;
+        };
+        let Some(index) = self.base_config.profile_index(name) else {
+            self.warn(&tr!("obs.unknown_profile", name));
+            return;
+        };
+        match self.obs_running {
+            Some(_) if self.profile.is_none() => self.switch_profile(Some(index)),
+            None if self.profile == Some(index) => self.switch_profile(None),
+            _ => {}
+        }
+    }
+
+    /// Leaves the cursor free while a mouse sharing tool moves the pointer on
+    /// another computer; it keeps warping the local cursor to measure motion,
+    /// which a clip on another monitor turns into erratic jumps
+    fn check_mouse_sharing(&mut self) {
+        let config = &self.config.mouse_sharing;
+        if !config.pause {
+            self.resume(Suspension::MouseSharing);
+            return;
+        }
+        // While the pointer is away the tool's own (often invisible) window
+        // has the foreground
+        let controlling = self.mouse_sharing_tool.is_some() && {
+            let foreground = self.system.foreground_window();
+            !foreground.0.is_null()
+                && self
+                    .system
+                    .process_name(self.system.window_process_id(foreground))
+                    .is_some_and(|name| {
+                        config
+                            .processes
+                            .iter()
+                            .any(|tool| tool.eq_ignore_ascii_case(&name))
+                    })
+        };
+        if controlling {
+            self.suspend(Suspension::MouseSharing);
+        } else {
+            self.resume(Suspension::MouseSharing);
+        }
+    }
+
+    /// Reacts to a `WM_POWERBROADCAST` notification
+    fn handle_power(&mut self, code: u32) {
+        match code {
+            PBT_APMSUSPEND => self.suspend(Suspension::Sleep),
+            // Plugged in or unplugged
+            PBT_APMPOWERSTATUSCHANGE => self.check_power_source(),
+            // Automatic resume always arrives, the other one only after user input
+            PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND => {
+                self.resume(Suspension::Sleep);
+                // Notifications sent while asleep may have been lost
+                self.check_input_desktop();
+                self.check_remote_session();
+                self.check_power_source();
+            }
+            _ => {}
+        }
+    }
+
+    /// Releases the clip without moving the cursor and forgets the current lock
+    fn drop_lock(&mut self) {
+        self.unconfine();
+        self.machine.unlock();
+        self.saved_cursor_pos = None;
+    }
+
+    /// Releases the clip after the lock ended, repositioning the cursor if configured
+    fn release_lock(&mut self, locked_rect: Option<RECT>) {
+        self.unconfine();
+
+        match self.config.cursor.on_release {
+            ReleasePosition::Keep => {}
+            ReleasePosition::Restore => {
+                if let Some(pos) = self.saved_cursor_pos {
+                    self.system.set_cursor_pos(pos.x, pos.y);
+                }
+            }
+            ReleasePosition::Center => {
+                if let Some(rect) = locked_rect {
+                    self.system.set_cursor_pos(
+                        rect.left + (rect.right - rect.left) / 2,
+                        rect.top + (rect.bottom - rect.top) / 2,
+                    );
+                }
+            }
+        }
+
+        self.saved_cursor_pos = None;
+    }
+
+    /// Checks the edge-escape gesture and frees the cursor for a while if it completed
+    fn check_edge_escape(&mut self) -> bool {
+        let settings = &self.config.edge_escape;
+        if !settings.enabled {
+            return false;
+        }
+        let (Some(pos), Some(clip)) = (self.system.cursor_pos(), self.clip_rect()) else {
+            return false;
+        };
+        if !self.edge_escape.sample(
+            pos,
+            &clip,
+            self.system.now(),
+            Duration::from_millis(settings.double_hit_ms),
+        ) {
+            return false;
+        }
+
+        let release = Duration::from_millis(settings.release_ms);
+        // Like Alt+Tab, keep the saved cursor position for when the lock really ends
+        self.unconfine();
+        self.machine.hold_free(self.system.now() + release);
+        self.timed_unlock = false;
+        self.emit(Event::EdgeEscaped { release });
+        true
+    }
+
+    /// Frees the cursor for `release`, for "Unlock for 30s" in the tray; a
+    /// manual lock is lifted for good
+    fn unlock_for(&mut self, release: Duration) {
+        if self.manual_lock.is_some() {
+            self.toggle_manual_lock();
+            return;
+        }
+        if !self.machine.is_locked() {
+            return;
+        }
+        // Like the edge escape, the lock comes back once the time is up
+        self.unconfine();
+        self.machine.hold_free(self.system.now() + release);
+        self.timed_unlock = true;
+        self.emit(Event::TimedUnlock { release });
+    }
+
+    /// Warns once per process when an elevated game is about to be locked from a
+    /// non-elevated instance, where UIPI may silently defeat the lock
+    fn check_elevation(&mut self, pid: u32) {
+        if self.elevated || pid == 0 || self.warned_elevated_pid == Some(pid) {
+            return;
+        }
+        // An unreadable token almost always means the process is elevated
+        if self.system.is_process_elevated(pid) == Some(false) {
+            return;
+        }
+
+        self.warned_elevated_pid = Some(pid);
+        let name = self
+            .system
+            .process_name(pid)
+            .unwrap_or_else(|| tr!("elevation.process", pid));
+        warn!("{}", tr!("elevation.game_elevated", name));
+    }
+
+    /// Asks whether the app that was just unlocked should always or never be
+    /// locked, now that the question no longer interrupts it
+    fn ask_about_locked_app(&mut self) {
+        let Some(app) = self.learn_candidate.take() else {
+            return;
+        };
+        let Some(path) = Config::default_path() else {
+            return;
+        };
+        // Cancel means "ask again", but next run rather than after every lock
+        self.prompted.insert(app.clone());
+        prompt::ask_about(app, path);
+    }
+
+    /// Keeps the lock on a window the locked game created to replace its old one
+    /// (common when toggling display modes) instead of unlocking and relocking
+    ///
+    /// A launcher or settings window of the game shares its process and often
+    /// its class, so the new window must also be fullscreen or named by a rule,
+    /// and on the monitor the lock is on.
+    fn adopt_recreated_window(&mut self, hwnd: HWND, tolerance: i32) {
+        let Some(locked) = self.machine.locked_hwnd() else {
+            return;
+        };
+        if locked == hwnd.0 as isize
+            || self.system.window_process_id(hwnd) != self.locked_pid
+            || self.tracked.class != self.locked_class
+        {
+            return;
+        }
+        if self.system.fullscreen_rect(hwnd, tolerance).is_none()
+            && !self.rules.has_match(&self.tracked)
+        {
+            return;
+        }
+        let same_monitor = self
+            .machine
+            .locked_rect()
+            .zip(self.system.window_monitor_rect(hwnd))
+            .is_some_and(|(rect, monitor)| {
+                rect_contains(&monitor, &rect) || rect_contains(&rect, &monitor)
+            });
+        if !same_monitor {
+            return;
+        }
+
+        info!(
+            "{}",
+            tr!(
+                "window.recreated",
+                format!("{:#x}", locked),
+                format!("{:#x}", hwnd.0 as isize)
+            )
+        );
+        self.machine.adopt(hwnd.0 as isize);
+    }
+
+    /// Re-applies the clip only if another program changed or cleared it
+    fn reassert_clip(&mut self, foreground: HWND) {
+        // The hook checks every mouse move by itself
+        if self.mouse_hook.is_some() || self.config.cursor.reclip == Reclip::Never {
+            return;
+        }
+        let Some(rect) = self.clip_rect() else {
+            return;
+        };
+        let mut interval = Duration::from_millis(self.config.cursor.reclip_interval_ms);
+        // The low-power profile trades quicker re-clipping for fewer wakeups
+        if self.low_power {
+            interval = interval.max(Duration::from_millis(self.config.power.low_power_reclip_ms));
+        }
+        if !interval.is_zero() {
+            let now = self.system.now();
+            if self
+                .last_clip_check
+                .is_some_and(|last| now.duration_since(last) < interval)
+            {
+                return;
+            }
+            self.last_clip_check = Some(now);
+        }
+        // For titles that only behave with a steady stream of ClipCursor calls
+        if self.config.cursor.reclip == Reclip::Always {
+            self.clip_cursor(&rect);
+            return;
+        }
+
+        // Negative margins reach past the screen, which Windows clamps away
+        let screen = self.system.virtual_screen_rect();
+        let expected = clamp_rect(&rect, &screen);
+        let clip = self.system.cursor_clip();
+        // A game confining the cursor to its own window keeps it on the
+        // monitor anyway, and fighting over that makes the cursor stutter
+        if clip.is_some_and(|clip| rect_contains(&expected, &clip)) {
+            return;
+        }
+        // Next to another confinement tool, only step in once nobody clips
+        let cooperate = self.conflicting_tool.is_some() && self.config.conflicts.cooperate;
+        if cooperate && clip.is_some_and(|clip| !rects_equal(&clip, &screen)) {
+            return;
+        }
+
+        // Whoever has focus is the most likely culprit (the game itself or an overlay)
+        let thief = self
+            .system
+            .process_name(self.system.window_process_id(foreground))
+            .unwrap_or_else(|| tr!("process.unknown_culprit").to_string());
+        // Repeated thefts by the same program collapse into one counted console line
+        self.emit(Event::ClipStolen {
+            hwnd: foreground.0 as isize,
+            culprit: thief,
+        });
+
+        if self.escalate_if_fighting(&rect) {
+            return;
+        }
+        self.clip_cursor(&rect);
+        self.stats().clip_reapplied();
+    }
+
+    /// Moves a game that clears the clip over and over (some engines do it
+    /// every frame, faster than re-applying can keep up) to the mouse hook,
+    /// which it can't undo; returns true if it did
+    fn escalate_if_fighting(&mut self, rect: &RECT) -> bool {
+        if !self.can_escalate() {
+            return false;
+        }
+        let now = self.system.now();
+        self.clip_thefts
+            .retain(|&theft| now.duration_since(theft) < THEFT_WINDOW);
+        self.clip_thefts.push(now);
+        if self.clip_thefts.len() < THEFTS_TO_ESCALATE {
+            return false;
+        }
+        let Some(process) = self.system.process_name(self.locked_pid) else {
+            return false;
+        };
+        if !self.escalated.insert(process.clone()) {
+            return false;
+        }
+        self.save_state();
+
+        let thefts = self.clip_thefts.len();
+        let within = now.duration_since(self.clip_thefts[0]);
+        self.clip_thefts.clear();
+        self.emit(Event::BackendEscalated {
+            process,
+            thefts,
+            within,
+        });
+        self.confine(rect, LockBackend::Hook)
+    }
+
+    /// Whether the clip backend may give way to the mouse hook on its own,
+    /// which neither a rule's backend nor the manual lock allow
+    fn can_escalate(&self) -> bool {
+        self.config.cursor.escalate_to_hook
+            && self.tracked_overrides.backend.is_none()
+            && self.manual_lock.is_none()
+    }
+
+    fn update(&mut self) {
+        if let Some(pid) = self.yielded_to {
+            if self.system.is_process_running(pid) {
+                // Another tool owns the clip, stay out of its way
+                return;
+            }
+            self.yielded_to = None;
+            info!("{}", tr!("handoff.reclaimed", pid));
+        }
+
+        self.check_panic();
+        self.check_idle();
+        self.check_gamepad();
+        self.check_assistive_tools();
+        self.check_presentation();
+        self.scan_processes();
+        self.check_mouse_sharing();
+        if !self.suspensions.is_empty() {
+            return;
+        }
+        if let Some(rect) = self.manual_lock {
+            // Detection stays out of it until the manual lock is toggled off
+            self.hold_manual_lock(rect);
+            return;
+        }
+
+        self.release_hidden_lock();
+        self.release_recycled_lock();
+
+        let foreground = self.system.foreground_window();
+        // Once another app was in front, the one that refused the clip gets another try
+        if self
+            .clip_refused_by
+            .is_some_and(|pid| pid != self.system.window_process_id(foreground))
+        {
+            self.clip_refused_by = None;
+        }
+        self.candidate = !foreground.0.is_null();
+        let input = if foreground.0.is_null() {
+            Input::NoForeground
+        } else if let Some(switcher) = self.system.task_switcher(foreground) {
+            Input::TaskSwitcher(switcher)
+        } else {
+            match self.observe(foreground) {
+                Some(input) => input,
+                None => return,
+            }
+        };
+
+        let locked_rect = self.machine.locked_rect();
+        let actions = self.machine.step(input, self.system.now(), &self.timing);
+        for action in actions {
+            self.apply(action, foreground, locked_rect);
+        }
+    }
+
+    /// Ends the lock right away, without a grace period, once the locked
+    /// window is minimized (Win+D), left behind on another virtual desktop
+    /// (Win+Ctrl+Arrow) or otherwise cloaked, since the user can't get back to
+    /// it with a trapped cursor. Switching back locks it again as usual.
+    ///
+    /// A locked window that was hidden or destroyed is let go as well, before
+    /// Windows gives its handle to a new window the lock would stick to.
+    fn release_hidden_lock(&mut self) {
+        // The verdict cached for a destroyed window isn't one for its successor
+        if self.tracked_hwnd != 0 && !self.system.is_window(HWND(self.tracked_hwnd as *mut _)) {
+            self.tracked_hwnd = 0;
+        }
+        let Some(hwnd) = self.machine.locked_hwnd() else {
+            return;
+        };
+        let window = HWND(hwnd as *mut _);
+        let reason = if !self.system.is_window(window) {
+            // A new window of the game in front is adopt_recreated_window's
+            let foreground = self.system.foreground_window();
+            if self.locked_pid != 0 && self.system.window_process_id(foreground) == self.locked_pid
+            {
+                return;
+            }
+            UnlockReason::Closed
+        } else if !self.system.is_visible(window) {
+            UnlockReason::Hidden
+        } else if self.system.is_minimized(window) {
+            UnlockReason::Minimized
+        } else if !self.system.is_on_current_desktop(window) {
+            UnlockReason::OtherDesktop
+        } else if self.system.is_cloaked(window) {
+            UnlockReason::Cloaked
+        } else {
+            return;
+        };
+        let locked_rect = self.machine.locked_rect();
+        self.machine.unlock();
+        self.release_lock(locked_rect);
+        self.emit(Event::Unlocked { hwnd, reason });
+    }
+
+    /// Lets go of a locked window whose handle now belongs to another process:
+    /// Windows hands the handles of destroyed windows to new ones, and the one
+    /// under it isn't the window the lock engaged on
+    fn release_recycled_lock(&mut self) {
+        let Some(hwnd) = self.machine.locked_hwnd() else {
+            return;
+        };
+        let pid = self.system.current_window_process_id(HWND(hwnd as *mut _));
+        if self.locked_pid == 0 || pid == self.locked_pid {
+            return;
+        }
+        self.tracked_hwnd = 0;
+        let locked_rect = self.machine.locked_rect();
+        self.machine.unlock();
+        self.release_lock(locked_rect);
+        self.emit(Event::Unlocked {
+            hwnd,
+            reason: UnlockReason::Closed,
+        });
+    }
+
+    /// Fullscreen tolerance for the tracked window, looser for known games
+    fn tolerance(&self) -> i32 {
+        let detection = &self.config.detection;
+        if self.rules.is_known_game(&self.tracked) {
+            detection.tolerance.max(detection.known_game_tolerance)
+        } else {
+            detection.tolerance
+        }
+    }
+
+    /// The monitor of `hwnd` if the window covers `min_coverage` of it, for
+    /// letterboxed games a few rows short of fullscreen
+    fn covered_monitor(&self, hwnd: HWND, tolerance: i32) -> Option<RECT> {
+        let min_coverage = self.config.detection.min_coverage?;
+        let window = self.system.window_rect(hwnd)?;
+        let own = self.system.window_monitor_rect(hwnd)?;
+        let margin = tolerance.max(FRAME_OVERHANG);
+        covers_share(&self.system.monitors(), &own, &window, min_coverage, margin).then_some(own)
+    }
+
+    /// `rect` widened to the monitors of the rule's `pair` when `hwnd` is on
+    /// one of them
+    fn paired(&self, hwnd: HWND, rect: RECT) -> RECT {
+        let Some(ref pair) = self.tracked_overrides.pair else {
+            return rect;
+        };
+        self.system
+            .window_monitor_rect(hwnd)
+            .and_then(|own| pair_rect(&self.system.monitors(), pair, &own))
+            .unwrap_or(rect)
+    }
+
+    /// The first window above `hwnd` in the Z order that shows over `area`,
+    /// leaving out `hwnd`'s own popups and click-through overlays
+    fn window_over(&self, hwnd: HWND, area: &RECT) -> Option<HWND> {
+        let pid = self.system.window_process_id(hwnd);
+        self.system
+            .windows()
+            .into_iter()
+            .take_while(|&above| above != hwnd)
+            .find(|&above| {
+                self.system.is_visible(above)
+                    && !self.system.is_minimized(above)
+                    && !self.system.is_cloaked(above)
+                    && !self.system.is_click_through(above)
+                    && self.system.window_process_id(above) != pid
+                    && self
+                        .system
+                        .window_rect(above)
+                        .is_some_and(|rect| rects_overlap(&rect, area))
+            })
+    }
+
+    /// What the `[[monitors]]` entries say about the monitor `hwnd` is on
+    fn monitor_policy(&self, hwnd: HWND) -> MonitorLock {
+        if self.config.monitors.is_empty() {
+            return MonitorLock::Auto;
+        }
+        self.system
+            .window_monitor_rect(hwnd)
+            .map_or(MonitorLock::Auto, |rect| {
+                monitor_policy(&self.config.monitors, &self.system.monitors(), &rect)
+            })
+    }
+
+    /// Classifies a regular foreground window for the state machine, or
+    /// handles it right away when the rules just stopped matching the locked one
+    fn observe(&mut self, foreground: HWND) -> Option<Input> {
+        let hwnd = foreground.0 as isize;
+
+        // Rules may flip when a launcher turns into the game (or back) by changing its title
+        let verdict = self.classify(foreground);
+        if self.rules.is_transient(&self.tracked) {
+            return Some(Input::Transient);
+        }
+        let policy = self.monitor_policy(foreground);
+        let verdict = match policy {
+            MonitorLock::Always => Verdict::Lock,
+            MonitorLock::Auto | MonitorLock::Never => verdict,
+        };
+        self.candidate = verdict == Verdict::Lock
+            && policy != MonitorLock::Never
+            && !is_shell_class(&self.tracked.class);
+        if verdict == Verdict::Ignore && self.machine.locked_hwnd() == Some(hwnd) {
+            let locked_rect = self.machine.locked_rect();
+            self.machine.unlock();
+            self.release_lock(locked_rect);
+            self.emit(Event::Unlocked {
+                hwnd,
+                reason: UnlockReason::RulesChanged,
+            });
+            return None;
+        }
+
+        let tolerance = self.tolerance();
+        let lock_rect = match (verdict, self.tracked_overrides.clip_to.unwrap_or_default()) {
+            (Verdict::Lock, ClipTarget::Monitor) => self
+                .system
+                .fullscreen_rect(foreground, tolerance)
+                .or_else(|| self.covered_monitor(foreground, tolerance)),
+            (Verdict::Lock, ClipTarget::Window) => self.system.client_rect(foreground),
+            (Verdict::Lock, ClipTarget::WorkArea) => {
+                self.system.work_area_rect(foreground, tolerance)
+            }
+            (Verdict::Ignore, _) => None,
+        }
+        .map(|rect| self.paired(foreground, rect))
+        .map(|rect| match self.tracked_overrides.confine_to {
+            Some(region) => region.apply(rect),
+            None => rect,
+        })
+        .filter(|_| policy != MonitorLock::Never)
+        // A hidden, minimized or cloaked window can't be played, whatever its rect says
+        .filter(|_| {
+            self.system.is_visible(foreground)
+                && !self.system.is_minimized(foreground)
+                && !self.system.is_cloaked(foreground)
+        })
+        // Strict mode only engages on what's actually on screen, a lock it
+        // holds isn't dropped for a toast
+        .filter(|rect| {
+            self.config.detection.level == DetectionLevel::Casual
+                || self.machine.locked_hwnd() == Some(hwnd)
+                || self.window_over(foreground, rect).is_none()
+        });
+        if lock_rect.is_some() {
+            self.adopt_recreated_window(foreground, tolerance);
+        }
+
+        Some(Input::Window {
+            hwnd,
+            lock_rect,
+            clicked: lock_rect.is_some()
+                && self.config.alt_tab.require_click
+                && self.system.mouse_button_pressed(),
+        })
+    }
+
+    /// Carries out what the state machine decided; `locked_rect` is the lock
+    /// from before the step
+    fn apply(&mut self, action: Action, foreground: HWND, locked_rect: Option<RECT>) {
+        match action {
+            Action::Lock { hwnd, rect, relock } => {
+                if self.saved_cursor_pos.is_none() {
+                    self.saved_cursor_pos = self.system.cursor_pos();
+                }
+                let pid = self.system.window_process_id(foreground);
+                self.check_elevation(pid);
+                let process = self.system.process_name(pid);
+                let clip = self.config.cursor.margins.apply(rect);
+                let escalated = process
+                    .as_ref()
+                    .is_some_and(|name| self.escalated.contains(name));
+                let backend = self.tracked_overrides.backend.unwrap_or({
+                    if escalated {
+                        LockBackend::Hook
+                    } else {
+                        self.config.cursor.backend
+                    }
+                });
+                if !self.confine(&clip, backend) {
+                    // Try again on the next tick
+                    self.machine.unlock();
+                    return;
+                }
+
+                let center = self
+                    .tracked_overrides
+                    .center_on_lock
+                    .unwrap_or(self.config.cursor.center_on_lock);
+                // Not on a relock, the cursor is already where the user wants it
+                if center && relock.is_none() {
+                    self.system.set_cursor_pos(
+                        clip.left + (clip.right - clip.left) / 2,
+                        clip.top + (clip.bottom - clip.top) / 2,
+                    );
+                }
+
+                self.edge_escape.reset();
+                self.locked_pid = pid;
+                self.locked_class.clone_from(&self.tracked.class);
+                if relock.is_none() {
+                    self.learn_candidate = process.clone().filter(|name| {
+                        self.config.learning.prompt
+                            && !self.rules.has_match(&self.tracked)
+                            && !self.prompted.contains(name)
+                    });
+                }
+                let app = process.unwrap_or_else(|| tr!("process.unknown").to_string());
+                let monitor = self
+                    .system
+                    .window_monitor_rect(foreground)
+                    .map(|rect| describe_rect(&rect))
+                    .unwrap_or_else(|| tr!("process.unknown").to_string());
+                self.stats().lock_started(app, monitor);
+                match relock {
+                    Some((trigger, after)) => self.emit(Event::Relocked { trigger, after }),
+                    None => self.emit(Event::Locked {
+                        hwnd,
+                        rect,
+                        target: self.tracked_overrides.clip_to.unwrap_or_default(),
+                        backend: self.confined_with,
+                        steam_app: self.system.steam_app(pid),
+                    }),
+                }
+            }
+            Action::Follow { rect } => {
+                // The locked window moved, resized or went to another monitor
+                let clip = self.config.cursor.margins.apply(rect);
+                if self.confine(&clip, self.confined_with) {
+                    info!(
+                        "{}",
+                        tr!("clip.follows", rect.left, rect.top, rect.right, rect.bottom)
+                    );
+                }
+            }
+            Action::Refresh => {
+                if self.check_edge_escape() {
+                    return;
+                }
+                // Re-apply clip if some app/overlay stole it
+                self.reassert_clip(foreground);
+            }
+            Action::Hold => {
+                // Still in grace period - keep clip active
+                // This handles transient overlays, notifications, etc.
+                self.reassert_clip(foreground);
+            }
+            Action::Unlock { hwnd, reason } => {
+                self.release_lock(locked_rect);
+                self.emit(Event::Unlocked { hwnd, reason });
+                self.ask_about_locked_app();
+            }
+            Action::AltTabStarted {
+                was_locked,
+                switcher,
+            } => {
+                // Temporarily release cursor for Alt+Tab navigation, keeping the
+                // saved cursor position for when the lock really ends
+                self.unconfine();
+                self.emit(Event::AltTabStarted {
+                    was_locked,
+                    switcher,
+                });
+            }
+            Action::AltTabEnded {
+                switcher,
+                duration,
+                chosen_hwnd,
+                outcome,
+            } => {
+                if outcome == AltTabOutcome::SwitchedAway {
+                    self.saved_cursor_pos = None;
+                }
+                let chosen_process = self
+                    .system
+                    .process_name(self.system.window_process_id(foreground));
+                self.emit(Event::AltTabEnded {
+                    switcher,
+                    duration,
+                    chosen_hwnd,
+                    chosen_process,
+                    outcome,
+                });
+            }
+            Action::Forgot { after } => self.emit(Event::RememberedWindowExpired { after }),
+            Action::HoldEnded if std::mem::take(&mut self.timed_unlock) => {
+                self.emit(Event::TimedUnlockEnded)
+            }
+            Action::HoldEnded => self.emit(Event::EdgeEscapeEnded),
+        }
+    }
+}
+
+fn print_banner() {
+    println!("╔═══════════════════════════════════════════════════════════╗");
+    println!("║              SmartLockCursor v0.1.0                       ║");
+    println!("║  {:<57}║", tr!("main.tagline"));
+    println!("╠═══════════════════════════════════════════════════════════╣");
+    println!("║  {:<57}║", tr!("main.exit_hint"));
+    println!("╚═══════════════════════════════════════════════════════════╝");
+    println!();
+}
+
+fn print_monitor_info() {
+    let monitors = monitor_details();
+    info!("{}", tr!("main.monitors", monitors.len()));
+    for monitor in &monitors {
+        println!("  {}", monitor.describe());
+    }
+    println!();
+}
+
+/// The `status` report: statistics and the monitor layout
+fn status_report(stats: &Stats) -> String {
+    let mut report = stats.summary();
+    report.push_str(&format!("\n{}\n", tr!("cli.monitors")));
+    for monitor in monitor_details() {
+        report.push_str(&format!("  {}\n", monitor.describe()));
+    }
+    report
+}
+
+/// Registers the `[system_log]` event source with the Application log
+fn register_event_source() {
+    let source = Config::load().system_log.source;
+    match systemlog::register_source(&source) {
+        Ok(()) => println!("{}", tr!("cli.source_registered", source)),
+        Err(e) => {
+            eprintln!("{}", tr!("cli.source_failed", source, e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Replaces this executable with the latest GitHub release
+fn self_update() {
+    let repository = Config::load().updates.repository;
+    println!("{}", tr!("cli.update_checking", repository));
+    match update::self_update(&repository) {
+        Ok(Some(tag)) => println!("{}", tr!("cli.update_installed", tag)),
+        Ok(None) => println!("{}", tr!("cli.update_latest", env!("CARGO_PKG_VERSION"))),
+        Err(e) => {
+            eprintln!("{}", tr!("cli.update_failed", e));
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    // Until a config is read, messages follow the Windows display language
+    i18n::set_language(i18n::Language::Auto);
+    match cli::parse(std::env::args()) {
+        Ok(Command::Run(options)) => run(options),
+        Ok(Command::RegisterEventSource) => register_event_source(),
+        Ok(Command::SelfUpdate) => self_update(),
+        Ok(Command::Elevate) => {
+            if is_elevated() {
+                run(RunOptions::default());
+            } else if !relaunch_elevated(&[]) {
+                eprintln!("{}", tr!("cli.elevate_failed"));
+                std::process::exit(1);
+            }
+        }
+        Ok(Command::Explain { delay }) => run_explain(delay),
+        Ok(Command::ListWindows { include_hidden }) => {
+            print!("{}", list_windows(include_hidden, &Config::load()))
+        }
+        Ok(Command::ListMice) => print_mice(&Config::load()),
+        Ok(Command::CheckConfig { path }) => check_config(path),
+        Ok(Command::ExportRules { path }) => export_rules(&path),
+        Ok(Command::ImportRules { path }) => import_rules(&path),
+        Ok(Command::Status) => print_status(),
+        Ok(Command::Snapshot) => take_snapshot(),
+        Ok(Command::Replay { path, config }) => run_replay(&path, config.as_deref()),
+        Ok(Command::ToggleLock) => toggle_lock(),
+        Ok(Command::Janitor { parent_pid }) => janitor::run(parent_pid),
+        Ok(Command::Help) => println!("{}", cli::USAGE),
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Explains the lock decision for whatever window is in front after `delay`
+fn run_explain(delay: Duration) {
+    let config = Config::load();
+    if !delay.is_zero() {
+        info!("{}", tr!("explain.waiting", delay.as_secs()));
+        std::thread::sleep(delay);
+    }
+    println!();
+    print!("{}", explain(unsafe { GetForegroundWindow() }, &config));
+}
+
+/// Lists the attached mice, marking the one `[cursor] device` confines
+fn print_mice(config: &Config) {
+    let mice = rawinput::mice();
+    if mice.is_empty() {
+        println!("{}", tr!("cli.no_mice"));
+        return;
+    }
+    let pattern = config.cursor.device.as_deref();
+    for mouse in mice {
+        let confined = pattern.is_some_and(|pattern| rawinput::path_matches(&mouse.path, pattern));
+        println!("{} {}", if confined { "*" } else { " " }, mouse.path);
+    }
+    match pattern {
+        Some(pattern) => println!("\n{}", tr!("cli.mouse_matches", pattern)),
+        None => println!("\n{}", tr!("cli.mouse_hint")),
+    }
+}
+
+/// Lints a config file and exits with 1 if it has errors
+fn check_config(path: Option<PathBuf>) {
+    let Some(path) = path.or_else(Config::default_path) else {
+        eprintln!("{}", tr!("cli.no_config_dir_to_check"));
+        std::process::exit(1);
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}", tr!("cli.read_failed", path.display(), e));
+            std::process::exit(1);
+        }
+    };
+
+    let findings = check::check_config(&text, &get_all_monitors());
+    for finding in &findings {
+        println!("{}: {}", path.display(), finding);
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    println!(
+        "{}",
+        tr!(
+            "cli.check_summary",
+            path.display(),
+            errors,
+            findings.len() - errors
+        )
+    );
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// The default config file and its text, or exits if there is none
+fn read_default_config() -> (PathBuf, String) {
+    let Some(path) = Config::default_path() else {
+        eprintln!("{}", tr!("cli.no_config_dir"));
+        std::process::exit(1);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => (path, text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (path, String::new()),
+        Err(e) => {
+            eprintln!("{}", tr!("cli.read_failed", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes the config's rules and profiles to a rule pack
+fn export_rules(pack: &Path) {
+    let (path, text) = read_default_config();
+    let exported = rulepack::export(&text)
+        .and_then(|exported| std::fs::write(pack, exported).map_err(|e| e.to_string()));
+    if let Err(e) = exported {
+        eprintln!("{}", tr!("cli.export_failed", path.display(), e));
+        std::process::exit(1);
+    }
+    println!("{}", tr!("cli.exported", path.display(), pack.display()));
+}
+
+/// Merges a rule pack into the config file, which the engine reloads
+fn import_rules(pack: &Path) {
+    let (path, text) = read_default_config();
+    let source = pack.file_name().map_or_else(
+        || pack.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let merged = std::fs::read_to_string(pack)
+        .map_err(|e| e.to_string())
+        .and_then(|pack| rulepack::merge(&text, &pack, &source))
+        .and_then(|merge| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, &merge.text).map_err(|e| e.to_string())?;
+            Ok(merge)
+        });
+    let merge = match merged {
+        Ok(merge) => merge,
+        Err(e) => {
+            eprintln!("{}", tr!("cli.import_failed", pack.display(), e));
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "{}",
+        tr!(
+            "cli.imported",
+            merge.rules_added,
+            path.display(),
+            merge.rules_skipped
+        )
+    );
+    if !merge.profiles_added.is_empty() {
+        println!(
+            "{}",
+            tr!("cli.profiles_added", merge.profiles_added.join(", "))
+        );
+    }
+    if !merge.profiles_skipped.is_empty() {
+        println!(
+            "{}",
+            tr!("cli.profiles_kept", merge.profiles_skipped.join(", "))
+        );
+    }
+}
+
+/// Asks the running instance for its status report and prints it
+fn print_status() {
+    let state = match send_request(Request::Query) {
+        Some(Reply::Locked) => tr!("cli.state_locked"),
+        Some(Reply::Unlocked) => tr!("cli.state_unlocked"),
+        Some(Reply::Yielded) => tr!("cli.state_yielded"),
+        _ => {
+            eprintln!("{}", tr!("cli.not_running"));
+            std::process::exit(1);
+        }
+    };
+    println!("{}", tr!("cli.running", state));
+
+    let report = match send_request(Request::WriteStatus) {
+        Some(Reply::Ack) => status_path().and_then(|path| std::fs::read_to_string(path).ok()),
+        _ => None,
+    };
+    match report {
+        Some(report) => print!("\n{}", report),
+        None => eprintln!("{}", tr!("cli.no_statistics")),
+    }
+}
+
+/// Has the running instance write a snapshot and waits for it, or captures
+/// one without engine state when none is running
+fn take_snapshot() {
+    let Some(path) = snapshot_path() else {
+        eprintln!("{}", tr!("cli.no_config_dir"));
+        std::process::exit(1);
+    };
+    let _ = std::fs::remove_file(&path);
+    if send_request(Request::WriteSnapshot) == Some(Reply::Ack) {
+        // Written once the main loop drains its messages
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !path.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if !path.exists() {
+            eprintln!("{}", tr!("cli.snapshot_missing"));
+            std::process::exit(1);
+        }
+    } else {
+        eprintln!("{}", tr!("cli.snapshot_offline"));
+        let config = Config::load();
+        let text = Config::default_path().and_then(|path| std::fs::read_to_string(path).ok());
+        let snapshot = snapshot::capture(
+            &Win32System::default(),
+            &RuleSet::from_config(&config),
+            config.detection.tolerance,
+            None,
+            text.as_deref(),
+        );
+        if let Err(e) = std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+            .and_then(|()| std::fs::write(&path, snapshot))
+        {
+            eprintln!("{}", tr!("cli.write_failed", path.display(), e));
+            std::process::exit(1);
+        }
+    }
+    println!("{}", tr!("snapshot.written", path.display()));
+}
+
+/// Replays a snapshot file through the engine and prints its decisions
+fn run_replay(path: &Path, config_path: Option<&Path>) {
+    let recording = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| replay::parse(&text));
+    let recording = match recording {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("{}", tr!("cli.read_failed", path.display(), e));
+            std::process::exit(1);
+        }
+    };
+    let config = match config_path {
+        Some(config_path) => std::fs::read_to_string(config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                Config::parse(&text, recording.machine.as_deref()).map_err(|e| e.to_string())
+            }),
+        None => recording.config(),
+    };
+    match config {
+        Ok(config) => {
+            for line in replay_decisions(&recording, config) {
+                println!("{}", line);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("cli.config_failed", e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the engine over the recorded frames, ticking like the poll loop in
+/// between, and describes each frame and every change of the lock state
+fn replay_decisions(recording: &Recording, config: Config) -> Vec<String> {
+    let config = replay::offline(config);
+    let timing = Timing::from_config(&config);
+    let settle = timing.lock_delay.max(timing.grace) + replay::TICK;
+    let mut state = AppState::new(config, MockSystem::new(RECT::default()));
+    let mut lines = Vec::new();
+    let mut decided = String::new();
+    let mut now = Duration::ZERO;
+    let mut tick = |state: &mut AppState<MockSystem>, lines: &mut Vec<String>| {
+        state.update();
+        let decision = match state.system.clip() {
+            Some(clip) => format!("{}, clip {}", state.state_name(), describe_rect(&clip)),
+            None => format!("{}, clip released", state.state_name()),
+        };
+        if decision != decided {
+            lines.push(format!("    -> {}", decision));
+            decided = decision;
+        }
+    };
+
+    for (i, frame) in recording.frames.iter().enumerate() {
+        while now + replay::TICK < frame.at {
+            state.system.advance(replay::TICK);
+            now += replay::TICK;
+            tick(&mut state, &mut lines);
+        }
+        state
+            .system
+            .advance(frame.at.saturating_sub(now).max(replay::TICK));
+        now = now.max(frame.at);
+        frame.apply(&mut state.system);
+        lines.push(format!(
+            "{:>7}ms  frame {}: foreground {}",
+            frame.at.as_millis(),
+            i + 1,
+            frame.describe_foreground()
+        ));
+        tick(&mut state, &mut lines);
+    }
+    // Let a lock delay or grace period run out after the last frame
+    let mut settled = Duration::ZERO;
+    while settled < settle {
+        state.system.advance(replay::TICK);
+        settled += replay::TICK;
+        tick(&mut state, &mut lines);
+    }
+    lines
+}
+
+/// Asks the running instance to toggle its manual lock
+fn toggle_lock() {
+    if send_request(Request::ToggleManualLock) != Some(Reply::Ack) {
+        eprintln!("{}", tr!("cli.not_running"));
+        std::process::exit(1);
+    }
+    println!("{}", tr!("cli.toggled"));
+}
+
+/// Runs the lock engine until Ctrl+C
+fn run(options: RunOptions) {
+    // Before anything is printed, so log lines already go to stderr
+    let stdio = match options.stdio.then(StdioChannel::open) {
+        Some(Ok(channel)) => Some(channel),
+        Some(Err(e)) => {
+            eprintln!("{}", tr!("main.stdio_failed", e));
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    console::init(options.quiet);
+    let mut dashboard = match options.tui.then(Dashboard::open) {
+        Some(Ok(dashboard)) => Some(dashboard),
+        Some(Err(e)) => {
+            eprintln!("{}", tr!("main.dashboard_failed", e));
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    // The dashboard shows all of this by itself
+    let quiet = options.quiet || dashboard.is_some();
+    if !quiet {
+        print_banner();
+    }
+    let mut config = Config::load();
+    config.detection.only_known_games |= options.only_known_games;
+    i18n::set_language(config.ui.language);
+
+    if config.startup.run_elevated && !is_elevated() && stdio.is_some() {
+        warn!("{}", tr!("elevation.stdio"));
+    } else if config.startup.run_elevated && !is_elevated() {
+        // Carry over the flags that change what the engine does
+        let args: Vec<String> = [
+            (options.only_known_games, "--only-known-games"),
+            (options.dry_run, "--dry-run"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| flag.to_string())
+        .collect();
+        if relaunch_elevated(&args) {
+            info!("{}", tr!("elevation.relaunched"));
+            return;
+        }
+        warn!("{}", tr!("elevation.relaunch_failed"));
+    }
+    update::clean_up();
+    update::spawn_check(&config.updates);
+    if !quiet {
+        print_monitor_info();
+        info!("{}", tr!("main.monitoring"));
+        println!();
+    }
+    if options.dry_run {
+        warn!("{}", tr!("main.dry_run"));
+    }
+
+    let system = if options.dry_run {
+        Win32System::dry_run()
+    } else {
+        Win32System::default()
+    };
+    let mut state = AppState::new(config, system);
+    state.dry_run = options.dry_run;
+    state.stdio = stdio;
+    state.open_event_log();
+    state.open_system_log();
+    if let Some(ref log) = state.system_log {
+        log.started();
+    }
+    state.start_webhook();
+    state.start_mqtt();
+    state.start_discord();
+    state.start_obs();
+    state.start_status_server();
+    state.check_remote_session();
+    state.check_power_source();
+    state.sync_efficiency_mode();
+    if state.config.startup.restore_state {
+        let path = persist::state_path();
+        if let Some(saved) = path.as_deref().and_then(SavedState::load) {
+            state.restore_state(saved);
+        }
+        state.state_file = path;
+    }
+
+    // Release the cursor on Ctrl+C, closing the console, logoff and shutdown
+    exit_handlers(Arc::clone(&state.stats), options.dry_run);
+    // Only with consent, and chained after the handlers so the cursor is free first
+    #[cfg(feature = "crash-reports")]
+    {
+        crashreport::install(&state.config.crash_reports);
+        crashreport::offer_upload(&state.config.crash_reports);
+    }
+    #[cfg(not(feature = "crash-reports"))]
+    if state.config.crash_reports.enabled {
+        warn!("{}", tr!("crash.unsupported"));
+    }
+
+    // Covers the exits the handlers above never see (killed, access violation, ...)
+    if options.dry_run {
+        // Nothing to clean up, and clearing a clip the game set would hurt
+    } else if let Err(e) = janitor::spawn() {
+        warn!("{}", tr!("main.janitor_failed", e));
+    }
+
+    // Receives clip handoff requests from cooperating tools
+    let control = match ControlWindow::create() {
+        Ok(control) => {
+            let stats = Arc::clone(&state.stats);
+            control.set_report(move || {
+                status_report(&stats.lock().unwrap_or_else(|e| e.into_inner()))
+            });
+            sync_hotkeys(&control, &state.config);
+            // A process with windows hears of logoff and shutdown only here
+            let stats = Arc::clone(&state.stats);
+            let dry_run = options.dry_run;
+            control.set_session_end(move || {
+                release_on_exit(tr!("main.session_ending"), &stats, dry_run);
+                std::process::exit(0);
+            });
+            Some(control)
+        }
+        Err(e) => {
+            warn!("{}", tr!("main.control_failed", e));
+            None
+        }
+    };
+
+    // Releases the clip while a UAC prompt or the lock screen owns the input
+    let _desktop_hook = DesktopSwitchHook::install();
+
+    // Picks up config edits without a restart
+    let config_path = Config::default_path();
+    let mut watcher = config_path.as_deref().and_then(ConfigWatcher::new);
+    let mut handles: Vec<HANDLE> = watcher.iter().map(ConfigWatcher::handle).collect();
+    let stdio_index = handles.len();
+    handles.extend(state.stdio.as_ref().map(StdioChannel::handle));
+
+    // Event-driven mode, if enabled
+    let mut hooks = None;
+    sync_hooks(&mut hooks, &state.config.engine);
+    let mut foreground_hook = None;
+    sync_foreground_hook(&mut foreground_hook, &state.config.engine);
+    let mut resyncs = 0u32;
+    let mut watchdog_due = false;
+
+    // Lets people who don't know the hotkeys free the cursor
+    let mut tray = None;
+    sync_tray(&mut tray, &state.config.tray);
+    let mut tray_locked = false;
+
+    // Frees the cursor and starts over when the loop below stops turning
+    let heartbeat = Arc::new(Heartbeat::new(Instant::now()));
+    if state.config.engine.stall_timeout_ms > 0 {
+        let dry_run = options.dry_run;
+        let restart = state.stdio.is_none();
+        if let Err(e) = supervisor::spawn(Arc::clone(&heartbeat), move |late| {
+            restart_after_stall(late, dry_run, restart)
+        }) {
+            warn!("{}", tr!("main.supervisor_failed", e));
+        }
+    }
+
+    // Main loop - check every 100ms (or sleep until an event when idle in event mode)
+    loop {
+        match hooks {
+            Some(ref hooks) if watchdog_due => run_watchdog(&mut state, hooks, &mut resyncs),
+            _ => state.update(),
+        }
+
+        if let Some(ref control) = control {
+            control.set_status(state.status());
+        }
+        if let Some(ref tray) = tray {
+            let locked = state.status() == Reply::Locked;
+            if locked != tray_locked {
+                tray.set_locked(locked);
+                if locked && state.config.tray.lock_notifications {
+                    let unlock_for = Duration::from_millis(state.config.tray.unlock_for_ms);
+                    tray.notify(
+                        Balloon::Lock,
+                        tr!("tray.locked_title"),
+                        &tr!("tray.locked_hint", unlock_for.as_secs()),
+                    );
+                }
+                tray_locked = locked;
+            }
+            if let Some(advice) = state.clip_alert.take() {
+                tray.notify(Balloon::Warning, tr!("clip.failing_title"), &advice);
+            }
+        }
+        let clip = state.clip_rect();
+        state.stats().set_state(state.state_name(), clip);
+        if let Some(ref mut dashboard) = dashboard {
+            let grace_left = match state.machine.state() {
+                State::GracePeriod { remaining, .. } => Some(remaining),
+                _ => None,
+            };
+            dashboard.draw(&state.stats(), grace_left);
+        }
+
+        let idle = hooks.is_some() && !state.needs_ticks();
+        let timeout = wait_timeout(
+            &state.config.engine,
+            idle,
+            hooks.is_none() && foreground_hook.is_some(),
+            state.can_back_off(),
+            state.tick_interval(),
+        );
+
+        // Keep the dashboard moving even while nothing happens
+        let timeout = match dashboard {
+            Some(_) => timeout.min(tui::REFRESH),
+            None => timeout,
+        };
+        let stall = Duration::from_millis(state.config.engine.stall_timeout_ms);
+        heartbeat.beat(
+            Instant::now(),
+            (!stall.is_zero()).then_some(timeout + stall),
+        );
+        let wake = control::wait(timeout, &handles);
+        watchdog_due = idle && wake == Wake::Timeout;
+
+        match wake {
+            Wake::Timeout | Wake::Event => {}
+            Wake::Message => {
+                if let Some(ref control) = control {
+                    for message in control.take_messages() {
+                        match message {
+                            ControlMessage::Handoff(request, pid) => {
+                                state.handle_request(request, pid)
+                            }
+                            ControlMessage::SessionChange(code) => {
+                                state.handle_session_change(code)
+                            }
+                            ControlMessage::DesktopSwitch => state.check_input_desktop(),
+                            ControlMessage::Power(code) => state.handle_power(code),
+                            ControlMessage::Hotkey(action) => state.handle_hotkey(action),
+                            ControlMessage::Tray(TrayCommand::Unlock) => state
+                                .unlock_for(Duration::from_millis(state.config.tray.unlock_for_ms)),
+                            ControlMessage::Tray(TrayCommand::Exit) => {
+                                info!("{}", tr!("main.tray_exit"));
+                                state.unconfine();
+                                let stats = state.stats();
+                                println!("\n{}", stats.summary());
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            Wake::Handle(index) if index == stdio_index => {
+                let commands = state
+                    .stdio
+                    .as_ref()
+                    .map(StdioChannel::take_commands)
+                    .unwrap_or_default();
+                for command in commands {
+                    match command {
+                        Ok(StdioCommand::Quit) => {
+                            info!("{}", tr!("main.stdin_closed"));
+                            state.unconfine();
+                            let stats = state.stats();
+                            println!("\n{}", stats.summary());
+                            return;
+                        }
+                        Ok(command) => state.handle_stdio(command),
+                        Err(e) => {
+                            if let Some(ref mut stdio) = state.stdio {
+                                stdio.send_error(&e);
+                            }
+                        }
+                    }
+                }
+            }
+            Wake::Handle(_) => {
+                if let (Some(watcher), Some(path)) = (watcher.as_mut(), config_path.as_deref()) {
+                    if watcher.file_changed() {
+                        let hotkeys = state.config.hotkey_bindings();
+                        reload_config(&mut state, path, options);
+                        if let Some(ref control) = control {
+                            if state.config.hotkey_bindings() != hotkeys {
+                                sync_hotkeys(control, &state.config);
+                            }
+                        }
+                        sync_hooks(&mut hooks, &state.config.engine);
+                        sync_foreground_hook(&mut foreground_hook, &state.config.engine);
+                        sync_tray(&mut tray, &state.config.tray);
+                        tray_locked = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Registers the configured global hotkeys and drops the ones no longer configured
+fn sync_hotkeys(control: &ControlWindow, config: &Config) {
+    let bindings = config.hotkey_bindings();
+    for action in control.registered_hotkeys() {
+        if !bindings.iter().any(|&(bound, _)| bound == action) {
+            control.unregister_hotkey(action);
+        }
+    }
+    for (action, hotkey) in &bindings {
+        let action = *action;
+        let described = match action {
+            HotkeyAction::Profile(index) => format!("profile {}", config.profiles[index].name),
+            _ => format!("{:?}", action),
+        };
+        match control.register_hotkey(action, hotkey) {
+            Ok(()) => info!("{}", tr!("hotkey.registered", hotkey, described)),
+            Err(e) => warn!("{}", tr!("hotkey.failed", hotkey, e)),
+        }
+    }
+}
+
+/// How long the main loop may sleep before its next tick
+///
+/// `idle` is event mode with nothing to tick for, which only needs the
+/// watchdog. Polling backs off to `idle_interval_ms` while nothing could be
+/// locked, but only with the foreground hook there to wake it as soon as
+/// another window comes up, and goes back to `tick` as soon as something could.
+fn wait_timeout(
+    engine: &EngineConfig,
+    idle: bool,
+    foreground_hook: bool,
+    can_back_off: bool,
+    tick: Duration,
+) -> Duration {
+    if idle {
+        Duration::from_millis(engine.watchdog_interval_ms)
+    } else if foreground_hook && engine.idle_interval_ms > 0 && can_back_off {
+        Duration::from_millis(engine.idle_interval_ms)
+    } else {
+        tick
+    }
+}
+
+/// Installs or removes the WinEvent hooks to match the configured engine mode
+fn sync_hooks(hooks: &mut Option<EventHooks>, engine: &EngineConfig) {
+    match (engine.mode, hooks.is_some()) {
+        (EngineMode::Events, false) => {
+            *hooks = EventHooks::install(unsafe { GetForegroundWindow() });
+            if hooks.is_some() {
+                info!(
+                    "{}",
+                    tr!("engine.event_driven", engine.watchdog_interval_ms)
+                );
+            } else {
+                warn!("{}", tr!("engine.hooks_failed"));
+            }
+        }
+        (EngineMode::Poll, true) => {
+            *hooks = None;
+            info!("{}", tr!("engine.polling"));
+        }
+        _ => {}
+    }
+}
+
+/// Keeps the foreground hook installed in poll mode, where it wakes the loop
+/// on focus changes instead of leaving them to the next tick (and lets
+/// adaptive polling back off)
+fn sync_foreground_hook(hook: &mut Option<ForegroundHook>, engine: &EngineConfig) {
+    let wanted = engine.mode == EngineMode::Poll;
+    if wanted && hook.is_none() {
+        *hook = ForegroundHook::install();
+    } else if !wanted {
+        *hook = None;
+    }
+}
+
+/// Adds or removes the tray icon to match `[tray]`
+fn sync_tray(tray: &mut Option<TrayIcon>, config: &TrayConfig) {
+    // Recreated so a changed unlock time shows up in the menu
+    *tray = None;
+    if !config.enabled {
+        return;
+    }
+    match TrayIcon::create(Duration::from_millis(config.unlock_for_ms)) {
+        Ok(icon) => *tray = Some(icon),
+        Err(e) => warn!("{}", tr!("tray.failed", e)),
+    }
+}
+
+/// Frees the cursor from a main loop that stopped turning and hands over to a
+/// new copy of the program, started with the same arguments
+///
+/// The stuck thread can't be stopped, so this process has to go. With
+/// `restart` off (`--stdio`, whose caller owns stdin) it only exits.
+fn restart_after_stall(late: Duration, dry_run: bool, restart: bool) {
+    if !dry_run {
+        release_cursor_clip();
+        restore_system_cursors();
+    }
+    warn!(
+        "{}",
+        tr!("supervisor.stalled", format!("{:.1}", late.as_secs_f64()))
+    );
+    if !restart {
+        std::process::exit(1);
+    }
+
+    let spawned = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .spawn()
+    });
+    match spawned {
+        // A clean exit, so the janitor leaves the new copy's clip alone
+        Ok(_) => {
+            info!("{}", tr!("supervisor.restarted"));
+            std::process::exit(0);
+        }
+        Err(e) => {
+            warn!("{}", tr!("supervisor.restart_failed", e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Cross-checks the event-derived state against reality and logs a resync if
+/// the hooks missed something
+fn run_watchdog(state: &mut AppState, hooks: &EventHooks, resyncs: &mut u32) {
+    let expected = hooks.last_foreground();
+    let actual = unsafe { GetForegroundWindow() };
+    let was_locked = state.machine.is_locked();
+
+    state.update();
+
+    let reason = if actual.0 as isize != expected {
+        tr!("watchdog.missed_foreground")
+    } else if state.machine.is_locked() != was_locked {
+        tr!("watchdog.missed_window")
+    } else {
+        return;
+    };
+
+    *resyncs += 1;
+    hooks.resync(actual);
+    warn!("{}", tr!("watchdog.resync", resyncs, reason));
+}
+
+/// Re-reads the config file, keeping the current config if the new one is invalid
+///
+/// Sync clients replace the file by deleting, truncating or renaming it, so a
+/// missing or empty file means a write is under way rather than a wish for the
+/// defaults; its next change brings the reload.
+fn reload_config(state: &mut AppState, path: &Path, options: RunOptions) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) if !text.trim().is_empty() => text,
+        _ => {
+            info!("{}", tr!("config.missing", path.display()));
+            return;
+        }
+    };
+    warn_conflict_copies(path);
+    match Config::parse(&text, Config::machine_name().as_deref()) {
+        Ok(mut config) => {
+            // The command line flag outlives config edits
+            config.detection.only_known_games |= options.only_known_games;
+            state.apply_config(config);
+            info!("{}", tr!("config.reloaded", path.display()));
+        }
+        Err(e) => warn!("{}", tr!("config.parse_failed", path.display(), e)),
+    }
+}
+
+/// What the console close handler cleans up, set once by [`exit_handlers`]
+static CLOSE_CLEANUP: OnceLock<(Arc<Mutex<Stats>>, bool)> = OnceLock::new();
+
+/// Says why it is shutting down, releases the clip and the hidden cursor and
+/// prints the session summary
+fn release_on_exit(reason: &str, stats: &Mutex<Stats>, dry_run: bool) {
+    tui::restore();
+    println!();
+    info!("{}", reason);
+    // A dry run never clipped, and a clip left now is the game's own
+    if !dry_run {
+        unsafe {
+            let _ = ClipCursor(None);
+        }
+        restore_system_cursors();
+    }
+    let mut stats = stats.lock().unwrap_or_else(|e| e.into_inner());
+    stats.lock_ended();
+    println!("\n{}", stats.summary());
+}
+
+/// Handles the console window being closed, and logoff and shutdown when
+/// Windows tells console handlers about them; Ctrl+C and Ctrl+Break go on to
+/// the `ctrlc` handler
+unsafe extern "system" fn console_close_proc(event: u32) -> BOOL {
+    if !matches!(
+        event,
+        CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT
+    ) {
+        return FALSE;
+    }
+    // Windows ends the process as soon as this returns, so the cleanup can't
+    // be left to another thread
+    let reason = if event == CTRL_CLOSE_EVENT {
+        tr!("main.console_closed")
+    } else {
+        tr!("main.session_ending")
+    };
+    if let Some((stats, dry_run)) = CLOSE_CLEANUP.get() {
+        release_on_exit(reason, stats, *dry_run);
+    }
+    TRUE
+}
+
+/// Sets up the handlers that release the cursor clip and print the session
+/// summary on Ctrl+C, a panic and the console window closing
+fn exit_handlers(stats: Arc<Mutex<Stats>>, dry_run: bool) {
+    // A dry run never clipped, and a clip left now is the game's own
+    std::panic::set_hook(Box::new(move |_| {
+        if !dry_run {
+            unsafe {
+                let _ = ClipCursor(None);
+            }
+            restore_system_cursors();
+        }
+    }));
+
+    // Handle Ctrl+C
+    let _ = CLOSE_CLEANUP.set((Arc::clone(&stats), dry_run));
+    let _ = ctrlc::set_handler(move || {
+        release_on_exit(tr!("main.shutting_down"), &stats, dry_run);
+        std::process::exit(0);
+    });
+    // Registered last, so it is asked first
+    unsafe {
+        let _ = SetConsoleCtrlHandler(Some(console_close_proc), true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smartlockcursor::config::{
+        ClipMargins, ConfineRegion, MonitorConfig, RuleAction, RuleConfig,
+    };
+    use smartlockcursor::events::Switcher;
+    use smartlockcursor::steam::SteamApp;
+    use smartlockcursor::system::{MockSystem, MockWindow};
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+    const GAME: isize = 0x100;
+    const BROWSER: isize = 0x200;
+    const SWITCHER: isize = 0x300;
+    const TASK_VIEW: isize = 0x400;
+
+    fn desktop() -> AppState<MockSystem> {
+        let system = MockSystem::new(MONITOR);
+        system.add_window(
+            GAME,
+            MockWindow {
+                class: "UnityWndClass".to_string(),
+                pid: 10,
+                process: "Game.exe".to_string(),
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        system.add_window(
+            BROWSER,
+            MockWindow {
+                class: "Chrome_WidgetWin_1".to_string(),
+                pid: 20,
+                process: "chrome.exe".to_string(),
+                rect: RECT {
+                    left: 100,
+                    top: 100,
+                    right: 900,
+                    bottom: 700,
+                },
+                ..Default::default()
+            },
+        );
+        system.add_window(
+            SWITCHER,
+            MockWindow {
+                class: "XamlExplorerHostIslandWindow".to_string(),
+                pid: 30,
+                process: "explorer.exe".to_string(),
+                switcher: Some(Switcher::AltTab),
+                ..Default::default()
+            },
+        );
+        system.add_window(
+            TASK_VIEW,
+            MockWindow {
+                class: "Windows.UI.Core.CoreWindow".to_string(),
+                pid: 30,
+                process: "explorer.exe".to_string(),
+                // Task View covers the whole monitor, but must never be locked
+                rect: MONITOR,
+                switcher: Some(Switcher::TaskView),
+                ..Default::default()
+            },
+        );
+        AppState::new(Config::default(), system)
+    }
+
+    fn focus(state: &mut AppState<MockSystem>, hwnd: isize) {
+        state.system.advance(Duration::from_millis(100));
+        state.system.set_foreground(hwnd);
+        state.update();
+    }
+
+    #[test]
+    fn locks_fullscreen_window() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert_eq!(state.machine.locked_hwnd(), Some(GAME));
+        assert!(state
+            .system
+            .clip()
+            .is_some_and(|r| rects_equal(&r, &MONITOR)));
+    }
+
+    #[test]
+    fn monitor_entries_match_part_of_the_monitor_id() {
+        for (id, locked) in [("mock0001", false), ("MOCK0002", true)] {
+            let mut state = desktop();
+            state.config.monitors = vec![MonitorConfig {
+                id: Some(id.to_string()),
+                lock: MonitorLock::Never,
+                ..Default::default()
+            }];
+            focus(&mut state, GAME);
+            assert_eq!(state.machine.is_locked(), locked, "{}", id);
+        }
+    }
+
+    #[test]
+    fn never_locks_on_excluded_monitor() {
+        let mut state = desktop();
+        state.config.monitors = vec![MonitorConfig {
+            index: Some(1),
+            lock: MonitorLock::Never,
+            ..Default::default()
+        }];
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn locks_window_spanning_monitors_to_their_union() {
+        let mut state = desktop();
+        let second = RECT {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        let both = RECT {
+            right: 3840,
+            ..MONITOR
+        };
+        state.system.add_monitor(second);
+        state.system.update_window(GAME, |w| w.rect = both);
+        focus(&mut state, GAME);
+        assert_eq!(state.machine.locked_rect(), Some(both));
+        assert_eq!(state.system.clip(), Some(both));
+    }
+
+    #[test]
+    fn min_coverage_locks_a_letterboxed_window() {
+        let mut state = desktop();
+        let letterboxed = RECT {
+            bottom: 1050,
+            ..MONITOR
+        };
+        // Not a known game, so the plain 5 pixel tolerance applies
+        state.system.update_window(GAME, |w| {
+            w.class = "LetterboxedWindow".to_string();
+            w.rect = letterboxed;
+        });
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        // A window that covers its monitor by reaching well onto the next one
+        // is not fullscreen either way
+        state.config.detection.min_coverage = Some(0.97);
+        state.system.add_monitor(RECT {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        });
+        state.system.update_window(GAME, |w| {
+            w.rect = RECT {
+                left: 20,
+                right: 2500,
+                ..MONITOR
+            }
+        });
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        state.system.update_window(GAME, |w| w.rect = letterboxed);
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn a_pair_rule_locks_the_game_to_its_companion_monitor_too() {
+        let mut state = desktop();
+        let map = RECT {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        let third = RECT {
+            left: 3840,
+            right: 5760,
+            ..map
+        };
+        state.system.add_monitor(map);
+        state.system.add_monitor(third);
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("game.exe".to_string()),
+            overrides: LockOverrides {
+                // By device name and by part of the device ID
+                pair: Some([r"\\.\DISPLAY1".to_string(), "mock0002".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        let both = RECT {
+            right: 3840,
+            ..MONITOR
+        };
+        assert_eq!(state.system.clip(), Some(both));
+
+        // On a monitor outside the pair it is locked as usual
+        state.system.update_window(GAME, |w| w.rect = third);
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(third));
+    }
+
+    /// A portrait monitor left of `MONITOR`, sitting higher, and one above it
+    const PORTRAIT: RECT = RECT {
+        left: -1080,
+        top: -420,
+        right: 0,
+        bottom: 1500,
+    };
+    const ABOVE: RECT = RECT {
+        left: 0,
+        top: -1080,
+        right: 1920,
+        bottom: 0,
+    };
+
+    #[test]
+    fn locks_fullscreen_windows_on_portrait_and_stacked_monitors() {
+        for monitor in [PORTRAIT, ABOVE] {
+            let mut state = desktop();
+            state.system.add_monitor(PORTRAIT);
+            state.system.add_monitor(ABOVE);
+            state.system.update_window(GAME, |w| w.rect = monitor);
+            focus(&mut state, GAME);
+            assert_eq!(state.machine.locked_rect(), Some(monitor));
+            assert_eq!(state.system.clip(), Some(monitor));
+        }
+
+        // The invisible frame of a maximized window reaching onto the
+        // neighbors doesn't make it span them
+        let mut state = desktop();
+        state.system.add_monitor(PORTRAIT);
+        state.system.add_monitor(ABOVE);
+        state.system.update_window(GAME, |w| {
+            w.rect = RECT {
+                left: PORTRAIT.left - 8,
+                top: PORTRAIT.top - 8,
+                right: PORTRAIT.right + 8,
+                bottom: PORTRAIT.bottom + 8,
+            }
+        });
+        focus(&mut state, GAME);
+        assert_eq!(state.machine.locked_rect(), Some(PORTRAIT));
+    }
+
+    #[test]
+    fn wide_window_on_a_portrait_monitor_is_not_fullscreen() {
+        let mut state = desktop();
+        state.system.add_monitor(PORTRAIT);
+        state.system.add_monitor(ABOVE);
+        // Covers the portrait monitor, and a third of the main one too
+        state.system.update_window(BROWSER, |w| {
+            w.rect = RECT {
+                left: PORTRAIT.left - 10,
+                top: PORTRAIT.top - 10,
+                right: 600,
+                bottom: PORTRAIT.bottom + 10,
+            }
+        });
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn work_area_and_monitor_lookups_follow_the_window_to_its_monitor() {
+        let mut state = desktop();
+        let work_area = RECT {
+            bottom: PORTRAIT.bottom - 48,
+            ..PORTRAIT
+        };
+        state.system.add_monitor_with_work_area(PORTRAIT, work_area);
+        state.system.update_window(BROWSER, |w| w.rect = work_area);
+        assert_eq!(
+            state.system.window_monitor_rect(HWND(BROWSER as *mut _)),
+            Some(PORTRAIT)
+        );
+        // Filling the portrait work area is not fullscreen either
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("chrome.exe".to_string()),
+            overrides: LockOverrides {
+                clip_to: Some(ClipTarget::WorkArea),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        state.apply_config(config);
+        state.update();
+        assert_eq!(state.machine.locked_hwnd(), Some(BROWSER));
+        assert_eq!(state.system.clip(), Some(work_area));
+        let details = state.lock_details(&work_area);
+        assert_eq!(details.monitor, r"\\.\DISPLAY2");
+    }
+
+    #[test]
+    fn locks_to_work_area_when_a_rule_asks() {
+        let mut state = desktop();
+        let work_area = RECT {
+            bottom: 1040,
+            ..MONITOR
+        };
+        state.system.work_area = Some(work_area);
+        state.system.update_window(BROWSER, |w| w.rect = work_area);
+        // Filling just the work area is not fullscreen
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("chrome.exe".to_string()),
+            overrides: LockOverrides {
+                clip_to: Some(ClipTarget::WorkArea),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        state.apply_config(config);
+
+        state.update();
+        assert_eq!(state.machine.locked_hwnd(), Some(BROWSER));
+        assert_eq!(state.system.clip(), Some(work_area));
+    }
+
+    #[test]
+    fn rules_match_path_globs_and_product_names() {
+        let state = desktop();
+        state.system.update_window(GAME, |w| {
+            w.path = r"D:\SteamLibrary\steamapps\common\ELDEN RING\Game\Game.exe".to_string();
+            w.product = Some("ELDEN RING".to_string());
+        });
+        let verdict = |rule: RuleConfig| {
+            let rules = RuleSet::from_config(&Config {
+                rules: vec![RuleConfig {
+                    action: RuleAction::Ignore,
+                    ..rule
+                }],
+                ..Default::default()
+            });
+            rules.evaluate(&state.system.window_info(HWND(GAME as *mut _), &rules))
+        };
+
+        for process in [
+            r"d:\steamlibrary\**\game.exe",
+            "D:/**/ELDEN RING/*/*.exe",
+            "g?me.*",
+        ] {
+            let rule = RuleConfig {
+                process: Some(process.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(verdict(rule), Verdict::Ignore, "{}", process);
+        }
+        for process in [r"C:\Games\**\*.exe", r"D:\*\Game.exe", "*.com"] {
+            let rule = RuleConfig {
+                process: Some(process.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(verdict(rule), Verdict::Lock, "{}", process);
+        }
+        let product = |product: &str| RuleConfig {
+            process: Some("game.exe".to_string()),
+            product: Some(product.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(verdict(product("elden*")), Verdict::Ignore);
+        assert_eq!(verdict(product("Sekiro*")), Verdict::Lock);
+    }
+
+    #[test]
+    fn match_children_locks_what_the_launcher_started() {
+        let mut state = desktop();
+        state.system.update_window(GAME, |w| {
+            w.ancestors = vec!["Steam.exe".to_string(), "explorer.exe".to_string()];
+        });
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "ViewerWindow".to_string(),
+                pid: 50,
+                process: "viewer.exe".to_string(),
+                ancestors: vec!["explorer.exe".to_string()],
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        let mut config = Config::default();
+        config.detection.whitelist_only = true;
+        config.rules.push(RuleConfig {
+            process: Some("steam.exe".to_string()),
+            match_children: true,
+            action: RuleAction::Lock,
+            ..Default::default()
+        });
+        state.apply_config(config);
+
+        focus(&mut state, 0x500);
+        assert!(!state.machine.is_locked());
+
+        focus(&mut state, GAME);
+        assert_eq!(state.machine.locked_hwnd(), Some(GAME));
+    }
+
+    #[test]
+    fn without_match_children_a_launcher_rule_covers_only_the_launcher() {
+        let mut state = desktop();
+        state.system.update_window(GAME, |w| {
+            w.ancestors = vec!["Steam.exe".to_string()];
+        });
+        let mut config = Config::default();
+        config.detection.whitelist_only = true;
+        config.rules.push(RuleConfig {
+            process: Some("steam.exe".to_string()),
+            action: RuleAction::Lock,
+            ..Default::default()
+        });
+        state.apply_config(config);
+
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+    }
+
+    #[test]
+    fn confine_to_rule_keeps_the_cursor_in_a_region() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("game.exe".to_string()),
+            action: RuleAction::ConfineTo,
+            overrides: LockOverrides {
+                confine_to: Some(ConfineRegion::Center {
+                    width_percent: 50,
+                    height_percent: 100,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        state.apply_config(config);
+
+        focus(&mut state, GAME);
+        assert_eq!(
+            state.system.clip(),
+            Some(RECT {
+                left: 480,
+                right: 1440,
+                ..MONITOR
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_windowed_apps() {
+        let mut state = desktop();
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn grace_period_keeps_lock_on_transient_focus() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        for _ in 0..49 {
+            focus(&mut state, BROWSER);
+        }
+        assert!(state.machine.is_locked());
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn minimized_or_cloaked_window_releases_at_once() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        // Win+D leaves the desktop in front
+        state.system.update_window(GAME, |w| w.minimized = true);
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        state.system.update_window(GAME, |w| w.minimized = false);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        // Hidden by its app, yet still reported as in front
+        state.system.update_window(GAME, |w| w.cloaked = true);
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn a_handle_reused_between_ticks_is_classified_afresh() {
+        const OVERLAY: isize = 0x500;
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("overlay.exe".to_string()),
+            action: RuleAction::Ignore,
+            ..Default::default()
+        });
+        state.apply_config(config);
+        state.system.add_window(
+            OVERLAY,
+            MockWindow {
+                class: "OverlayWindow".to_string(),
+                pid: 50,
+                process: "overlay.exe".to_string(),
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, OVERLAY);
+        assert!(!state.machine.is_locked());
+
+        // Destroyed and handed to a game's window before the next tick looks
+        state.system.remove_window(OVERLAY);
+        state.system.add_window(
+            OVERLAY,
+            MockWindow {
+                class: "UnityWndClass".to_string(),
+                pid: 60,
+                process: "Other.exe".to_string(),
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, OVERLAY);
+        assert_eq!(state.machine.locked_hwnd(), Some(OVERLAY));
+        assert_eq!(state.tracked.class, "UnityWndClass");
+    }
+
+    #[test]
+    fn hidden_or_destroyed_window_releases_at_once() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.system.update_window(GAME, |w| w.hidden = true);
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        state.system.update_window(GAME, |w| w.hidden = false);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        // Gone without another window of the game to take over
+        state.system.remove_window(GAME);
+        focus(&mut state, 0);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert_eq!(state.tracked_hwnd, 0);
+    }
+
+    #[test]
+    fn strict_detection_waits_for_the_game_to_be_on_top() {
+        let mut state = desktop();
+        state.config.detection.level = DetectionLevel::Strict;
+        // A borderless window of another program over the game, which is
+        // still the foreground window; the mock stacks lower handles on top
+        const PLAYER: isize = 0x50;
+        state.system.add_window(
+            PLAYER,
+            MockWindow {
+                class: "Qt5QWindowIcon".to_string(),
+                pid: 40,
+                process: "Player.exe".to_string(),
+                rect: RECT {
+                    left: 0,
+                    top: 0,
+                    right: 800,
+                    bottom: 600,
+                },
+                ..Default::default()
+            },
+        );
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        // An overlay the clicks go through doesn't count
+        state
+            .system
+            .update_window(PLAYER, |w| w.click_through = true);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        // Nor does a window showing up over a lock that is already held
+        state
+            .system
+            .update_window(PLAYER, |w| w.click_through = false);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn a_recycled_handle_is_a_new_window() {
+        // Also where the clip is never looked at again: with reclip = "never"
+        // and with the hook backend (which the dry run keeps on ClipCursor)
+        let setups: [fn(&mut Config); 3] = [
+            |_| {},
+            |config| config.cursor.reclip = Reclip::Never,
+            |config| config.cursor.backend = LockBackend::Hook,
+        ];
+        for (i, setup) in setups.iter().enumerate() {
+            let mut state = desktop();
+            setup(&mut state.config);
+            focus(&mut state, GAME);
+            assert_eq!(state.locked_pid, 10);
+            // The game closed and a windowed program's window got its handle
+            state.system.update_window(GAME, |w| {
+                w.pid = 30;
+                w.process = "Player.exe".to_string();
+                w.rect = RECT {
+                    left: 100,
+                    top: 100,
+                    right: 900,
+                    bottom: 700,
+                };
+            });
+            state.update();
+            assert!(!state.machine.is_locked(), "setup {}", i);
+            assert!(state.system.clip().is_none(), "setup {}", i);
+        }
+
+        // Judged afresh, a fullscreen successor locks as a window of its own
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.system.update_window(GAME, |w| {
+            w.pid = 30;
+            w.process = "Player.exe".to_string();
+        });
+        state.update();
+        assert!(state.machine.is_locked());
+        assert_eq!(state.locked_pid, 30);
+    }
+
+    #[test]
+    fn switching_virtual_desktops_releases_and_relocks() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        // Win+Ctrl+Right: the game stays behind, cloaked, and the new desktop
+        // has nothing in front yet
+        state.system.update_window(GAME, |w| {
+            w.other_desktop = true;
+            w.cloaked = true;
+        });
+        focus(&mut state, 0);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        state.system.update_window(GAME, |w| {
+            w.other_desktop = false;
+            w.cloaked = false;
+        });
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert!(state.system.clip().is_some());
+    }
+
+    #[test]
+    fn hides_cursor_only_while_locked_and_shown() {
+        let mut state = desktop();
+        state.config.cursor.hide_while_locked = true;
+        state.system.set_cursor_showing(false);
+        focus(&mut state, GAME);
+        assert!(!state.system.cursor_hidden());
+
+        focus(&mut state, SWITCHER);
+        state.system.set_cursor_showing(true);
+        focus(&mut state, GAME);
+        assert!(state.system.cursor_hidden());
+        focus(&mut state, SWITCHER);
+        assert!(!state.system.cursor_hidden());
+    }
+
+    #[test]
+    fn centers_cursor_on_new_lock_only() {
+        let mut state = desktop();
+        state.system.move_cursor(POINT { x: 5, y: 5 });
+        focus(&mut state, GAME);
+        assert_eq!(state.system.cursor_pos(), Some(POINT { x: 5, y: 5 }));
+
+        focus(&mut state, BROWSER);
+        state.config.rules.push(RuleConfig {
+            class: Some("UnityWndClass".to_string()),
+            overrides: LockOverrides {
+                center_on_lock: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        state.rules = RuleSet::from_config(&state.config);
+        for _ in 0..50 {
+            focus(&mut state, BROWSER);
+        }
+        focus(&mut state, GAME);
+        assert_eq!(state.system.cursor_pos(), Some(POINT { x: 960, y: 540 }));
+
+        state.system.move_cursor(POINT { x: 5, y: 5 });
+        focus(&mut state, SWITCHER);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert_eq!(state.system.cursor_pos(), Some(POINT { x: 5, y: 5 }));
+    }
+
+    #[test]
+    fn puts_the_cursor_back_as_configured_when_a_lock_ends() {
+        for (on_release, expected) in [
+            (ReleasePosition::Keep, POINT { x: 1500, y: 900 }),
+            (ReleasePosition::Restore, POINT { x: 5, y: 5 }),
+            (ReleasePosition::Center, POINT { x: 960, y: 540 }),
+        ] {
+            let mut state = desktop();
+            state.config.cursor.on_release = on_release;
+            state.system.move_cursor(POINT { x: 5, y: 5 });
+            focus(&mut state, GAME);
+            assert!(state.machine.is_locked());
+            state.system.move_cursor(POINT { x: 1500, y: 900 });
+
+            state.system.update_window(GAME, |w| w.minimized = true);
+            focus(&mut state, BROWSER);
+            assert!(!state.machine.is_locked());
+            assert_eq!(
+                state.system.cursor_pos(),
+                Some(expected),
+                "{:?}",
+                on_release
+            );
+        }
+
+        // Switching away with Alt+Tab forgets where the cursor was
+        let mut state = desktop();
+        state.config.cursor.on_release = ReleasePosition::Restore;
+        state.system.move_cursor(POINT { x: 5, y: 5 });
+        focus(&mut state, GAME);
+        focus(&mut state, SWITCHER);
+        focus(&mut state, BROWSER);
+        state.system.move_cursor(POINT { x: 1500, y: 900 });
+        focus(&mut state, GAME);
+        state.system.update_window(GAME, |w| w.minimized = true);
+        focus(&mut state, BROWSER);
+        assert_eq!(state.system.cursor_pos(), Some(POINT { x: 1500, y: 900 }));
+    }
+
+    #[test]
+    fn transient_windows_keep_the_lock() {
+        let mut state = desktop();
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "Windows.UI.Core.CoreWindow".to_string(),
+                pid: 40,
+                process: "ShellExperienceHost.exe".to_string(),
+                ..Default::default()
+            },
+        );
+        focus(&mut state, GAME);
+        for _ in 0..100 {
+            focus(&mut state, 0x500);
+        }
+        assert!(state.machine.is_locked());
+        assert!(state.system.clip().is_some());
+
+        // Only as long as the list names them
+        state.config.detection.transient_processes.clear();
+        state.rules = RuleSet::from_config(&state.config);
+        state.tracked_hwnd = 0;
+        for _ in 0..100 {
+            focus(&mut state, 0x500);
+        }
+        assert!(!state.machine.is_locked());
+    }
+
+    #[test]
+    fn assistive_tools_suspend_locking() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state
+            .system
+            .set_assistive_tool(Some(AssistiveTool::Magnifier));
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        state.system.set_assistive_tool(None);
+        state.update();
+        assert!(state.machine.is_locked());
+
+        state.config.accessibility.pause_for_tools = false;
+        state
+            .system
+            .set_assistive_tool(Some(AssistiveTool::ScreenReader));
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn locking_the_session_or_switching_users_releases_until_back() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.handle_session_change(WTS_SESSION_LOCK);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        // Switching users on the lock screen; unlocking alone isn't enough
+        state.handle_session_change(WTS_CONSOLE_DISCONNECT);
+        state.handle_session_change(WTS_SESSION_UNLOCK);
+        focus(&mut state, GAME);
+        assert!(state.system.clip().is_none());
+        state.handle_session_change(WTS_CONSOLE_CONNECT);
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+
+        // A UAC prompt takes input to the secure desktop
+        state.system.set_secure_desktop(true);
+        state.check_input_desktop();
+        focus(&mut state, GAME);
+        assert!(state.system.clip().is_none());
+        state.system.set_secure_desktop(false);
+        state.check_input_desktop();
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn sleep_releases_the_clip_and_resume_checks_again() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.handle_power(PBT_APMSUSPEND);
+        assert!(state.system.clip().is_none());
+        assert!(!state.machine.is_locked());
+
+        // The lock screen that comes up on wake was missed while asleep
+        state.system.set_secure_desktop(true);
+        state.handle_power(PBT_APMRESUMEAUTOMATIC);
+        assert_eq!(state.suspensions, [Suspension::SecureDesktop]);
+        focus(&mut state, GAME);
+        assert!(state.system.clip().is_none());
+
+        state.system.set_secure_desktop(false);
+        state.check_input_desktop();
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn remote_desktop_sessions_suspend_locking() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.system.set_remote_session(true);
+        state.handle_session_change(WTS_REMOTE_CONNECT);
+        assert!(state.suspensions.contains(&Suspension::RemoteSession));
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        // Back at the console
+        state.system.set_remote_session(false);
+        state.handle_session_change(WTS_CONSOLE_CONNECT);
+        assert!(state.suspensions.is_empty());
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn presenting_suspends_locking() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.system.set_presenting(true);
+        state.update();
+        // Not checked again right away
+        assert!(state.machine.is_locked());
+
+        state.system.advance(PRESENTATION_CHECK_INTERVAL);
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        state.config.presentation.pause = false;
+        state.system.advance(PRESENTATION_CHECK_INTERVAL);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn mouse_sharing_tool_with_the_foreground_suspends_locking() {
+        let mut state = desktop();
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "BarrierDeskWindowClass".to_string(),
+                pid: 50,
+                process: "barriers.exe".to_string(),
+                // Covers the screen to hide the local cursor
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, GAME);
+        assert!(state.mouse_sharing_tool.is_some());
+        assert!(state.machine.is_locked());
+
+        // The pointer moves to the other computer
+        focus(&mut state, 0x500);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn reapplies_stolen_clip() {
+        let mut state = desktop();
+        state.config.cursor.margins.left = 10;
+        focus(&mut state, GAME);
+        let calls = state.system.clip_calls();
+        state.system.steal_clip();
+        state.update();
+        assert_eq!(state.system.clip_calls(), calls + 1);
+        assert!(state.system.clip().is_some());
+    }
+
+    #[test]
+    fn clips_again_only_when_the_clip_was_changed() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        let calls = state.system.clip_calls();
+        for _ in 0..10 {
+            focus(&mut state, GAME);
+        }
+        assert_eq!(state.system.clip_calls(), calls);
+
+        state.system.steal_clip();
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip_calls(), calls + 1);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+        assert!(state
+            .stats()
+            .to_json()
+            .contains("\"clip_reapplications\":1,"));
+    }
+
+    #[test]
+    fn reclip_strategy_and_interval_decide_when_the_clip_is_applied() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.cursor.reclip = Reclip::Never;
+        state.apply_config(config.clone());
+        focus(&mut state, GAME);
+        state.system.steal_clip();
+        state.update();
+        assert!(state.system.clip().is_none());
+
+        config.cursor.reclip = Reclip::Always;
+        config.cursor.reclip_interval_ms = 500;
+        state.apply_config(config);
+        let calls = state.system.clip_calls();
+        for _ in 0..10 {
+            state.system.advance(Duration::from_millis(100));
+            state.update();
+        }
+        assert_eq!(state.system.clip_calls(), calls + 2);
+    }
+
+    #[test]
+    fn escalates_a_game_that_keeps_clearing_the_clip() {
+        let mut state = desktop();
+        // Keeps the real mouse hook out of the test
+        state.dry_run = true;
+        focus(&mut state, GAME);
+        for _ in 0..THEFTS_TO_ESCALATE {
+            state.system.steal_clip();
+            state.system.advance(Duration::from_millis(100));
+            state.update();
+        }
+        assert!(state.escalated.contains("Game.exe"));
+
+        // A single theft now and then is left to re-clipping
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        for _ in 0..THEFTS_TO_ESCALATE {
+            state.system.steal_clip();
+            state.system.advance(THEFT_WINDOW);
+            state.update();
+        }
+        assert!(state.escalated.is_empty());
+    }
+
+    #[test]
+    fn tells_the_tray_once_when_clips_keep_failing() {
+        let mut state = desktop();
+        state.system.set_clip_error(Some(ClipError::Other(1400)));
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        for _ in 1..CLIP_FAILURES_TO_WARN {
+            assert!(state.clip_alert.is_none());
+            state.update();
+        }
+        assert_eq!(
+            state.clip_alert.take().as_deref(),
+            Some(tr!("clip.failing"))
+        );
+
+        // The console hears about it again later, the tray doesn't
+        for _ in 0..CLIP_FAILURES_TO_WARN * 4 {
+            state.update();
+        }
+        assert_eq!(state.clip_failures, CLIP_FAILURES_TO_WARN * 5);
+        assert!(state.clip_alert.is_none());
+
+        state.system.set_clip_error(None);
+        state.update();
+        assert!(state.machine.is_locked());
+        assert_eq!(state.clip_failures, 0);
+    }
+
+    #[test]
+    fn leaves_an_elevated_game_alone_until_another_app_is_in_front() {
+        let mut state = desktop();
+        state.elevated = false;
+        state.system.set_process_elevated(10, true);
+        state.system.set_clip_error(Some(ClipError::AccessDenied));
+        focus(&mut state, GAME);
+        let advice = state.clip_alert.take().unwrap();
+        assert!(advice.contains("Game.exe"), "{}", advice);
+
+        let calls = state.system.clip_calls();
+        for _ in 0..5 {
+            state.update();
+        }
+        assert_eq!(state.system.clip_calls(), calls);
+
+        focus(&mut state, BROWSER);
+        state.system.set_clip_error(None);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn moves_a_game_whose_clip_lands_elsewhere_to_the_mouse_hook() {
+        let mut state = desktop();
+        // Keeps the real mouse hook out of the test
+        state.dry_run = true;
+        let scaled = RECT {
+            left: 0,
+            top: 0,
+            right: 1280,
+            bottom: 720,
+        };
+        state.system.set_clip_error(Some(ClipError::Moved(scaled)));
+        focus(&mut state, GAME);
+        assert!(state.escalated.contains("Game.exe"));
+        assert_eq!(state.system.cursor_clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn ignores_fullscreen_browsers_unless_a_rule_locks_them() {
+        let mut state = desktop();
+        state.system.update_window(BROWSER, |w| w.rect = MONITOR);
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            process: Some("Chrome.exe".to_string()),
+            title: Some("Game".to_string()),
+            ..Default::default()
+        });
+        state.apply_config(config);
+        state
+            .system
+            .update_window(BROWSER, |w| w.title = "Web Game".to_string());
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn dry_run_keeps_to_the_clip_backend() {
+        let mut state = desktop();
+        state.dry_run = true;
+        state.config.cursor.backend = LockBackend::Hook;
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert!(state.mouse_hook.is_none());
+        assert_eq!(state.confined_with, LockBackend::Clip);
+    }
+
+    #[test]
+    fn rules_can_target_steam_apps() {
+        let mut state = desktop();
+        state.system.update_window(GAME, |w| {
+            w.steam_app = Some(SteamApp {
+                id: 1245620,
+                name: "ELDEN RING".to_string(),
+            })
+        });
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            steam_app: Some(1245620),
+            action: RuleAction::Ignore,
+            ..Default::default()
+        });
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        let mut config = Config::default();
+        config.rules.push(RuleConfig {
+            steam_app: Some(570),
+            action: RuleAction::Ignore,
+            ..Default::default()
+        });
+        state.apply_config(config);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn reloading_the_config_keeps_a_lock_that_still_applies() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+
+        // New margins take effect at once and aren't taken for clip theft
+        let mut config = Config::default();
+        config.cursor.margins.left = 10;
+        state.apply_config(config.clone());
+        let margined = RECT {
+            left: 10,
+            ..MONITOR
+        };
+        assert_eq!(state.system.clip(), Some(margined));
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert_eq!(state.system.clip(), Some(margined));
+        assert!(state.clip_thefts.is_empty());
+
+        // A rule that now ignores the game ends the lock
+        config.rules.push(RuleConfig {
+            process: Some("game.exe".to_string()),
+            action: RuleAction::Ignore,
+            ..Default::default()
+        });
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+
+        // And one without it brings the lock back
+        state.apply_config(Config::default());
+        focus(&mut state, GAME);
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn profile_hotkeys_switch_rule_sets() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.profiles.push(ProfileConfig {
+            name: "Streaming".to_string(),
+            rules: vec![RuleConfig {
+                process: Some("game.exe".to_string()),
+                action: RuleAction::Ignore,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        config.profiles.push(ProfileConfig {
+            name: "FPS".to_string(),
+            margins: Some(ClipMargins {
+                left: 8,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        state.handle_hotkey(HotkeyAction::Profile(0));
+        state.update();
+        assert!(!state.machine.is_locked());
+
+        // Survives a reload of the same file
+        let reloaded = state.base_config.clone();
+        state.apply_config(reloaded);
+        state.update();
+        assert_eq!(state.profile, Some(0));
+        assert!(!state.machine.is_locked());
+
+        state.handle_hotkey(HotkeyAction::NextProfile);
+        state.update();
+        assert!(state.machine.is_locked());
+        assert_eq!(state.config.cursor.margins.left, 8);
+
+        state.handle_hotkey(HotkeyAction::NextProfile);
+        assert_eq!(state.profile, None);
+        assert_eq!(state.config.cursor.margins.left, 0);
+
+        state.handle_hotkey(HotkeyAction::Profile(0));
+        state.handle_hotkey(HotkeyAction::Profile(0));
+        assert_eq!(state.profile, None);
+    }
+
+    #[test]
+    fn restores_pause_profile_and_escalations_of_the_last_run() {
+        let mut config = Config::default();
+        config.profiles.push(ProfileConfig {
+            name: "Streaming".to_string(),
+            ..Default::default()
+        });
+        let mut state = desktop();
+        state.apply_config(config.clone());
+        state.handle_hotkey(HotkeyAction::Profile(0));
+        state.handle_stdio(StdioCommand::Pause);
+        state.escalated.insert("game.exe".to_string());
+        let saved = state.saved_state();
+        assert_eq!(saved.profile.as_deref(), Some("Streaming"));
+
+        let mut restarted = desktop();
+        restarted.apply_config(config);
+        restarted.restore_state(saved.clone());
+        assert_eq!(restarted.profile, Some(0));
+        assert_eq!(restarted.state_name(), "paused");
+        assert!(restarted.escalated.contains("game.exe"));
+        focus(&mut restarted, GAME);
+        assert!(!restarted.machine.is_locked());
+
+        // A profile no longer in the config is left out
+        let mut edited = desktop();
+        edited.restore_state(SavedState {
+            paused: false,
+            ..saved
+        });
+        assert_eq!(edited.profile, None);
+        focus(&mut edited, GAME);
+        assert!(edited.machine.is_locked());
+    }
+
+    #[test]
+    fn obs_projectors_keep_the_lock() {
+        let mut state = desktop();
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "Qt663QWindowIcon".to_string(),
+                title: "Windowed Projector (Preview)".to_string(),
+                pid: 50,
+                process: "obs64.exe".to_string(),
+                ..Default::default()
+            },
+        );
+        focus(&mut state, GAME);
+        focus(&mut state, 0x500);
+        assert!(matches!(state.machine.state(), State::Locked { .. }));
+
+        // The main OBS window is an ordinary window, which starts the grace period
+        state
+            .system
+            .update_window(0x500, |w| w.title = "OBS 30.2.0 - Scenes".to_string());
+        focus(&mut state, GAME);
+        focus(&mut state, 0x500);
+        assert!(matches!(state.machine.state(), State::GracePeriod { .. }));
+    }
+
+    #[test]
+    fn obs_game_capture_switches_to_the_streaming_profile() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.obs.profile = Some("streaming".to_string());
+        config.profiles.push(ProfileConfig {
+            name: "Streaming".to_string(),
+            center_on_lock: Some(true),
+            ..Default::default()
+        });
+        state.apply_config(config);
+        state.system.update_window(GAME, |w| {
+            w.modules = vec!["graphics-hook64.dll".to_string()]
+        });
+        focus(&mut state, GAME);
+        state.system.advance(PROCESS_SCAN_INTERVAL);
+        state.update();
+        assert!(state.obs_running.is_some());
+        assert_eq!(state.profile, Some(0));
+        assert!(state.config.cursor.center_on_lock);
+
+        state.system.update_window(GAME, |w| w.modules.clear());
+        state.system.advance(PROCESS_SCAN_INTERVAL);
+        state.update();
+        assert_eq!(state.obs_running, None);
+        assert_eq!(state.profile, None);
+    }
+
+    #[test]
+    fn known_games_get_a_looser_fullscreen_tolerance() {
+        let mut state = desktop();
+        let almost = RECT {
+            right: MONITOR.right - 10,
+            ..MONITOR
+        };
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "ViewerWindow".to_string(),
+                pid: 50,
+                process: "viewer.exe".to_string(),
+                rect: almost,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, 0x500);
+        assert!(!state.machine.is_locked());
+
+        state.system.update_window(GAME, |w| w.rect = almost);
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn only_known_games_ignores_other_fullscreen_windows() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.detection.only_known_games = true;
+        state.apply_config(config);
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "ViewerWindow".to_string(),
+                pid: 50,
+                process: "viewer.exe".to_string(),
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, 0x500);
+        assert!(!state.machine.is_locked());
+
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn leaves_a_tighter_clip_set_by_the_game() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        let window = RECT {
+            left: 100,
+            top: 100,
+            right: 900,
+            bottom: 700,
+        };
+        let _ = state.system.clip_cursor(&window);
+        let calls = state.system.clip_calls();
+        state.update();
+        assert_eq!(state.system.clip_calls(), calls);
+        assert_eq!(state.system.clip(), Some(window));
+    }
+
+    #[test]
+    fn cooperates_with_other_confinement_tools() {
+        let mut state = desktop();
+        let second = RECT {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        state.system.add_monitor(second);
+        state.system.add_window(
+            0x500,
+            MockWindow {
+                class: "WindowsForms10.Window.8.app".to_string(),
+                pid: 50,
+                process: "DualMonitorTools.exe".to_string(),
+                ..Default::default()
+            },
+        );
+        focus(&mut state, GAME);
+        assert!(state.conflicting_tool.is_some());
+
+        // The other tool's clip stays until it lets go
+        let _ = state.system.clip_cursor(&second);
+        let calls = state.system.clip_calls();
+        state.update();
+        assert_eq!(state.system.clip_calls(), calls);
+        state.system.steal_clip();
+        state.update();
+        assert_eq!(state.system.clip(), Some(MONITOR));
+
+        state.config.conflicts.cooperate = false;
+        let _ = state.system.clip_cursor(&second);
+        state.update();
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn alt_tab_releases_and_relocks_on_return() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        focus(&mut state, SWITCHER);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert_eq!(state.machine.remembered(), Some(GAME));
+
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn task_view_releases_and_remembers_like_alt_tab() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        for _ in 0..100 {
+            focus(&mut state, TASK_VIEW);
+        }
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert_eq!(state.machine.remembered(), Some(GAME));
+
+        focus(&mut state, BROWSER);
+        assert_eq!(state.machine.remembered(), Some(GAME));
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn alt_tab_away_waits_for_return() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.alt_tab.require_click = true;
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        focus(&mut state, SWITCHER);
+        focus(&mut state, BROWSER);
+        assert!(matches!(state.machine.state(), State::SwitchedAway { .. }));
+
+        // Focus alone doesn't bring the lock back with require_click
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        state.system.set_button(true);
+        state.update();
+        assert!(state.machine.is_locked());
+        assert!(matches!(state.machine.state(), State::Locked { .. }));
+    }
+
+    #[test]
+    fn keeps_lock_when_window_is_recreated() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.system.remove_window(GAME);
+        state.system.add_window(
+            0x101,
+            MockWindow {
+                class: "UnityWndClass".to_string(),
+                pid: 10,
+                process: "Game.exe".to_string(),
+                rect: MONITOR,
+                ..Default::default()
+            },
+        );
+        focus(&mut state, 0x101);
+        assert!(state.machine.is_locked());
+        assert_eq!(state.machine.locked_hwnd(), Some(0x101));
+        assert!(state.stats().summary().contains("Game.exe"));
+        assert!(state.stats().summary().contains(" 1 lock(s)"));
+    }
+
+    #[test]
+    fn a_windowed_sibling_of_the_game_is_not_adopted() {
+        let mut state = desktop();
+        // Locks the launcher below through coverage, not through adoption
+        state.config.detection.min_coverage = Some(0.9);
+        focus(&mut state, GAME);
+        state.system.remove_window(GAME);
+        state.system.add_window(
+            0x101,
+            MockWindow {
+                class: "UnityWndClass".to_string(),
+                pid: 10,
+                process: "Game.exe".to_string(),
+                rect: RECT {
+                    bottom: 1000,
+                    ..MONITOR
+                },
+                ..Default::default()
+            },
+        );
+        focus(&mut state, 0x101);
+        assert_eq!(state.machine.locked_hwnd(), Some(0x101));
+        assert!(state.stats().summary().contains(" 2 lock(s)"));
+    }
+
+    #[test]
+    fn idle_releases_until_input() {
+        let mut state = desktop();
+        state.config.idle.enabled = true;
+        focus(&mut state, GAME);
+
+        state.system.set_idle_time(Duration::from_secs(300));
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert!(state.needs_ticks());
+
+        state.system.set_idle_time(Duration::ZERO);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn a_controller_in_use_defers_the_lock() {
+        let mut state = desktop();
+        state.config.gamepad.enabled = true;
+        state
+            .system
+            .set_gamepad_idle_time(Some(Duration::from_secs(1)));
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert!(state.suspensions.contains(&Suspension::Gamepad));
+
+        // Put down for longer than the timeout, the game locks as usual
+        state
+            .system
+            .set_gamepad_idle_time(Some(Duration::from_secs(10)));
+        state.update();
+        assert!(!state.suspensions.contains(&Suspension::Gamepad));
+        assert!(state.machine.is_locked());
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn picking_up_a_controller_releases_the_lock() {
+        let mut state = desktop();
+        state.config.gamepad.enabled = true;
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        state.system.set_gamepad_idle_time(Some(Duration::ZERO));
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        // Ticking on to notice the controller being put down
+        assert!(state.needs_ticks());
+
+        state.system.set_gamepad_idle_time(None);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn controllers_are_ignored_unless_enabled() {
+        let mut state = desktop();
+        state.system.set_gamepad_idle_time(Some(Duration::ZERO));
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert!(state.suspensions.is_empty());
+    }
+
+    #[test]
+    fn manual_lock_confines_windowed_app_until_toggled() {
+        let mut state = desktop();
+        focus(&mut state, BROWSER);
+        state.system.move_cursor(POINT { x: 500, y: 500 });
+        state.toggle_manual_lock();
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert_eq!(state.system.clip(), Some(MONITOR));
+        assert_eq!(state.status(), Reply::Locked);
+
+        // Detection stays out of it, even for a fullscreen window
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        state.toggle_manual_lock();
+        assert!(state.system.clip().is_none());
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn panic_hotkey_pauses_locking_for_the_cool_down() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        state.handle_hotkey(HotkeyAction::Panic);
+        assert!(state.system.clip().is_none());
+        state.system.advance(Duration::from_secs(59));
+        state.update();
+        assert!(!state.machine.is_locked());
+        assert_eq!(state.state_name(), "suspended");
+
+        state.system.advance(Duration::from_secs(1));
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn tray_unlock_frees_the_cursor_for_a_while() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+
+        state.unlock_for(Duration::from_secs(30));
+        assert!(state.system.clip().is_none());
+        assert_eq!(state.status(), Reply::Unlocked);
+        state.system.advance(Duration::from_secs(29));
+        state.update();
+        assert!(!state.machine.is_locked());
+
+        state.system.advance(Duration::from_secs(1));
+        state.update();
+        assert!(state.machine.is_locked());
+        assert!(!state.timed_unlock);
+    }
+
+    #[test]
+    fn tray_unlock_lifts_a_manual_lock() {
+        let mut state = desktop();
+        focus(&mut state, BROWSER);
+        state.system.move_cursor(POINT { x: 500, y: 500 });
+        state.toggle_manual_lock();
+        state.update();
+        state.unlock_for(Duration::from_secs(30));
+        assert!(state.manual_lock.is_none());
+        assert!(state.system.clip().is_none());
+    }
+
+    #[test]
+    fn cycles_manual_lock_through_monitors() {
+        let mut state = desktop();
+        let second = RECT {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        state.system.add_monitor(second);
+        focus(&mut state, BROWSER);
+        state.toggle_manual_lock();
+        state.update();
+        assert_eq!(state.system.clip(), Some(MONITOR));
+
+        state.cycle_manual_lock();
+        assert_eq!(state.system.clip(), Some(second));
+        assert_eq!(state.system.cursor_pos(), Some(POINT { x: 2880, y: 540 }));
+        state.update();
+        assert_eq!(state.system.clip(), Some(second));
+
+        state.cycle_manual_lock();
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn replaying_snapshots_repeats_the_decisions() {
+        let state = desktop();
+        let capture = |foreground: isize| {
+            state.system.set_foreground(foreground);
+            snapshot::capture(&state.system, &state.rules, 5, None, None)
+        };
+        let game = capture(GAME);
+        let browser = capture(BROWSER).replacen('{', "{\"at_ms\":1000,", 1);
+        let recording = replay::parse(&format!("[{},{}]", game, browser)).unwrap();
+
+        let lines = replay_decisions(&recording, Config::default());
+        assert_eq!(
+            lines[..2],
+            [
+                "      0ms  frame 1: foreground 0x100 Game.exe (UnityWndClass)",
+                "    -> locked, clip 1920x1080 at (0, 0)",
+            ]
+        );
+        assert_eq!(
+            lines[2],
+            "   1000ms  frame 2: foreground 0x200 chrome.exe (Chrome_WidgetWin_1)"
+        );
+        // Held through the grace period, then released
+        assert_eq!(lines[3..], ["    -> unlocked, clip released"]);
+    }
+
+    #[test]
+    fn stdio_pause_holds_until_resumed() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.handle_stdio(StdioCommand::Toggle);
+        assert!(!state.machine.is_locked());
+        assert!(state.system.clip().is_none());
+        assert_eq!(state.state_name(), "paused");
+        state.update();
+        assert!(!state.machine.is_locked());
+
+        state.handle_stdio(StdioCommand::Resume);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn reclaims_clip_after_yielding_process_exits() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.handle_request(Request::Yield, 20);
+        assert!(!state.machine.is_locked());
+        state.update();
+        assert!(!state.machine.is_locked());
+
+        state.system.set_process_running(20, false);
+        state.update();
+        assert!(state.machine.is_locked());
+    }
+
+    #[test]
+    fn asks_for_ticks_only_while_something_is_going_on() {
+        let mut state = desktop();
+        focus(&mut state, BROWSER);
+        assert!(!state.needs_ticks());
+
+        // A process holding the clip is checked on until it hands it back
+        state.handle_request(Request::Yield, 20);
+        assert!(state.needs_ticks());
+        state.handle_request(Request::TakeOver, 20);
+        assert!(!state.needs_ticks());
+
+        // The clip has to be watched while locked
+        focus(&mut state, GAME);
+        assert!(state.needs_ticks());
+
+        // And the remembered game after switching away, for the way back
+        focus(&mut state, SWITCHER);
+        focus(&mut state, BROWSER);
+        assert!(!state.machine.is_locked());
+        assert!(state.needs_ticks());
+    }
+
+    #[test]
+    fn yields_the_clip_until_handed_back() {
+        let mut state = desktop();
+        focus(&mut state, GAME);
+        state.handle_request(Request::Yield, 20);
+        assert!(state.system.clip().is_none());
+
+        // The other process clips as it likes meanwhile
+        let calls = state.system.clip_calls();
+        for _ in 0..10 {
+            focus(&mut state, GAME);
+        }
+        assert_eq!(state.system.clip_calls(), calls);
+        assert!(state.system.clip().is_none());
+
+        state.handle_request(Request::TakeOver, 20);
+        state.update();
+        assert!(state.machine.is_locked());
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn hitting_the_edge_twice_frees_the_cursor_for_a_while() {
+        let mut state = desktop();
+        let mut config = Config::default();
+        config.edge_escape.enabled = true;
+        state.apply_config(config);
+        focus(&mut state, GAME);
+        assert!(state.system.clip().is_some());
+
+        for x in [960, 0, 960, 1919] {
+            state.system.move_cursor(POINT { x, y: 540 });
+            focus(&mut state, GAME);
+        }
+        assert!(state.system.clip().is_none());
+
+        // The lock comes back once the release is over
+        state.system.advance(Duration::from_millis(5000));
+        focus(&mut state, GAME);
+        assert!(state.machine.is_locked());
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[cfg(feature = "rhai")]
+    #[test]
+    fn a_detection_script_overrides_the_defaults() {
+        let mut state = desktop();
+        let second = RECT {
+            left: 1920,
+            right: 3840,
+            ..MONITOR
+        };
+        state.system.add_monitor(second);
+        // Lock browsers, and games only on the second monitor
+        let path = std::env::temp_dir().join(format!("slc-detect-{}.rhai", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+                if window.exe == "chrome.exe" { return "lock"; }
+                if window.class == "UnityWndClass" {
+                    if window.monitor.left == window.rect.left && window.rect.left > 0 { "lock" } else { "ignore" }
+                }
+            "#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.detection.script = Some(path.clone());
+        state.apply_config(config);
+        std::fs::remove_file(&path).unwrap();
+
+        focus(&mut state, GAME);
+        assert!(!state.machine.is_locked());
+
+        // Moving the game is noticed without it losing the foreground
+        state.system.update_window(GAME, |w| w.rect = second);
+        focus(&mut state, GAME);
+        assert_eq!(state.machine.locked_rect(), Some(second));
+
+        // ignore_browsers is a default the script overrides
+        state.system.update_window(BROWSER, |w| w.rect = MONITOR);
+        focus(&mut state, BROWSER);
+        assert_eq!(state.machine.locked_hwnd(), Some(BROWSER));
+        assert_eq!(state.system.clip(), Some(MONITOR));
+    }
+
+    #[test]
+    fn polling_backs_off_only_while_nothing_could_lock() {
+        let engine = EngineConfig::default();
+        let tick = Duration::from_millis(100);
+        let idle = Duration::from_millis(engine.idle_interval_ms);
+        let watchdog = Duration::from_millis(engine.watchdog_interval_ms);
+
+        // Event mode with nothing to tick for sleeps until the watchdog
+        assert_eq!(wait_timeout(&engine, true, false, true, tick), watchdog);
+        assert_eq!(wait_timeout(&engine, true, false, false, tick), watchdog);
+        // Polling with the foreground hook slows down while nothing could lock...
+        assert_eq!(wait_timeout(&engine, false, true, true, tick), idle);
+        // ...and is back to every tick as soon as something could
+        assert_eq!(wait_timeout(&engine, false, true, false, tick), tick);
+        // Without the hook nothing would wake it early, so it never backs off
+        assert_eq!(wait_timeout(&engine, false, false, true, tick), tick);
+        // The low-power tick is kept as it is
+        let slow = Duration::from_millis(1000);
+        assert_eq!(wait_timeout(&engine, false, false, true, slow), slow);
+
+        let never = EngineConfig {
+            idle_interval_ms: 0,
+            ..EngineConfig::default()
+        };
+        assert_eq!(wait_timeout(&never, false, true, true, tick), tick);
+    }
+
+    #[test]
+    fn a_game_in_front_stops_the_back_off() {
+        let mut state = desktop();
+        focus(&mut state, 0);
+        assert!(state.can_back_off());
+
+        focus(&mut state, GAME);
+        assert!(!state.can_back_off());
+    }
 }
@@ -0,0 +1,604 @@
+//! Monitor enumeration and geometry helpers
+
+use std::cell::RefCell;
+use std::mem::zeroed;
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::{BOOL, HWND, LPARAM, POINT, RECT, TRUE};
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayDevicesW, EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW,
+    MonitorFromWindow, DEVMODEW, DISPLAY_DEVICEW, ENUM_CURRENT_SETTINGS, HDC, HMONITOR,
+    MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST,
+};
+use windows::Win32::UI::HiDpi::{
+    GetDpiForMonitor, SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    MDT_EFFECTIVE_DPI,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetSystemMetrics, EDD_GET_DEVICE_INTERFACE_NAME, MONITORINFOF_PRIMARY, SM_CXVIRTUALSCREEN,
+    SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+};
+
+use crate::config::{MonitorConfig, MonitorLock};
+use crate::json;
+
+/// Represents a monitor's information
+#[derive(Debug, Clone)]
+pub struct MonitorBounds {
+    pub rect: RECT,
+    pub handle: HMONITOR,
+    /// GDI device name, e.g. `\\.\DISPLAY1`, numbered in enumeration order
+    pub device: String,
+    /// Device interface path of the display, e.g.
+    /// `\\?\DISPLAY#DEL40F5#5&2b3c4d5&0&UID4353#{e6f07b5f-...}`, which stays the
+    /// same across reboots and re-enumeration; empty if Windows doesn't say
+    pub id: String,
+}
+
+impl MonitorBounds {
+    /// Whether `[[monitors]] id` names this monitor, by any part of its ID
+    pub fn id_matches(&self, pattern: &str) -> bool {
+        !self.id.is_empty()
+            && self
+                .id
+                .to_ascii_lowercase()
+                .contains(&pattern.to_ascii_lowercase())
+    }
+
+    /// Whether a rule's `pair` names this monitor, by its device name or any
+    /// part of its ID
+    pub fn is_named(&self, name: &str) -> bool {
+        self.device.eq_ignore_ascii_case(name) || self.id_matches(name)
+    }
+}
+
+/// The EDID manufacturer and product code in a monitor ID (`DEL40F5`), the
+/// same whichever port the monitor is plugged into
+pub fn hardware_id(id: &str) -> Option<&str> {
+    id.split('#').nth(1).filter(|code| !code.is_empty())
+}
+
+/// The first display attached to a GDI device
+fn display_device(device: &str, flags: u32) -> Option<DISPLAY_DEVICEW> {
+    let mut display = DISPLAY_DEVICEW {
+        cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+        ..Default::default()
+    };
+    unsafe { EnumDisplayDevicesW(&HSTRING::from(device), 0, &mut display, flags) }
+        .as_bool()
+        .then_some(display)
+}
+
+fn wide_str(text: &[u16]) -> String {
+    let len = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+    String::from_utf16_lossy(&text[..len])
+}
+
+/// Device interface path of the display attached to a GDI device
+fn monitor_id(device: &str) -> String {
+    display_device(device, EDD_GET_DEVICE_INTERFACE_NAME)
+        .map(|display| wide_str(&display.DeviceID))
+        .unwrap_or_default()
+}
+
+/// What the startup banner and the status reports tell about a monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorDetails {
+    /// 1-based, as `[[monitors]] index` counts
+    pub index: usize,
+    pub rect: RECT,
+    pub work_area: RECT,
+    pub device: String,
+    pub id: String,
+    /// Name of the display, e.g. `DELL U2720Q` or `Generic PnP Monitor`
+    pub name: String,
+    /// Display scaling in percent, 0 if unknown
+    pub scale: u32,
+    /// 0 if unknown
+    pub refresh_hz: u32,
+    pub primary: bool,
+}
+
+impl MonitorDetails {
+    /// One line for the banner and the `status` report
+    pub fn describe(&self) -> String {
+        let mut out = format!("Monitor {}: {}", self.index, describe_rect(&self.rect));
+        if self.primary {
+            out.push_str(", primary");
+        }
+        if self.scale != 0 {
+            out.push_str(&format!(", {}% scale", self.scale));
+        }
+        if self.refresh_hz != 0 {
+            out.push_str(&format!(", {} Hz", self.refresh_hz));
+        }
+        if !rects_equal(&self.work_area, &self.rect) {
+            out.push_str(&format!(", work area {}", describe_rect(&self.work_area)));
+        }
+        out.push_str(&format!(
+            ", {} {} id {}",
+            if self.name.is_empty() {
+                "unnamed"
+            } else {
+                &self.name
+            },
+            self.device,
+            hardware_id(&self.id).unwrap_or("unknown")
+        ));
+        out
+    }
+
+    /// A JSON object for the `/status` endpoint
+    pub fn to_json(&self) -> String {
+        let rect = |r: &RECT| format!("[{},{},{},{}]", r.left, r.top, r.right, r.bottom);
+        format!(
+            "{{\"index\":{},\"device\":{},\"id\":{},\"name\":{},\"rect\":{},\"work_area\":{},\"scale_percent\":{},\"refresh_hz\":{},\"primary\":{}}}",
+            self.index,
+            json::string(&self.device),
+            json::string(&self.id),
+            json::string(&self.name),
+            rect(&self.rect),
+            rect(&self.work_area),
+            self.scale,
+            self.refresh_hz,
+            self.primary
+        )
+    }
+}
+
+/// Details of every connected monitor, in enumeration order
+pub fn monitor_details() -> Vec<MonitorDetails> {
+    get_all_monitors()
+        .into_iter()
+        .enumerate()
+        .map(|(i, monitor)| {
+            let mut info: MONITORINFO = unsafe { zeroed() };
+            info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+            let known = unsafe { GetMonitorInfoW(monitor.handle, &mut info) }.as_bool();
+            let mut mode = DEVMODEW {
+                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                ..Default::default()
+            };
+            let refresh_hz = unsafe {
+                EnumDisplaySettingsW(
+                    &HSTRING::from(&monitor.device),
+                    ENUM_CURRENT_SETTINGS,
+                    &mut mode,
+                )
+            }
+            .as_bool()
+            // 0 and 1 stand for the hardware default
+            .then_some(mode.dmDisplayFrequency)
+            .filter(|&hz| hz > 1)
+            .unwrap_or(0);
+            MonitorDetails {
+                index: i + 1,
+                rect: monitor.rect,
+                work_area: if known { info.rcWork } else { monitor.rect },
+                name: display_device(&monitor.device, 0)
+                    .map(|display| wide_str(&display.DeviceString))
+                    .unwrap_or_default(),
+                scale: monitor_scale(monitor.handle),
+                refresh_hz,
+                primary: known && info.dwFlags & MONITORINFOF_PRIMARY != 0,
+                device: monitor.device,
+                id: monitor.id,
+            }
+        })
+        .collect()
+}
+
+/// Display scaling of a monitor in percent
+///
+/// The process isn't DPI aware, which makes Windows report 96 DPI everywhere,
+/// so the thread is made per-monitor aware for the query and then set back.
+fn monitor_scale(monitor: HMONITOR) -> u32 {
+    let (mut x, mut y) = (0, 0);
+    unsafe {
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let result = GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut x, &mut y);
+        if !previous.is_invalid() {
+            SetThreadDpiAwarenessContext(previous);
+        }
+        if result.is_err() {
+            return 0;
+        }
+    }
+    (x * 100 + 48) / 96
+}
+
+/// The monitors as a JSON array
+pub fn monitors_json(monitors: &[MonitorDetails]) -> String {
+    let objects: Vec<String> = monitors.iter().map(MonitorDetails::to_json).collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Collects all monitor bounds in the system
+pub fn get_all_monitors() -> Vec<MonitorBounds> {
+    let mut monitors: Vec<MonitorBounds> = Vec::new();
+
+    unsafe extern "system" fn enum_monitor_proc(
+        hmonitor: HMONITOR,
+        _hdc: HDC,
+        _lprect: *mut RECT,
+        lparam: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(lparam.0 as *mut Vec<MonitorBounds>);
+
+        let mut monitor_info: MONITORINFOEXW = zeroed();
+        monitor_info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+
+        if GetMonitorInfoW(hmonitor, &mut monitor_info.monitorInfo).as_bool() {
+            let len = monitor_info
+                .szDevice
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(monitor_info.szDevice.len());
+            let device = String::from_utf16_lossy(&monitor_info.szDevice[..len]);
+            monitors.push(MonitorBounds {
+                rect: monitor_info.monitorInfo.rcMonitor,
+                handle: hmonitor,
+                id: monitor_id(&device),
+                device,
+            });
+        }
+
+        TRUE
+    }
+
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(enum_monitor_proc),
+            LPARAM(&mut monitors as *mut _ as isize),
+        );
+    }
+
+    monitors
+}
+
+/// The monitors as of the last enumeration, and the virtual screen back then
+struct MonitorCache {
+    screen: RECT,
+    monitors: Vec<MonitorBounds>,
+}
+
+thread_local! {
+    static MONITOR_CACHE: RefCell<Option<MonitorCache>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` on all monitors, enumerated again only after
+/// [`invalidate_monitors`] or when the virtual screen changed
+///
+/// Enumerating asks every display for its device interface path, which is
+/// too slow to repeat on every check. The cache belongs to the calling thread.
+pub fn with_monitors<R>(f: impl FnOnce(&[MonitorBounds]) -> R) -> R {
+    let screen = virtual_screen_rect();
+    MONITOR_CACHE.with(|cache| {
+        let stale = cache
+            .borrow()
+            .as_ref()
+            .is_none_or(|cached| !rects_equal(&cached.screen, &screen));
+        if stale {
+            *cache.borrow_mut() = Some(MonitorCache {
+                screen,
+                monitors: get_all_monitors(),
+            });
+        }
+        let cache = cache.borrow();
+        f(cache.as_ref().map_or(&[], |cached| &cached.monitors))
+    })
+}
+
+/// Makes the next [`with_monitors`] on this thread enumerate again, for when
+/// the display configuration changed (`WM_DISPLAYCHANGE`)
+pub fn invalidate_monitors() {
+    MONITOR_CACHE.with(|cache| *cache.borrow_mut() = None);
+}
+
+/// Gets the rect of a monitor from the cache, falling back to asking Windows
+/// for handles it doesn't know
+pub fn cached_monitor_rect(hmonitor: HMONITOR) -> Option<RECT> {
+    let find = |monitors: &[MonitorBounds]| {
+        monitors
+            .iter()
+            .find(|monitor| monitor.handle == hmonitor)
+            .map(|monitor| monitor.rect)
+    };
+    if let Some(rect) = with_monitors(find) {
+        return Some(rect);
+    }
+    // A new handle means the displays changed before the message arrived
+    invalidate_monitors();
+    with_monitors(find).or_else(|| get_monitor_rect(hmonitor))
+}
+
+/// Gets monitor rect for a specific monitor handle
+pub fn get_monitor_rect(hmonitor: HMONITOR) -> Option<RECT> {
+    unsafe {
+        let mut monitor_info: MONITORINFO = zeroed();
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+
+        if GetMonitorInfoW(hmonitor, &mut monitor_info).as_bool() {
+            Some(monitor_info.rcMonitor)
+        } else {
+            None
+        }
+    }
+}
+/// Gets the work area (the monitor minus the taskbar and docked toolbars) of a
+/// specific monitor
+pub fn get_monitor_work_area(hmonitor: HMONITOR) -> Option<RECT> {
+    unsafe {
+        let mut monitor_info: MONITORINFO = zeroed();
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+
+        if GetMonitorInfoW(hmonitor, &mut monitor_info).as_bool() {
+            Some(monitor_info.rcWork)
+        } else {
+            None
+        }
+    }
+}
+
+/// Gets the rect of the monitor a window is mostly on
+pub fn get_window_monitor_rect(hwnd: HWND) -> Option<RECT> {
+    cached_monitor_rect(unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) })
+}
+
+/// Describes a monitor rect the way the startup banner lists monitors
+pub fn describe_rect(rect: &RECT) -> String {
+    format!(
+        "{}x{} at ({}, {})",
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        rect.left,
+        rect.top
+    )
+}
+
+/// Gets the bounding rect of all monitors
+pub fn virtual_screen_rect() -> RECT {
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        RECT {
+            left,
+            top,
+            right: left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            bottom: top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        }
+    }
+}
+
+/// Clamps a rect to the bounds of another, as Windows does with clip rects
+pub fn clamp_rect(rect: &RECT, bounds: &RECT) -> RECT {
+    RECT {
+        left: rect.left.clamp(bounds.left, bounds.right),
+        top: rect.top.clamp(bounds.top, bounds.bottom),
+        right: rect.right.clamp(bounds.left, bounds.right),
+        bottom: rect.bottom.clamp(bounds.top, bounds.bottom),
+    }
+}
+
+/// Looks up what the `[[monitors]]` entries say about the monitor at `rect`
+///
+/// `monitors` is the enumeration the entries' 1-based `index` refers to. The
+/// first entry whose given fields all match wins.
+pub fn monitor_policy(
+    entries: &[MonitorConfig],
+    monitors: &[MonitorBounds],
+    rect: &RECT,
+) -> MonitorLock {
+    let Some((index, monitor)) = monitors
+        .iter()
+        .enumerate()
+        .find(|(_, monitor)| rects_equal(&monitor.rect, rect))
+    else {
+        return MonitorLock::Auto;
+    };
+
+    entries
+        .iter()
+        .find(|entry| {
+            entry.index.is_none_or(|i| i == index + 1)
+                && entry
+                    .device
+                    .as_deref()
+                    .is_none_or(|device| device.eq_ignore_ascii_case(&monitor.device))
+                && entry.id.as_deref().is_none_or(|id| monitor.id_matches(id))
+                && entry
+                    .position
+                    .is_none_or(|[x, y]| x == monitor.rect.left && y == monitor.rect.top)
+        })
+        .map_or(MonitorLock::Auto, |entry| entry.lock)
+}
+
+/// The union of the monitors a window spans, when it exactly covers two or more
+/// of them (NVIDIA Surround / AMD Eyefinity style setups without driver-level
+/// spanning, or a borderless window stretched across monitors)
+///
+/// The monitors must tile the union without gaps, and every window edge must
+/// match the union within `tolerance`, so a maximized window overhanging its
+/// neighbor by the frame width does not count.
+pub fn spanned_rect(monitors: &[MonitorBounds], window: &RECT, tolerance: i32) -> Option<RECT> {
+    let area = |r: &RECT| (r.right - r.left) as i64 * (r.bottom - r.top) as i64;
+    // Runs on every check of a maximized window, so nothing is collected
+    let mut spanned = 0;
+    let mut union: Option<RECT> = None;
+    let mut tiles = 0i64;
+    for (i, monitor) in monitors.iter().enumerate() {
+        let r = monitor.rect;
+        let overlap_x = window.right.min(r.right) - window.left.max(r.left);
+        let overlap_y = window.bottom.min(r.bottom) - window.top.max(r.top);
+        // Mirrored monitors share a rect and count once
+        if overlap_x <= tolerance
+            || overlap_y <= tolerance
+            || monitors[..i].iter().any(|m| rects_equal(&m.rect, &r))
+        {
+            continue;
+        }
+        union = Some(union.map_or(r, |all| RECT {
+            left: all.left.min(r.left),
+            top: all.top.min(r.top),
+            right: all.right.max(r.right),
+            bottom: all.bottom.max(r.bottom),
+        }));
+        spanned += 1;
+        tiles += area(&r);
+    }
+    let union = union.filter(|_| spanned >= 2)?;
+    let tiled = tiles == area(&union);
+
+    let matches = (window.left - union.left).abs() <= tolerance
+        && (window.top - union.top).abs() <= tolerance
+        && (window.right - union.right).abs() <= tolerance
+        && (window.bottom - union.bottom).abs() <= tolerance;
+    (tiled && matches).then_some(union)
+}
+
+/// How far a maximized window reaches past its monitor with its invisible
+/// resize frame, at up to 200% scaling
+pub const FRAME_OVERHANG: i32 = 16;
+
+/// Whether a window reaches more than `margin` pixels both ways into a monitor
+/// other than its own, like a wide window on a narrow portrait monitor that
+/// sticks out onto the neighbor
+pub fn reaches_other_monitor(
+    monitors: &[MonitorBounds],
+    own: &RECT,
+    window: &RECT,
+    margin: i32,
+) -> bool {
+    monitors
+        .iter()
+        .filter(|monitor| !rects_equal(&monitor.rect, own))
+        .any(|monitor| {
+            let r = monitor.rect;
+            let overlap_x = window.right.min(r.right) - window.left.max(r.left);
+            let overlap_y = window.bottom.min(r.bottom) - window.top.max(r.top);
+            overlap_x > margin && overlap_y > margin
+        })
+}
+
+/// Finds the monitor containing a point, such as the cursor position
+pub fn monitor_at(monitors: &[MonitorBounds], pos: POINT) -> Option<&MonitorBounds> {
+    monitors.iter().find(|monitor| {
+        pos.x >= monitor.rect.left
+            && pos.x < monitor.rect.right
+            && pos.y >= monitor.rect.top
+            && pos.y < monitor.rect.bottom
+    })
+}
+
+/// Compare two RECTs for equality
+pub fn rects_equal(a: &RECT, b: &RECT) -> bool {
+    a.left == b.left && a.top == b.top && a.right == b.right && a.bottom == b.bottom
+}
+
+/// Whether `inner` lies entirely within `outer`
+pub fn rect_contains(outer: &RECT, inner: &RECT) -> bool {
+    inner.left >= outer.left
+        && inner.top >= outer.top
+        && inner.right <= outer.right
+        && inner.bottom <= outer.bottom
+}
+
+/// The union of the monitors `pair` names, when both are connected and `own`
+/// is one of them
+pub fn pair_rect(monitors: &[MonitorBounds], pair: &[String; 2], own: &RECT) -> Option<RECT> {
+    let find = |name: &str| {
+        monitors
+            .iter()
+            .find(|monitor| monitor.is_named(name))
+            .map(|monitor| monitor.rect)
+    };
+    let (a, b) = (find(&pair[0])?, find(&pair[1])?);
+    (rects_equal(&a, own) || rects_equal(&b, own)).then(|| RECT {
+        left: a.left.min(b.left),
+        top: a.top.min(b.top),
+        right: a.right.max(b.right),
+        bottom: a.bottom.max(b.bottom),
+    })
+}
+
+/// The share of `monitor` that `window` covers, from 0 to 1
+pub fn coverage(window: &RECT, monitor: &RECT) -> f64 {
+    let area = |width: i32, height: i32| width.max(0) as f64 * height.max(0) as f64;
+    let monitor_area = area(monitor.right - monitor.left, monitor.bottom - monitor.top);
+    if monitor_area == 0.0 {
+        return 0.0;
+    }
+    let covered = area(
+        window.right.min(monitor.right) - window.left.max(monitor.left),
+        window.bottom.min(monitor.bottom) - window.top.max(monitor.top),
+    );
+    covered / monitor_area
+}
+
+/// Whether `window` covers at least `min_coverage` of `own`, its monitor,
+/// without reaching into another monitor by more than `margin` pixels
+pub fn covers_share(
+    monitors: &[MonitorBounds],
+    own: &RECT,
+    window: &RECT,
+    min_coverage: f64,
+    margin: i32,
+) -> bool {
+    min_coverage > 0.0
+        && coverage(window, own) >= min_coverage
+        && !reaches_other_monitor(monitors, own, window, margin)
+}
+
+/// Whether `a` and `b` share any pixels
+pub fn rects_overlap(a: &RECT, b: &RECT) -> bool {
+    a.left < b.right && b.left < a.right && a.top < b.bottom && b.top < a.bottom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_a_monitor_for_reports() {
+        let rect = RECT {
+            left: 0,
+            top: 0,
+            right: 2560,
+            bottom: 1440,
+        };
+        let mut monitor = MonitorDetails {
+            index: 1,
+            rect,
+            work_area: RECT {
+                bottom: 1392,
+                ..rect
+            },
+            device: r"\\.\DISPLAY1".to_string(),
+            id: r"\\?\DISPLAY#DEL40F5#5&2b3c4d5&0&UID4353#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}"
+                .to_string(),
+            name: "DELL U2720Q".to_string(),
+            scale: 150,
+            refresh_hz: 144,
+            primary: true,
+        };
+        assert_eq!(
+            monitor.describe(),
+            r"Monitor 1: 2560x1440 at (0, 0), primary, 150% scale, 144 Hz, work area 2560x1392 at (0, 0), DELL U2720Q \\.\DISPLAY1 id DEL40F5"
+        );
+        assert!(monitor
+            .to_json()
+            .contains("\"work_area\":[0,0,2560,1392],\"scale_percent\":150,\"refresh_hz\":144,\"primary\":true}"));
+
+        monitor.work_area = rect;
+        monitor.primary = false;
+        monitor.scale = 0;
+        monitor.refresh_hz = 0;
+        monitor.id.clear();
+        assert_eq!(
+            monitor.describe(),
+            r"Monitor 1: 2560x1440 at (0, 0), DELL U2720Q \\.\DISPLAY1 id unknown"
+        );
+    }
+}
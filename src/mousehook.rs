@@ -0,0 +1,360 @@
+//! Low-level mouse hook confinement
+//!
+//! An alternative to `ClipCursor` for games that clear the clip every frame and
+//! win the fight against re-application. A `WH_MOUSE_LL` hook sees every mouse
+//! move before it is applied, swallows the ones leaving the rect and puts the
+//! cursor on the edge instead. The hook runs on the installing thread, which
+//! must keep pumping messages in [`control::wait`](crate::control::wait).
+//!
+//! With a resistance set, the edge is soft: pushing against it for that long
+//! lets the cursor through, and the wall comes back once it returns.
+//!
+//! Given a [`MouseWatch`], only moves of the watched mouse are held; other
+//! pointing devices (a tablet, a trackball for the second monitor) pass.
+//!
+//! The hook also swallows the buttons in `suppress_buttons`, such as thumb
+//! buttons that would send a browser on another monitor back a page.
+
+use std::cell::{Cell, RefCell};
+
+use windows::Win32::Foundation::{LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, SetCursorPos, SetWindowsHookExW, UnhookWindowsHookEx, HC_ACTION, HHOOK,
+    MSLLHOOKSTRUCT, WH_MOUSE_LL, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+    WM_MOUSEWHEEL, WM_XBUTTONDOWN, WM_XBUTTONUP, XBUTTON1, XBUTTON2,
+};
+
+use crate::config::MouseButton;
+use crate::rawinput::MouseWatch;
+
+/// A push against the edge stops counting after this long without movement
+const PUSH_GAP_MS: u32 = 100;
+
+#[derive(Debug, Clone, Copy)]
+struct Confinement {
+    rect: RECT,
+    /// Sustained push in milliseconds needed to cross the edge, `None` for a hard wall
+    resistance_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Push {
+    /// Event time of the first and the latest move against the edge
+    started: Option<(u32, u32)>,
+    /// The cursor broke through and is roaming outside the rect
+    crossed: bool,
+}
+
+/// The buttons being swallowed and the presses that were
+#[derive(Debug, Default)]
+struct Suppression {
+    buttons: Vec<MouseButton>,
+    /// Buttons whose press was swallowed, so their release is too
+    held: Vec<MouseButton>,
+}
+
+impl Suppression {
+    /// Whether a hook message is swallowed
+    ///
+    /// A release is swallowed only if its press was: one let through before
+    /// the lock engaged or the list changed has to reach the game, or it
+    /// thinks the button is still down.
+    fn swallows(&mut self, message: u32, mouse_data: u32) -> bool {
+        let Some(button) = button(message, mouse_data) else {
+            return false;
+        };
+        if matches!(message, WM_MBUTTONUP | WM_XBUTTONUP) {
+            let held = self.held.len();
+            self.held.retain(|&b| b != button);
+            return self.held.len() != held;
+        }
+        let blocked = self.buttons.contains(&button);
+        // The wheel has no release to wait for
+        if blocked && button != MouseButton::Wheel && !self.held.contains(&button) {
+            self.held.push(button);
+        }
+        blocked
+    }
+}
+
+thread_local! {
+    static CONFINE: Cell<Option<Confinement>> = const { Cell::new(None) };
+    static PUSH: Cell<Push> = const {
+        Cell::new(Push {
+            started: None,
+            crossed: false,
+        })
+    };
+    /// Whether a [`MouseWatch`] decides which moves are held
+    static FILTERED: Cell<bool> = const { Cell::new(false) };
+    static SUPPRESSED: RefCell<Suppression> = const {
+        RefCell::new(Suppression {
+            buttons: Vec::new(),
+            held: Vec::new(),
+        })
+    };
+}
+
+unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        let message = wparam.0 as u32;
+        // Neither moves nor buttons of another pointing device are held
+        let other_device = FILTERED.with(Cell::get) && !MouseWatch::last_move_matched();
+        let suppressed =
+            !other_device && SUPPRESSED.with(|s| s.borrow_mut().swallows(message, info.mouseData));
+        if suppressed {
+            return LRESULT(1);
+        }
+        let confine = CONFINE
+            .with(Cell::get)
+            .filter(|_| message == WM_MOUSEMOVE && !other_device);
+        if let Some(confine) = confine {
+            if !allow_move(&confine, info) {
+                // Right and bottom are exclusive, like with ClipCursor
+                let rect = confine.rect;
+                let x = info.pt.x.clamp(rect.left, rect.right - 1);
+                let y = info.pt.y.clamp(rect.top, rect.bottom - 1);
+                let _ = SetCursorPos(x, y);
+                return LRESULT(1);
+            }
+        }
+    }
+
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// The button (or wheel) a hook message is about
+fn button(message: u32, mouse_data: u32) -> Option<MouseButton> {
+    match message {
+        WM_MBUTTONDOWN | WM_MBUTTONUP => Some(MouseButton::Middle),
+        WM_XBUTTONDOWN | WM_XBUTTONUP => match (mouse_data >> 16) as u16 {
+            XBUTTON1 => Some(MouseButton::X1),
+            XBUTTON2 => Some(MouseButton::X2),
+            _ => None,
+        },
+        WM_MOUSEWHEEL | WM_MOUSEHWHEEL => Some(MouseButton::Wheel),
+        _ => None,
+    }
+}
+
+/// Decides whether a mouse move may go through, tracking pushes against a soft edge
+fn allow_move(confine: &Confinement, info: &MSLLHOOKSTRUCT) -> bool {
+    let rect = confine.rect;
+    let inside = info.pt.x >= rect.left
+        && info.pt.x < rect.right
+        && info.pt.y >= rect.top
+        && info.pt.y < rect.bottom;
+
+    let mut push = PUSH.with(Cell::get);
+    let allow = if inside {
+        push = Push::default();
+        true
+    } else if push.crossed {
+        true
+    } else {
+        match confine.resistance_ms {
+            None => false,
+            Some(resistance) => {
+                let now = info.time;
+                let first = match push.started {
+                    Some((first, last)) if now.wrapping_sub(last) <= PUSH_GAP_MS => first,
+                    _ => now,
+                };
+                push.started = Some((first, now));
+                push.crossed = now.wrapping_sub(first) >= resistance;
+                push.crossed
+            }
+        }
+    };
+
+    PUSH.with(|p| p.set(push));
+    allow
+}
+
+/// An installed confinement hook, removed on drop
+pub struct MouseHook {
+    hook: HHOOK,
+    watch: Option<MouseWatch>,
+}
+
+impl MouseHook {
+    /// Installs the hook on the current thread, confining the cursor to `rect`
+    ///
+    /// With `resistance_ms` the edge gives way after being pushed for that long,
+    /// and with `watch` only the mouse it watches is held.
+    pub fn install(
+        rect: &RECT,
+        resistance_ms: Option<u32>,
+        watch: Option<MouseWatch>,
+    ) -> Option<Self> {
+        set_confinement(rect, resistance_ms);
+        let hook = unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), None, 0).ok()? };
+        FILTERED.with(|f| f.set(watch.is_some()));
+        Some(Self { hook, watch })
+    }
+
+    /// The `[cursor] device` the hook holds, `None` if it holds every mouse
+    pub fn device(&self) -> Option<&str> {
+        self.watch.as_ref().map(MouseWatch::pattern)
+    }
+
+    /// Moves the confinement to a different rect or resistance
+    pub fn set_rect(&self, rect: &RECT, resistance_ms: Option<u32>) {
+        set_confinement(rect, resistance_ms);
+    }
+
+    /// Swallows `buttons` from now on, and no others, along with the release
+    /// of any button whose press was swallowed already
+    pub fn set_suppressed(&self, buttons: &[MouseButton]) {
+        SUPPRESSED.with(|s| buttons.clone_into(&mut s.borrow_mut().buttons));
+    }
+}
+
+impl Drop for MouseHook {
+    fn drop(&mut self) {
+        CONFINE.with(|c| c.set(None));
+        FILTERED.with(|f| f.set(false));
+        SUPPRESSED.with(|s| *s.borrow_mut() = Suppression::default());
+        unsafe {
+            let _ = UnhookWindowsHookEx(self.hook);
+        }
+    }
+}
+
+fn set_confinement(rect: &RECT, resistance_ms: Option<u32>) {
+    CONFINE.with(|c| {
+        c.set(Some(Confinement {
+            rect: *rect,
+            resistance_ms,
+        }))
+    });
+    PUSH.with(|p| p.set(Push::default()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::Foundation::POINT;
+
+    const SCREEN: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    fn moved(x: i32, y: i32, time: u32) -> MSLLHOOKSTRUCT {
+        MSLLHOOKSTRUCT {
+            pt: POINT { x, y },
+            time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_hard_wall_holds_every_move_leaving_the_rect() {
+        let confine = Confinement {
+            rect: SCREEN,
+            resistance_ms: None,
+        };
+        assert!(allow_move(&confine, &moved(0, 0, 0)));
+        assert!(allow_move(&confine, &moved(1919, 1079, 10)));
+        // Right and bottom are outside
+        assert!(!allow_move(&confine, &moved(1920, 500, 20)));
+        assert!(!allow_move(&confine, &moved(500, 1080, 30)));
+        for time in (40..2000).step_by(10) {
+            assert!(!allow_move(&confine, &moved(-1, 500, time)));
+        }
+    }
+
+    const SOFT: Confinement = Confinement {
+        rect: SCREEN,
+        resistance_ms: Some(300),
+    };
+
+    /// Pushes against the left edge every 50ms from `from` until `to`,
+    /// returning the time of the first move let through
+    fn push(from: u32, to: u32) -> Option<u32> {
+        (0..)
+            .map(|step| from.wrapping_add(step * 50))
+            .take_while(|&time| time.wrapping_sub(from) <= to.wrapping_sub(from))
+            .find(|&time| allow_move(&SOFT, &moved(-1, 500, time)))
+    }
+
+    #[test]
+    fn a_soft_edge_gives_way_after_a_sustained_push() {
+        assert_eq!(push(1000, 1250), None);
+        assert_eq!(push(1300, 1500), Some(1300));
+        // Once through, the cursor roams freely outside
+        assert!(allow_move(&SOFT, &moved(-500, 200, 1550)));
+        assert!(allow_move(&SOFT, &moved(-900, 900, 5000)));
+    }
+
+    #[test]
+    fn a_pause_in_the_push_starts_it_over() {
+        assert_eq!(push(1000, 1200), None);
+        // More than PUSH_GAP_MS without a move against the edge
+        let resumed = 1200 + PUSH_GAP_MS + 50;
+        assert_eq!(push(resumed, resumed + 250), None);
+        assert_eq!(push(resumed + 300, resumed + 300), Some(resumed + 300));
+    }
+
+    #[test]
+    fn coming_back_inside_puts_the_wall_back() {
+        assert_eq!(push(1000, 1300), Some(1300));
+        assert!(allow_move(&SOFT, &moved(100, 500, 1350)));
+        // The next push starts from zero
+        assert_eq!(push(1400, 1650), None);
+        assert_eq!(push(1700, 1700), Some(1700));
+    }
+
+    #[test]
+    fn the_push_timer_survives_the_tick_count_wrapping() {
+        let start = u32::MAX - 120;
+        assert_eq!(push(start, start.wrapping_add(250)), None);
+        assert_eq!(
+            push(start.wrapping_add(300), start.wrapping_add(300)),
+            Some(start.wrapping_add(300))
+        );
+    }
+
+    #[test]
+    fn tells_thumb_buttons_apart() {
+        assert_eq!(
+            button(WM_XBUTTONDOWN, (XBUTTON1 as u32) << 16),
+            Some(MouseButton::X1)
+        );
+        assert_eq!(
+            button(WM_XBUTTONUP, (XBUTTON2 as u32) << 16),
+            Some(MouseButton::X2)
+        );
+        assert_eq!(button(WM_MOUSEMOVE, 0), None);
+    }
+
+    #[test]
+    fn swallows_listed_buttons_with_their_release() {
+        let mut suppression = Suppression {
+            buttons: vec![MouseButton::Middle, MouseButton::X1, MouseButton::Wheel],
+            held: Vec::new(),
+        };
+        let (x1, x2) = ((XBUTTON1 as u32) << 16, (XBUTTON2 as u32) << 16);
+
+        assert!(suppression.swallows(WM_XBUTTONDOWN, x1));
+        assert!(suppression.swallows(WM_XBUTTONUP, x1));
+        assert!(!suppression.swallows(WM_XBUTTONDOWN, x2));
+        assert!(!suppression.swallows(WM_XBUTTONUP, x2));
+        assert!(suppression.swallows(WM_MOUSEWHEEL, 0));
+        assert!(!suppression.swallows(WM_MOUSEMOVE, 0));
+
+        // Pressed before the lock, so the game has to see it go up
+        assert!(!suppression.swallows(WM_MBUTTONUP, 0));
+
+        // Released after the list changed, the button still belongs to the
+        // swallowed press
+        assert!(suppression.swallows(WM_MBUTTONDOWN, 0));
+        suppression.buttons.clear();
+        assert!(suppression.swallows(WM_MBUTTONUP, 0));
+        assert!(!suppression.swallows(WM_MBUTTONUP, 0));
+    }
+}
@@ -0,0 +1,248 @@
+//! MQTT lock state publishing
+//!
+//! Publishes a retained JSON message such as
+//! `{"state":"locked","process":"game.exe","monitor":"\\\\.\\DISPLAY1","rect":[0,0,1920,1080]}`
+//! to a topic whenever the cursor gets locked or released, for smart-home setups
+//! that switch to a "gaming" scene. A minimal MQTT 3.1.1 client (QoS 0 only)
+//! runs on a background thread and reconnects with backoff when the broker
+//! goes away, re-publishing the current state. The broker publishes
+//! `{"state":"offline"}` as the last will if SmartLockCursor disappears.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::commands::LockDetails;
+use crate::config::MqttConfig;
+use crate::json;
+use crate::{info, tr, warn};
+
+const OFFLINE: &str = "{\"state\":\"offline\"}";
+const UNLOCKED: &str = "{\"state\":\"unlocked\"}";
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A running state publisher
+#[derive(Debug)]
+pub struct MqttPublisher {
+    updates: Sender<String>,
+}
+
+impl MqttPublisher {
+    /// Starts the client thread for `[mqtt]`, or returns `Ok(None)` if no
+    /// broker is configured
+    pub fn start(config: &MqttConfig) -> Result<Option<Self>, String> {
+        if config.host.is_none() {
+            return Ok(None);
+        }
+        let (updates, pending) = mpsc::channel();
+        let settings = config.clone();
+        thread::Builder::new()
+            .name("mqtt".to_string())
+            .spawn(move || run(settings, pending))
+            .map_err(|e| e.to_string())?;
+        Ok(Some(Self { updates }))
+    }
+
+    /// Publishes the lock state; `details` is `None` once the cursor is free
+    pub fn publish_state(&self, details: Option<&LockDetails>) {
+        let _ = self.updates.send(state_payload(details));
+    }
+}
+
+/// The retained state message
+fn state_payload(details: Option<&LockDetails>) -> String {
+    let Some(details) = details else {
+        return UNLOCKED.to_string();
+    };
+    let rect = details.rect;
+    format!(
+        "{{\"state\":\"locked\",\"process\":{},\"monitor\":{},\"rect\":[{},{},{},{}]}}",
+        json::string(&details.process),
+        json::string(&details.monitor),
+        rect.left,
+        rect.top,
+        rect.right,
+        rect.bottom
+    )
+}
+
+/// Keeps a connection up and publishes every update until the publisher is dropped
+fn run(config: MqttConfig, updates: Receiver<String>) {
+    let mut state = UNLOCKED.to_string();
+    let mut backoff = Duration::from_secs(1);
+    let keepalive = Duration::from_secs(config.keepalive_s.clamp(5, 3600));
+
+    loop {
+        let mut stream = match connect(&config) {
+            Ok(stream) => {
+                info!("{}", tr!("mqtt.connected"));
+                backoff = Duration::from_secs(1);
+                stream
+            }
+            Err(e) => {
+                warn!("{}", tr!("mqtt.connect_failed", backoff.as_secs(), e));
+                // Keep the latest state for when the broker is back
+                let deadline = Instant::now() + backoff;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match updates.recv_timeout(left) {
+                        Ok(update) => state = update,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let mut result = publish(&mut stream, &config, &state);
+        while result.is_ok() {
+            result = match updates.recv_timeout(keepalive / 2) {
+                Ok(update) => {
+                    state = update;
+                    publish(&mut stream, &config, &state)
+                }
+                Err(RecvTimeoutError::Timeout) => ping(&mut stream),
+                Err(RecvTimeoutError::Disconnected) => {
+                    // Shutting down cleanly, which suppresses the will
+                    let _ = publish(&mut stream, &config, OFFLINE);
+                    let _ = stream.write_all(&[0xE0, 0x00]);
+                    return;
+                }
+            };
+        }
+        if let Err(e) = result {
+            warn!("{}", tr!("mqtt.connection_lost", e));
+        }
+    }
+}
+
+fn connect(config: &MqttConfig) -> io::Result<TcpStream> {
+    let host = config.host.as_deref().unwrap_or_default();
+    let addr = (host, config.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host did not resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+    stream.write_all(&connect_packet(config))?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack)?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(io::Error::other(format!(
+            "broker refused the connection (code {})",
+            connack[3]
+        )));
+    }
+    Ok(stream)
+}
+
+fn publish(stream: &mut TcpStream, config: &MqttConfig, payload: &str) -> io::Result<()> {
+    stream.write_all(&publish_packet(&config.topic, payload, config.retain))
+}
+
+/// Sends PINGREQ and waits for PINGRESP, which also proves the broker is alive
+fn ping(stream: &mut TcpStream) -> io::Result<()> {
+    stream.write_all(&[0xC0, 0x00])?;
+    let mut response = [0u8; 2];
+    stream.read_exact(&mut response)?;
+    if response != [0xD0, 0x00] {
+        return Err(io::Error::other("unexpected reply to a ping"));
+    }
+    Ok(())
+}
+
+fn connect_packet(config: &MqttConfig) -> Vec<u8> {
+    // Clean session, with a retained QoS 0 will
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+
+    let mut body = Vec::new();
+    put_string(&mut body, "MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&(config.keepalive_s.clamp(5, 3600) as u16).to_be_bytes());
+    put_string(&mut body, &config.client_id);
+    put_string(&mut body, &config.topic);
+    put_string(&mut body, OFFLINE);
+    if let Some(ref username) = config.username {
+        put_string(&mut body, username);
+    }
+    if let Some(ref password) = config.password {
+        put_string(&mut body, password);
+    }
+    packet(0x10, body)
+}
+
+fn publish_packet(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_string(&mut body, topic);
+    body.extend_from_slice(payload.as_bytes());
+    packet(0x30 | u8::from(retain), body)
+}
+
+/// Prefixes a packet body with its fixed header
+fn packet(kind: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut out = vec![kind];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend(body);
+    out
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_remaining_length() {
+        assert_eq!(packet(0x30, vec![0; 3])[..2], [0x30, 3]);
+        assert_eq!(packet(0x30, vec![0; 200])[..3], [0x30, 0xC8, 0x01]);
+    }
+
+    #[test]
+    fn builds_retained_publish() {
+        assert_eq!(
+            publish_packet("a/b", "{}", true),
+            [0x31, 7, 0, 3, b'a', b'/', b'b', b'{', b'}']
+        );
+    }
+
+    #[test]
+    fn connect_sets_credential_flags() {
+        let config = MqttConfig {
+            host: Some("broker".to_string()),
+            username: Some("user".to_string()),
+            ..Default::default()
+        };
+        let connect = connect_packet(&config);
+        // Fixed header, remaining length, "MQTT", level, then the flags
+        assert_eq!(connect[0], 0x10);
+        assert_eq!(connect[8], 4);
+        assert_eq!(connect[9], 0x80 | 0x20 | 0x04 | 0x02);
+    }
+}
@@ -0,0 +1,421 @@
+//! obs-websocket lock state broadcasting
+//!
+//! Connects to the obs-websocket server built into OBS Studio 28 and later and
+//! broadcasts a `CustomEvent` whenever the cursor gets locked or released, with
+//! event data such as
+//! `{"source":"smartlockcursor","state":"locked","process":"game.exe","monitor":"\\\\.\\DISPLAY1","rect":[0,0,1920,1080]}`,
+//! so scene switchers and other websocket clients can react. A minimal
+//! WebSocket client (text frames only, no extensions) runs on a background
+//! thread and reconnects with backoff while OBS is closed, re-sending the
+//! current state.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::commands::LockDetails;
+use crate::config::ObsConfig;
+use crate::json;
+use crate::update::sha256;
+use crate::{info, tr, warn};
+
+const UNLOCKED: &str = "{\"source\":\"smartlockcursor\",\"state\":\"unlocked\"}";
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long the connection may stay quiet before it is pinged
+const KEEPALIVE: Duration = Duration::from_secs(30);
+/// Largest frame accepted from OBS
+const MAX_FRAME: u64 = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// A running state broadcaster
+#[derive(Debug)]
+pub struct ObsBroadcaster {
+    updates: Sender<String>,
+}
+
+impl ObsBroadcaster {
+    /// Starts the client thread, or returns `Ok(None)` if `[obs] websocket` is off
+    pub fn start(config: &ObsConfig) -> Result<Option<Self>, String> {
+        if !config.websocket {
+            return Ok(None);
+        }
+        let (updates, pending) = mpsc::channel();
+        let settings = config.clone();
+        thread::Builder::new()
+            .name("obs".to_string())
+            .spawn(move || run(settings, pending))
+            .map_err(|e| e.to_string())?;
+        Ok(Some(Self { updates }))
+    }
+
+    /// Broadcasts the lock state; `details` is `None` once the cursor is free
+    pub fn publish_state(&self, details: Option<&LockDetails>) {
+        let _ = self.updates.send(event_data(details));
+    }
+}
+
+/// The `eventData` of the custom event
+fn event_data(details: Option<&LockDetails>) -> String {
+    let Some(details) = details else {
+        return UNLOCKED.to_string();
+    };
+    let rect = details.rect;
+    format!(
+        "{{\"source\":\"smartlockcursor\",\"state\":\"locked\",\"process\":{},\"monitor\":{},\"rect\":[{},{},{},{}]}}",
+        json::string(&details.process),
+        json::string(&details.monitor),
+        rect.left,
+        rect.top,
+        rect.right,
+        rect.bottom
+    )
+}
+
+/// Keeps a connection up and broadcasts every update until the broadcaster is dropped
+fn run(config: ObsConfig, updates: Receiver<String>) {
+    let mut state = UNLOCKED.to_string();
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        let mut connection = match Connection::open(&config) {
+            Ok(connection) => {
+                info!("{}", tr!("obs.connected"));
+                backoff = Duration::from_secs(1);
+                connection
+            }
+            Err(e) => {
+                // OBS not running is the usual case, so only say so once
+                if backoff == Duration::from_secs(1) {
+                    warn!("{}", tr!("obs.connect_failed", e));
+                }
+                let deadline = Instant::now() + backoff;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match updates.recv_timeout(left) {
+                        Ok(update) => state = update,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let mut result = connection.broadcast(&state);
+        while result.is_ok() {
+            result = match updates.recv_timeout(KEEPALIVE) {
+                Ok(update) => {
+                    state = update;
+                    connection.broadcast(&state)
+                }
+                Err(RecvTimeoutError::Timeout) => connection.ping(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = write_frame(&mut connection.stream, OP_CLOSE, &1000u16.to_be_bytes());
+                    return;
+                }
+            };
+        }
+        if let Err(e) = result {
+            warn!("{}", tr!("obs.connection_lost", e));
+        }
+    }
+}
+
+/// An identified obs-websocket session
+struct Connection {
+    stream: TcpStream,
+    next_request: u64,
+}
+
+impl Connection {
+    fn open(config: &ObsConfig) -> io::Result<Self> {
+        let addr = (config.host.as_str(), config.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host did not resolve"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+        handshake(&mut stream, &config.host, config.port)?;
+
+        // Hello, then Identify, then Identified
+        let hello = read_message(&mut stream)?;
+        expect_op(&hello, 0)?;
+        let authentication = match hello.get("d").and_then(|d| d.get("authentication")) {
+            Some(auth) => {
+                let password = config.password.as_deref().ok_or_else(|| {
+                    io::Error::other("OBS asks for a password, set [obs] password")
+                })?;
+                let field = |name| auth.get(name).and_then(json::Value::as_str).unwrap_or("");
+                let answer = authentication(password, field("salt"), field("challenge"))
+                    .map_err(io::Error::other)?;
+                format!(",\"authentication\":{}", json::string(&answer))
+            }
+            None => String::new(),
+        };
+        let identify = format!(
+            "{{\"op\":1,\"d\":{{\"rpcVersion\":1{},\"eventSubscriptions\":0}}}}",
+            authentication
+        );
+        write_frame(&mut stream, OP_TEXT, identify.as_bytes())?;
+        expect_op(&read_message(&mut stream)?, 2)?;
+
+        Ok(Self {
+            stream,
+            next_request: 1,
+        })
+    }
+
+    /// Sends a `BroadcastCustomEvent` request and waits for its response
+    fn broadcast(&mut self, event_data: &str) -> io::Result<()> {
+        let request = format!(
+            "{{\"op\":6,\"d\":{{\"requestType\":\"BroadcastCustomEvent\",\"requestId\":\"{}\",\"requestData\":{{\"eventData\":{}}}}}}}",
+            self.next_request, event_data
+        );
+        self.next_request += 1;
+        write_frame(&mut self.stream, OP_TEXT, request.as_bytes())?;
+        expect_op(&read_message(&mut self.stream)?, 7)
+    }
+
+    /// Pings OBS and waits for the pong, which also proves it is alive
+    fn ping(&mut self) -> io::Result<()> {
+        write_frame(&mut self.stream, OP_PING, b"")?;
+        read_until(&mut self.stream, OP_PONG).map(drop)
+    }
+}
+
+fn expect_op(message: &json::Value, op: u8) -> io::Result<()> {
+    match message.get("op") {
+        Some(json::Value::Number(n)) if *n == f64::from(op) => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "unexpected message from OBS, wanted op {}",
+            op
+        ))),
+    }
+}
+
+/// The answer to an authentication challenge, as the obs-websocket protocol
+/// specifies it
+fn authentication(password: &str, salt: &str, challenge: &str) -> Result<String, String> {
+    let secret = base64(&sha256(format!("{}{}", password, salt).as_bytes())?);
+    Ok(base64(&sha256(
+        format!("{}{}", secret, challenge).as_bytes(),
+    )?))
+}
+
+/// Upgrades the connection to a WebSocket speaking the obs-websocket JSON protocol
+fn handshake(stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+    let key = base64(&random_bytes::<16>());
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Protocol: obswebsocket.json\r\n\r\n",
+        host, port, key
+    )?;
+
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > 8192 {
+            return Err(io::Error::other("oversized handshake response"));
+        }
+        stream.read_exact(&mut byte)?;
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(io::Error::other(format!(
+            "not an obs-websocket server ({})",
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the next text message and parses it
+fn read_message(stream: &mut TcpStream) -> io::Result<json::Value> {
+    let payload = read_until(stream, OP_TEXT)?;
+    json::parse(&String::from_utf8_lossy(&payload)).map_err(io::Error::other)
+}
+
+/// Reads frames until one with `opcode` arrives, answering pings on the way
+fn read_until(stream: &mut TcpStream, opcode: u8) -> io::Result<Vec<u8>> {
+    loop {
+        let (fin, op, mut payload) = read_frame(stream)?;
+        match op {
+            OP_PING => write_frame(stream, OP_PONG, &payload)?,
+            OP_CLOSE => {
+                let code = payload
+                    .get(..2)
+                    .map_or(0, |code| u16::from_be_bytes([code[0], code[1]]));
+                return Err(io::Error::other(format!(
+                    "OBS closed the connection (code {})",
+                    code
+                )));
+            }
+            op if op == opcode => {
+                let mut fin = fin;
+                while !fin {
+                    let (last, op, more) = read_frame(stream)?;
+                    if op != OP_CONTINUATION {
+                        return Err(io::Error::other("interleaved fragmented message"));
+                    }
+                    payload.extend(more);
+                    fin = last;
+                }
+                return Ok(payload);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads one frame as (FIN flag, opcode, payload)
+fn read_frame(stream: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if len > MAX_FRAME {
+        return Err(io::Error::other("oversized frame"));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    if masked {
+        apply_mask(&mut payload, mask);
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Writes one unfragmented frame, masked as clients must
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&frame(opcode, payload, random_bytes()))
+}
+
+fn frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut out = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => out.push(0x80 | len as u8),
+        len @ 126..=0xFFFF => {
+            out.push(0x80 | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(0x80 | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(&mask);
+    let start = out.len();
+    out.extend_from_slice(payload);
+    apply_mask(&mut out[start..], mask);
+    out
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// Unpredictable enough for WebSocket keys and masks, which only have to keep
+/// proxies from caching or misreading frames
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_nanos() as u64)
+        ^ u64::from(std::process::id()) << 32;
+    std::array::from_fn(|_| {
+        // xorshift64
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as u8
+    })
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn answers_the_documented_challenge() {
+        assert_eq!(
+            authentication(
+                "supersecretpassword",
+                "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=",
+                "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY="
+            )
+            .unwrap(),
+            "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+        );
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let payload = vec![b'x'; 300];
+        let encoded = frame(OP_TEXT, &payload, [1, 2, 3, 4]);
+        assert_eq!(encoded[..4], [0x81, 0x80 | 126, 0x01, 0x2C]);
+        let (fin, opcode, decoded) = read_frame(&mut encoded.as_slice()).unwrap();
+        assert!(fin);
+        assert_eq!(opcode, OP_TEXT);
+        assert_eq!(decoded, payload);
+    }
+}
@@ -0,0 +1,87 @@
+//! Engine state kept across restarts
+//!
+//! Pausing, the profile picked and the games moved to the mouse hook are
+//! written to `state.toml` in [`Config::default_dir`] whenever they change, and
+//! read back at launch, so an instance started with Windows carries on where
+//! the last one left off instead of reverting to the defaults. Learned rules
+//! need nothing extra, the learning prompt writes them to the config file.
+//! `[startup] restore_state = false` starts fresh every time.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Where the engine state is kept
+pub fn state_path() -> Option<PathBuf> {
+    Config::default_dir().map(|dir| dir.join("state.toml"))
+}
+
+/// What a restart brings back
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    /// Locking was paused over stdio
+    pub paused: bool,
+    /// Name of the active `[[profiles]]` entry
+    pub profile: Option<String>,
+    /// Processes that fought the clip and were moved to the mouse hook
+    pub escalated: Vec<String>,
+}
+
+impl SavedState {
+    /// Reads the state from the text of a state file, `None` if it isn't one
+    pub fn parse(text: &str) -> Option<Self> {
+        toml::from_str(text).ok()
+    }
+
+    /// The state as the text of a state file
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(self).unwrap_or_default();
+        format!("# Written by SmartLockCursor, restored at launch\n{}", body)
+    }
+
+    /// Reads a state file; a missing or damaged one gives `None`
+    pub fn load(path: &Path) -> Option<Self> {
+        Self::parse(&fs::read_to_string(path).ok()?)
+    }
+
+    /// Writes a state file, replacing the old one only once the new one is
+    /// complete, so shutting down mid-write doesn't lose it
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension("toml.tmp");
+        fs::write(&partial, self.to_toml())?;
+        fs::rename(&partial, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_survives_a_round_trip() {
+        let state = SavedState {
+            paused: true,
+            profile: Some("Streaming".to_string()),
+            escalated: vec!["game.exe".to_string()],
+        };
+        assert_eq!(SavedState::parse(&state.to_toml()), Some(state));
+        assert_eq!(
+            SavedState::parse(&SavedState::default().to_toml()),
+            Some(SavedState::default())
+        );
+        // Written by a newer version, keeping what this one knows
+        assert_eq!(
+            SavedState::parse("paused = true\nmonitor = 2\n").map(|state| state.paused),
+            Some(true)
+        );
+        assert_eq!(SavedState::parse("paused = \"yes\""), None);
+    }
+}
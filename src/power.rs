@@ -0,0 +1,50 @@
+//! Power source queries and thread power settings
+
+use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+use windows::Win32::System::Threading::{
+    GetCurrentThread, SetThreadInformation, SetThreadPriority, ThreadPowerThrottling,
+    THREAD_POWER_THROTTLING_CURRENT_VERSION, THREAD_POWER_THROTTLING_EXECUTION_SPEED,
+    THREAD_POWER_THROTTLING_STATE, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_NORMAL,
+};
+
+/// Checks if the system currently runs on battery
+///
+/// Desktops without a battery and systems that don't report their power
+/// source count as plugged in.
+pub fn is_on_battery() -> bool {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.is_ok() && status.ACLineStatus == 0
+}
+
+/// Puts the current thread into (or out of) efficiency mode: EcoQoS, which lets
+/// Windows run it on efficiency cores at low clocks, plus below-normal priority
+/// so it never competes with the game for CPU
+pub fn set_efficiency_mode(enabled: bool) -> bool {
+    let state = THREAD_POWER_THROTTLING_STATE {
+        Version: THREAD_POWER_THROTTLING_CURRENT_VERSION,
+        ControlMask: THREAD_POWER_THROTTLING_EXECUTION_SPEED,
+        // Leaving the bit clear while controlling it opts out explicitly
+        StateMask: if enabled {
+            THREAD_POWER_THROTTLING_EXECUTION_SPEED
+        } else {
+            0
+        },
+    };
+    let priority = if enabled {
+        THREAD_PRIORITY_BELOW_NORMAL
+    } else {
+        THREAD_PRIORITY_NORMAL
+    };
+
+    unsafe {
+        let thread = GetCurrentThread();
+        let throttled = SetThreadInformation(
+            thread,
+            ThreadPowerThrottling,
+            (&state as *const THREAD_POWER_THROTTLING_STATE).cast(),
+            std::mem::size_of::<THREAD_POWER_THROTTLING_STATE>() as u32,
+        )
+        .is_ok();
+        SetThreadPriority(thread, priority).is_ok() && throttled
+    }
+}
@@ -0,0 +1,301 @@
+//! Process queries
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use windows::core::{HSTRING, PWSTR};
+use windows::Win32::Foundation::{CloseHandle, FILETIME, WAIT_TIMEOUT};
+use windows::Win32::Storage::FileSystem::{
+    GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW,
+};
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, Process32FirstW, Process32NextW,
+    MODULEENTRY32W, PROCESSENTRY32W, TH32CS_SNAPMODULE, TH32CS_SNAPMODULE32, TH32CS_SNAPPROCESS,
+};
+use windows::Win32::System::Threading::{
+    GetExitCodeProcess, GetProcessTimes, OpenProcess, QueryFullProcessImageNameW,
+    WaitForSingleObject, INFINITE, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    PROCESS_SYNCHRONIZE,
+};
+
+/// Checks if a process with the given ID is still running
+pub fn is_process_running(pid: u32) -> bool {
+    unsafe {
+        let Ok(handle) = OpenProcess(PROCESS_SYNCHRONIZE, false, pid) else {
+            return false;
+        };
+
+        let running = WaitForSingleObject(handle, 0) == WAIT_TIMEOUT;
+        let _ = CloseHandle(handle);
+        running
+    }
+}
+
+/// Blocks until a process exits and returns its exit code, or `None` if the
+/// process could not be opened
+pub fn wait_for_exit(pid: u32) -> Option<u32> {
+    unsafe {
+        let handle = OpenProcess(
+            PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION,
+            false,
+            pid,
+        )
+        .ok()?;
+
+        WaitForSingleObject(handle, INFINITE);
+        let mut code = 0;
+        let result = GetExitCodeProcess(handle, &mut code);
+        let _ = CloseHandle(handle);
+        result.ok()?;
+        Some(code)
+    }
+}
+
+/// Gets the full path of a process's executable
+pub fn get_process_path(pid: u32) -> Option<PathBuf> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+        let mut buffer = [0u16; 1024];
+        let mut len = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(handle);
+        result.ok()?;
+
+        Some(PathBuf::from(String::from_utf16_lossy(
+            &buffer[..len as usize],
+        )))
+    }
+}
+
+/// Gets the executable file name of a process (e.g. `game.exe`)
+pub fn get_process_name(pid: u32) -> Option<String> {
+    get_process_path(pid)?
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Gets the product name from an executable's version info (e.g. `ELDEN RING`),
+/// which tells apart games that all ship a `game.exe`
+pub fn get_product_name(path: &Path) -> Option<String> {
+    let file = HSTRING::from(path.as_os_str());
+    unsafe {
+        let size = GetFileVersionInfoSizeW(&file, None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(&file, 0, size, data.as_mut_ptr().cast()).ok()?;
+
+        let query = |block: &str| {
+            let mut value = std::ptr::null_mut();
+            let mut len = 0u32;
+            VerQueryValueW(
+                data.as_ptr().cast(),
+                &HSTRING::from(block),
+                &mut value,
+                &mut len,
+            )
+            .as_bool()
+            .then_some((value, len))
+            .filter(|&(value, len)| !value.is_null() && len > 0)
+        };
+        // The strings live under the first language and code page listed,
+        // US English in Unicode if the file lists none
+        let (lang, codepage) = match query("\\VarFileInfo\\Translation") {
+            Some((value, len)) if len >= 4 => {
+                let pair = value as *const u16;
+                (*pair, *pair.add(1))
+            }
+            _ => (0x0409, 0x04B0),
+        };
+        let (value, len) = query(&format!(
+            "\\StringFileInfo\\{:04x}{:04x}\\ProductName",
+            lang, codepage
+        ))?;
+        // The length counts UTF-16 units, including the terminator
+        let name = std::slice::from_raw_parts(value as *const u16, len as usize);
+        let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let name = String::from_utf16_lossy(&name[..end]).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// Gets the creation time of a process as a `FILETIME` value, `None` if the
+/// process can't be opened
+fn get_process_creation_time(pid: u32) -> Option<u64> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut created = FILETIME::default();
+        let mut exited = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        let result = GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user);
+        let _ = CloseHandle(handle);
+        result.ok()?;
+        Some((u64::from(created.dwHighDateTime) << 32) | u64::from(created.dwLowDateTime))
+    }
+}
+
+/// Gets the executable names of a process's parent, grandparent and so on,
+/// closest first
+///
+/// Parents that already exited end the chain, and so does a parent started
+/// after its child, whose ID was reused by an unrelated process.
+pub fn get_ancestor_names(pid: u32) -> Vec<String> {
+    ancestor_names(&snapshot(), get_process_creation_time, pid)
+}
+
+/// Walks the parent chain of `pid` through `processes`, stopping at a parent
+/// that `created` says started after its child
+fn ancestor_names(
+    processes: &HashMap<u32, (u32, String)>,
+    created: impl Fn(u32) -> Option<u64>,
+    pid: u32,
+) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut current = pid;
+    let mut current_created = created(pid);
+    // The depth limit also guards against cycles through reused IDs
+    while names.len() < 16 {
+        let Some(&(parent, _)) = processes.get(&current) else {
+            break;
+        };
+        let Some((_, name)) = processes.get(&parent) else {
+            break;
+        };
+        if parent == 0 || parent == current {
+            break;
+        }
+        // Times that can't be read (e.g. of protected processes) don't end it
+        let parent_created = created(parent);
+        if let (Some(parent_created), Some(child_created)) = (parent_created, current_created) {
+            if parent_created > child_created {
+                break;
+            }
+        }
+        names.push(name.clone());
+        current = parent;
+        current_created = parent_created;
+    }
+    names
+}
+
+/// Finds a running process whose executable is one of `names` (compared
+/// ignoring case) and returns its name as listed
+pub fn find_running_process(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        return None;
+    }
+    snapshot()
+        .into_values()
+        .map(|(_, name)| name)
+        .find(|name| names.iter().any(|wanted| wanted.eq_ignore_ascii_case(name)))
+}
+
+/// Checks if a process has loaded a module (DLL) named one of `names`,
+/// compared ignoring case
+pub fn has_module(pid: u32, names: &[String]) -> bool {
+    if names.is_empty() || pid == 0 {
+        return false;
+    }
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, pid)
+        else {
+            return false;
+        };
+
+        let mut entry = MODULEENTRY32W {
+            dwSize: std::mem::size_of::<MODULEENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut found = false;
+        let mut more = Module32FirstW(snapshot, &mut entry).is_ok();
+        while more && !found {
+            let len = entry
+                .szModule
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(entry.szModule.len());
+            let name = String::from_utf16_lossy(&entry.szModule[..len]);
+            found = names
+                .iter()
+                .any(|wanted| wanted.eq_ignore_ascii_case(&name));
+            more = Module32NextW(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+        found
+    }
+}
+
+/// All running processes: pid -> (parent pid, exe name)
+fn snapshot() -> HashMap<u32, (u32, String)> {
+    let mut processes = HashMap::new();
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return processes;
+        };
+
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+        while more {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            processes.insert(
+                entry.th32ProcessID,
+                (
+                    entry.th32ParentProcessID,
+                    String::from_utf16_lossy(&entry.szExeFile[..len]),
+                ),
+            );
+            more = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    processes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_ancestor_chain_ends_at_a_reused_parent_id() {
+        // game (30) <- steam (20) <- explorer (10); 20 is also a reused ID below
+        let processes = HashMap::from([
+            (10, (0, "explorer.exe".to_string())),
+            (20, (10, "steam.exe".to_string())),
+            (30, (20, "game.exe".to_string())),
+            (40, (20, "tool.exe".to_string())),
+        ]);
+        // 40's real parent exited before steam.exe got its ID
+        let created = |pid: u32| match pid {
+            10 => Some(100),
+            20 => Some(200),
+            30 => Some(300),
+            40 => Some(150),
+            _ => None,
+        };
+        assert_eq!(
+            ancestor_names(&processes, created, 30),
+            ["steam.exe", "explorer.exe"]
+        );
+        assert!(ancestor_names(&processes, created, 40).is_empty());
+        // Unknown times keep the chain going
+        assert_eq!(
+            ancestor_names(&processes, |_| None, 40),
+            ["steam.exe", "explorer.exe"]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! "Lock this app in the future?" prompt
+//!
+//! After an app that no rule mentions was locked, SmartLockCursor can ask
+//! whether to always or never lock it and append the answer to the config file
+//! as a `[[rules]]` entry. The config watcher then picks the new rule up like
+//! any other edit.
+
+use std::path::PathBuf;
+use std::thread;
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::{
+    MessageBoxW, IDNO, IDYES, MB_ICONQUESTION, MB_YESNOCANCEL,
+};
+
+use crate::config::{append_rule, RuleAction};
+use crate::{info, tr, warn};
+
+/// Asks about `process` from a background thread, so the main loop keeps
+/// running while the message box is open
+///
+/// Yes adds a `lock` rule, No an `ignore` rule, and Cancel leaves the config
+/// alone so the question comes up again next time.
+pub fn ask_about(process: String, config_path: PathBuf) {
+    let spawned = thread::Builder::new()
+        .name("rule-prompt".to_string())
+        .spawn(move || {
+            let text = tr!("prompt.text", process);
+            let answer = unsafe {
+                MessageBoxW(
+                    HWND::default(),
+                    &HSTRING::from(text),
+                    &HSTRING::from(tr!("prompt.title")),
+                    MB_YESNOCANCEL | MB_ICONQUESTION,
+                )
+            };
+            let action = match answer {
+                IDYES => RuleAction::Lock,
+                IDNO => RuleAction::Ignore,
+                _ => return,
+            };
+
+            match append_rule(&config_path, &process, action) {
+                Ok(()) => {
+                    let key = match action {
+                        RuleAction::Lock | RuleAction::ConfineTo => "prompt.added_lock",
+                        RuleAction::Ignore => "prompt.added_ignore",
+                    };
+                    info!("{}", tr!(key, config_path.display(), process));
+                }
+                Err(e) => warn!(
+                    "{}",
+                    tr!("prompt.add_failed", process, config_path.display(), e)
+                ),
+            }
+        });
+
+    if let Err(e) = spawned {
+        warn!("{}", tr!("prompt.failed", e));
+    }
+}
@@ -0,0 +1,180 @@
+//! Cooperative cursor clip handoff protocol
+//!
+//! Other cursor-managing tools (display switchers, KVM software, ...) can ask a
+//! running SmartLockCursor to step aside before calling `ClipCursor`
+//! themselves, and hand the clip back once they are done, instead of both
+//! programs overwriting each other's clip.
+//!
+//! 1. Find the control window with `FindWindowW(WINDOW_CLASS, NULL)`
+//! 2. Register the message with `RegisterWindowMessageW(MESSAGE_NAME)`
+//! 3. Send it with a [`Request`] as `wParam` and your process ID as `lParam`
+//!
+//! The returned `LRESULT` is a [`Reply`]. [`send_request`] does all three steps.
+//!
+//! While yielded, SmartLockCursor never calls `ClipCursor`. If the process that
+//! asked it to yield exits without sending [`Request::TakeOver`], the clip is
+//! reclaimed automatically.
+//!
+//! [`Request::WriteStatus`] is answered only after the report has been written
+//! to [`status_path`], so it can be read as soon as the reply arrives.
+//!
+//! Nothing here is authenticated. The process ID in `lParam` is taken on trust,
+//! and the control window lets the message through from lower-integrity
+//! processes so that tools can reach an elevated instance, so any program on
+//! the desktop can send any request. None of them takes a path or other data
+//! from the sender: the reports only ever go to their fixed files in the config
+//! directory.
+
+use std::path::PathBuf;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+use windows::Win32::System::Threading::GetCurrentProcessId;
+use windows::Win32::UI::WindowsAndMessaging::{
+    FindWindowW, RegisterWindowMessageW, SendMessageTimeoutW, SMTO_ABORTIFHUNG,
+};
+
+use crate::config::Config;
+
+/// Class name of the hidden control window
+pub const WINDOW_CLASS: &str = "SmartLockCursorControl";
+
+/// Name passed to `RegisterWindowMessageW` to obtain the handoff message ID
+pub const MESSAGE_NAME: &str = "SmartLockCursor.ClipHandoff.v1";
+
+/// How long [`send_request`] waits for a reply, in milliseconds
+pub const SEND_TIMEOUT_MS: u32 = 1000;
+
+/// Where [`Request::WriteStatus`] puts the status report
+pub fn status_path() -> Option<PathBuf> {
+    Config::default_dir().map(|dir| dir.join("status.txt"))
+}
+
+/// A request sent in `wParam`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Request {
+    /// Release the clip and stop clipping until [`Request::TakeOver`]
+    Yield = 1,
+    /// Resume normal operation and re-apply the clip if a fullscreen window is active
+    TakeOver = 2,
+    /// Report the current state without changing anything
+    Query = 3,
+    /// Write a status report with statistics to [`status_path`]
+    WriteStatus = 4,
+    /// Lock the cursor to the monitor it is on regardless of fullscreen
+    /// detection, or lift that lock if it is already active
+    ToggleManualLock = 5,
+    /// Write a snapshot to [`snapshot_path`](crate::snapshot::snapshot_path);
+    /// acknowledged at once and written once the main loop takes the request
+    /// from its queue, so the file may not be there yet when the reply arrives
+    WriteSnapshot = 6,
+}
+
+impl Request {
+    pub fn from_wparam(value: usize) -> Option<Self> {
+        match value {
+            1 => Some(Self::Yield),
+            2 => Some(Self::TakeOver),
+            3 => Some(Self::Query),
+            4 => Some(Self::WriteStatus),
+            5 => Some(Self::ToggleManualLock),
+            6 => Some(Self::WriteSnapshot),
+            _ => None,
+        }
+    }
+}
+
+/// A reply returned as the message `LRESULT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(isize)]
+pub enum Reply {
+    /// The request was not understood
+    Unsupported = 0,
+    /// Any request other than [`Request::Query`] was accepted
+    Ack = 1,
+    /// The cursor is currently clipped by SmartLockCursor
+    Locked = 2,
+    /// SmartLockCursor is active but not clipping
+    Unlocked = 3,
+    /// SmartLockCursor has yielded the clip to another process
+    Yielded = 4,
+}
+
+impl Reply {
+    pub fn from_lresult(value: isize) -> Option<Self> {
+        match value {
+            0 => Some(Self::Unsupported),
+            1 => Some(Self::Ack),
+            2 => Some(Self::Locked),
+            3 => Some(Self::Unlocked),
+            4 => Some(Self::Yielded),
+            _ => None,
+        }
+    }
+}
+
+/// Sends a request to the running instance, returning `None` if none is running
+/// or it did not answer in time
+pub fn send_request(request: Request) -> Option<Reply> {
+    unsafe {
+        let hwnd = FindWindowW(&HSTRING::from(WINDOW_CLASS), PCWSTR::null()).ok()?;
+
+        let message = RegisterWindowMessageW(&HSTRING::from(MESSAGE_NAME));
+        if message == 0 {
+            return None;
+        }
+
+        let mut result = 0usize;
+        let sent = SendMessageTimeoutW(
+            hwnd,
+            message,
+            WPARAM(request as usize),
+            LPARAM(GetCurrentProcessId() as isize),
+            SMTO_ABORTIFHUNG,
+            SEND_TIMEOUT_MS,
+            Some(&mut result),
+        );
+        if sent.0 == 0 {
+            return None;
+        }
+
+        Reply::from_lresult(result as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_replies_survive_the_message() {
+        for request in [
+            Request::Yield,
+            Request::TakeOver,
+            Request::Query,
+            Request::WriteStatus,
+            Request::ToggleManualLock,
+            Request::WriteSnapshot,
+        ] {
+            assert_eq!(Request::from_wparam(request as usize), Some(request));
+        }
+        for reply in [
+            Reply::Unsupported,
+            Reply::Ack,
+            Reply::Locked,
+            Reply::Unlocked,
+            Reply::Yielded,
+        ] {
+            assert_eq!(Reply::from_lresult(reply as isize), Some(reply));
+        }
+    }
+
+    #[test]
+    fn unknown_values_are_not_requests_or_replies() {
+        assert_eq!(Request::from_wparam(0), None);
+        assert_eq!(Request::from_wparam(7), None);
+        assert_eq!(Reply::from_lresult(-1), None);
+        assert_eq!(Reply::from_lresult(5), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Telling mice apart with Raw Input
+//!
+//! Windows moves a single cursor for every pointing device, and `ClipCursor`
+//! holds all of them. To confine just one mouse (`[cursor] device`), the mouse
+//! hook asks [`MouseWatch::last_move_matched`] whether the device that moved
+//! last is the configured one and lets every other device past the edge. Raw
+//! Input reports the device of each move to a message-only window on the main
+//! thread, even while a game has the focus.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{HANDLE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Input::{
+    GetRawInputData, GetRawInputDeviceInfoW, GetRawInputDeviceList, RegisterRawInputDevices,
+    HRAWINPUT, RAWINPUTDEVICE, RAWINPUTDEVICELIST, RAWINPUTHEADER, RIDEV_INPUTSINK, RIDEV_REMOVE,
+    RIDI_DEVICENAME, RID_HEADER, RIM_TYPEMOUSE,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, RegisterClassW, HMENU, HWND_MESSAGE,
+    WINDOW_EX_STYLE, WINDOW_STYLE, WM_INPUT, WNDCLASSW,
+};
+
+const WINDOW_CLASS: &str = "SmartLockCursorRawInput";
+/// HID usage page and usage of a mouse
+const USAGE_PAGE_GENERIC: u16 = 0x01;
+const USAGE_MOUSE: u16 = 0x02;
+
+/// A mouse as Raw Input lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MouseDevice {
+    pub handle: isize,
+    /// Device interface path, e.g. `\\?\HID#VID_046D&PID_C08B&MI_00#...`
+    pub path: String,
+}
+
+/// Every mouse currently attached
+pub fn mice() -> Vec<MouseDevice> {
+    let size = std::mem::size_of::<RAWINPUTDEVICELIST>() as u32;
+    let mut count = 0u32;
+    unsafe {
+        if GetRawInputDeviceList(None, &mut count, size) == u32::MAX || count == 0 {
+            return Vec::new();
+        }
+        let mut devices = vec![RAWINPUTDEVICELIST::default(); count as usize];
+        let found = GetRawInputDeviceList(Some(devices.as_mut_ptr()), &mut count, size);
+        if found == u32::MAX {
+            return Vec::new();
+        }
+        devices.truncate(found as usize);
+        devices
+            .iter()
+            .filter(|device| device.dwType == RIM_TYPEMOUSE)
+            .filter_map(|device| {
+                Some(MouseDevice {
+                    handle: device.hDevice.0 as isize,
+                    path: device_path(device.hDevice)?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn device_path(device: HANDLE) -> Option<String> {
+    let mut len = 0u32;
+    unsafe {
+        GetRawInputDeviceInfoW(device, RIDI_DEVICENAME, None, &mut len);
+        if len == 0 {
+            return None;
+        }
+        let mut name = vec![0u16; len as usize];
+        let copied = GetRawInputDeviceInfoW(
+            device,
+            RIDI_DEVICENAME,
+            Some(name.as_mut_ptr().cast()),
+            &mut len,
+        );
+        if copied == u32::MAX {
+            return None;
+        }
+        let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        Some(String::from_utf16_lossy(&name[..end]))
+    }
+}
+
+/// Whether a device path is the one `[cursor] device` names
+pub fn path_matches(path: &str, pattern: &str) -> bool {
+    path.to_ascii_lowercase()
+        .contains(&pattern.to_ascii_lowercase())
+}
+
+thread_local! {
+    static PATTERN: RefCell<String> = const { RefCell::new(String::new()) };
+    /// Whether each device seen so far matches the pattern
+    static MATCHES: RefCell<HashMap<isize, bool>> = RefCell::new(HashMap::new());
+    /// Until the first move nothing is known, so the cursor stays confined
+    static LAST_MATCHED: Cell<bool> = const { Cell::new(true) };
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_INPUT {
+        let mut header = RAWINPUTHEADER::default();
+        let mut size = std::mem::size_of::<RAWINPUTHEADER>() as u32;
+        let read = GetRawInputData(
+            HRAWINPUT(lparam.0 as *mut _),
+            RID_HEADER,
+            Some((&mut header as *mut RAWINPUTHEADER).cast()),
+            &mut size,
+            size,
+        );
+        // Injected input has no device, leave the last answer as it was
+        if read != u32::MAX && !header.hDevice.is_invalid() {
+            let device = header.hDevice.0 as isize;
+            let matched = MATCHES.with(|m| {
+                *m.borrow_mut().entry(device).or_insert_with(|| {
+                    device_path(header.hDevice).is_some_and(|path| {
+                        PATTERN.with(|pattern| path_matches(&path, &pattern.borrow()))
+                    })
+                })
+            });
+            LAST_MATCHED.with(|last| last.set(matched));
+        }
+    }
+    // Also for WM_INPUT, which frees the input buffer
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Raw Input registration for mouse moves, removed on drop
+pub struct MouseWatch {
+    hwnd: HWND,
+    pattern: String,
+}
+
+impl MouseWatch {
+    /// Starts following which mouse moves, for a device path containing
+    /// `pattern`; the current thread must pump messages
+    pub fn start(pattern: &str) -> windows::core::Result<Self> {
+        PATTERN.with(|p| *p.borrow_mut() = pattern.to_string());
+        MATCHES.with(|m| m.borrow_mut().clear());
+        LAST_MATCHED.with(|last| last.set(true));
+        unsafe {
+            let instance = GetModuleHandleW(PCWSTR::null())?;
+            let class_name = HSTRING::from(WINDOW_CLASS);
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                &class_name,
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                HMENU::default(),
+                instance,
+                None,
+            )?;
+
+            let device = RAWINPUTDEVICE {
+                usUsagePage: USAGE_PAGE_GENERIC,
+                usUsage: USAGE_MOUSE,
+                // Also while the game, not us, has the focus
+                dwFlags: RIDEV_INPUTSINK,
+                hwndTarget: hwnd,
+            };
+            if let Err(e) =
+                RegisterRawInputDevices(&[device], std::mem::size_of::<RAWINPUTDEVICE>() as u32)
+            {
+                let _ = DestroyWindow(hwnd);
+                return Err(e);
+            }
+            Ok(Self {
+                hwnd,
+                pattern: pattern.to_string(),
+            })
+        }
+    }
+
+    /// The `[cursor] device` this watch was started for
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the device behind the latest mouse move is the configured one
+    pub fn last_move_matched() -> bool {
+        LAST_MATCHED.with(Cell::get)
+    }
+}
+
+impl Drop for MouseWatch {
+    fn drop(&mut self) {
+        let device = RAWINPUTDEVICE {
+            usUsagePage: USAGE_PAGE_GENERIC,
+            usUsage: USAGE_MOUSE,
+            dwFlags: RIDEV_REMOVE,
+            hwndTarget: HWND::default(),
+        };
+        unsafe {
+            let _ =
+                RegisterRawInputDevices(&[device], std::mem::size_of::<RAWINPUTDEVICE>() as u32);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_paths_match_case_insensitively() {
+        let path = r"\\?\HID#VID_046D&PID_C08B&MI_00#8&1a2b3c&0&0000#{378de44c-56ef-11d1-bc8c-00a0c91405dd}";
+        assert!(path_matches(path, "vid_046d&pid_c08b"));
+        assert!(!path_matches(path, "VID_056A"));
+    }
+}
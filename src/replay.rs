@@ -0,0 +1,328 @@
+//! Replaying snapshots through the lock engine
+//!
+//! `smartlockcursor replay dump.json` rebuilds the desktop a
+//! [`snapshot`](crate::snapshot) recorded on a [`MockSystem`] and lets the
+//! engine decide again, so a bug report becomes something to step through and
+//! turn into a test. The file holds one snapshot, or an array of them replayed
+//! in order as frames. A frame happens `at_ms` after the first one if it says
+//! so, else as far after it as the snapshot `time`s are apart.
+
+use std::time::Duration;
+
+use windows::Win32::Foundation::{POINT, RECT};
+use windows::Win32::Graphics::Gdi::HMONITOR;
+
+use crate::config::{Config, LowPowerMode};
+use crate::events::Switcher;
+use crate::json::{self, Value};
+use crate::monitor::MonitorBounds;
+use crate::system::{MockSystem, MockWindow, WindowSystem};
+
+/// How often the replayed engine ticks, like the default poll interval
+pub const TICK: Duration = Duration::from_millis(100);
+
+/// A recorded desktop
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    /// Time since the first frame
+    pub at: Duration,
+    pub foreground: isize,
+    pub cursor: Option<POINT>,
+    pub monitors: Vec<MonitorBounds>,
+    pub windows: Vec<(isize, MockWindow)>,
+}
+
+impl Frame {
+    /// Puts the frame's monitors, windows, foreground and cursor on `system`,
+    /// replacing what was there; processes without windows stop running
+    pub fn apply(&self, system: &mut MockSystem) {
+        system.set_monitors(&self.monitors);
+        for hwnd in system.windows() {
+            let pid = system.window_process_id(hwnd);
+            system.set_process_running(pid, false);
+        }
+        system.clear_windows();
+        for (hwnd, window) in &self.windows {
+            system.add_window(*hwnd, window.clone());
+        }
+        system.set_foreground(self.foreground);
+        if let Some(pos) = self.cursor {
+            system.set_cursor_pos(pos.x, pos.y);
+        }
+    }
+
+    /// A one-line description of the foreground window
+    pub fn describe_foreground(&self) -> String {
+        match self
+            .windows
+            .iter()
+            .find(|(hwnd, _)| *hwnd == self.foreground)
+        {
+            Some((hwnd, w)) => format!("{:#x} {} ({})", hwnd, w.process, w.class),
+            None if self.foreground == 0 => "none".to_string(),
+            None => format!("{:#x} (not recorded)", self.foreground),
+        }
+    }
+}
+
+/// What a snapshot file holds
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// Config file text of the first frame that has one
+    pub config: Option<String>,
+    /// PC the snapshots were taken on, for its `[machines.NAME]` table
+    pub machine: Option<String>,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    /// The recorded config, or the defaults without one
+    pub fn config(&self) -> Result<Config, String> {
+        match self.config {
+            Some(ref text) => {
+                Config::parse(text, self.machine.as_deref()).map_err(|e| e.to_string())
+            }
+            None => Ok(Config::default()),
+        }
+    }
+}
+
+/// Reads a snapshot file
+pub fn parse(text: &str) -> Result<Recording, String> {
+    let value = json::parse(text)?;
+    let snapshots = match value {
+        Value::Array(snapshots) => snapshots,
+        snapshot => vec![snapshot],
+    };
+    if snapshots.is_empty() {
+        return Err("the file holds no snapshots".to_string());
+    }
+
+    let mut recording = Recording::default();
+    let start = snapshots.first().and_then(|s| number(s.get("time")));
+    let mut last = Duration::ZERO;
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let frame = frame(snapshot, start).map_err(|e| format!("snapshot {}: {}", i + 1, e))?;
+        // The clock never runs backwards
+        last = last.max(frame.at);
+        recording.frames.push(Frame { at: last, ..frame });
+        if recording.config.is_none() {
+            recording.config = snapshot
+                .get("config")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            recording.machine = snapshot
+                .get("machine")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+        }
+    }
+    Ok(recording)
+}
+
+fn frame(snapshot: &Value, start: Option<f64>) -> Result<Frame, String> {
+    match number(snapshot.get("schema")) {
+        Some(schema) if schema as i64 > crate::snapshot::SCHEMA => {
+            return Err(format!(
+                "schema {} is newer than this version reads ({})",
+                schema,
+                crate::snapshot::SCHEMA
+            ))
+        }
+        Some(_) => {}
+        None => return Err("not a snapshot, it has no schema version".to_string()),
+    }
+    let at = match (
+        number(snapshot.get("at_ms")),
+        number(snapshot.get("time")),
+        start,
+    ) {
+        (Some(ms), _, _) => Duration::from_millis(ms.max(0.0) as u64),
+        (None, Some(time), Some(start)) => Duration::from_secs((time - start).max(0.0) as u64),
+        _ => Duration::ZERO,
+    };
+
+    let monitors: Vec<MonitorBounds> = list(snapshot, "monitors")?
+        .iter()
+        .map(|monitor| {
+            Ok(MonitorBounds {
+                rect: rect(monitor.get("rect")).ok_or("a monitor has no rect")?,
+                handle: HMONITOR::default(),
+                device: string(monitor.get("device")),
+                id: string(monitor.get("id")),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    if monitors.is_empty() {
+        return Err("no monitors".to_string());
+    }
+
+    let windows = list(snapshot, "windows")?
+        .iter()
+        .map(|window| {
+            let hwnd = number(window.get("hwnd")).ok_or("a window has no hwnd")? as isize;
+            let switcher = match window.get("switcher").and_then(Value::as_str) {
+                Some("AltTab") => Some(Switcher::AltTab),
+                Some("TaskView") => Some(Switcher::TaskView),
+                _ => None,
+            };
+            Ok((
+                hwnd,
+                MockWindow {
+                    class: string(window.get("class")),
+                    title: string(window.get("title")),
+                    pid: number(window.get("pid")).unwrap_or(0.0) as u32,
+                    process: string(window.get("process")),
+                    path: string(window.get("path")),
+                    product: window
+                        .get("product")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    rect: rect(window.get("rect")).unwrap_or_default(),
+                    client: rect(window.get("client")),
+                    switcher,
+                    minimized: boolean(window.get("minimized")),
+                    cloaked: boolean(window.get("cloaked")),
+                    other_desktop: boolean(window.get("other_desktop")),
+                    ..Default::default()
+                },
+            ))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let cursor = snapshot
+        .get("cursor")
+        .and_then(Value::as_array)
+        .and_then(|pos| match pos {
+            [x, y] => Some(POINT {
+                x: number(Some(x))? as i32,
+                y: number(Some(y))? as i32,
+            }),
+            _ => None,
+        });
+    Ok(Frame {
+        at,
+        foreground: number(snapshot.get("foreground")).unwrap_or(0.0) as isize,
+        cursor,
+        monitors,
+        windows,
+    })
+}
+
+/// Turns off everything that would reach out of a replay: commands, prompts,
+/// logs, network integrations, and the battery and gamepad checks that ask
+/// this machine rather than the recorded one
+pub fn offline(mut config: Config) -> Config {
+    config.commands = Default::default();
+    config.learning.prompt = false;
+    config.event_log.enabled = false;
+    config.system_log.enabled = false;
+    config.webhook.url = None;
+    config.mqtt.host = None;
+    config.discord.client_id = None;
+    config.http.port = None;
+    config.obs.websocket = false;
+    config.power.low_power = LowPowerMode::Never;
+    config.power.efficiency_mode = false;
+    config.gamepad.enabled = false;
+    config
+}
+
+fn list<'a>(snapshot: &'a Value, key: &str) -> Result<&'a [Value], String> {
+    snapshot
+        .get(key)
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("no {} list", key))
+}
+
+fn number(value: Option<&Value>) -> Option<f64> {
+    match value {
+        Some(Value::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn string(value: Option<&Value>) -> String {
+    value
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn boolean(value: Option<&Value>) -> bool {
+    matches!(value, Some(Value::Bool(true)))
+}
+
+fn rect(value: Option<&Value>) -> Option<RECT> {
+    match value?.as_array()? {
+        [left, top, right, bottom] => Some(RECT {
+            left: number(Some(left))? as i32,
+            top: number(Some(top))? as i32,
+            right: number(Some(right))? as i32,
+            bottom: number(Some(bottom))? as i32,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT: &str = r#"{"schema":1,"time":1000,"machine":"DESK","foreground":256,
+        "cursor":[960,540],"monitors":[{"device":"\\\\.\\DISPLAY1","id":"x","rect":[0,0,1920,1080]}],
+        "windows":[{"hwnd":256,"title":"Game","class":"UnityWndClass","pid":10,
+        "process":"Game.exe","rect":[0,0,1920,1080],"client":null,"minimized":false,
+        "switcher":null}],"config":"[detection]\ntolerance = 7\n"}"#;
+
+    #[test]
+    fn reads_single_snapshots_and_sequences() {
+        let single = parse(SNAPSHOT).unwrap();
+        assert_eq!(single.frames.len(), 1);
+        assert_eq!(single.machine.as_deref(), Some("DESK"));
+        assert_eq!(single.config().unwrap().detection.tolerance, 7);
+        let frame = &single.frames[0];
+        assert_eq!(frame.monitors[0].device, r"\\.\DISPLAY1");
+        assert_eq!(frame.windows[0].0, 256);
+        assert_eq!(frame.windows[0].1.process, "Game.exe");
+        assert_eq!(
+            frame.describe_foreground(),
+            "0x100 Game.exe (UnityWndClass)"
+        );
+
+        let later = SNAPSHOT.replace("\"time\":1000", "\"time\":1003");
+        let timed = SNAPSHOT.replace("\"time\":1000", "\"at_ms\":500,\"time\":1000");
+        let sequence = parse(&format!("[{},{},{}]", SNAPSHOT, later, timed)).unwrap();
+        let at: Vec<u64> = sequence
+            .frames
+            .iter()
+            .map(|frame| frame.at.as_millis() as u64)
+            .collect();
+        assert_eq!(at, [0, 3000, 3000]);
+    }
+
+    #[test]
+    fn frames_replace_the_desktop() {
+        let recording = parse(SNAPSHOT).unwrap();
+        let mut system = MockSystem::new(RECT::default());
+        system.add_window(
+            0x200,
+            MockWindow {
+                pid: 20,
+                ..Default::default()
+            },
+        );
+        recording.frames[0].apply(&mut system);
+        assert!(!system.is_process_running(20));
+        assert!(system.is_process_running(10));
+        assert_eq!(system.foreground_window().0 as isize, 256);
+        assert_eq!(system.monitors()[0].id, "x");
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_snapshots() {
+        assert!(parse("[]").is_err());
+        assert!(parse(r#"{"windows":[]}"#).is_err());
+        assert!(parse(&SNAPSHOT.replace("\"schema\":1", "\"schema\":9")).is_err());
+    }
+}
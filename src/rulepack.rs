@@ -0,0 +1,228 @@
+//! Rule packs: `[[rules]]` and `[[profiles]]` shared between machines
+//!
+//! `export-rules` writes the rules and profiles of the config file to a pack,
+//! a TOML file with a `schema` version on top, and `import-rules` merges a pack
+//! into the config file. Merging appends the rules the config doesn't already
+//! have after its own, so local rules keep deciding first, and skips profiles
+//! whose name is already taken. Like [`append_rule`](crate::config::append_rule)
+//! it only appends text, so comments and formatting elsewhere survive.
+
+use crate::config::{Config, ProfileConfig, RuleConfig};
+
+/// Version of the pack format this build writes and reads
+pub const SCHEMA: i64 = 1;
+
+/// What importing a pack changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Merge {
+    /// The config file text with the pack merged in
+    pub text: String,
+    pub rules_added: usize,
+    /// Rules the config already has, word for word
+    pub rules_skipped: usize,
+    pub profiles_added: Vec<String>,
+    /// Profiles not imported because the config has one of the same name
+    pub profiles_skipped: Vec<String>,
+}
+
+/// Builds a pack from the rules and profiles in a config file's text
+pub fn export(config: &str) -> Result<String, String> {
+    let config: toml::Table = toml::from_str(config).map_err(|e| e.to_string())?;
+    let rules = entries(&config, "rules")?;
+    let profiles = entries(&config, "profiles")?;
+    let mut pack = format!(
+        "# SmartLockCursor rule pack, merge it with `smartlockcursor import-rules`\n\
+         schema = {}\n",
+        SCHEMA
+    );
+    pack.push_str(&tables("rules", rules)?);
+    pack.push_str(&tables("profiles", profiles)?);
+    Ok(pack)
+}
+
+/// Merges the pack text into the config file text; `source` names the pack in
+/// the comment above the imported entries
+pub fn merge(config: &str, pack: &str, source: &str) -> Result<Merge, String> {
+    let existing: toml::Table = toml::from_str(config).map_err(|e| e.to_string())?;
+    let pack: toml::Table = toml::from_str(pack).map_err(|e| format!("not a rule pack: {}", e))?;
+    match pack.get("schema").and_then(toml::Value::as_integer) {
+        Some(schema) if schema > SCHEMA => {
+            return Err(format!(
+            "the pack uses schema {}, this version reads up to {}, update SmartLockCursor first",
+            schema, SCHEMA
+        ))
+        }
+        Some(schema) if schema >= 1 => {}
+        Some(schema) => return Err(format!("invalid schema {}", schema)),
+        None => return Err("not a rule pack, it has no schema version".to_string()),
+    }
+    let rules = entries(&pack, "rules")?;
+    let profiles = entries(&pack, "profiles")?;
+    // Catch entries this version can't read before touching the config
+    for rule in rules {
+        rule.clone()
+            .try_into::<RuleConfig>()
+            .map_err(|e| format!("invalid rule in the pack: {}", e))?;
+    }
+    for profile in profiles {
+        profile
+            .clone()
+            .try_into::<ProfileConfig>()
+            .map_err(|e| format!("invalid profile in the pack: {}", e))?;
+    }
+
+    let mut merge = Merge::default();
+    let known_rules = entries(&existing, "rules")?;
+    let mut taken: Vec<String> = entries(&existing, "profiles")?
+        .iter()
+        .filter_map(profile_name)
+        .collect();
+    let mut new_rules = Vec::new();
+    for rule in rules {
+        if known_rules.contains(rule) || new_rules.contains(rule) {
+            merge.rules_skipped += 1;
+        } else {
+            new_rules.push(rule.clone());
+        }
+    }
+    let mut new_profiles = Vec::new();
+    for profile in profiles {
+        let name = profile_name(profile).unwrap_or_default();
+        if taken.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+            merge.profiles_skipped.push(name);
+        } else {
+            taken.push(name.clone());
+            merge.profiles_added.push(name);
+            new_profiles.push(profile.clone());
+        }
+    }
+    merge.rules_added = new_rules.len();
+
+    merge.text = config.to_string();
+    if !new_rules.is_empty() || !new_profiles.is_empty() {
+        if !merge.text.is_empty() && !merge.text.ends_with('\n') {
+            merge.text.push('\n');
+        }
+        let added = tables("rules", &new_rules)? + tables("profiles", &new_profiles)?.as_str();
+        merge
+            .text
+            .push_str(&format!("\n# Imported from {}\n", source));
+        merge.text.push_str(added.trim_start());
+    }
+    // An inline `rules = [...]` in the config can't be extended by [[rules]]
+    toml::from_str::<Config>(&merge.text)
+        .map_err(|e| format!("the merged config doesn't parse: {}", e))?;
+    Ok(merge)
+}
+
+/// The array of tables under `key`, empty if there is none
+fn entries<'a>(table: &'a toml::Table, key: &str) -> Result<&'a [toml::Value], String> {
+    match table.get(key) {
+        None => Ok(&[]),
+        Some(toml::Value::Array(entries)) if entries.iter().all(toml::Value::is_table) => {
+            Ok(entries)
+        }
+        Some(_) => Err(format!("{} must be a list of [[{}]] tables", key, key)),
+    }
+}
+
+fn profile_name(profile: &toml::Value) -> Option<String> {
+    profile.get("name")?.as_str().map(str::to_string)
+}
+
+/// Writes `entries` as `[[key]]` tables, preceded by a blank line
+fn tables(key: &str, entries: &[toml::Value]) -> Result<String, String> {
+    if entries.is_empty() {
+        return Ok(String::new());
+    }
+    let mut table = toml::Table::new();
+    table.insert(key.to_string(), toml::Value::Array(entries.to_vec()));
+    toml::to_string(&table)
+        .map(|text| format!("\n{}", text))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# My rules
+[cursor]
+backend = \"hook\"
+
+[[rules]]
+process = \"game.exe\"
+action = \"lock\"
+
+[[profiles]]
+name = \"Streaming\"
+center_on_lock = true
+";
+
+    #[test]
+    fn exported_packs_merge_back_without_duplicates() {
+        let pack = export(CONFIG).unwrap();
+        assert!(pack.contains("schema = 1"));
+        assert!(!pack.contains("[cursor]"));
+
+        let merge = merge(CONFIG, &pack, "pack.toml").unwrap();
+        assert_eq!(merge.text, CONFIG);
+        assert_eq!(merge.rules_skipped, 1);
+        assert_eq!(merge.profiles_skipped, ["Streaming"]);
+    }
+
+    #[test]
+    fn appends_new_rules_after_the_local_ones() {
+        let pack = "\
+schema = 1
+
+[[rules]]
+process = \"game.exe\"
+action = \"lock\"
+
+[[rules]]
+class = \"UnrealWindow\"
+action = \"ignore\"
+
+[[profiles]]
+name = \"streaming\"
+
+[[profiles]]
+name = \"Competitive\"
+backend = \"hook\"
+
+[[profiles.rules]]
+process = \"cs2.exe\"
+";
+        let merge = merge(CONFIG, pack, "community.toml").unwrap();
+        assert_eq!(merge.rules_added, 1);
+        assert_eq!(merge.profiles_added, ["Competitive"]);
+        assert_eq!(merge.profiles_skipped, ["streaming"]);
+        assert!(merge.text.starts_with(CONFIG));
+        assert!(merge.text.contains("# Imported from community.toml"));
+
+        let config: Config = toml::from_str(&merge.text).unwrap();
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[1].class.as_deref(), Some("UnrealWindow"));
+        assert_eq!(
+            config.profiles[1].rules[0].process.as_deref(),
+            Some("cs2.exe")
+        );
+    }
+
+    #[test]
+    fn refuses_packs_it_cannot_read() {
+        assert!(merge(CONFIG, "[[rules]]\nprocess = \"a.exe\"\n", "x").is_err());
+        assert!(merge(CONFIG, "schema = 2\n", "x")
+            .unwrap_err()
+            .contains("schema 2"));
+        assert!(merge(CONFIG, "schema = 1\n[[rules]]\naction = \"explode\"\n", "x").is_err());
+        assert!(merge(
+            "rules = []\n",
+            "schema = 1\n[[rules]]\nprocess = \"a.exe\"\n",
+            "x"
+        )
+        .is_err());
+    }
+}
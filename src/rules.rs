@@ -0,0 +1,479 @@
+//! Per-window lock rules
+//!
+//! Rules are matched against the window class, title, process, product name and
+//! Steam AppID of the foreground window. The first matching rule decides whether the window may be
+//! locked. With `match_children`, a process rule also matches everything the
+//! process launched, so a trusted launcher covers its games.
+//!
+//! Process and product patterns are case-insensitive globs: `*` and `?` stay
+//! within one path component and `**` spans any number of folders. A process
+//! pattern with a `\` or `/` in it is matched against the executable's full
+//! path instead of its name, for games that all ship a `game.exe`.
+//!
+//! A detection script (`[detection] script`, see `script`) decides about the
+//! windows no rule matches, before the defaults do.
+
+use regex::Regex;
+use windows::Win32::Foundation::{HWND, RECT};
+
+use crate::config::{Config, LockOverrides, RuleAction};
+use crate::games::{ENGINE_CLASSES, LAUNCHERS};
+use crate::monitor::get_window_monitor_rect;
+use crate::process::{get_ancestor_names, get_process_path, get_product_name};
+#[cfg(feature = "rhai")]
+use crate::script::Script;
+use crate::steam::find_app;
+use crate::window::{get_class_name, get_window_process_id, get_window_rect, get_window_title};
+use crate::{tr, warn};
+
+/// The facts about a window that rules are matched against
+#[derive(Debug, Clone, Default)]
+pub struct WindowInfo {
+    pub class: String,
+    pub title: String,
+    /// Executable name, lowercased
+    pub process: String,
+    /// Full executable path, looked up only when a rule matches on paths
+    pub path: String,
+    /// Product name from the executable's version info, looked up only when
+    /// a rule asks for one
+    pub product: Option<String>,
+    /// Executable names of the parent processes, closest first, lowercased
+    pub ancestors: Vec<String>,
+    /// Steam AppID of the game, looked up only when a rule asks for one
+    pub steam_app: Option<u32>,
+    /// Outer window rect and the rect of its monitor, looked up only for a
+    /// detection script
+    pub rect: Option<RECT>,
+    pub monitor: Option<RECT>,
+}
+
+impl WindowInfo {
+    /// Gathers the facts about `hwnd` that `rules` look at, skipping the rest
+    pub fn query(hwnd: HWND, rules: &RuleSet) -> Self {
+        let pid = if rules.uses_processes() {
+            get_window_process_id(hwnd)
+        } else {
+            0
+        };
+
+        let path = if pid != 0 {
+            get_process_path(pid)
+        } else {
+            None
+        };
+        let process = path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        Self {
+            class: get_class_name(hwnd).unwrap_or_default(),
+            // Projectors are told apart from the main OBS window by their title
+            title: if rules.uses_titles() || rules.projector_processes.contains(&process) {
+                get_window_title(hwnd)
+            } else {
+                String::new()
+            },
+            process,
+            product: path
+                .as_ref()
+                .filter(|_| rules.uses_products())
+                .and_then(|path| get_product_name(path)),
+            ancestors: if pid != 0 && rules.uses_ancestry() {
+                get_ancestor_names(pid)
+                    .iter()
+                    .map(|name| name.to_lowercase())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            steam_app: path
+                .as_ref()
+                .filter(|_| rules.uses_steam_apps())
+                .and_then(|path| find_app(path))
+                .map(|app| app.id),
+            path: path
+                .filter(|_| rules.uses_paths())
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            rect: rules.uses_script().then(|| get_window_rect(hwnd)).flatten(),
+            monitor: rules
+                .uses_script()
+                .then(|| get_window_monitor_rect(hwnd))
+                .flatten(),
+        }
+    }
+}
+
+/// Outcome of evaluating the rules against a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The window may be locked when it is fullscreen
+    Lock,
+    /// The window must never be locked
+    Ignore,
+}
+
+/// Compiles a case-insensitive glob into an anchored regex
+///
+/// `**` followed by a separator also matches no folder at all, so
+/// `C:\Games\**\*.exe` covers `C:\Games\game.exe`.
+pub fn glob(pattern: &str) -> Regex {
+    const SEPARATOR: &str = r"[\\/]";
+    const NOT_SEPARATOR: &str = r"[^\\/]";
+    let mut re = String::from("(?i)^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.next_if(|&c| c == '\\' || c == '/').is_some() {
+                    re.push_str(&format!("(?:.*{})?", SEPARATOR));
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str(&format!("{}*", NOT_SEPARATOR)),
+            '?' => re.push_str(NOT_SEPARATOR),
+            '\\' | '/' => re.push_str(SEPARATOR),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    // Everything but the wildcards is escaped, so this always compiles
+    Regex::new(&re).expect("glob compiles to a valid regex")
+}
+
+/// Whether a process pattern names a full path rather than an executable
+pub fn is_path_pattern(pattern: &str) -> bool {
+    pattern.contains(['\\', '/'])
+}
+
+/// A compiled `process` pattern
+#[derive(Debug)]
+struct ProcessPattern {
+    glob: Regex,
+    /// Matched against the full path rather than the executable name
+    path: bool,
+}
+
+/// A rule with its patterns compiled
+#[derive(Debug)]
+struct CompiledRule {
+    class: Option<String>,
+    title: Option<Regex>,
+    process: Option<ProcessPattern>,
+    product: Option<Regex>,
+    steam_app: Option<u32>,
+    match_children: bool,
+    action: RuleAction,
+    overrides: LockOverrides,
+}
+
+impl CompiledRule {
+    fn matches(&self, window: &WindowInfo) -> bool {
+        self.class.as_ref().is_none_or(|c| *c == window.class)
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|re| re.is_match(&window.title))
+            && self.process.as_ref().is_none_or(|p| {
+                if p.path {
+                    p.glob.is_match(&window.path)
+                } else {
+                    p.glob.is_match(&window.process)
+                        || (self.match_children
+                            && window.ancestors.iter().any(|name| p.glob.is_match(name)))
+                }
+            })
+            && self.product.as_ref().is_none_or(|glob| {
+                window
+                    .product
+                    .as_ref()
+                    .is_some_and(|product| glob.is_match(product))
+            })
+            && self.steam_app.is_none_or(|id| window.steam_app == Some(id))
+    }
+}
+
+/// The compiled rule list
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    whitelist_only: bool,
+    /// Transient windows from `[detection]`, executables lowercased
+    transient_processes: Vec<String>,
+    transient_classes: Vec<String>,
+    /// Browser executables no rule needs to mention to be ignored, lowercased
+    browsers: Vec<String>,
+    /// OBS executables and projector title words (lowercased) for
+    /// `hold_for_projectors`, empty when it is off
+    projector_processes: Vec<String>,
+    projector_titles: Vec<String>,
+    /// Known game engine classes and launchers (lowercased), built-in and configured
+    game_classes: Vec<String>,
+    game_launchers: Vec<String>,
+    only_known_games: bool,
+    #[cfg(feature = "rhai")]
+    script: Option<Script>,
+}
+
+impl RuleSet {
+    /// Compiles the rules from the config, skipping entries with invalid patterns
+    pub fn from_config(config: &Config) -> Self {
+        let mut rules = Vec::new();
+
+        #[cfg(not(feature = "rhai"))]
+        if config.detection.script.is_some() {
+            warn!("{}", tr!("rules.script_unsupported"));
+        }
+
+        for (i, rule) in config.rules.iter().enumerate() {
+            if rule.class.is_none()
+                && rule.title.is_none()
+                && rule.process.is_none()
+                && rule.product.is_none()
+                && rule.steam_app.is_none()
+            {
+                warn!("{}", tr!("rules.no_matcher", i + 1));
+                continue;
+            }
+            if rule.match_children && rule.process.is_none() {
+                warn!("{}", tr!("rules.children_without_process", i + 1));
+            }
+
+            let title = match rule.title.as_deref().map(Regex::new).transpose() {
+                Ok(title) => title,
+                Err(e) => {
+                    warn!("{}", tr!("rules.invalid_title", i + 1, e));
+                    continue;
+                }
+            };
+
+            rules.push(CompiledRule {
+                class: rule.class.clone(),
+                title,
+                process: rule.process.as_deref().map(|p| ProcessPattern {
+                    glob: glob(p),
+                    path: is_path_pattern(p),
+                }),
+                product: rule.product.as_deref().map(glob),
+                steam_app: rule.steam_app,
+                match_children: rule.match_children,
+                action: rule.action,
+                overrides: rule.overrides.clone(),
+            });
+        }
+
+        Self {
+            rules,
+            whitelist_only: config.detection.whitelist_only,
+            transient_processes: config
+                .detection
+                .transient_processes
+                .iter()
+                .map(|p| p.to_lowercase())
+                .collect(),
+            transient_classes: config.detection.transient_classes.clone(),
+            browsers: if config.detection.ignore_browsers {
+                config
+                    .detection
+                    .browsers
+                    .iter()
+                    .map(|p| p.to_lowercase())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            projector_processes: if config.obs.hold_for_projectors {
+                config
+                    .obs
+                    .processes
+                    .iter()
+                    .map(|p| p.to_lowercase())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            projector_titles: config
+                .obs
+                .projector_titles
+                .iter()
+                .map(|t| t.to_lowercase())
+                .collect(),
+            game_classes: ENGINE_CLASSES
+                .iter()
+                .map(|c| c.to_string())
+                .chain(config.detection.game_classes.iter().cloned())
+                .collect(),
+            game_launchers: LAUNCHERS
+                .iter()
+                .map(|p| p.to_string())
+                .chain(
+                    config
+                        .detection
+                        .game_launchers
+                        .iter()
+                        .map(|p| p.to_lowercase()),
+                )
+                .collect(),
+            only_known_games: config.detection.only_known_games,
+            #[cfg(feature = "rhai")]
+            script: config.detection.script.as_deref().and_then(|path| {
+                let path = match Config::default_dir() {
+                    Some(dir) if path.is_relative() => dir.join(path),
+                    _ => path.to_path_buf(),
+                };
+                Script::load(&path)
+                    .map_err(|e| warn!("{}", tr!("script.load_failed", path.display(), e)))
+                    .ok()
+            }),
+        }
+    }
+
+    /// Returns true if any rule or the detection script looks at the window title
+    pub fn uses_titles(&self) -> bool {
+        self.uses_script() || self.rules.iter().any(|r| r.title.is_some())
+    }
+
+    /// Returns true if any rule, transient window, browser or OBS entry, or
+    /// the detection script, looks at the window's process
+    pub fn uses_processes(&self) -> bool {
+        self.uses_script()
+            || !self.transient_processes.is_empty()
+            || !self.browsers.is_empty()
+            || !self.projector_processes.is_empty()
+            || self
+                .rules
+                .iter()
+                .any(|r| r.process.is_some() || r.product.is_some())
+            || self.uses_steam_apps()
+    }
+
+    /// Returns true if any rule matches on the executable's full path
+    pub fn uses_paths(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.process.as_ref().is_some_and(|p| p.path))
+    }
+
+    /// Returns true if any rule matches on the product name
+    pub fn uses_products(&self) -> bool {
+        self.rules.iter().any(|r| r.product.is_some())
+    }
+
+    /// Returns true if any rule targets a Steam AppID
+    pub fn uses_steam_apps(&self) -> bool {
+        self.rules.iter().any(|r| r.steam_app.is_some())
+    }
+
+    /// Returns true if any rule also matches child processes, or
+    /// `only_known_games` needs the launchers
+    pub fn uses_ancestry(&self) -> bool {
+        self.only_known_games
+            || self
+                .rules
+                .iter()
+                .any(|r| r.process.as_ref().is_some_and(|p| !p.path) && r.match_children)
+    }
+
+    /// Returns true if a detection script is loaded, which looks at where the
+    /// window is as well
+    #[cfg(feature = "rhai")]
+    pub fn uses_script(&self) -> bool {
+        self.script.is_some()
+    }
+
+    #[cfg(not(feature = "rhai"))]
+    pub fn uses_script(&self) -> bool {
+        false
+    }
+
+    /// What the detection script says about the window, whether or not a
+    /// rule matches it; `None` without a script
+    #[cfg(feature = "rhai")]
+    pub fn script_verdict(&self, window: &WindowInfo) -> Option<Verdict> {
+        self.script
+            .as_ref()
+            .and_then(|script| script.verdict(window))
+    }
+
+    #[cfg(not(feature = "rhai"))]
+    pub fn script_verdict(&self, _window: &WindowInfo) -> Option<Verdict> {
+        None
+    }
+
+    /// Returns true if a rule decides about the window, rather than the
+    /// script or the defaults
+    pub fn has_match(&self, window: &WindowInfo) -> bool {
+        self.rules.iter().any(|r| r.matches(window))
+    }
+
+    /// Returns true for the transient windows (toasts, OSDs, shell flyouts,
+    /// OBS projectors) that briefly take the foreground and should neither end
+    /// nor start a lock
+    pub fn is_transient(&self, window: &WindowInfo) -> bool {
+        self.transient_processes.contains(&window.process)
+            || self.transient_classes.contains(&window.class)
+            || self.is_projector(window)
+    }
+
+    /// Returns true for OBS projector windows while `hold_for_projectors` is on
+    pub fn is_projector(&self, window: &WindowInfo) -> bool {
+        self.projector_processes.contains(&window.process) && {
+            let title = window.title.to_lowercase();
+            self.projector_titles
+                .iter()
+                .any(|word| title.contains(word))
+        }
+    }
+
+    /// Returns true for web browsers, which are ignored unless a rule matches
+    pub fn is_browser(&self, window: &WindowInfo) -> bool {
+        self.browsers.contains(&window.process)
+    }
+
+    /// Returns true for windows of a known game engine class, or of a process
+    /// started by a known launcher
+    pub fn is_known_game(&self, window: &WindowInfo) -> bool {
+        self.game_classes.contains(&window.class)
+            || window
+                .ancestors
+                .iter()
+                .any(|name| self.game_launchers.contains(name))
+    }
+
+    /// Decides whether a window may be locked: the first matching rule, else
+    /// the detection script, else the defaults
+    pub fn evaluate(&self, window: &WindowInfo) -> Verdict {
+        match self.rules.iter().find(|r| r.matches(window)) {
+            Some(rule) => match rule.action {
+                RuleAction::Lock | RuleAction::ConfineTo => Verdict::Lock,
+                RuleAction::Ignore => Verdict::Ignore,
+            },
+            None => match self.script_verdict(window) {
+                Some(verdict) => verdict,
+                None if self.whitelist_only || self.is_browser(window) => Verdict::Ignore,
+                None if self.only_known_games && !self.is_known_game(window) => Verdict::Ignore,
+                None => Verdict::Lock,
+            },
+        }
+    }
+
+    /// Lock settings overridden by the first matching rule
+    pub fn overrides(&self, window: &WindowInfo) -> LockOverrides {
+        self.rules
+            .iter()
+            .find(|r| r.matches(window))
+            .map(|rule| LockOverrides {
+                confine_to: rule
+                    .overrides
+                    .confine_to
+                    .filter(|_| rule.action == RuleAction::ConfineTo),
+                ..rule.overrides.clone()
+            })
+            .unwrap_or_default()
+    }
+}
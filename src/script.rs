@@ -0,0 +1,218 @@
+//! Detection scripts (`[detection] script`)
+//!
+//! A Rhai script decides about the windows no `[[rules]]` entry matches,
+//! before `whitelist_only`, `ignore_browsers` and `only_known_games` get a
+//! say. It runs whenever a new window comes to the front, and again when the
+//! title, rect or monitor of the one in front changes, with the window in
+//! `window`:
+//!
+//! ```rhai
+//! // Lock the main window of a simulator, never its instrument panels
+//! if window.exe == "dcs.exe" {
+//!     if window.rect.width < window.monitor.width { "ignore" } else { "lock" }
+//! }
+//! ```
+//!
+//! `window.class`, `window.title` and `window.exe` (lowercased) are strings,
+//! `window.rect` and `window.monitor` maps of `left`, `top`, `right`,
+//! `bottom`, `width` and `height`, or `()` when unknown. The script ends in
+//! `"lock"` or `"ignore"`, which count like the action of a matching rule;
+//! anything else leaves the window to the defaults. `print` writes to the
+//! log.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use windows::Win32::Foundation::RECT;
+
+use crate::rules::{Verdict, WindowInfo};
+use crate::{info, tr, warn};
+
+/// Operations a script may run per window, so a runaway loop can't stall
+/// the detection loop
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A compiled detection script
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").finish_non_exhaustive()
+    }
+}
+
+impl Script {
+    /// Reads and compiles the script at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::compile(&source)
+    }
+
+    /// Compiles a script from its source
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| info!("{}", tr!("script.print", text)));
+        engine.on_debug(|text, _, _| info!("{}", tr!("script.print", text)));
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(Self { engine, ast })
+    }
+
+    /// What the script says about `window`, `None` if it leaves the window to
+    /// the defaults or fails
+    pub fn verdict(&self, window: &WindowInfo) -> Option<Verdict> {
+        let mut scope = Scope::new();
+        scope.push_constant("window", window_map(window));
+        let result = match self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+        {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("{}", tr!("script.failed", window.process, e));
+                return None;
+            }
+        };
+        if result.is_unit() {
+            return None;
+        }
+        match result.into_string().as_deref() {
+            Ok("lock") => Some(Verdict::Lock),
+            Ok("ignore") => Some(Verdict::Ignore),
+            Ok(other) => {
+                warn!("{}", tr!("script.unexpected", other));
+                None
+            }
+            Err(kind) => {
+                warn!("{}", tr!("script.wrong_type", kind));
+                None
+            }
+        }
+    }
+}
+
+fn window_map(window: &WindowInfo) -> Map {
+    let mut map = Map::new();
+    map.insert("class".into(), window.class.clone().into());
+    map.insert("title".into(), window.title.clone().into());
+    map.insert("exe".into(), window.process.clone().into());
+    map.insert("rect".into(), rect_value(window.rect));
+    map.insert("monitor".into(), rect_value(window.monitor));
+    map
+}
+
+fn rect_value(rect: Option<RECT>) -> Dynamic {
+    let Some(rect) = rect else {
+        return Dynamic::UNIT;
+    };
+    let mut map = Map::new();
+    for (key, value) in [
+        ("left", rect.left),
+        ("top", rect.top),
+        ("right", rect.right),
+        ("bottom", rect.bottom),
+        ("width", rect.right - rect.left),
+        ("height", rect.bottom - rect.top),
+    ] {
+        map.insert(key.into(), i64::from(value).into());
+    }
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+    use crate::system::{MockSystem, MockWindow, WindowSystem};
+    use windows::Win32::Foundation::HWND;
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+    const MAIN: isize = 0x100;
+    const PANEL: isize = 0x200;
+    const OTHER: isize = 0x300;
+
+    /// A simulator with a fullscreen main window and a smaller instrument
+    /// panel, and a game of some other program
+    fn desktop() -> MockSystem {
+        let system = MockSystem::new(MONITOR);
+        for (hwnd, title, process, rect) in [
+            (MAIN, "DCS", "DCS.exe", MONITOR),
+            (
+                PANEL,
+                "Instruments",
+                "DCS.exe",
+                RECT {
+                    left: 100,
+                    top: 100,
+                    right: 900,
+                    bottom: 700,
+                },
+            ),
+            (OTHER, "Game", "Game.exe", MONITOR),
+        ] {
+            system.add_window(
+                hwnd,
+                MockWindow {
+                    class: "SimWindow".to_string(),
+                    title: title.to_string(),
+                    pid: hwnd as u32,
+                    process: process.to_string(),
+                    rect,
+                    ..Default::default()
+                },
+            );
+        }
+        system
+    }
+
+    fn verdict(script: &str, hwnd: isize) -> Option<Verdict> {
+        let system = desktop();
+        let window = system.window_info(HWND(hwnd as *mut _), &RuleSet::default());
+        Script::compile(script).unwrap().verdict(&window)
+    }
+
+    #[test]
+    fn decides_by_the_window_and_its_monitor() {
+        let script = r#"
+            if window.exe == "dcs.exe" {
+                if window.rect.width < window.monitor.width { "ignore" } else { "lock" }
+            }
+        "#;
+        assert_eq!(verdict(script, MAIN), Some(Verdict::Lock));
+        assert_eq!(verdict(script, PANEL), Some(Verdict::Ignore));
+        assert_eq!(verdict(script, OTHER), None);
+    }
+
+    #[test]
+    fn sees_the_class_and_title() {
+        let script = r#"
+            if window.class == "SimWindow" && window.title.contains("Instr") { "ignore" }
+        "#;
+        assert_eq!(verdict(script, PANEL), Some(Verdict::Ignore));
+        assert_eq!(verdict(script, MAIN), None);
+    }
+
+    #[test]
+    fn anything_but_a_verdict_leaves_the_window_to_the_defaults() {
+        assert_eq!(verdict(r#""maybe""#, MAIN), None);
+        assert_eq!(verdict("42", MAIN), None);
+        // Runtime errors and runaway loops too
+        assert_eq!(verdict("window.rect.depth + 1", MAIN), None);
+        assert_eq!(verdict("loop {}", MAIN), None);
+    }
+
+    #[test]
+    fn rejects_a_script_that_does_not_compile() {
+        assert!(Script::compile("if {").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Session state queries
+
+use std::time::Duration;
+
+use windows::core::{w, PCWSTR};
+use windows::Win32::Devices::Display::{
+    GetDisplayConfigBufferSizes, QueryDisplayConfig, DISPLAYCONFIG_MODE_INFO,
+    DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_TOPOLOGY_CLONE, DISPLAYCONFIG_TOPOLOGY_ID,
+    QDC_DATABASE_CURRENT,
+};
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Foundation::{BOOL, ERROR_SUCCESS};
+use windows::Win32::System::StationsAndDesktops::{
+    CloseDesktop, GetUserObjectInformationW, OpenInputDesktop, DESKTOP_CONTROL_FLAGS,
+    DESKTOP_READOBJECTS, UOI_NAME,
+};
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+use windows::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_PRESENTATION_MODE};
+use windows::Win32::UI::WindowsAndMessaging::{
+    FindWindowW, GetSystemMetrics, IsWindowVisible, SystemParametersInfoW, SM_REMOTESESSION,
+    SPI_GETSCREENREADER, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+};
+
+/// An assistive tool that a confined cursor would get in the way of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssistiveTool {
+    /// Windows Magnifier, in any view
+    Magnifier,
+    /// Narrator or another screen reader
+    ScreenReader,
+    OnScreenKeyboard,
+}
+
+/// Checks if the current session is a remote desktop session
+pub fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// Checks if the desktop receiving input is the normal user desktop, as
+/// opposed to the secure desktop used by UAC prompts and the lock screen
+pub fn is_input_desktop_default() -> bool {
+    unsafe {
+        // Opening the secure desktop is denied, which is as good as an answer
+        let Ok(desktop) = OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_READOBJECTS)
+        else {
+            return false;
+        };
+
+        let mut name = [0u16; 64];
+        let result = GetUserObjectInformationW(
+            HANDLE(desktop.0),
+            UOI_NAME,
+            Some(name.as_mut_ptr().cast()),
+            (name.len() * 2) as u32,
+            None,
+        );
+        let _ = CloseDesktop(desktop);
+
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        result.is_ok() && String::from_utf16_lossy(&name[..len]).eq_ignore_ascii_case("Default")
+    }
+}
+
+/// The first assistive tool found running in this session
+pub fn get_assistive_tool() -> Option<AssistiveTool> {
+    unsafe {
+        // The Magnifier toolbar exists in every view, even when collapsed
+        if FindWindowW(w!("MagUIClass"), PCWSTR::null()).is_ok_and(|hwnd| !hwnd.0.is_null()) {
+            return Some(AssistiveTool::Magnifier);
+        }
+
+        // Set by Narrator and by third-party screen readers alike
+        let mut screen_reader = BOOL(0);
+        if SystemParametersInfoW(
+            SPI_GETSCREENREADER,
+            0,
+            Some(&mut screen_reader as *mut BOOL as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .is_ok()
+            && screen_reader.as_bool()
+        {
+            return Some(AssistiveTool::ScreenReader);
+        }
+
+        if FindWindowW(w!("OSKMainClass"), PCWSTR::null())
+            .is_ok_and(|hwnd| !hwnd.0.is_null() && IsWindowVisible(hwnd).as_bool())
+        {
+            return Some(AssistiveTool::OnScreenKeyboard);
+        }
+    }
+    None
+}
+
+/// Checks if the user is presenting: Windows presentation mode is on, or the
+/// displays are duplicated as for a projector
+pub fn is_presenting() -> bool {
+    if unsafe { SHQueryUserNotificationState() } == Ok(QUNS_PRESENTATION_MODE) {
+        return true;
+    }
+
+    unsafe {
+        let (mut paths, mut modes) = (0u32, 0u32);
+        if GetDisplayConfigBufferSizes(QDC_DATABASE_CURRENT, &mut paths, &mut modes)
+            != ERROR_SUCCESS
+        {
+            return false;
+        }
+        let mut path_array = vec![DISPLAYCONFIG_PATH_INFO::default(); paths as usize];
+        let mut mode_array = vec![DISPLAYCONFIG_MODE_INFO::default(); modes as usize];
+        let mut topology = DISPLAYCONFIG_TOPOLOGY_ID::default();
+        QueryDisplayConfig(
+            QDC_DATABASE_CURRENT,
+            &mut paths,
+            path_array.as_mut_ptr(),
+            &mut modes,
+            mode_array.as_mut_ptr(),
+            Some(&mut topology),
+        ) == ERROR_SUCCESS
+            && topology == DISPLAYCONFIG_TOPOLOGY_CLONE
+    }
+}
+
+/// Time since the last keyboard or mouse input in this session
+///
+/// Controllers don't count as input here.
+pub fn get_input_idle_time() -> Duration {
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+        return Duration::ZERO;
+    }
+    // Both wrap around after 49.7 days
+    Duration::from_millis(unsafe { GetTickCount() }.wrapping_sub(info.dwTime) as u64)
+}
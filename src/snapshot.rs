@@ -0,0 +1,249 @@
+//! Snapshots of what detection sees, for bug reports
+//!
+//! "It didn't lock" is hard to reproduce on someone else's desktop. A snapshot
+//! holds every visible window with the facts the rules and the fullscreen
+//! check look at, the monitors, the cursor and its clip, the engine's state
+//! and the config file, as one JSON file to attach to an issue. The running
+//! engine writes it to [`snapshot_path`] on the `[hotkeys] snapshot` hotkey or
+//! the `snapshot` command.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use windows::Win32::Foundation::{HWND, RECT};
+
+use crate::config::Config;
+use crate::json;
+use crate::rules::{RuleSet, Verdict};
+use crate::system::WindowSystem;
+
+/// Version of the snapshot format
+pub const SCHEMA: i64 = 1;
+
+/// Where the engine writes snapshots
+pub fn snapshot_path() -> Option<PathBuf> {
+    Config::default_dir().map(|dir| dir.join("snapshot.json"))
+}
+
+/// The engine's side of a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineState {
+    /// `locked`, `paused`, ... as in the status report
+    pub state: String,
+    /// The lock state machine's state, in Rust debug notation
+    pub machine: String,
+    /// Name of the active `[[profiles]]` entry
+    pub profile: Option<String>,
+    /// Why locking is suspended, if it is
+    pub suspensions: Vec<String>,
+    /// Monitor held by the manual lock
+    pub manual_lock: Option<RECT>,
+    /// Process the clip was yielded to
+    pub yielded_to: Option<u32>,
+    /// Window the cached rule verdict belongs to
+    pub tracked_hwnd: isize,
+}
+
+impl EngineState {
+    fn to_json(&self) -> String {
+        let mut out = format!(
+            "{{\"state\":{},\"machine\":{},\"profile\":{},\"suspensions\":[",
+            json::string(&self.state),
+            json::string(&self.machine),
+            self.profile
+                .as_deref()
+                .map_or("null".to_string(), json::string)
+        );
+        let suspensions: Vec<String> = self.suspensions.iter().map(|s| json::string(s)).collect();
+        out.push_str(&suspensions.join(","));
+        let _ = write!(
+            out,
+            "],\"manual_lock\":{},\"yielded_to\":{},\"tracked_hwnd\":{}}}",
+            rect_json(self.manual_lock),
+            self.yielded_to
+                .map_or("null".to_string(), |pid| pid.to_string()),
+            self.tracked_hwnd
+        );
+        out
+    }
+}
+
+/// Captures the desktop as `system` sees it, with windows judged by `rules`
+/// and `tolerance`; `engine` is `None` when no engine is running
+pub fn capture<S: WindowSystem>(
+    system: &S,
+    rules: &RuleSet,
+    tolerance: i32,
+    engine: Option<&EngineState>,
+    config: Option<&str>,
+) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let foreground = system.foreground_window();
+    let mut out = format!(
+        "{{\"schema\":{},\"version\":{},\"time\":{},\"machine\":{},\"foreground\":{}",
+        SCHEMA,
+        json::string(env!("CARGO_PKG_VERSION")),
+        time,
+        Config::machine_name().map_or("null".to_string(), |name| json::string(&name)),
+        foreground.0 as isize
+    );
+    let _ = write!(
+        out,
+        ",\"cursor\":{},\"clip\":{},\"virtual_screen\":{},\"monitors\":[",
+        system
+            .cursor_pos()
+            .map_or("null".to_string(), |pos| format!("[{},{}]", pos.x, pos.y)),
+        rect_json(system.cursor_clip()),
+        rect_json(Some(system.virtual_screen_rect()))
+    );
+    let monitors: Vec<String> = system
+        .monitors()
+        .iter()
+        .map(|monitor| {
+            format!(
+                "{{\"device\":{},\"id\":{},\"rect\":{}}}",
+                json::string(&monitor.device),
+                json::string(&monitor.id),
+                rect_json(Some(monitor.rect))
+            )
+        })
+        .collect();
+    out.push_str(&monitors.join(","));
+
+    out.push_str("],\"windows\":[");
+    let mut windows = system.windows();
+    if !foreground.0.is_null() && !windows.contains(&foreground) {
+        windows.insert(0, foreground);
+    }
+    let windows: Vec<String> = windows
+        .into_iter()
+        .map(|hwnd| window_json(system, rules, tolerance, hwnd))
+        .collect();
+    out.push_str(&windows.join(","));
+
+    let _ = write!(
+        out,
+        "],\"engine\":{},\"config\":{}}}",
+        engine.map_or("null".to_string(), EngineState::to_json),
+        config.map_or("null".to_string(), json::string)
+    );
+    out
+}
+
+fn window_json<S: WindowSystem>(system: &S, rules: &RuleSet, tolerance: i32, hwnd: HWND) -> String {
+    let info = system.window_info(hwnd, rules);
+    let pid = system.window_process_id(hwnd);
+    let process = system.process_name(pid).unwrap_or_default();
+    let verdict = match rules.evaluate(&info) {
+        Verdict::Lock => "lock",
+        Verdict::Ignore => "ignore",
+    };
+    format!(
+        "{{\"hwnd\":{},\"title\":{},\"class\":{},\"pid\":{},\"process\":{},\"path\":{},\
+         \"product\":{},\"rect\":{},\"client\":{},\"monitor\":{},\"minimized\":{},\
+         \"cloaked\":{},\"other_desktop\":{},\"switcher\":{},\"fullscreen\":{},\"verdict\":\"{}\"}}",
+        hwnd.0 as isize,
+        json::string(&system.window_title(hwnd)),
+        json::string(&info.class),
+        pid,
+        json::string(&process),
+        json::string(&info.path),
+        info.product
+            .as_deref()
+            .map_or("null".to_string(), json::string),
+        rect_json(system.window_rect(hwnd)),
+        rect_json(system.client_rect(hwnd)),
+        rect_json(system.window_monitor_rect(hwnd)),
+        system.is_minimized(hwnd),
+        system.is_cloaked(hwnd),
+        !system.is_on_current_desktop(hwnd),
+        system
+            .task_switcher(hwnd)
+            .map_or("null".to_string(), |switcher| json::string(&format!(
+                "{:?}",
+                switcher
+            ))),
+        rect_json(system.fullscreen_rect(hwnd, tolerance)),
+        verdict
+    )
+}
+
+/// A rect as `[left, top, right, bottom]`, or `null`
+fn rect_json(rect: Option<RECT>) -> String {
+    rect.map_or("null".to_string(), |r| {
+        format!("[{},{},{},{}]", r.left, r.top, r.right, r.bottom)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use crate::system::{MockSystem, MockWindow};
+
+    #[test]
+    fn captures_windows_monitors_and_engine_state_as_json() {
+        let monitor = RECT {
+            left: 0,
+            top: 0,
+            right: 1920,
+            bottom: 1080,
+        };
+        let system = MockSystem::new(monitor);
+        system.add_window(
+            0x100,
+            MockWindow {
+                class: "UnityWndClass".to_string(),
+                title: "My \"Game\"".to_string(),
+                pid: 10,
+                process: "Game.exe".to_string(),
+                rect: monitor,
+                ..Default::default()
+            },
+        );
+        system.set_foreground(0x100);
+        let engine = EngineState {
+            state: "locked".to_string(),
+            machine: "Locked { hwnd: 256 }".to_string(),
+            suspensions: vec!["Idle".to_string()],
+            ..Default::default()
+        };
+        let rules = RuleSet::from_config(&Config::default());
+        let text = capture(&system, &rules, 5, Some(&engine), Some("[cursor]\n"));
+
+        let snapshot = json::parse(&text).unwrap();
+        assert_eq!(snapshot.get("schema"), Some(&json::Value::Number(1.0)));
+        assert_eq!(
+            snapshot.get("foreground"),
+            Some(&json::Value::Number(256.0))
+        );
+        assert_eq!(
+            snapshot.get("config").and_then(json::Value::as_str),
+            Some("[cursor]\n")
+        );
+        let windows = snapshot
+            .get("windows")
+            .and_then(json::Value::as_array)
+            .unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(
+            windows[0].get("title").and_then(json::Value::as_str),
+            Some("My \"Game\"")
+        );
+        assert_eq!(
+            windows[0].get("fullscreen"),
+            windows[0].get("rect"),
+            "{}",
+            text
+        );
+        let engine = snapshot.get("engine").unwrap();
+        assert_eq!(
+            engine.get("state").and_then(json::Value::as_str),
+            Some("locked")
+        );
+        assert_eq!(engine.get("manual_lock"), Some(&json::Value::Null));
+    }
+}
@@ -0,0 +1,326 @@
+//! Lock statistics
+//!
+//! Tracks how long the cursor stayed locked per executable and per monitor,
+//! plus a few counters, for the exit summary, the `status` command and the
+//! HTTP status endpoint.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::RECT;
+
+use crate::json;
+use crate::machine::Timing;
+
+/// The lock currently being timed
+#[derive(Debug)]
+struct ActiveLock {
+    app: String,
+    monitor: String,
+    since: Instant,
+}
+
+/// Counters and locked time for the current session
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+    per_app: BTreeMap<String, Duration>,
+    per_monitor: BTreeMap<String, Duration>,
+    locks: u32,
+    unlocks: u32,
+    reapplications: u32,
+    active: Option<ActiveLock>,
+    /// Engine state as reported to handoff queries (`locked`, `unlocked`, ...)
+    state: &'static str,
+    clip: Option<RECT>,
+    /// Engage and release thresholds in effect, for the reports
+    lock_delay: Duration,
+    grace: Duration,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            per_app: BTreeMap::new(),
+            per_monitor: BTreeMap::new(),
+            locks: 0,
+            unlocks: 0,
+            reapplications: 0,
+            active: None,
+            state: "unlocked",
+            clip: None,
+            lock_delay: Duration::ZERO,
+            grace: Duration::ZERO,
+        }
+    }
+}
+
+impl Stats {
+    /// Starts timing a lock, ending the previous one if it is still running
+    pub fn lock_started(&mut self, app: String, monitor: String) {
+        self.lock_ended();
+        self.locks += 1;
+        self.active = Some(ActiveLock {
+            app,
+            monitor,
+            since: Instant::now(),
+        });
+    }
+
+    /// Stops timing the current lock, if any
+    pub fn lock_ended(&mut self) {
+        let Some(lock) = self.active.take() else {
+            return;
+        };
+        let elapsed = lock.since.elapsed();
+        *self.per_app.entry(lock.app).or_default() += elapsed;
+        *self.per_monitor.entry(lock.monitor).or_default() += elapsed;
+        self.unlocks += 1;
+    }
+
+    /// Counts a clip that had to be re-applied after another program changed it
+    pub fn clip_reapplied(&mut self) {
+        self.reapplications += 1;
+    }
+
+    /// Records the current engine state and clip rect for [`Stats::to_json`]
+    /// and [`Stats::to_metrics`]
+    pub fn set_state(&mut self, state: &'static str, clip: Option<RECT>) {
+        self.state = state;
+        self.clip = clip;
+    }
+
+    /// Engine state last recorded by [`Stats::set_state`]
+    pub fn state(&self) -> &'static str {
+        self.state
+    }
+
+    /// Clip rect last recorded by [`Stats::set_state`]
+    pub fn clip(&self) -> Option<RECT> {
+        self.clip
+    }
+
+    /// Locks and unlocks so far this session
+    pub fn counts(&self) -> (u32, u32) {
+        (self.locks, self.unlocks)
+    }
+
+    /// Records the engage and release thresholds the engine runs with
+    pub fn set_timing(&mut self, timing: &Timing) {
+        self.lock_delay = timing.lock_delay;
+        self.grace = timing.grace;
+    }
+
+    /// Locked time per app, counting a running lock up to now
+    fn app_times(&self) -> BTreeMap<String, Duration> {
+        let mut per_app = self.per_app.clone();
+        if let Some(ref lock) = self.active {
+            *per_app.entry(lock.app.clone()).or_default() += lock.since.elapsed();
+        }
+        per_app
+    }
+
+    /// The current state and counters as a JSON object
+    pub fn to_json(&self) -> String {
+        self.json_object(None)
+    }
+
+    /// [`Stats::to_json`] led by a `kind` field, as the stdio channel tags its
+    /// messages
+    pub fn to_json_of_kind(&self, kind: &str) -> String {
+        self.json_object(Some(kind))
+    }
+
+    fn json_object(&self, kind: Option<&str>) -> String {
+        let mut out = String::from("{");
+        if let Some(kind) = kind {
+            let _ = write!(out, "\"kind\":{},", json::string(kind));
+        }
+        let _ = write!(out, "\"state\":{}", json::string(self.state));
+        if let Some(ref lock) = self.active {
+            let _ = write!(
+                out,
+                ",\"process\":{},\"monitor\":{},\"locked_for_s\":{}",
+                json::string(&lock.app),
+                json::string(&lock.monitor),
+                lock.since.elapsed().as_secs()
+            );
+        }
+        if let Some(clip) = self.clip {
+            let _ = write!(
+                out,
+                ",\"clip\":[{},{},{},{}]",
+                clip.left, clip.top, clip.right, clip.bottom
+            );
+        }
+        let _ = write!(
+            out,
+            ",\"lock_delay_ms\":{},\"grace_ms\":{}",
+            self.lock_delay.as_millis(),
+            self.grace.as_millis()
+        );
+        let _ = write!(
+            out,
+            ",\"uptime_s\":{},\"locks\":{},\"unlocks\":{},\"clip_reapplications\":{},\"locked_time_s\":{{",
+            self.started.elapsed().as_secs(),
+            self.locks,
+            self.unlocks,
+            self.reapplications
+        );
+        for (i, (app, time)) in self.app_times().iter().enumerate() {
+            let comma = if i > 0 { "," } else { "" };
+            let _ = write!(out, "{}{}:{}", comma, json::string(app), time.as_secs());
+        }
+        out.push_str("}}");
+        out
+    }
+
+    /// The current state and counters in the Prometheus text format
+    pub fn to_metrics(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP smartlockcursor_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE smartlockcursor_{} {}", name, kind);
+            let _ = writeln!(out, "smartlockcursor_{}{}", name, value);
+        };
+        metric(
+            "locked",
+            "gauge",
+            "Whether the cursor is locked",
+            format!(" {}", u8::from(self.state == "locked")),
+        );
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Time since SmartLockCursor started",
+            format!(" {}", self.started.elapsed().as_secs()),
+        );
+        metric(
+            "locks_total",
+            "counter",
+            "Locks engaged",
+            format!(" {}", self.locks),
+        );
+        metric(
+            "unlocks_total",
+            "counter",
+            "Locks released",
+            format!(" {}", self.unlocks),
+        );
+        metric(
+            "clip_reapplications_total",
+            "counter",
+            "Clips re-applied after another program changed them",
+            format!(" {}", self.reapplications),
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP smartlockcursor_locked_seconds_total Time the cursor was locked per app"
+        );
+        let _ = writeln!(out, "# TYPE smartlockcursor_locked_seconds_total counter");
+        for (app, time) in self.app_times() {
+            let _ = writeln!(
+                out,
+                "smartlockcursor_locked_seconds_total{{app=\"{}\"}} {:.3}",
+                app.replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n"),
+                time.as_secs_f64()
+            );
+        }
+        out
+    }
+
+    /// Multi-line report, counting a running lock up to now
+    pub fn summary(&self) -> String {
+        let mut per_app = self.per_app.clone();
+        let mut per_monitor = self.per_monitor.clone();
+        if let Some(ref lock) = self.active {
+            *per_app.entry(lock.app.clone()).or_default() += lock.since.elapsed();
+            *per_monitor.entry(lock.monitor.clone()).or_default() += lock.since.elapsed();
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Running for {}: {} lock(s), {} unlock(s), {} clip re-application(s)",
+            format_duration(self.started.elapsed()),
+            self.locks,
+            self.unlocks,
+            self.reapplications
+        );
+        let _ = writeln!(
+            out,
+            "Locks after {}ms in front, releases after {}ms of grace",
+            self.lock_delay.as_millis(),
+            self.grace.as_millis()
+        );
+        if let Some(ref lock) = self.active {
+            let _ = writeln!(
+                out,
+                "Currently locked: {} for {}",
+                lock.app,
+                format_duration(lock.since.elapsed())
+            );
+        }
+        write_table(&mut out, "Locked time by app:", &per_app);
+        write_table(&mut out, "Locked time by monitor:", &per_monitor);
+        out
+    }
+}
+
+fn write_table(out: &mut String, heading: &str, times: &BTreeMap<String, Duration>) {
+    if times.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(out, "{}", heading);
+    // Longest first
+    let mut rows: Vec<_> = times.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1));
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, time) in rows {
+        let _ = writeln!(
+            out,
+            "  {:<width$}  {}",
+            name,
+            format_duration(*time),
+            width = width
+        );
+    }
+}
+
+/// Formats a duration as e.g. `1h 02m 05s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{self, Value};
+
+    #[test]
+    fn tags_the_status_object_with_its_kind() {
+        let mut stats = Stats::default();
+        stats.lock_started("game.exe".to_string(), "DISPLAY1".to_string());
+        stats.set_state("locked", None);
+
+        let status = json::parse(&stats.to_json_of_kind("status")).unwrap();
+        assert_eq!(status.get("kind").and_then(Value::as_str), Some("status"));
+        assert_eq!(status.get("state").and_then(Value::as_str), Some("locked"));
+        assert_eq!(status.get("locks"), Some(&Value::Number(1.0)));
+        assert_eq!(json::parse(&stats.to_json()).unwrap().get("kind"), None);
+    }
+}
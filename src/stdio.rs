@@ -0,0 +1,134 @@
+//! Line-based command channel on stdin/stdout (`--stdio`)
+//!
+//! Wrappers such as Stream Deck plugins or AutoHotkey scripts write commands
+//! (`pause`, `resume`, `toggle`, `status`) to stdin, one per line, and read
+//! JSON objects from stdout: every event in the event log format, and a
+//! `{"kind":"status",...}` reply after each command. The usual log lines move
+//! to stderr so stdout carries nothing but JSON. Closing stdin (or sending
+//! `quit`) releases the cursor and exits, so the engine never outlives its
+//! wrapper.
+
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::os::windows::io::FromRawHandle;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::System::Console::{
+    GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE,
+};
+use windows::Win32::System::Threading::{CreateEventW, SetEvent};
+
+use crate::json;
+
+/// A command read from stdin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdioCommand {
+    /// Release the cursor and stop locking until `resume`
+    Pause,
+    Resume,
+    /// `pause` if running, `resume` if paused
+    Toggle,
+    /// Just report the state
+    Status,
+    /// stdin was closed, which means the wrapper went away
+    Quit,
+}
+
+impl StdioCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        match line.trim().to_ascii_lowercase().as_str() {
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            "toggle" => Ok(Self::Toggle),
+            "status" => Ok(Self::Status),
+            "quit" | "exit" => Ok(Self::Quit),
+            other => Err(format!("Unknown command: {}", other)),
+        }
+    }
+}
+
+/// Reads commands on a background thread and writes JSON to the original stdout
+pub struct StdioChannel {
+    out: File,
+    event: HANDLE,
+    commands: Receiver<Result<StdioCommand, String>>,
+}
+
+impl StdioChannel {
+    /// Takes over stdin and stdout, pointing the process' stdout at stderr
+    pub fn open() -> windows::core::Result<Self> {
+        unsafe {
+            let stdout = GetStdHandle(STD_OUTPUT_HANDLE)?;
+            let stderr = GetStdHandle(STD_ERROR_HANDLE)?;
+            // Auto-reset, so every wake-up lines up with fresh commands
+            let event = CreateEventW(None, false, false, None)?;
+            SetStdHandle(STD_OUTPUT_HANDLE, stderr)?;
+
+            let (sender, commands) = mpsc::channel();
+            // Raw handles are plain pointers and not Send
+            let wake = event.0 as isize;
+            let spawned = thread::Builder::new()
+                .name("stdio".to_string())
+                .spawn(move || {
+                    for line in io::stdin().lock().lines() {
+                        let Ok(line) = line else { break };
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        if sender.send(StdioCommand::parse(&line)).is_err() {
+                            return;
+                        }
+                        let _ = SetEvent(HANDLE(wake as *mut _));
+                    }
+                    let _ = sender.send(Ok(StdioCommand::Quit));
+                    let _ = SetEvent(HANDLE(wake as *mut _));
+                });
+            if let Err(e) = spawned {
+                let _ = SetStdHandle(STD_OUTPUT_HANDLE, stdout);
+                let _ = CloseHandle(event);
+                return Err(windows::core::Error::from(e));
+            }
+
+            Ok(Self {
+                out: File::from_raw_handle(stdout.0),
+                event,
+                commands,
+            })
+        }
+    }
+
+    /// Event that becomes signaled when a command line was read
+    pub fn handle(&self) -> HANDLE {
+        self.event
+    }
+
+    /// Takes the lines received since the last call, with an error message
+    /// for each one that was not a command
+    pub fn take_commands(&self) -> Vec<Result<StdioCommand, String>> {
+        self.commands.try_iter().collect()
+    }
+
+    /// Writes one JSON object as a line; a closed pipe is ignored
+    pub fn send(&mut self, json: &str) {
+        let _ = writeln!(self.out, "{}", json);
+        let _ = self.out.flush();
+    }
+
+    /// Reports a line that was not understood
+    pub fn send_error(&mut self, message: &str) {
+        self.send(&format!(
+            "{{\"kind\":\"error\",\"message\":{}}}",
+            json::string(message)
+        ));
+    }
+}
+
+impl Drop for StdioChannel {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.event);
+        }
+    }
+}
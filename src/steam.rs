@@ -0,0 +1,256 @@
+//! Steam game lookup
+//!
+//! Finds the Steam app a game executable belongs to by reading the app
+//! manifests of every Steam library folder. An executable inside
+//! `steamapps\common\<installdir>` of a library is the app whose manifest names
+//! that install directory. Lookups are cached per executable, so only the first
+//! lock of a game touches the disk.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use windows::core::w;
+use windows::Win32::Foundation::ERROR_SUCCESS;
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_SZ};
+
+/// A game installed through Steam
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SteamApp {
+    /// The AppID, as in `store.steampowered.com/app/<id>`
+    pub id: u32,
+    /// Display name from the manifest
+    pub name: String,
+}
+
+static CACHE: Mutex<Option<HashMap<PathBuf, Option<SteamApp>>>> = Mutex::new(None);
+
+/// Finds the Steam app whose install directory contains `exe`
+pub fn find_app(exe: &Path) -> Option<SteamApp> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some(app) = cache.get(exe) {
+        return app.clone();
+    }
+    let app = steam_dir().and_then(|steam| {
+        libraries(&steam)
+            .iter()
+            .find_map(|library| app_in_library(library, exe))
+    });
+    cache.insert(exe.to_path_buf(), app.clone());
+    app
+}
+
+/// Where the Steam client is installed, from its registry key
+fn steam_dir() -> Option<PathBuf> {
+    let mut buffer = [0u16; 512];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!(r"Software\Valve\Steam"),
+            w!("SteamPath"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(PathBuf::from(String::from_utf16_lossy(&buffer[..len])))
+}
+
+/// The Steam install itself and every library folder it lists
+fn libraries(steam: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam.to_path_buf()];
+    if let Ok(text) = fs::read_to_string(steam.join("steamapps").join("libraryfolders.vdf")) {
+        for (key, value) in pairs(&text) {
+            let path = PathBuf::from(value);
+            if key == "path" && !libraries.contains(&path) {
+                libraries.push(path);
+            }
+        }
+    }
+    libraries
+}
+
+fn app_in_library(library: &Path, exe: &Path) -> Option<SteamApp> {
+    let steamapps = library.join("steamapps");
+    let common = steamapps.join("common");
+    let installdir = relative_first_component(&common, exe)?;
+
+    fs::read_dir(&steamapps)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            name.starts_with("appmanifest_") && name.ends_with(".acf")
+        })
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .find_map(|manifest| parse_manifest(&manifest, &installdir))
+}
+
+/// The directory right below `base` that `path` lies in, compared ignoring case
+fn relative_first_component(base: &Path, path: &Path) -> Option<String> {
+    let base = base
+        .to_string_lossy()
+        .replace('/', "\\")
+        .to_ascii_lowercase();
+    let path = path.to_string_lossy().replace('/', "\\");
+    let prefix = format!("{}\\", base.trim_end_matches('\\'));
+    // ASCII only, so byte offsets stay valid in the original
+    if !path.to_ascii_lowercase().starts_with(&prefix) {
+        return None;
+    }
+    path[prefix.len()..]
+        .split('\\')
+        .next()
+        .filter(|dir| !dir.is_empty())
+        .map(str::to_string)
+}
+
+/// The app an `appmanifest_<id>.acf` describes, if it installs to `installdir`
+fn parse_manifest(text: &str, installdir: &str) -> Option<SteamApp> {
+    let pairs = pairs(text);
+    let value = |wanted: &str| {
+        pairs
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.as_str())
+    };
+    if !value("installdir")?.eq_ignore_ascii_case(installdir) {
+        return None;
+    }
+    Some(SteamApp {
+        id: value("appid")?.parse().ok()?,
+        name: value("name")?.to_string(),
+    })
+}
+
+/// The `"key" "value"` pairs of a Valve KeyValues (VDF) file, all nesting
+/// levels flattened in file order
+fn pairs(text: &str) -> Vec<(String, String)> {
+    enum Token {
+        Text(String),
+        Open,
+        Close,
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        _ => value.push(c),
+                    }
+                }
+                tokens.push(Token::Text(value));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if let Token::Text(key) = token {
+            if let Some(Token::Text(_)) = tokens.peek() {
+                if let Some(Token::Text(value)) = tokens.next() {
+                    pairs.push((key, value));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+"AppState"
+{
+	"appid"		"1245620"
+	"name"		"ELDEN RING"
+	"installdir"		"ELDEN RING"
+	"UserConfig"
+	{
+		"language"		"english"
+	}
+}
+"#;
+
+    #[test]
+    fn reads_library_paths() {
+        let text = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"C:\\Program Files (x86)\\Steam"
+		"apps"
+		{
+			"228980"		"0"
+		}
+	}
+	"1"
+	{
+		"path"		"D:\\SteamLibrary"
+	}
+}
+"#;
+        let paths: Vec<_> = pairs(text)
+            .into_iter()
+            .filter(|(key, _)| key == "path")
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(paths, [r"C:\Program Files (x86)\Steam", r"D:\SteamLibrary"]);
+    }
+
+    #[test]
+    fn matches_the_manifest_by_install_dir() {
+        assert_eq!(
+            parse_manifest(MANIFEST, "elden ring"),
+            Some(SteamApp {
+                id: 1245620,
+                name: "ELDEN RING".to_string(),
+            })
+        );
+        assert_eq!(parse_manifest(MANIFEST, "Hades"), None);
+    }
+
+    #[test]
+    fn finds_the_install_dir_of_an_executable() {
+        let common = Path::new(r"D:\SteamLibrary\steamapps\common");
+        assert_eq!(
+            relative_first_component(
+                common,
+                Path::new(r"d:\steamlibrary\SteamApps\common\ELDEN RING\Game\eldenring.exe")
+            ),
+            Some("ELDEN RING".to_string())
+        );
+        assert_eq!(
+            relative_first_component(common, Path::new(r"C:\Games\eldenring.exe")),
+            None
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Supervisor for a stalled main loop
+//!
+//! The engine, the hooks and the message windows all live on the main thread,
+//! so one Win32 call that never returns (a hung window, a stuck driver) freezes
+//! the lock as it is, usually with the cursor still trapped. The main loop
+//! beats a [`Heartbeat`] on every turn, saying how long it may take until the
+//! next one; a supervisor thread calls `on_stall` once that is overdue.
+//! `ClipCursor` is global, so the clip can be released from there, but a
+//! thread stuck in a system call can't be stopped from outside, so the only
+//! fresh worker is a fresh process.
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the supervisor looks at the heartbeat
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// When the main loop is due to beat next
+#[derive(Debug)]
+pub struct Heartbeat {
+    start: Instant,
+    /// Milliseconds since `start` by which the next beat is due, or `NOT_DUE`
+    due: AtomicU64,
+    /// Milliseconds the last beat allowed
+    allowance: AtomicU64,
+}
+
+const NOT_DUE: u64 = u64::MAX;
+
+impl Heartbeat {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            start: now,
+            due: AtomicU64::new(NOT_DUE),
+            allowance: AtomicU64::new(0),
+        }
+    }
+
+    /// Reports the loop alive at `now`, with the next beat due within `within`,
+    /// or not watched until the next beat without a limit
+    pub fn beat(&self, now: Instant, within: Option<Duration>) {
+        let due = within.map_or(NOT_DUE, |within| {
+            self.allowance
+                .store(within.as_millis() as u64, Ordering::Relaxed);
+            (now.saturating_duration_since(self.start) + within).as_millis() as u64
+        });
+        self.due.store(due, Ordering::Relaxed);
+    }
+
+    /// Gives the loop the allowance of its last beat again, counted from `now`
+    fn extend(&self, now: Instant) {
+        let allowance = self.allowance.load(Ordering::Relaxed);
+        self.beat(now, Some(Duration::from_millis(allowance)));
+    }
+
+    /// How long the next beat is overdue at `now`, if it is
+    pub fn overdue(&self, now: Instant) -> Option<Duration> {
+        let due = self.due.load(Ordering::Relaxed);
+        let elapsed = now.saturating_duration_since(self.start).as_millis() as u64;
+        (due != NOT_DUE && elapsed > due).then(|| Duration::from_millis(elapsed - due))
+    }
+}
+
+/// Starts the supervisor thread for `heartbeat`; `on_stall` runs on it, with
+/// how long the beat is overdue, the first time it is
+pub fn spawn(
+    heartbeat: Arc<Heartbeat>,
+    on_stall: impl FnOnce(Duration) + Send + 'static,
+) -> io::Result<()> {
+    thread::Builder::new()
+        .name("supervisor".to_string())
+        .spawn(move || {
+            let mut last = Instant::now();
+            loop {
+                thread::sleep(CHECK_INTERVAL);
+                let now = Instant::now();
+                // Waking up long after we meant to means the PC was asleep, and
+                // the main loop gets its turn again before it counts as stalled
+                let asleep = now.duration_since(last) > CHECK_INTERVAL * 4;
+                last = now;
+                match heartbeat.overdue(now) {
+                    Some(_) if asleep => heartbeat.extend(now),
+                    Some(late) => return on_stall(late),
+                    None => {}
+                }
+            }
+        })
+        .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_beat_is_overdue_once_its_allowance_runs_out() {
+        let start = Instant::now();
+        let heartbeat = Heartbeat::new(start);
+        assert_eq!(heartbeat.overdue(start + Duration::from_secs(60)), None);
+
+        heartbeat.beat(start, Some(Duration::from_secs(2)));
+        assert_eq!(heartbeat.overdue(start + Duration::from_secs(2)), None);
+        assert_eq!(
+            heartbeat.overdue(start + Duration::from_secs(3)),
+            Some(Duration::from_secs(1))
+        );
+
+        heartbeat.extend(start + Duration::from_secs(3));
+        assert_eq!(heartbeat.overdue(start + Duration::from_secs(5)), None);
+
+        // Unwatched until the next beat
+        heartbeat.beat(start + Duration::from_secs(3), None);
+        assert_eq!(heartbeat.overdue(start + Duration::from_secs(60)), None);
+    }
+}
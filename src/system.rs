@@ -0,0 +1,868 @@
+//! The slice of Win32 the lock engine talks to
+//!
+//! [`WindowSystem`] covers everything the engine asks about windows, monitors
+//! and the cursor, and everything it does to them. [`Win32System`] forwards to
+//! the real API; [`MockSystem`] is a scripted desktop for tests, so grace
+//! periods and the Alt+Tab flow can be exercised without a display.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::{HWND, POINT, RECT};
+use windows::Win32::Graphics::Gdi::HMONITOR;
+use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+use crate::cursor::{
+    clip_cursor_to_rect, get_cursor_clip, get_cursor_pos, hide_system_cursors, is_cursor_showing,
+    mouse_button_pressed, release_cursor_clip, restore_system_cursors, set_cursor_pos, ClipError,
+};
+use crate::elevation::is_process_elevated;
+use crate::events::Switcher;
+use crate::gamepad::GamepadMonitor;
+use crate::monitor::{
+    get_window_monitor_rect, reaches_other_monitor, spanned_rect, virtual_screen_rect,
+    with_monitors, MonitorBounds, FRAME_OVERHANG,
+};
+use crate::process::{
+    find_running_process, get_process_name, get_process_path, has_module, is_process_running,
+};
+use crate::rules::{RuleSet, WindowInfo};
+use crate::session::{
+    get_assistive_tool, get_input_idle_time, is_input_desktop_default, is_presenting,
+    is_remote_session, AssistiveTool,
+};
+use crate::steam::{find_app, SteamApp};
+use crate::window::{
+    check_fullscreen, check_work_area, current_window_process_id, enumerate_windows,
+    get_client_screen_rect, get_task_switcher, get_window_process_id, get_window_rect,
+    get_window_title, is_window, is_window_click_through, is_window_cloaked, is_window_minimized,
+    is_window_visible, VirtualDesktops,
+};
+
+/// Window, monitor and cursor operations used by the lock engine
+pub trait WindowSystem {
+    /// The foreground window, null when there is none
+    fn foreground_window(&self) -> HWND;
+    /// The visible top-level windows, topmost first
+    fn windows(&self) -> Vec<HWND>;
+    /// Which switcher `hwnd` is, if it is the Alt+Tab switcher or Task View
+    fn task_switcher(&self, hwnd: HWND) -> Option<Switcher>;
+    fn window_title(&self, hwnd: HWND) -> String;
+    /// The facts about `hwnd` that `rules` look at
+    fn window_info(&self, hwnd: HWND, rules: &RuleSet) -> WindowInfo;
+    fn window_process_id(&self, hwnd: HWND) -> u32;
+    /// Like [`window_process_id`](Self::window_process_id), but asked of
+    /// Windows every time, to tell a recycled handle from the window it named
+    fn current_window_process_id(&self, hwnd: HWND) -> u32;
+    /// Whether `hwnd` still names a window
+    fn is_window(&self, hwnd: HWND) -> bool;
+    /// Whether `hwnd` is shown rather than hidden
+    fn is_visible(&self, hwnd: HWND) -> bool;
+    fn is_minimized(&self, hwnd: HWND) -> bool;
+    /// Whether clicks on `hwnd` go through to the windows below
+    fn is_click_through(&self, hwnd: HWND) -> bool;
+    /// Whether `hwnd` is cloaked, e.g. because it is on another virtual desktop
+    fn is_cloaked(&self, hwnd: HWND) -> bool;
+    /// Whether `hwnd` is on the virtual desktop being shown
+    fn is_on_current_desktop(&self, hwnd: HWND) -> bool;
+    fn process_name(&self, pid: u32) -> Option<String>;
+    fn is_process_running(&self, pid: u32) -> bool;
+    /// A running process whose executable is one of `names`, ignoring case
+    fn find_process(&self, names: &[String]) -> Option<String>;
+    /// Whether a process has loaded one of the modules `names`, ignoring case
+    fn has_module(&self, pid: u32, names: &[String]) -> bool;
+    /// The Steam game a process was installed as, if any
+    fn steam_app(&self, pid: u32) -> Option<SteamApp>;
+    /// `None` when the elevation can't be read, like [`is_process_elevated`]
+    fn is_process_elevated(&self, pid: u32) -> Option<bool>;
+    /// The monitor rect if `hwnd` is fullscreen on it, or the union of the
+    /// monitors it is fullscreen across
+    fn fullscreen_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT>;
+    /// The work area of `hwnd`'s monitor if the window fills at least that
+    fn work_area_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT>;
+    /// The outer rect of `hwnd`
+    fn window_rect(&self, hwnd: HWND) -> Option<RECT>;
+    /// The client area of `hwnd` in screen coordinates
+    fn client_rect(&self, hwnd: HWND) -> Option<RECT>;
+    /// The monitor `hwnd` is mostly on
+    fn window_monitor_rect(&self, hwnd: HWND) -> Option<RECT>;
+    /// The bounding rect of all monitors
+    fn virtual_screen_rect(&self) -> RECT;
+    /// All monitors in enumeration order
+    fn monitors(&self) -> Vec<MonitorBounds>;
+    fn cursor_pos(&self) -> Option<POINT>;
+    fn set_cursor_pos(&self, x: i32, y: i32) -> bool;
+    /// Whether a mouse button is down or was pressed since the last call
+    fn mouse_button_pressed(&self) -> bool;
+    /// The current clip rect (the whole virtual screen when nothing is clipped)
+    fn cursor_clip(&self) -> Option<RECT>;
+    fn clip_cursor(&self, rect: &RECT) -> Result<(), ClipError>;
+    fn release_clip(&self) -> bool;
+    /// Whether the cursor is drawn, false when the foreground app hid it
+    fn cursor_showing(&self) -> bool;
+    /// Makes the cursor invisible everywhere
+    fn hide_cursor(&self) -> bool;
+    /// Undoes [`WindowSystem::hide_cursor`]
+    fn show_cursor(&self);
+    /// The time the lock timers run on
+    fn now(&self) -> Instant;
+    /// Time since the last keyboard or mouse input
+    fn input_idle_time(&self) -> Duration;
+    /// Time since a controller was last used, `None` if none has been
+    fn gamepad_idle_time(&self) -> Option<Duration>;
+    /// An assistive tool running in the session, if any
+    fn assistive_tool(&self) -> Option<AssistiveTool>;
+    /// Whether presentation mode is on or the displays are duplicated
+    fn is_presenting(&self) -> bool;
+    /// Whether the session is shown over Remote Desktop
+    fn is_remote_session(&self) -> bool;
+    /// Whether input goes to the user's desktop rather than the secure one
+    /// of UAC prompts and the lock screen
+    fn is_input_desktop_default(&self) -> bool;
+}
+
+/// The real thing
+///
+/// In a dry run (`--dry-run`) it only pretends to clip, hide or move the
+/// cursor, remembering the clip it was asked for so the engine behaves exactly
+/// as it would otherwise.
+#[derive(Debug, Default)]
+pub struct Win32System {
+    dry_run: bool,
+    pretend_clip: Cell<Option<RECT>>,
+    desktops: VirtualDesktops,
+    gamepads: RefCell<GamepadMonitor>,
+}
+
+impl Win32System {
+    /// A system that never touches the cursor
+    pub fn dry_run() -> Self {
+        Self {
+            dry_run: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl WindowSystem for Win32System {
+    fn foreground_window(&self) -> HWND {
+        unsafe { GetForegroundWindow() }
+    }
+
+    fn windows(&self) -> Vec<HWND> {
+        enumerate_windows(false)
+    }
+
+    fn task_switcher(&self, hwnd: HWND) -> Option<Switcher> {
+        get_task_switcher(hwnd)
+    }
+
+    fn window_title(&self, hwnd: HWND) -> String {
+        get_window_title(hwnd)
+    }
+
+    fn window_info(&self, hwnd: HWND, rules: &RuleSet) -> WindowInfo {
+        WindowInfo::query(hwnd, rules)
+    }
+
+    fn window_process_id(&self, hwnd: HWND) -> u32 {
+        get_window_process_id(hwnd)
+    }
+
+    fn current_window_process_id(&self, hwnd: HWND) -> u32 {
+        current_window_process_id(hwnd)
+    }
+
+    fn is_window(&self, hwnd: HWND) -> bool {
+        is_window(hwnd)
+    }
+
+    fn is_visible(&self, hwnd: HWND) -> bool {
+        is_window_visible(hwnd)
+    }
+
+    fn is_minimized(&self, hwnd: HWND) -> bool {
+        is_window_minimized(hwnd)
+    }
+
+    fn is_click_through(&self, hwnd: HWND) -> bool {
+        is_window_click_through(hwnd)
+    }
+
+    fn is_cloaked(&self, hwnd: HWND) -> bool {
+        is_window_cloaked(hwnd)
+    }
+
+    fn is_on_current_desktop(&self, hwnd: HWND) -> bool {
+        self.desktops.is_on_current(hwnd)
+    }
+
+    fn process_name(&self, pid: u32) -> Option<String> {
+        get_process_name(pid)
+    }
+
+    fn is_process_running(&self, pid: u32) -> bool {
+        is_process_running(pid)
+    }
+
+    fn find_process(&self, names: &[String]) -> Option<String> {
+        find_running_process(names)
+    }
+
+    fn has_module(&self, pid: u32, names: &[String]) -> bool {
+        has_module(pid, names)
+    }
+
+    fn steam_app(&self, pid: u32) -> Option<SteamApp> {
+        get_process_path(pid).and_then(|path| find_app(&path))
+    }
+
+    fn is_process_elevated(&self, pid: u32) -> Option<bool> {
+        is_process_elevated(pid)
+    }
+
+    fn fullscreen_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT> {
+        check_fullscreen(hwnd, tolerance)
+    }
+
+    fn work_area_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT> {
+        check_work_area(hwnd, tolerance)
+    }
+
+    fn window_rect(&self, hwnd: HWND) -> Option<RECT> {
+        get_window_rect(hwnd)
+    }
+
+    fn client_rect(&self, hwnd: HWND) -> Option<RECT> {
+        get_client_screen_rect(hwnd)
+    }
+
+    fn window_monitor_rect(&self, hwnd: HWND) -> Option<RECT> {
+        get_window_monitor_rect(hwnd)
+    }
+
+    fn virtual_screen_rect(&self) -> RECT {
+        virtual_screen_rect()
+    }
+
+    fn monitors(&self) -> Vec<MonitorBounds> {
+        with_monitors(<[MonitorBounds]>::to_vec)
+    }
+
+    fn cursor_pos(&self) -> Option<POINT> {
+        get_cursor_pos()
+    }
+
+    fn set_cursor_pos(&self, x: i32, y: i32) -> bool {
+        self.dry_run || set_cursor_pos(x, y)
+    }
+
+    fn mouse_button_pressed(&self) -> bool {
+        mouse_button_pressed()
+    }
+
+    fn cursor_clip(&self) -> Option<RECT> {
+        if self.dry_run {
+            return Some(self.pretend_clip.get().unwrap_or_else(virtual_screen_rect));
+        }
+        get_cursor_clip()
+    }
+
+    fn clip_cursor(&self, rect: &RECT) -> Result<(), ClipError> {
+        if self.dry_run {
+            self.pretend_clip.set(Some(*rect));
+            return Ok(());
+        }
+        clip_cursor_to_rect(rect)
+    }
+
+    fn release_clip(&self) -> bool {
+        if self.dry_run {
+            self.pretend_clip.set(None);
+            return true;
+        }
+        release_cursor_clip()
+    }
+
+    fn cursor_showing(&self) -> bool {
+        is_cursor_showing()
+    }
+
+    fn hide_cursor(&self) -> bool {
+        !self.dry_run && hide_system_cursors()
+    }
+
+    fn show_cursor(&self) {
+        if !self.dry_run {
+            restore_system_cursors();
+        }
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn input_idle_time(&self) -> Duration {
+        get_input_idle_time()
+    }
+
+    fn gamepad_idle_time(&self) -> Option<Duration> {
+        self.gamepads.borrow_mut().poll().map(|last| last.elapsed())
+    }
+
+    fn assistive_tool(&self) -> Option<AssistiveTool> {
+        get_assistive_tool()
+    }
+
+    fn is_presenting(&self) -> bool {
+        is_presenting()
+    }
+
+    fn is_remote_session(&self) -> bool {
+        is_remote_session()
+    }
+
+    fn is_input_desktop_default(&self) -> bool {
+        is_input_desktop_default()
+    }
+}
+
+/// A window on the [`MockSystem`] desktop
+#[derive(Debug, Clone, Default)]
+pub struct MockWindow {
+    pub class: String,
+    pub title: String,
+    pub pid: u32,
+    /// Executable name as reported by the system (not lowercased)
+    pub process: String,
+    /// Full executable path
+    pub path: String,
+    /// Product name from the executable's version info
+    pub product: Option<String>,
+    /// Executable names of the parent processes, closest first, as reported
+    /// by the system (not lowercased)
+    pub ancestors: Vec<String>,
+    /// Outer window rect
+    pub rect: RECT,
+    /// Client area, the outer rect when `None`
+    pub client: Option<RECT>,
+    pub switcher: Option<Switcher>,
+    pub steam_app: Option<SteamApp>,
+    /// DLLs loaded into the process
+    pub modules: Vec<String>,
+    pub minimized: bool,
+    /// Has `WS_EX_TRANSPARENT`, like game overlays
+    pub click_through: bool,
+    /// Hidden with `ShowWindow(SW_HIDE)`
+    pub hidden: bool,
+    pub cloaked: bool,
+    /// On a virtual desktop other than the one shown
+    pub other_desktop: bool,
+}
+
+/// A scripted single-threaded desktop for tests
+///
+/// Windows are identified by the raw `HWND` value they were added under and
+/// stacked by it, the lowest on top. The first monitor is `monitor`,
+/// [`MockSystem::add_monitor`] adds more anywhere around it, and like
+/// `MONITOR_DEFAULTTONEAREST` each window belongs to the monitor it overlaps
+/// most, or else the closest one. The clip starts out released.
+#[derive(Debug)]
+pub struct MockSystem {
+    pub monitor: RECT,
+    /// The first monitor's work area, the whole monitor unless set
+    pub work_area: Option<RECT>,
+    /// Monitors after the first, with their work areas
+    extra_monitors: RefCell<Vec<(RECT, RECT)>>,
+    /// Device names and IDs of the monitors, made up where missing
+    monitor_names: Vec<(String, String)>,
+    windows: RefCell<HashMap<isize, MockWindow>>,
+    foreground: Cell<isize>,
+    running: RefCell<HashSet<u32>>,
+    elevated: RefCell<HashSet<u32>>,
+    cursor: Cell<POINT>,
+    button: Cell<bool>,
+    clip: Cell<Option<RECT>>,
+    clip_calls: Cell<u32>,
+    clip_error: Cell<Option<ClipError>>,
+    /// Whether the foreground app shows the cursor
+    cursor_showing: Cell<bool>,
+    cursor_hidden: Cell<bool>,
+    idle: Cell<Duration>,
+    gamepad_idle: Cell<Option<Duration>>,
+    clock: Cell<Instant>,
+    assistive_tool: Cell<Option<AssistiveTool>>,
+    presenting: Cell<bool>,
+    remote_session: Cell<bool>,
+    secure_desktop: Cell<bool>,
+}
+
+impl MockSystem {
+    /// An empty desktop with one monitor
+    pub fn new(monitor: RECT) -> Self {
+        Self {
+            monitor,
+            work_area: None,
+            extra_monitors: RefCell::default(),
+            monitor_names: Vec::new(),
+            windows: RefCell::default(),
+            foreground: Cell::new(0),
+            running: RefCell::default(),
+            elevated: RefCell::default(),
+            cursor: Cell::new(POINT::default()),
+            button: Cell::new(false),
+            clip: Cell::new(None),
+            clip_calls: Cell::new(0),
+            clip_error: Cell::new(None),
+            cursor_showing: Cell::new(true),
+            cursor_hidden: Cell::new(false),
+            idle: Cell::new(Duration::ZERO),
+            gamepad_idle: Cell::new(None),
+            clock: Cell::new(Instant::now()),
+            assistive_tool: Cell::new(None),
+            presenting: Cell::new(false),
+            remote_session: Cell::new(false),
+            secure_desktop: Cell::new(false),
+        }
+    }
+
+    /// Adds a monitor after the existing ones, without a taskbar
+    pub fn add_monitor(&self, rect: RECT) {
+        self.add_monitor_with_work_area(rect, rect);
+    }
+
+    /// Adds a monitor after the existing ones, with part of it taken by a taskbar
+    pub fn add_monitor_with_work_area(&self, rect: RECT, work_area: RECT) {
+        self.extra_monitors.borrow_mut().push((rect, work_area));
+    }
+
+    /// Replaces every monitor, keeping their device names and IDs; the work
+    /// areas are the whole monitors
+    pub fn set_monitors(&mut self, monitors: &[MonitorBounds]) {
+        let Some((first, rest)) = monitors.split_first() else {
+            return;
+        };
+        self.monitor = first.rect;
+        self.work_area = None;
+        *self.extra_monitors.get_mut() = rest.iter().map(|m| (m.rect, m.rect)).collect();
+        self.monitor_names = monitors
+            .iter()
+            .map(|m| (m.device.clone(), m.id.clone()))
+            .collect();
+    }
+
+    /// The monitor and work area a window rect belongs to
+    fn monitor_of(&self, window: &RECT) -> (RECT, RECT) {
+        let first = (self.monitor, self.work_area.unwrap_or(self.monitor));
+        let monitors: Vec<(RECT, RECT)> = std::iter::once(first)
+            .chain(self.extra_monitors.borrow().iter().copied())
+            .collect();
+        let score = |m: &RECT| {
+            let overlap_x = window.right.min(m.right) - window.left.max(m.left);
+            let overlap_y = window.bottom.min(m.bottom) - window.top.max(m.top);
+            if overlap_x > 0 && overlap_y > 0 {
+                overlap_x as i64 * overlap_y as i64
+            } else {
+                // Closer is better, below any overlap
+                let dx = overlap_x.min(0) as i64;
+                let dy = overlap_y.min(0) as i64;
+                -(dx * dx + dy * dy) - 1
+            }
+        };
+        // The first of equally good monitors wins
+        monitors
+            .into_iter()
+            .rev()
+            .max_by_key(|(monitor, _)| score(monitor))
+            .unwrap_or(first)
+    }
+
+    /// Adds (or replaces) a window; its process counts as running
+    pub fn add_window(&self, hwnd: isize, window: MockWindow) {
+        self.running.borrow_mut().insert(window.pid);
+        self.windows.borrow_mut().insert(hwnd, window);
+    }
+
+    /// Removes every window
+    pub fn clear_windows(&self) {
+        let hwnds: Vec<isize> = self.windows.borrow().keys().copied().collect();
+        for hwnd in hwnds {
+            self.remove_window(hwnd);
+        }
+    }
+
+    /// Removes a window, as if it was destroyed
+    pub fn remove_window(&self, hwnd: isize) {
+        self.windows.borrow_mut().remove(&hwnd);
+        if self.foreground.get() == hwnd {
+            self.foreground.set(0);
+        }
+    }
+
+    /// Changes an existing window in place
+    pub fn update_window(&self, hwnd: isize, change: impl FnOnce(&mut MockWindow)) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&hwnd) {
+            change(window);
+        }
+    }
+
+    /// Makes `hwnd` the foreground window, 0 for none
+    pub fn set_foreground(&self, hwnd: isize) {
+        self.foreground.set(hwnd);
+    }
+
+    pub fn set_process_running(&self, pid: u32, running: bool) {
+        if running {
+            self.running.borrow_mut().insert(pid);
+        } else {
+            self.running.borrow_mut().remove(&pid);
+        }
+    }
+
+    pub fn set_process_elevated(&self, pid: u32, elevated: bool) {
+        if elevated {
+            self.elevated.borrow_mut().insert(pid);
+        } else {
+            self.elevated.borrow_mut().remove(&pid);
+        }
+    }
+
+    pub fn move_cursor(&self, pos: POINT) {
+        self.cursor.set(pos);
+    }
+
+    /// Moves the clock seen by [`WindowSystem::now`] forward
+    pub fn advance(&self, by: Duration) {
+        self.clock.set(self.clock.get() + by);
+    }
+
+    /// Pretends there was no input for `idle`
+    pub fn set_idle_time(&self, idle: Duration) {
+        self.idle.set(idle);
+    }
+
+    /// Pretends a controller was last used `idle` ago, `None` for never
+    pub fn set_gamepad_idle_time(&self, idle: Option<Duration>) {
+        self.gamepad_idle.set(idle);
+    }
+
+    /// Starts (or stops) an assistive tool
+    pub fn set_assistive_tool(&self, tool: Option<AssistiveTool>) {
+        self.assistive_tool.set(tool);
+    }
+
+    /// Turns presentation mode on or off
+    pub fn set_presenting(&self, presenting: bool) {
+        self.presenting.set(presenting);
+    }
+
+    /// Connects the session over Remote Desktop, or back to the console
+    pub fn set_remote_session(&self, remote: bool) {
+        self.remote_session.set(remote);
+    }
+
+    /// Switches input to the secure desktop (a UAC prompt) or back
+    pub fn set_secure_desktop(&self, secure: bool) {
+        self.secure_desktop.set(secure);
+    }
+
+    /// Holds a mouse button down (or lets go)
+    pub fn set_button(&self, pressed: bool) {
+        self.button.set(pressed);
+    }
+
+    /// The active clip, `None` when released
+    pub fn clip(&self) -> Option<RECT> {
+        self.clip.get()
+    }
+
+    /// Clears the clip behind the engine's back, like a misbehaving overlay
+    pub fn steal_clip(&self) {
+        self.clip.set(None);
+    }
+
+    /// How often the clip was set so far
+    pub fn clip_calls(&self) -> u32 {
+        self.clip_calls.get()
+    }
+
+    /// Makes every clip fail with `error` (or work again with `None`)
+    pub fn set_clip_error(&self, error: Option<ClipError>) {
+        self.clip_error.set(error);
+    }
+
+    /// Lets the foreground app hide its cursor (or show it again)
+    pub fn set_cursor_showing(&self, showing: bool) {
+        self.cursor_showing.set(showing);
+    }
+
+    /// Whether the engine made the cursor invisible
+    pub fn cursor_hidden(&self) -> bool {
+        self.cursor_hidden.get()
+    }
+
+    fn with_window<T>(&self, hwnd: HWND, f: impl FnOnce(&MockWindow) -> T) -> Option<T> {
+        self.windows.borrow().get(&(hwnd.0 as isize)).map(f)
+    }
+}
+
+impl WindowSystem for MockSystem {
+    fn foreground_window(&self) -> HWND {
+        HWND(self.foreground.get() as *mut _)
+    }
+
+    fn windows(&self) -> Vec<HWND> {
+        let mut windows: Vec<isize> = self.windows.borrow().keys().copied().collect();
+        windows.sort_unstable();
+        windows
+            .into_iter()
+            .map(|hwnd| HWND(hwnd as *mut _))
+            .collect()
+    }
+
+    fn task_switcher(&self, hwnd: HWND) -> Option<Switcher> {
+        self.with_window(hwnd, |w| w.switcher).flatten()
+    }
+
+    fn window_title(&self, hwnd: HWND) -> String {
+        self.with_window(hwnd, |w| w.title.clone())
+            .unwrap_or_default()
+    }
+
+    fn window_info(&self, hwnd: HWND, _rules: &RuleSet) -> WindowInfo {
+        self.with_window(hwnd, |w| WindowInfo {
+            class: w.class.clone(),
+            title: w.title.clone(),
+            process: w.process.to_lowercase(),
+            path: w.path.clone(),
+            product: w.product.clone(),
+            ancestors: w.ancestors.iter().map(|name| name.to_lowercase()).collect(),
+            steam_app: w.steam_app.as_ref().map(|app| app.id),
+            rect: Some(w.rect),
+            monitor: Some(self.monitor_of(&w.rect).0),
+        })
+        .unwrap_or_default()
+    }
+
+    fn window_process_id(&self, hwnd: HWND) -> u32 {
+        self.with_window(hwnd, |w| w.pid).unwrap_or(0)
+    }
+
+    fn current_window_process_id(&self, hwnd: HWND) -> u32 {
+        self.window_process_id(hwnd)
+    }
+
+    fn is_window(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |_| ()).is_some()
+    }
+
+    fn is_visible(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |w| !w.hidden).unwrap_or(false)
+    }
+
+    fn is_minimized(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |w| w.minimized).unwrap_or(false)
+    }
+
+    fn is_click_through(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |w| w.click_through).unwrap_or(false)
+    }
+
+    fn is_cloaked(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |w| w.cloaked).unwrap_or(false)
+    }
+
+    fn is_on_current_desktop(&self, hwnd: HWND) -> bool {
+        self.with_window(hwnd, |w| !w.other_desktop).unwrap_or(true)
+    }
+
+    fn process_name(&self, pid: u32) -> Option<String> {
+        self.windows
+            .borrow()
+            .values()
+            .find(|w| w.pid == pid && pid != 0)
+            .map(|w| w.process.clone())
+    }
+
+    fn is_process_running(&self, pid: u32) -> bool {
+        self.running.borrow().contains(&pid)
+    }
+
+    fn find_process(&self, names: &[String]) -> Option<String> {
+        // Only processes with a window exist on the mock desktop
+        self.windows
+            .borrow()
+            .values()
+            .map(|w| &w.process)
+            .find(|process| names.iter().any(|name| name.eq_ignore_ascii_case(process)))
+            .cloned()
+    }
+
+    fn has_module(&self, pid: u32, names: &[String]) -> bool {
+        self.windows.borrow().values().any(|w| {
+            w.pid == pid
+                && pid != 0
+                && w.modules
+                    .iter()
+                    .any(|module| names.iter().any(|name| name.eq_ignore_ascii_case(module)))
+        })
+    }
+
+    fn steam_app(&self, pid: u32) -> Option<SteamApp> {
+        self.windows
+            .borrow()
+            .values()
+            .find(|w| w.pid == pid && pid != 0)
+            .and_then(|w| w.steam_app.clone())
+    }
+
+    fn is_process_elevated(&self, pid: u32) -> Option<bool> {
+        Some(self.elevated.borrow().contains(&pid))
+    }
+
+    fn fullscreen_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT> {
+        let rect = self.with_window(hwnd, |w| w.rect)?;
+        let (m, _) = self.monitor_of(&rect);
+        let monitors = self.monitors();
+        let covers = rect.left <= m.left + tolerance
+            && rect.top <= m.top + tolerance
+            && rect.right >= m.right - tolerance
+            && rect.bottom >= m.bottom - tolerance
+            && !reaches_other_monitor(&monitors, &m, &rect, tolerance.max(FRAME_OVERHANG));
+        spanned_rect(&monitors, &rect, tolerance).or(covers.then_some(m))
+    }
+
+    fn work_area_rect(&self, hwnd: HWND, tolerance: i32) -> Option<RECT> {
+        let rect = self.with_window(hwnd, |w| w.rect)?;
+        let (_, work) = self.monitor_of(&rect);
+        let covers = rect.left <= work.left + tolerance
+            && rect.top <= work.top + tolerance
+            && rect.right >= work.right - tolerance
+            && rect.bottom >= work.bottom - tolerance;
+        covers.then_some(work)
+    }
+
+    fn window_rect(&self, hwnd: HWND) -> Option<RECT> {
+        self.with_window(hwnd, |w| w.rect)
+    }
+
+    fn client_rect(&self, hwnd: HWND) -> Option<RECT> {
+        self.with_window(hwnd, |w| w.client.unwrap_or(w.rect))
+    }
+
+    fn window_monitor_rect(&self, hwnd: HWND) -> Option<RECT> {
+        self.with_window(hwnd, |w| self.monitor_of(&w.rect).0)
+    }
+
+    fn virtual_screen_rect(&self) -> RECT {
+        self.extra_monitors
+            .borrow()
+            .iter()
+            .fold(self.monitor, |all, (m, _)| RECT {
+                left: all.left.min(m.left),
+                top: all.top.min(m.top),
+                right: all.right.max(m.right),
+                bottom: all.bottom.max(m.bottom),
+            })
+    }
+
+    fn monitors(&self) -> Vec<MonitorBounds> {
+        std::iter::once(self.monitor)
+            .chain(self.extra_monitors.borrow().iter().map(|&(rect, _)| rect))
+            .enumerate()
+            .map(|(i, rect)| {
+                let (device, id) = self.monitor_names.get(i).cloned().unwrap_or_else(|| {
+                    (
+                        format!(r"\\.\DISPLAY{}", i + 1),
+                        format!(r"\\?\DISPLAY#MOCK{:04}#{}", i + 1, i + 1),
+                    )
+                });
+                MonitorBounds {
+                    rect,
+                    handle: HMONITOR::default(),
+                    device,
+                    id,
+                }
+            })
+            .collect()
+    }
+
+    fn cursor_pos(&self) -> Option<POINT> {
+        Some(self.cursor.get())
+    }
+
+    fn set_cursor_pos(&self, x: i32, y: i32) -> bool {
+        self.cursor.set(POINT { x, y });
+        true
+    }
+
+    fn mouse_button_pressed(&self) -> bool {
+        self.button.get()
+    }
+
+    fn cursor_clip(&self) -> Option<RECT> {
+        Some(
+            self.clip
+                .get()
+                .unwrap_or_else(|| self.virtual_screen_rect()),
+        )
+    }
+
+    fn clip_cursor(&self, rect: &RECT) -> Result<(), ClipError> {
+        self.clip_calls.set(self.clip_calls.get() + 1);
+        match self.clip_error.get() {
+            // Windows still applies a clip it moves
+            Some(ClipError::Moved(clip)) => self.clip.set(Some(clip)),
+            Some(_) => {}
+            None => self.clip.set(Some(*rect)),
+        }
+        self.clip_error.get().map_or(Ok(()), Err)
+    }
+
+    fn release_clip(&self) -> bool {
+        self.clip.set(None);
+        true
+    }
+
+    fn cursor_showing(&self) -> bool {
+        self.cursor_showing.get() && !self.cursor_hidden.get()
+    }
+
+    fn hide_cursor(&self) -> bool {
+        self.cursor_hidden.set(true);
+        true
+    }
+
+    fn show_cursor(&self) {
+        self.cursor_hidden.set(false);
+    }
+
+    fn now(&self) -> Instant {
+        self.clock.get()
+    }
+
+    fn input_idle_time(&self) -> Duration {
+        self.idle.get()
+    }
+
+    fn gamepad_idle_time(&self) -> Option<Duration> {
+        self.gamepad_idle.get()
+    }
+
+    fn assistive_tool(&self) -> Option<AssistiveTool> {
+        self.assistive_tool.get()
+    }
+
+    fn is_presenting(&self) -> bool {
+        self.presenting.get()
+    }
+
+    fn is_remote_session(&self) -> bool {
+        self.remote_session.get()
+    }
+
+    fn is_input_desktop_default(&self) -> bool {
+        !self.secure_desktop.get()
+    }
+}
@@ -0,0 +1,180 @@
+//! Windows Application event log
+//!
+//! Reports startup, locks, unlocks, clip theft and errors under an event
+//! source, so fleets (training simulators, kiosks, ...) can be audited from a
+//! central collector. Events are written only while `[system_log]` enables it.
+//!
+//! Event Viewer needs the source registered to show the messages cleanly;
+//! [`register_source`] does that once from an administrator prompt. It points
+//! the source at the message file .NET ships for the same purpose, whose
+//! messages just repeat the logged text.
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{ERROR_SUCCESS, HANDLE};
+use windows::Win32::Security::PSID;
+use windows::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+    EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegCreateKeyExW, RegSetValueExW, HKEY, HKEY_LOCAL_MACHINE, KEY_SET_VALUE,
+    REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+};
+
+use crate::config::SystemLogConfig;
+use crate::events::Event;
+
+/// Message file whose messages are a bare `%1`
+const MESSAGE_FILE: &str =
+    r"%SystemRoot%\Microsoft.NET\Framework64\v4.0.30319\EventLogMessages.dll";
+
+/// Event IDs, so collectors can filter without parsing the text
+const STARTED_ID: u32 = 1;
+const ERROR_ID: u32 = 2;
+
+/// An open event source
+pub struct SystemLog {
+    handle: HANDLE,
+    events: Vec<String>,
+}
+
+impl SystemLog {
+    /// Opens the configured source, or returns `Ok(None)` if logging is off
+    pub fn open(config: &SystemLogConfig) -> windows::core::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let handle =
+            unsafe { RegisterEventSourceW(PCWSTR::null(), &HSTRING::from(&config.source))? };
+        Ok(Some(Self {
+            handle,
+            events: config.events.clone(),
+        }))
+    }
+
+    /// Logs that SmartLockCursor started, with its version
+    pub fn started(&self) {
+        self.report(
+            EVENTLOG_INFORMATION_TYPE,
+            STARTED_ID,
+            &format!("SmartLockCursor {} started", env!("CARGO_PKG_VERSION")),
+        );
+    }
+
+    /// Logs an event if its kind is among the configured ones
+    pub fn event(&self, event: &Event, details: &str) {
+        if !self.events.iter().any(|kind| kind == event.kind()) {
+            return;
+        }
+        let kind = if event.is_warning() {
+            EVENTLOG_WARNING_TYPE
+        } else {
+            EVENTLOG_INFORMATION_TYPE
+        };
+        self.report(kind, event_id(event), details);
+    }
+
+    /// Logs something that went wrong
+    pub fn error(&self, message: &str) {
+        self.report(EVENTLOG_ERROR_TYPE, ERROR_ID, message);
+    }
+
+    fn report(&self, kind: REPORT_EVENT_TYPE, id: u32, message: &str) {
+        let message = HSTRING::from(message);
+        unsafe {
+            let _ = ReportEventW(
+                self.handle,
+                kind,
+                0,
+                id,
+                PSID::default(),
+                0,
+                Some(&[PCWSTR(message.as_ptr())]),
+                None,
+            );
+        }
+    }
+}
+
+impl Drop for SystemLog {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeregisterEventSource(self.handle);
+        }
+    }
+}
+
+/// Stable ID per event kind, starting at 100
+fn event_id(event: &Event) -> u32 {
+    match event {
+        Event::Locked { .. } => 100,
+        Event::Unlocked { .. } => 101,
+        Event::ClipStolen { .. } => 102,
+        Event::AltTabStarted { .. } => 103,
+        Event::AltTabEnded { .. } => 104,
+        Event::Relocked { .. } => 105,
+        Event::RememberedWindowExpired { .. } => 106,
+        Event::EdgeEscaped { .. } => 107,
+        Event::EdgeEscapeEnded => 108,
+        Event::ManualLocked { .. } => 109,
+        Event::ManualLockMoved { .. } => 110,
+        Event::ManualUnlocked => 111,
+        Event::TimedUnlock { .. } => 112,
+        Event::TimedUnlockEnded => 113,
+        Event::BackendEscalated { .. } => 114,
+    }
+}
+
+/// Registers `source` under the Application log; needs administrator rights
+pub fn register_source(source: &str) -> Result<(), String> {
+    let subkey = HSTRING::from(format!(
+        r"SYSTEM\CurrentControlSet\Services\EventLog\Application\{}",
+        source
+    ));
+    let mut key = HKEY::default();
+    unsafe {
+        let status = RegCreateKeyExW(
+            HKEY_LOCAL_MACHINE,
+            &subkey,
+            0,
+            PCWSTR::null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_SET_VALUE,
+            None,
+            &mut key,
+            None,
+        );
+        if status != ERROR_SUCCESS {
+            return Err(windows::core::Error::from(status.to_hresult()).message());
+        }
+
+        let file: Vec<u8> = MESSAGE_FILE
+            .encode_utf16()
+            .chain(Some(0))
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let types =
+            (EVENTLOG_ERROR_TYPE.0 | EVENTLOG_WARNING_TYPE.0 | EVENTLOG_INFORMATION_TYPE.0) as u32;
+        let mut status = RegSetValueExW(
+            key,
+            &HSTRING::from("EventMessageFile"),
+            0,
+            REG_EXPAND_SZ,
+            Some(&file),
+        );
+        if status == ERROR_SUCCESS {
+            status = RegSetValueExW(
+                key,
+                &HSTRING::from("TypesSupported"),
+                0,
+                REG_DWORD,
+                Some(&types.to_le_bytes()),
+            );
+        }
+        let _ = RegCloseKey(key);
+        if status != ERROR_SUCCESS {
+            return Err(windows::core::Error::from(status.to_hresult()).message());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+//! Notification area icon
+//!
+//! Gives people who don't know the hotkeys a way out: the icon's menu has
+//! "Unlock for 30s" while the cursor is locked, and the balloon shown on a lock
+//! does the same when clicked. The icon has a hidden window of its own on the
+//! main thread; its menu choices and balloon clicks reach the main loop as
+//! [`ControlMessage::Tray`] through [`control::wait`](crate::control::wait).
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Shell::{
+    Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_SHOWTIP, NIF_TIP, NIIF_INFO,
+    NIIF_RESPECT_QUIET_TIME, NIIF_WARNING, NIM_ADD, NIM_DELETE, NIM_MODIFY, NIM_SETVERSION,
+    NIN_BALLOONUSERCLICK, NIN_SELECT, NOTIFYICONDATAW, NOTIFYICON_VERSION_4,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DestroyWindow,
+    GetCursorPos, LoadIconW, PostMessageW, RegisterClassW, RegisterWindowMessageW,
+    SetForegroundWindow, TrackPopupMenu, HMENU, IDI_APPLICATION, MF_GRAYED, MF_SEPARATOR,
+    MF_STRING, TPM_NONOTIFY, TPM_RETURNCMD, TPM_RIGHTBUTTON, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP,
+    WM_CONTEXTMENU, WM_NULL, WNDCLASSW,
+};
+
+use crate::control::{push_message, ControlMessage};
+use crate::tr;
+
+const WINDOW_CLASS: &str = "SmartLockCursorTray";
+/// Sent by the shell for clicks on the icon and its balloon
+const CALLBACK_MESSAGE: u32 = WM_APP + 1;
+const ICON_ID: u32 = 1;
+
+const MENU_UNLOCK: usize = 1;
+const MENU_EXIT: usize = 2;
+
+/// What was picked from the icon's menu or balloon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayCommand {
+    /// Free the cursor for the configured time
+    Unlock,
+    Exit,
+}
+
+/// How urgent a balloon is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balloon {
+    /// Shown on a lock; clicking it unlocks
+    Lock,
+    Warning,
+}
+
+thread_local! {
+    static TASKBAR_CREATED: Cell<u32> = const { Cell::new(0) };
+    static LOCKED: Cell<bool> = const { Cell::new(false) };
+    static UNLOCK_FOR: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+    /// Whether a click on the balloon up now unlocks
+    static BALLOON_UNLOCKS: Cell<bool> = const { Cell::new(false) };
+    /// The icon data to add again when Explorer restarts
+    static ICON: Cell<Option<NOTIFYICONDATAW>> = const { Cell::new(None) };
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == CALLBACK_MESSAGE {
+        // NOTIFYICON_VERSION_4 puts the event in the low word
+        match (lparam.0 & 0xFFFF) as u32 {
+            WM_CONTEXTMENU | NIN_SELECT => show_menu(hwnd),
+            NIN_BALLOONUSERCLICK
+                if BALLOON_UNLOCKS.with(|b| b.replace(false)) && LOCKED.with(Cell::get) =>
+            {
+                push_message(ControlMessage::Tray(TrayCommand::Unlock));
+            }
+            _ => {}
+        }
+        return LRESULT(0);
+    }
+
+    if msg != 0 && msg == TASKBAR_CREATED.with(Cell::get) {
+        // Explorer restarted and forgot every icon
+        if let Some(icon) = ICON.with(Cell::get) {
+            add_icon(&icon);
+        }
+        return LRESULT(0);
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+unsafe fn show_menu(hwnd: HWND) {
+    let Ok(menu) = CreatePopupMenu() else {
+        return;
+    };
+    let unlock = HSTRING::from(tr!("tray.unlock", UNLOCK_FOR.with(Cell::get).as_secs()));
+    let unlock_flags = if LOCKED.with(Cell::get) {
+        MF_STRING
+    } else {
+        MF_STRING | MF_GRAYED
+    };
+    let _ = AppendMenuW(menu, unlock_flags, MENU_UNLOCK, &unlock);
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, MENU_EXIT, &HSTRING::from(tr!("tray.exit")));
+
+    let mut pos = POINT::default();
+    let _ = GetCursorPos(&mut pos);
+    // Without this the menu stays open when clicking elsewhere
+    let _ = SetForegroundWindow(hwnd);
+    let chosen = TrackPopupMenu(
+        menu,
+        TPM_RETURNCMD | TPM_NONOTIFY | TPM_RIGHTBUTTON,
+        pos.x,
+        pos.y,
+        0,
+        hwnd,
+        None,
+    );
+    let _ = PostMessageW(hwnd, WM_NULL, WPARAM(0), LPARAM(0));
+    let _ = DestroyMenu(menu);
+
+    match chosen.0 as usize {
+        MENU_UNLOCK => push_message(ControlMessage::Tray(TrayCommand::Unlock)),
+        MENU_EXIT => push_message(ControlMessage::Tray(TrayCommand::Exit)),
+        _ => {}
+    }
+}
+
+fn add_icon(icon: &NOTIFYICONDATAW) -> bool {
+    unsafe {
+        if !Shell_NotifyIconW(NIM_ADD, icon).as_bool() {
+            return false;
+        }
+        let _ = Shell_NotifyIconW(NIM_SETVERSION, icon);
+    }
+    true
+}
+
+/// Copies `text` into a fixed-size wide string, cut short if needed
+fn copy_wide(dst: &mut [u16], text: &str) {
+    let len = dst.len() - 1;
+    let mut n = 0;
+    for unit in text.encode_utf16().take(len) {
+        dst[n] = unit;
+        n += 1;
+    }
+    dst[n] = 0;
+}
+
+/// The icon, removed on drop
+pub struct TrayIcon {
+    hwnd: HWND,
+}
+
+impl TrayIcon {
+    /// Adds the icon, backed by a hidden window on the current thread, which
+    /// must pump messages
+    pub fn create(unlock_for: Duration) -> windows::core::Result<Self> {
+        UNLOCK_FOR.with(|u| u.set(unlock_for));
+        unsafe {
+            let instance = GetModuleHandleW(PCWSTR::null())?;
+            let class_name = HSTRING::from(WINDOW_CLASS);
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                ..Default::default()
+            };
+            RegisterClassW(&class);
+
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                &class_name,
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND::default(),
+                HMENU::default(),
+                instance,
+                None,
+            )?;
+            TASKBAR_CREATED
+                .with(|m| m.set(RegisterWindowMessageW(&HSTRING::from("TaskbarCreated"))));
+
+            let mut icon = NOTIFYICONDATAW {
+                cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+                hWnd: hwnd,
+                uID: ICON_ID,
+                uFlags: NIF_MESSAGE | NIF_ICON | NIF_TIP | NIF_SHOWTIP,
+                uCallbackMessage: CALLBACK_MESSAGE,
+                hIcon: LoadIconW(None, IDI_APPLICATION)?,
+                ..Default::default()
+            };
+            icon.Anonymous.uVersion = NOTIFYICON_VERSION_4;
+            copy_wide(&mut icon.szTip, "SmartLockCursor");
+            if !add_icon(&icon) {
+                let _ = DestroyWindow(hwnd);
+                return Err(windows::core::Error::from_win32());
+            }
+            ICON.with(|i| i.set(Some(icon)));
+            Ok(Self { hwnd })
+        }
+    }
+
+    /// Tells the menu and the lock balloon whether there is a lock to end
+    pub fn set_locked(&self, locked: bool) {
+        LOCKED.with(|l| l.set(locked));
+        if !locked {
+            BALLOON_UNLOCKS.with(|b| b.set(false));
+        }
+    }
+
+    /// Shows a balloon (a toast on Windows 10 and later) next to the icon
+    pub fn notify(&self, kind: Balloon, title: &str, text: &str) {
+        let Some(mut icon) = ICON.with(Cell::get) else {
+            return;
+        };
+        icon.uFlags = NIF_INFO;
+        icon.dwInfoFlags = match kind {
+            Balloon::Lock => NIIF_INFO | NIIF_RESPECT_QUIET_TIME,
+            Balloon::Warning => NIIF_WARNING,
+        };
+        copy_wide(&mut icon.szInfoTitle, title);
+        copy_wide(&mut icon.szInfo, text);
+        BALLOON_UNLOCKS.with(|b| b.set(kind == Balloon::Lock));
+        unsafe {
+            let _ = Shell_NotifyIconW(NIM_MODIFY, &icon);
+        }
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        if let Some(icon) = ICON.with(|i| i.take()) {
+            unsafe {
+                let _ = Shell_NotifyIconW(NIM_DELETE, &icon);
+            }
+        }
+        unsafe {
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_strings_are_cut_to_fit() {
+        let mut tip = [0xFFFFu16; 4];
+        copy_wide(&mut tip, "SmartLockCursor");
+        assert_eq!(tip, [b'S' as u16, b'm' as u16, b'a' as u16, 0]);
+        copy_wide(&mut tip, "");
+        assert_eq!(tip[0], 0);
+    }
+}
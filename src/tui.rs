@@ -0,0 +1,408 @@
+//! Live dashboard (`--tui`)
+//!
+//! Redraws a full-screen view a few times per second: the foreground window's
+//! class, executable and rect, the lock state with the grace countdown, the
+//! monitor layout as ASCII art and the most recent log lines. It is drawn with
+//! plain VT escape sequences on the alternate screen, so quitting leaves the
+//! console as it was.
+//!
+//! There is no ratatui here on purpose: the screen is a list of text lines
+//! redrawn whole, with no input handling or layout to speak of, and the
+//! console is already switched to VT mode for it. A widget library and its
+//! terminal backend would be the biggest dependency in the build for what
+//! `draw` does in twenty lines.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use windows::Win32::Foundation::RECT;
+use windows::Win32::System::Console::{
+    GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle, SetConsoleMode,
+    CONSOLE_SCREEN_BUFFER_INFO, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_OUTPUT_HANDLE,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+
+use crate::console;
+use crate::monitor::{describe_rect, get_all_monitors, rects_overlap, MonitorBounds};
+use crate::process::get_process_name;
+use crate::stats::Stats;
+use crate::window::{get_class_name, get_window_process_id, get_window_title};
+
+/// Time between redraws
+pub const REFRESH: Duration = Duration::from_millis(250);
+/// Log lines kept for the event pane
+const LOG_LINES: usize = 200;
+/// Size of the monitor map in characters
+const MAP_WIDTH: usize = 56;
+const MAP_HEIGHT: usize = 10;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The dashboard; the previous screen comes back when it is dropped
+pub struct Dashboard {
+    last_draw: Option<Instant>,
+}
+
+impl Dashboard {
+    /// Switches to the alternate screen and starts capturing log lines
+    pub fn open() -> Result<Self, String> {
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE).map_err(|e| e.message())?;
+            let mut mode = Default::default();
+            GetConsoleMode(handle, &mut mode)
+                .and_then(|()| SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING))
+                .map_err(|_| "--tui needs a console window".to_string())?;
+        }
+        console::capture(LOG_LINES);
+        ACTIVE.store(true, Ordering::SeqCst);
+        // Alternate screen, hidden cursor
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+        Ok(Self { last_draw: None })
+    }
+
+    /// Redraws the screen unless it was drawn less than [`REFRESH`] ago;
+    /// `grace_left` is the time left before a lock that lost the foreground ends
+    pub fn draw(&mut self, stats: &Stats, grace_left: Option<Duration>) {
+        if self.last_draw.is_some_and(|last| last.elapsed() < REFRESH) {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let lines = screen(
+            stats,
+            grace_left,
+            foreground().as_ref(),
+            &get_all_monitors(),
+            &console::recent(),
+        );
+        let frame = frame(&lines, console_size());
+        let mut out = io::stdout().lock();
+        let _ = out.write_all(frame.as_bytes());
+        let _ = out.flush();
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leaves the alternate screen if the dashboard is up; safe to call from the
+/// Ctrl+C handler
+pub fn restore() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// The foreground window as the header shows it
+struct Foreground {
+    hwnd: isize,
+    process: Option<String>,
+    class: String,
+    title: String,
+    rect: Option<RECT>,
+}
+
+fn foreground() -> Option<Foreground> {
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd.0.is_null() {
+        return None;
+    }
+    let mut rect = RECT::default();
+    let has_rect = unsafe { GetWindowRect(hwnd, &mut rect) }.is_ok();
+    Some(Foreground {
+        hwnd: hwnd.0 as isize,
+        process: get_process_name(get_window_process_id(hwnd)),
+        class: get_class_name(hwnd).unwrap_or_default(),
+        title: get_window_title(hwnd),
+        rect: has_rect.then_some(rect),
+    })
+}
+
+/// Every line of the screen, events last so the ones that don't fit are the
+/// ones cut off
+fn screen(
+    stats: &Stats,
+    grace_left: Option<Duration>,
+    foreground: Option<&Foreground>,
+    monitors: &[MonitorBounds],
+    events: &[String],
+) -> Vec<String> {
+    let mut lines = header(stats, grace_left, foreground);
+    lines.push(String::new());
+    lines.extend(monitor_map(monitors, stats.clip()));
+    lines.push(String::new());
+    lines.push("Recent events:".to_string());
+    lines.extend(events.iter().cloned());
+    lines
+}
+
+/// The VT sequence drawing `lines` over the previous frame on a `width` x
+/// `height` console, keeping the newest events when they don't all fit
+fn frame(lines: &[String], (width, height): (usize, usize)) -> String {
+    let events = lines
+        .iter()
+        .position(|line| line == "Recent events:")
+        .map_or(lines.len(), |i| i + 1);
+    let skip = lines.len().saturating_sub(height).min(lines.len() - events);
+    let mut frame = String::from("\x1b[H");
+    for line in lines[..events]
+        .iter()
+        .chain(&lines[events + skip..])
+        .take(height)
+    {
+        let line: String = line.chars().take(width).collect();
+        let _ = write!(frame, "{}\x1b[K\r\n", line);
+    }
+    frame.push_str("\x1b[J");
+    frame
+}
+
+fn header(
+    stats: &Stats,
+    grace_left: Option<Duration>,
+    foreground: Option<&Foreground>,
+) -> Vec<String> {
+    let mut state = stats.state().to_string();
+    if let Some(left) = grace_left {
+        let _ = write!(state, " (grace period, {:.1}s left)", left.as_secs_f64());
+    }
+    let (locks, unlocks) = stats.counts();
+    let mut lines = vec![
+        format!(
+            "SmartLockCursor {} - Ctrl+C to exit",
+            env!("CARGO_PKG_VERSION")
+        ),
+        String::new(),
+        format!("State:       {}", state),
+        format!(
+            "Clip:        {}",
+            stats
+                .clip()
+                .map(|clip| describe_rect(&clip))
+                .unwrap_or_else(|| "none".to_string())
+        ),
+        format!("Session:     {} lock(s), {} unlock(s)", locks, unlocks),
+    ];
+
+    let Some(window) = foreground else {
+        lines.push("Foreground:  none".to_string());
+        return lines;
+    };
+    lines.push(format!(
+        "Foreground:  {:#x}  {}",
+        window.hwnd,
+        window.process.as_deref().unwrap_or("unknown")
+    ));
+    lines.push(format!("  class:     {}", window.class));
+    lines.push(format!("  title:     \"{}\"", window.title));
+    lines.push(format!(
+        "  rect:      {}",
+        window
+            .rect
+            .map(|rect| describe_rect(&rect))
+            .unwrap_or_else(|| "unknown".to_string())
+    ));
+    lines
+}
+
+/// Draws the monitors scaled into a [`MAP_WIDTH`] x [`MAP_HEIGHT`] box, with
+/// every monitor the clip reaches into filled in
+fn monitor_map(monitors: &[MonitorBounds], clip: Option<RECT>) -> Vec<String> {
+    let Some(first) = monitors.first() else {
+        return vec!["No monitors".to_string()];
+    };
+    let bounds = monitors.iter().fold(first.rect, |acc, m| RECT {
+        left: acc.left.min(m.rect.left),
+        top: acc.top.min(m.rect.top),
+        right: acc.right.max(m.rect.right),
+        bottom: acc.bottom.max(m.rect.bottom),
+    });
+    let span_x = (bounds.right - bounds.left).max(1) as i64;
+    let span_y = (bounds.bottom - bounds.top).max(1) as i64;
+    let col = |x: i32| ((x - bounds.left) as i64 * (MAP_WIDTH as i64 - 1) / span_x) as usize;
+    let row = |y: i32| ((y - bounds.top) as i64 * (MAP_HEIGHT as i64 - 1) / span_y) as usize;
+
+    let mut grid = vec![vec![' '; MAP_WIDTH]; MAP_HEIGHT];
+    for (i, monitor) in monitors.iter().enumerate() {
+        let (left, right) = (col(monitor.rect.left), col(monitor.rect.right));
+        let (top, bottom) = (row(monitor.rect.top), row(monitor.rect.bottom));
+        let clipped = clip.is_some_and(|clip| rects_overlap(&clip, &monitor.rect));
+        for (y, line) in grid.iter_mut().enumerate().take(bottom + 1).skip(top) {
+            for (x, cell) in line.iter_mut().enumerate().take(right + 1).skip(left) {
+                let edge_x = x == left || x == right;
+                let edge_y = y == top || y == bottom;
+                *cell = match (edge_x, edge_y) {
+                    (true, true) => '+',
+                    (false, true) => '-',
+                    (true, false) => '|',
+                    (false, false) if clipped => '#',
+                    (false, false) => ' ',
+                };
+            }
+        }
+        let label = format!(" {} ", i + 1);
+        let start = (left + right) / 2 - label.len().min((left + right) / 2) / 2;
+        for (offset, c) in label.chars().enumerate() {
+            if let Some(cell) = grid[(top + bottom) / 2].get_mut(start + offset) {
+                *cell = c;
+            }
+        }
+    }
+
+    let mut lines = vec!["Monitors (# = cursor locked here):".to_string()];
+    lines.extend(grid.into_iter().map(|line| line.into_iter().collect()));
+    lines
+}
+
+fn console_size() -> (usize, usize) {
+    let mut info = CONSOLE_SCREEN_BUFFER_INFO::default();
+    let ok = unsafe {
+        GetStdHandle(STD_OUTPUT_HANDLE)
+            .is_ok_and(|handle| GetConsoleScreenBufferInfo(handle, &mut info).is_ok())
+    };
+    if !ok {
+        return (80, 25);
+    }
+    let window = info.srWindow;
+    (
+        (window.Right - window.Left + 1).max(20) as usize,
+        (window.Bottom - window.Top + 1).max(10) as usize,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::Graphics::Gdi::HMONITOR;
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> RECT {
+        RECT {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Two 1920x1080 monitors side by side
+    fn monitors() -> Vec<MonitorBounds> {
+        (0..2)
+            .map(|i| MonitorBounds {
+                rect: rect(i * 1920, 0, (i + 1) * 1920, 1080),
+                handle: HMONITOR::default(),
+                device: format!(r"\\.\DISPLAY{}", i + 1),
+                id: String::new(),
+            })
+            .collect()
+    }
+
+    /// Whether the map row just under the top edges is filled at `column`;
+    /// the left monitor's inside runs from column 1 to 26, the right one's
+    /// from 28 to 54
+    fn filled(map: &[String], column: usize) -> bool {
+        map[2].chars().nth(column) == Some('#')
+    }
+
+    #[test]
+    fn fills_the_monitor_holding_the_clip() {
+        let map = monitor_map(&monitors(), Some(rect(0, 0, 1920, 1080)));
+        assert_eq!(map.len(), MAP_HEIGHT + 1);
+        assert!(filled(&map, 5));
+        assert!(!filled(&map, 40));
+    }
+
+    #[test]
+    fn fills_every_monitor_a_spanned_clip_reaches() {
+        let map = monitor_map(&monitors(), Some(rect(0, 0, 3840, 1080)));
+        assert!(filled(&map, 5));
+        assert!(filled(&map, 40));
+
+        // A window straddling the seam reaches both monitors too
+        let map = monitor_map(&monitors(), Some(rect(1800, 200, 2100, 600)));
+        assert!(filled(&map, 5));
+        assert!(filled(&map, 40));
+    }
+
+    #[test]
+    fn fills_nothing_without_a_clip() {
+        let map = monitor_map(&monitors(), None);
+        assert!(map.iter().skip(1).all(|line| !line.contains('#')));
+        assert_eq!(monitor_map(&[], None), ["No monitors"]);
+    }
+
+    /// One lock ended and one running on the left monitor
+    fn stats() -> Stats {
+        let mut stats = Stats::default();
+        stats.lock_started("Game.exe".to_string(), "DISPLAY1".to_string());
+        stats.lock_started("Game.exe".to_string(), "DISPLAY1".to_string());
+        stats.set_state("locked", Some(rect(0, 0, 1920, 1080)));
+        stats
+    }
+
+    fn game() -> Foreground {
+        Foreground {
+            hwnd: 0x100,
+            process: Some("Game.exe".to_string()),
+            class: "UnityWndClass".to_string(),
+            title: "Game".to_string(),
+            rect: None,
+        }
+    }
+
+    #[test]
+    fn header_shows_the_state_and_the_foreground_window() {
+        let lines = header(&stats(), Some(Duration::from_millis(1500)), Some(&game()));
+        assert_eq!(lines[2], "State:       locked (grace period, 1.5s left)");
+        assert_eq!(lines[3], "Clip:        1920x1080 at (0, 0)");
+        assert_eq!(lines[4], "Session:     2 lock(s), 1 unlock(s)");
+        assert_eq!(
+            lines[5..],
+            [
+                "Foreground:  0x100  Game.exe",
+                "  class:     UnityWndClass",
+                "  title:     \"Game\"",
+                "  rect:      unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn header_without_a_foreground_window() {
+        let lines = header(&Stats::default(), None, None);
+        assert_eq!(lines[2], "State:       unlocked");
+        assert_eq!(lines[3], "Clip:        none");
+        assert_eq!(lines[4], "Session:     0 lock(s), 0 unlock(s)");
+        assert_eq!(lines.last().unwrap(), "Foreground:  none");
+    }
+
+    #[test]
+    fn renders_a_frame_of_a_stats_snapshot() {
+        let events: Vec<String> = (1..=30).map(|i| format!("event {}", i)).collect();
+        let lines = screen(&stats(), None, Some(&game()), &monitors(), &events);
+        // 9 header lines, a blank, the map and its title, a blank and the
+        // events title leave room for the 7 newest events
+        let frame = frame(&lines, (40, 30));
+        assert!(frame.starts_with("\x1b[H"));
+        assert!(frame.ends_with("\x1b[J"));
+        let rows: Vec<&str> = frame[3..frame.len() - 3]
+            .split_terminator("\x1b[K\r\n")
+            .collect();
+        assert_eq!(rows.len(), 30);
+        assert_eq!(rows[2], "State:       locked");
+        assert_eq!(rows[4], "Session:     2 lock(s), 1 unlock(s)");
+        assert_eq!(rows[10], "Monitors (# = cursor locked here):");
+        assert!(rows[12].contains('#'));
+        // Cut to the console width
+        assert!(rows.iter().all(|row| row.chars().count() <= 40));
+        assert_eq!(rows[22], "Recent events:");
+        let shown: Vec<String> = (24..=30).map(|i| format!("event {}", i)).collect();
+        assert_eq!(rows[23..], shown);
+    }
+}
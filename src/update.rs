@@ -0,0 +1,468 @@
+//! Update check and self-update from GitHub releases
+//!
+//! The latest release is read from the GitHub API over WinHTTP, which brings
+//! its own TLS and honors the system proxy. `self-update` downloads the `.exe`
+//! asset, checks it against the SHA-256 digest GitHub publishes for the asset
+//! (or a `<asset>.sha256` file next to it) and swaps it in for the running
+//! binary. Windows lets a running executable be renamed but not overwritten, so
+//! the old one is moved aside as `<exe>.old` and cleaned up on the next start.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Networking::WinHttp::{
+    WinHttpCloseHandle, WinHttpConnect, WinHttpOpen, WinHttpOpenRequest, WinHttpQueryHeaders,
+    WinHttpReadData, WinHttpReceiveResponse, WinHttpSendRequest, INTERNET_DEFAULT_HTTPS_PORT,
+    WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY, WINHTTP_FLAG_SECURE, WINHTTP_QUERY_FLAG_NUMBER,
+    WINHTTP_QUERY_STATUS_CODE,
+};
+use windows::Win32::Security::Cryptography::{BCryptHash, BCRYPT_SHA256_ALG_HANDLE};
+
+use crate::config::UpdatesConfig;
+use crate::json;
+use crate::{info, tr, warn};
+
+/// A published release and the binary to install from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub tag: String,
+    asset_url: String,
+    /// Hex SHA-256 of the asset, from GitHub's `digest` field
+    digest: Option<String>,
+    /// A `<asset>.sha256` file, for releases without a digest
+    checksum_url: Option<String>,
+}
+
+impl Release {
+    /// Whether this release is newer than the running build
+    pub fn is_newer(&self) -> bool {
+        match (
+            parse_version(&self.tag),
+            parse_version(env!("CARGO_PKG_VERSION")),
+        ) {
+            (Some(latest), Some(current)) => latest > current,
+            _ => false,
+        }
+    }
+}
+
+/// Checks for a newer release on a background thread and mentions it once
+pub fn spawn_check(config: &UpdatesConfig) {
+    if !config.check {
+        return;
+    }
+    let repository = config.repository.clone();
+    let spawned = thread::Builder::new()
+        .name("update-check".to_string())
+        .spawn(move || match latest_release(&repository) {
+            Ok(release) if release.is_newer() => info!(
+                "{}",
+                tr!("update.available", release.tag, env!("CARGO_PKG_VERSION"))
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("{}", tr!("update.check_failed", e)),
+        });
+    if let Err(e) = spawned {
+        warn!("{}", tr!("update.spawn_failed", e));
+    }
+}
+
+/// Looks up the latest release of `repository` (`owner/name`)
+pub fn latest_release(repository: &str) -> Result<Release, String> {
+    let body = get(&format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        repository
+    ))?;
+    let text = String::from_utf8(body).map_err(|_| "release info is not UTF-8".to_string())?;
+    parse_release(&json::parse(&text)?)
+}
+
+fn parse_release(release: &json::Value) -> Result<Release, String> {
+    let tag = release
+        .get("tag_name")
+        .and_then(json::Value::as_str)
+        .ok_or("release has no tag")?
+        .to_string();
+    let assets = release
+        .get("assets")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default();
+    let name_of = |asset: &json::Value| {
+        asset
+            .get("name")
+            .and_then(json::Value::as_str)
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    let url_of = |asset: &json::Value| {
+        asset
+            .get("browser_download_url")
+            .and_then(json::Value::as_str)
+            .map(str::to_string)
+    };
+
+    let binary = assets
+        .iter()
+        .find(|asset| name_of(asset).ends_with(".exe"))
+        .ok_or_else(|| format!("release {} has no .exe asset", tag))?;
+    let binary_name = name_of(binary);
+    let digest = binary
+        .get("digest")
+        .and_then(json::Value::as_str)
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .map(str::to_ascii_lowercase);
+    let checksum_url = assets
+        .iter()
+        .find(|asset| name_of(asset) == format!("{}.sha256", binary_name))
+        .and_then(url_of);
+
+    Ok(Release {
+        asset_url: url_of(binary).ok_or("asset has no download URL")?,
+        tag,
+        digest,
+        checksum_url,
+    })
+}
+
+/// Downloads, verifies and installs the latest release in place of the
+/// running executable
+pub fn self_update(repository: &str) -> Result<Option<String>, String> {
+    let release = latest_release(repository)?;
+    if !release.is_newer() {
+        return Ok(None);
+    }
+
+    let expected = expected_digest(&release, get)?;
+    let binary = get(&release.asset_url)?;
+    verify(&release, &expected, &binary)?;
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    install(&exe, &binary).map_err(|e| format!("failed to replace {}: {}", exe.display(), e))?;
+    Ok(Some(release.tag))
+}
+
+/// The hex SHA-256 the release publishes for its binary, from the asset's
+/// digest or else from its `.sha256` file, fetched with `fetch`
+fn expected_digest(
+    release: &Release,
+    fetch: impl FnOnce(&str) -> Result<Vec<u8>, String>,
+) -> Result<String, String> {
+    match (&release.digest, &release.checksum_url) {
+        (Some(digest), _) => Ok(digest.clone()),
+        (None, Some(url)) => Ok(String::from_utf8_lossy(&fetch(url)?)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()),
+        (None, None) => Err(format!(
+            "release {} publishes no SHA-256 for its binary, not installing it",
+            release.tag
+        )),
+    }
+}
+
+/// Refuses a downloaded binary that does not hash to `expected`
+fn verify(release: &Release, expected: &str, binary: &[u8]) -> Result<(), String> {
+    let actual = sha256_hex(binary)?;
+    if actual != expected {
+        return Err(format!(
+            "checksum mismatch for {} (expected {}, got {}), not installing it",
+            release.tag, expected, actual
+        ));
+    }
+    Ok(())
+}
+
+fn install(exe: &Path, binary: &[u8]) -> std::io::Result<()> {
+    let new = sibling(exe, "new");
+    let old = sibling(exe, "old");
+    fs::write(&new, binary)?;
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old)?;
+    if let Err(e) = fs::rename(&new, exe) {
+        let _ = fs::rename(&old, exe);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Removes the binary a previous self-update moved aside
+pub fn clean_up() {
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = fs::remove_file(sibling(&exe, "old"));
+    }
+}
+
+/// `smartlockcursor.exe` -> `smartlockcursor.exe.<suffix>`
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// `v1.2.3` or `1.2.3-beta` -> `(1, 2, 3)`
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    let text = text.trim().trim_start_matches('v');
+    let core = text.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn sha256_hex(data: &[u8]) -> Result<String, String> {
+    let hash = sha256(data)?;
+    Ok(hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// SHA-256 digest of `data`, from CNG
+pub(crate) fn sha256(data: &[u8]) -> Result<[u8; 32], String> {
+    let mut hash = [0u8; 32];
+    let status = unsafe { BCryptHash(BCRYPT_SHA256_ALG_HANDLE, None, data, &mut hash) };
+    if status.is_err() {
+        return Err(format!("hashing failed ({:#x})", status.0));
+    }
+    Ok(hash)
+}
+
+/// Closes a WinHTTP handle when dropped
+struct Handle(*mut core::ffi::c_void);
+
+impl Handle {
+    fn new(raw: *mut core::ffi::c_void, what: &str) -> Result<Self, String> {
+        if raw.is_null() {
+            return Err(format!(
+                "{} failed: {}",
+                what,
+                windows::core::Error::from_win32().message()
+            ));
+        }
+        Ok(Self(raw))
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = WinHttpCloseHandle(self.0);
+        }
+    }
+}
+
+/// GETs an `https://` URL, following redirects, and returns the body of the response
+fn get(url: &str) -> Result<Vec<u8>, String> {
+    request("GET", url, "Accept: application/vnd.github+json\r\n", &[])
+}
+
+/// POSTs `body` to an `https://` URL and returns the body of the 2xx response
+#[cfg(feature = "crash-reports")]
+pub(crate) fn post(url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, String> {
+    request(
+        "POST",
+        url,
+        &format!("Content-Type: {}\r\n", content_type),
+        body,
+    )
+}
+
+fn request(method: &str, url: &str, headers: &str, body: &[u8]) -> Result<Vec<u8>, String> {
+    let rest = url
+        .strip_prefix("https://")
+        .ok_or_else(|| format!("not an https:// URL: {}", url))?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.is_empty() { "/" } else { path };
+
+    unsafe {
+        let agent = HSTRING::from(format!("SmartLockCursor/{}", env!("CARGO_PKG_VERSION")));
+        let session = Handle::new(
+            WinHttpOpen(
+                &agent,
+                WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                0,
+            ),
+            "WinHttpOpen",
+        )?;
+        let connection = Handle::new(
+            WinHttpConnect(
+                session.0,
+                &HSTRING::from(host),
+                INTERNET_DEFAULT_HTTPS_PORT,
+                0,
+            ),
+            "connecting",
+        )?;
+        let request = Handle::new(
+            WinHttpOpenRequest(
+                connection.0,
+                &HSTRING::from(method),
+                &HSTRING::from(path),
+                PCWSTR::null(),
+                PCWSTR::null(),
+                std::ptr::null(),
+                WINHTTP_FLAG_SECURE,
+            ),
+            "opening the request",
+        )?;
+
+        let headers: Vec<u16> = headers.encode_utf16().collect();
+        let length = body.len() as u32;
+        let optional = (!body.is_empty()).then_some(body.as_ptr() as *const _);
+        WinHttpSendRequest(request.0, Some(&headers), optional, length, length, 0)
+            .and_then(|()| WinHttpReceiveResponse(request.0, std::ptr::null_mut()))
+            .map_err(|e| format!("request to {} failed: {}", host, e.message()))?;
+
+        let mut status = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        WinHttpQueryHeaders(
+            request.0,
+            WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+            PCWSTR::null(),
+            Some(&mut status as *mut u32 as *mut _),
+            &mut size,
+            std::ptr::null_mut(),
+        )
+        .map_err(|e| e.message())?;
+        if !(200..300).contains(&status) {
+            return Err(format!("{} answered with status {}", host, status));
+        }
+
+        let mut body = Vec::new();
+        let mut chunk = vec![0u8; 64 * 1024];
+        loop {
+            let mut read = 0u32;
+            WinHttpReadData(
+                request.0,
+                chunk.as_mut_ptr() as *mut _,
+                chunk.len() as u32,
+                &mut read,
+            )
+            .map_err(|e| format!("download from {} failed: {}", host, e.message()))?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..read as usize]);
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.4"), Some((0, 4, 0)));
+        assert_eq!(parse_version("2.0.0-beta.1"), Some((2, 0, 0)));
+        assert_eq!(parse_version("nightly"), None);
+        assert!(parse_version("v0.10.0") > parse_version("v0.9.9"));
+    }
+
+    #[test]
+    fn picks_the_binary_and_its_digest() {
+        let release = json::parse(
+            r#"{
+                "tag_name": "v9.0.0",
+                "name": "Better \"detection\" é",
+                "draft": false,
+                "assets": [
+                    {"name": "smartlockcursor-src.zip", "browser_download_url": "https://x/src.zip"},
+                    {"name": "SmartLockCursor.exe", "digest": "sha256:ABCDEF",
+                     "size": 1234, "browser_download_url": "https://x/SmartLockCursor.exe"},
+                    {"name": "smartlockcursor.exe.sha256", "browser_download_url": "https://x/sum"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let release = parse_release(&release).unwrap();
+        assert_eq!(release.tag, "v9.0.0");
+        assert_eq!(release.asset_url, "https://x/SmartLockCursor.exe");
+        assert_eq!(release.digest.as_deref(), Some("abcdef"));
+        assert_eq!(release.checksum_url.as_deref(), Some("https://x/sum"));
+        assert!(release.is_newer());
+    }
+
+    fn release(digest: Option<&str>, checksum_url: Option<&str>) -> Release {
+        Release {
+            tag: "v9.0.0".to_string(),
+            asset_url: "https://x/smartlockcursor.exe".to_string(),
+            digest: digest.map(str::to_string),
+            checksum_url: checksum_url.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn a_release_without_a_binary_or_tag_is_refused() {
+        let no_exe = json::parse(
+            r#"{"tag_name": "v9.0.0", "assets": [
+                {"name": "source.zip", "browser_download_url": "https://x/src.zip"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_release(&no_exe),
+            Err("release v9.0.0 has no .exe asset".to_string())
+        );
+
+        let no_tag = json::parse(r#"{"assets": []}"#).unwrap();
+        assert!(parse_release(&no_tag).is_err());
+
+        let no_url = json::parse(r#"{"tag_name": "v9", "assets": [{"name": "a.exe"}]}"#).unwrap();
+        assert!(parse_release(&no_url).is_err());
+    }
+
+    #[test]
+    fn a_release_without_a_digest_falls_back_to_its_checksum_file() {
+        let release = json::parse(
+            r#"{"tag_name": "v9.0.0", "assets": [
+                {"name": "smartlockcursor.exe", "browser_download_url": "https://x/a.exe"},
+                {"name": "smartlockcursor.exe.sha256", "browser_download_url": "https://x/sum"}
+            ]}"#,
+        )
+        .unwrap();
+        let release = parse_release(&release).unwrap();
+        assert_eq!(release.digest, None);
+
+        let expected = expected_digest(&release, |url| {
+            assert_eq!(url, "https://x/sum");
+            Ok(b"ABCDEF  smartlockcursor.exe\n".to_vec())
+        });
+        assert_eq!(expected, Ok("abcdef".to_string()));
+    }
+
+    #[test]
+    fn the_published_digest_wins_over_the_checksum_file() {
+        let release = release(Some("abcdef"), Some("https://x/sum"));
+        let expected = expected_digest(&release, |_| panic!("fetched the checksum file"));
+        assert_eq!(expected, Ok("abcdef".to_string()));
+    }
+
+    #[test]
+    fn a_release_without_any_hash_is_not_installed() {
+        let expected = expected_digest(&release(None, None), |_| unreachable!());
+        assert!(expected.unwrap_err().contains("publishes no SHA-256"));
+    }
+
+    #[test]
+    fn a_binary_that_does_not_match_its_hash_is_refused() {
+        const ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let release = release(Some(ABC), None);
+        assert_eq!(verify(&release, ABC, b"abc"), Ok(()));
+
+        let error = verify(&release, ABC, b"abd").unwrap_err();
+        assert!(
+            error.starts_with("checksum mismatch for v9.0.0"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("not installing it"), "{}", error);
+        assert!(verify(&release, "", b"abc").is_err());
+    }
+}
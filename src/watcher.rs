@@ -0,0 +1,80 @@
+//! Config file change notifications
+//!
+//! Watches the directory containing the config file and reports when the file
+//! itself was modified, so the main loop can reload it without a restart.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use windows::core::HSTRING;
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Storage::FileSystem::{
+    FindCloseChangeNotification, FindFirstChangeNotificationW, FindNextChangeNotification,
+    FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE,
+};
+
+/// Watches a single file through a directory change notification handle
+pub struct ConfigWatcher {
+    handle: HANDLE,
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`, creating its directory if needed
+    pub fn new(path: &Path) -> Option<Self> {
+        let dir = path.parent()?;
+        fs::create_dir_all(dir).ok()?;
+
+        let handle = unsafe {
+            FindFirstChangeNotificationW(
+                &HSTRING::from(dir),
+                false,
+                FILE_NOTIFY_CHANGE_FILE_NAME
+                    | FILE_NOTIFY_CHANGE_LAST_WRITE
+                    | FILE_NOTIFY_CHANGE_SIZE,
+            )
+            .ok()?
+        };
+
+        Some(Self {
+            handle,
+            path: path.to_path_buf(),
+            last_modified: modified_time(path),
+        })
+    }
+
+    /// Handle that becomes signaled when something in the directory changes
+    pub fn handle(&self) -> HANDLE {
+        self.handle
+    }
+
+    /// Re-arms the notification after it fired and reports whether the
+    /// watched file was created, modified or removed
+    pub fn file_changed(&mut self) -> bool {
+        unsafe {
+            let _ = FindNextChangeNotification(self.handle);
+        }
+
+        let modified = modified_time(&self.path);
+        if modified == self.last_modified {
+            return false;
+        }
+
+        self.last_modified = modified;
+        true
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = FindCloseChangeNotification(self.handle);
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,191 @@
+//! HTTP callback notifications
+//!
+//! POSTs every event as the same JSON object the event log writes, for home
+//! and stream automation (Home Assistant, Node-RED, ...). Requests go out from
+//! a background thread so a slow or unreachable endpoint never holds up the
+//! lock engine. Only plain `http://` URLs are supported, which is what local
+//! automation servers usually listen on.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::WebhookConfig;
+use crate::{tr, warn};
+
+/// Events waiting to be sent before new ones are dropped
+const QUEUE_LEN: usize = 64;
+
+/// Where requests go
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Self, String> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            return Err(format!("only http:// URLs are supported: {}", url));
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("invalid port in {}", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in {}", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// A running webhook sender
+#[derive(Debug)]
+pub struct Webhook {
+    events: Vec<String>,
+    queue: SyncSender<String>,
+}
+
+impl Webhook {
+    /// Starts the sender thread for `[webhook]`, or returns `Ok(None)` if no
+    /// URL is configured
+    pub fn start(config: &WebhookConfig) -> Result<Option<Self>, String> {
+        let Some(ref url) = config.url else {
+            return Ok(None);
+        };
+        let endpoint = Endpoint::parse(url)?;
+        let (queue, pending) = mpsc::sync_channel(QUEUE_LEN);
+        let settings = config.clone();
+        thread::Builder::new()
+            .name("webhook".to_string())
+            .spawn(move || deliver(endpoint, settings, pending))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Some(Self {
+            events: config.events.clone(),
+            queue,
+        }))
+    }
+
+    /// Whether events of this kind are sent
+    pub fn wants(&self, kind: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == kind)
+    }
+
+    /// Queues a JSON body, dropping it if the endpoint has fallen far behind
+    pub fn send(&self, body: String) {
+        if let Err(TrySendError::Full(_)) = self.queue.try_send(body) {
+            warn!("{}", tr!("webhook.behind"));
+        }
+    }
+}
+
+/// Sends queued bodies one by one until the [`Webhook`] is dropped
+fn deliver(endpoint: Endpoint, config: WebhookConfig, pending: Receiver<String>) {
+    let timeout = Duration::from_millis(config.timeout_ms.max(1));
+    for body in pending {
+        let mut attempt = 0;
+        loop {
+            match post(&endpoint, &body, timeout) {
+                Ok(()) => break,
+                Err(e) if attempt >= config.retries => {
+                    warn!(
+                        "{}",
+                        tr!(
+                            "webhook.failed",
+                            endpoint.host,
+                            endpoint.port,
+                            endpoint.path,
+                            e
+                        )
+                    );
+                    break;
+                }
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(Duration::from_millis(config.retry_delay_ms));
+                }
+            }
+        }
+    }
+}
+
+/// POSTs one JSON body and checks for a 2xx status
+fn post(endpoint: &Endpoint, body: &str, timeout: Duration) -> io::Result<()> {
+    let addr = (endpoint.host.as_str(), endpoint.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host did not resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\n\
+         Host: {}:{}\r\n\
+         User-Agent: SmartLockCursor/{}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        endpoint.port,
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    )?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "endpoint answered {}",
+            status_line.trim()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_http_urls() {
+        assert_eq!(
+            Endpoint::parse("http://homeassistant.local:8123/api/webhook/game").unwrap(),
+            Endpoint {
+                host: "homeassistant.local".to_string(),
+                port: 8123,
+                path: "/api/webhook/game".to_string(),
+            }
+        );
+        assert_eq!(Endpoint::parse("http://10.0.0.5").unwrap().path, "/");
+        assert_eq!(Endpoint::parse("http://10.0.0.5").unwrap().port, 80);
+    }
+
+    #[test]
+    fn rejects_unsupported_urls() {
+        assert!(Endpoint::parse("https://example.com/hook").is_err());
+        assert!(Endpoint::parse("http://:8080/hook").is_err());
+        assert!(Endpoint::parse("http://host:port/hook").is_err());
+    }
+}
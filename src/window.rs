@@ -0,0 +1,597 @@
+//! Window queries and fullscreen detection
+
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+use std::mem::zeroed;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::POINT;
+use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT, TRUE};
+use windows::Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED};
+use windows::Win32::Graphics::Gdi::{ClientToScreen, MonitorFromWindow, MONITOR_DEFAULTTONEAREST};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_MENU};
+use windows::Win32::UI::Shell::{IVirtualDesktopManager, VirtualDesktopManager};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, FindWindowExW, GetClassNameW, GetClientRect, GetWindowLongW, GetWindowRect,
+    GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, GWL_EXSTYLE,
+    WS_EX_TRANSPARENT,
+};
+
+use crate::events::Switcher;
+use crate::monitor::{
+    cached_monitor_rect, get_monitor_work_area, reaches_other_monitor, spanned_rect, with_monitors,
+    FRAME_OVERHANG,
+};
+use crate::process::get_process_name;
+
+const UWP_FRAME_CLASS: &str = "ApplicationFrameWindow";
+const UWP_CORE_CLASS: &str = "Windows.UI.Core.CoreWindow";
+
+/// The individual fullscreen conditions evaluated for a window
+#[derive(Debug, Clone, Copy)]
+pub struct FullscreenCheck {
+    pub window_rect: RECT,
+    /// Bounds of the monitor the window is primarily on
+    pub monitor_rect: RECT,
+    pub tolerance: i32,
+    /// Window size matches the monitor size
+    pub width_match: bool,
+    pub height_match: bool,
+    /// Window position matches the monitor position
+    pub left_match: bool,
+    pub top_match: bool,
+    /// Window covers or exceeds every monitor edge, without reaching far into
+    /// another monitor
+    pub covers_monitor: bool,
+    /// Union of the monitors the window exactly spans, if it spans several
+    pub spanned_rect: Option<RECT>,
+}
+
+impl FullscreenCheck {
+    /// Whether the window counts as fullscreen
+    pub fn is_fullscreen(&self) -> bool {
+        let exact = self.width_match && self.height_match && self.left_match && self.top_match;
+        exact || self.covers_monitor || self.spanned_rect.is_some()
+    }
+
+    /// What to clip to when fullscreen: all spanned monitors, or just the one
+    pub fn lock_rect(&self) -> RECT {
+        self.spanned_rect.unwrap_or(self.monitor_rect)
+    }
+}
+
+/// Evaluates every fullscreen condition for a window
+///
+/// Returns `None` if the window or its monitor geometry can't be queried.
+pub fn analyze_fullscreen(hwnd: HWND, tolerance: i32) -> Option<FullscreenCheck> {
+    if hwnd.0.is_null() {
+        return None;
+    }
+    let hwnd = resolve_hosted_window(hwnd);
+
+    unsafe {
+        // Get window rect
+        let mut window_rect: RECT = zeroed();
+        if GetWindowRect(hwnd, &mut window_rect).is_err() {
+            return None;
+        }
+
+        // Get the monitor this window is primarily on
+        let hmonitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        let monitor_rect = cached_monitor_rect(hmonitor)?;
+
+        // Calculate dimensions
+        let window_width = window_rect.right - window_rect.left;
+        let window_height = window_rect.bottom - window_rect.top;
+        let monitor_width = monitor_rect.right - monitor_rect.left;
+        let monitor_height = monitor_rect.bottom - monitor_rect.top;
+
+        // Only windows reaching past their monitor can span others, which
+        // spares enumerating the monitors on every check
+        let overhangs = window_rect.left < monitor_rect.left - tolerance
+            || window_rect.top < monitor_rect.top - tolerance
+            || window_rect.right > monitor_rect.right + tolerance
+            || window_rect.bottom > monitor_rect.bottom + tolerance;
+        let (spanned_rect, spills) = if overhangs {
+            with_monitors(|monitors| {
+                (
+                    spanned_rect(monitors, &window_rect, tolerance),
+                    // A window covering a narrow portrait monitor is often just
+                    // wider than it, and the rest lands on the neighbor
+                    reaches_other_monitor(
+                        monitors,
+                        &monitor_rect,
+                        &window_rect,
+                        tolerance.max(FRAME_OVERHANG),
+                    ),
+                )
+            })
+        } else {
+            (None, false)
+        };
+
+        Some(FullscreenCheck {
+            window_rect,
+            monitor_rect,
+            tolerance,
+            // Check if window size matches monitor size (with tolerance)
+            width_match: (window_width - monitor_width).abs() <= tolerance,
+            height_match: (window_height - monitor_height).abs() <= tolerance,
+            // Check if window position matches monitor position (with tolerance)
+            left_match: (window_rect.left - monitor_rect.left).abs() <= tolerance,
+            top_match: (window_rect.top - monitor_rect.top).abs() <= tolerance,
+            // Alternative: window completely covers or exceeds monitor bounds
+            covers_monitor: window_rect.left <= monitor_rect.left + tolerance
+                && window_rect.top <= monitor_rect.top + tolerance
+                && window_rect.right >= monitor_rect.right - tolerance
+                && window_rect.bottom >= monitor_rect.bottom - tolerance
+                && window_width >= monitor_width - tolerance
+                && window_height >= monitor_height - tolerance
+                && !spills,
+            spanned_rect,
+        })
+    }
+}
+
+/// Checks if a window is in fullscreen mode and returns the monitor rect if so,
+/// or the union of all monitors it spans
+///
+/// `tolerance` is the number of pixels the window may be off from the monitor bounds.
+pub fn check_fullscreen(hwnd: HWND, tolerance: i32) -> Option<RECT> {
+    analyze_fullscreen(hwnd, tolerance)
+        .filter(FullscreenCheck::is_fullscreen)
+        .map(|check| check.lock_rect())
+}
+
+/// Checks if a window fills the work area of its monitor (or more) and returns
+/// the work area if so
+pub fn check_work_area(hwnd: HWND, tolerance: i32) -> Option<RECT> {
+    if hwnd.0.is_null() {
+        return None;
+    }
+    let hwnd = resolve_hosted_window(hwnd);
+
+    unsafe {
+        let mut window_rect: RECT = zeroed();
+        GetWindowRect(hwnd, &mut window_rect).ok()?;
+        let work = get_monitor_work_area(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST))?;
+
+        let covers = window_rect.left <= work.left + tolerance
+            && window_rect.top <= work.top + tolerance
+            && window_rect.right >= work.right - tolerance
+            && window_rect.bottom >= work.bottom - tolerance;
+        covers.then_some(work)
+    }
+}
+
+/// Gets the outer rect of a window, of the frame for a UWP app
+pub fn get_window_rect(hwnd: HWND) -> Option<RECT> {
+    let hwnd = resolve_hosted_window(hwnd);
+    unsafe {
+        let mut rect: RECT = zeroed();
+        GetWindowRect(hwnd, &mut rect).ok()?;
+        Some(rect)
+    }
+}
+
+/// Gets the client area of a window in screen coordinates, `None` if it is empty
+pub fn get_client_screen_rect(hwnd: HWND) -> Option<RECT> {
+    let hwnd = resolve_hosted_window(hwnd);
+    unsafe {
+        let mut rect: RECT = zeroed();
+        GetClientRect(hwnd, &mut rect).ok()?;
+        if rect.right <= rect.left || rect.bottom <= rect.top {
+            return None;
+        }
+
+        let mut origin = POINT::default();
+        if !ClientToScreen(hwnd, &mut origin).as_bool() {
+            return None;
+        }
+
+        Some(RECT {
+            left: origin.x,
+            top: origin.y,
+            right: origin.x + rect.right,
+            bottom: origin.y + rect.bottom,
+        })
+    }
+}
+
+/// Collects the top-level windows in Z order, optionally skipping invisible ones
+pub fn enumerate_windows(include_hidden: bool) -> Vec<HWND> {
+    unsafe extern "system" fn enum_window_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let windows = &mut *(lparam.0 as *mut Vec<HWND>);
+        windows.push(hwnd);
+        TRUE
+    }
+
+    let mut windows: Vec<HWND> = Vec::new();
+    unsafe {
+        let _ = EnumWindows(
+            Some(enum_window_proc),
+            LPARAM(&mut windows as *mut _ as isize),
+        );
+    }
+
+    if !include_hidden {
+        windows.retain(|&hwnd| unsafe { IsWindowVisible(hwnd).as_bool() });
+    }
+    windows
+}
+
+/// What stays the same about a window for as long as it exists
+#[derive(Debug, Default)]
+pub struct WindowFacts {
+    /// Empty if it can't be read
+    pub class: String,
+    /// Process owning the window, the hosted app for UWP frames (0 if unknown)
+    pub pid: u32,
+    /// Executable name of that process, looked up on first use
+    process: OnceCell<Option<String>>,
+}
+
+impl WindowFacts {
+    fn query(hwnd: HWND) -> Self {
+        let class = with_class_name(hwnd, str::to_string);
+        Self {
+            pid: owning_process(hwnd, class == UWP_FRAME_CLASS),
+            class,
+            process: OnceCell::new(),
+        }
+    }
+
+    /// Whether `hwnd` is still the window these facts were read from, rather
+    /// than a later one that got its handle
+    fn describes(&self, hwnd: HWND) -> bool {
+        is_window(hwnd)
+            && owning_process(hwnd, false) == self.pid
+            && with_class_name(hwnd, |class| class == self.class)
+    }
+
+    /// Executable name of the owning process
+    pub fn process(&self) -> Option<&str> {
+        self.process
+            .get_or_init(|| get_process_name(self.pid))
+            .as_deref()
+    }
+}
+
+/// Process owning a window, the hosted app for a UWP frame
+fn owning_process(hwnd: HWND, uwp_frame: bool) -> u32 {
+    let owner = if uwp_frame {
+        hosted_core_window(hwnd)
+    } else {
+        hwnd
+    };
+    let mut pid = 0u32;
+    unsafe {
+        GetWindowThreadProcessId(owner, Some(&mut pid));
+    }
+    pid
+}
+
+/// More windows than anyone has open; past it, the handles of long-gone
+/// windows are assumed and the cache starts over
+const MAX_CACHED_WINDOWS: usize = 512;
+
+thread_local! {
+    /// Facts per window handle
+    static WINDOW_FACTS: RefCell<HashMap<isize, WindowFacts>> = RefCell::new(HashMap::new());
+}
+
+/// Runs `f` on the facts about a window, looked up once per window
+///
+/// Windows hands the handle of a destroyed window to new windows, so cached
+/// facts are only used while the handle still names a window of the same
+/// class and process; the class and owner are cheap to ask for, the
+/// executable name behind the owner is not. `f` must not look up other
+/// windows' facts.
+pub fn with_window_facts<R>(hwnd: HWND, f: impl FnOnce(&WindowFacts) -> R) -> R {
+    let key = hwnd.0 as isize;
+    WINDOW_FACTS.with(|cache| {
+        if let Some(facts) = cache.borrow().get(&key) {
+            if facts.describes(hwnd) {
+                return f(facts);
+            }
+        }
+
+        let facts = WindowFacts::query(hwnd);
+        let result = f(&facts);
+        let mut cache = cache.borrow_mut();
+        // The app hosted by a UWP frame can come and go
+        if facts.class != UWP_FRAME_CLASS && !facts.class.is_empty() {
+            if cache.len() >= MAX_CACHED_WINDOWS {
+                cache.clear();
+            }
+            cache.insert(key, facts);
+        } else {
+            cache.remove(&key);
+        }
+        result
+    })
+}
+
+/// The process owning a window as Windows sees it now, bypassing the cache;
+/// cached facts that disagree belong to an earlier window under the same
+/// handle and are dropped
+pub fn current_window_process_id(hwnd: HWND) -> u32 {
+    let pid = owning_process(
+        hwnd,
+        with_class_name(hwnd, |class| class == UWP_FRAME_CLASS),
+    );
+    WINDOW_FACTS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let key = hwnd.0 as isize;
+        if cache.get(&key).is_some_and(|facts| facts.pid != pid) {
+            cache.remove(&key);
+        }
+    });
+    pid
+}
+
+/// Gets the class name of a window
+pub fn get_class_name(hwnd: HWND) -> Option<String> {
+    if hwnd.0.is_null() {
+        return None;
+    }
+    with_window_facts(hwnd, |facts| {
+        (!facts.class.is_empty()).then(|| facts.class.clone())
+    })
+}
+
+/// Runs `f` on the class name of a window (empty if it can't be read),
+/// converted on the stack without allocating
+pub fn with_class_name<R>(hwnd: HWND, f: impl FnOnce(&str) -> R) -> R {
+    let mut class_name = [0u16; 256];
+    let len = if hwnd.0.is_null() {
+        0
+    } else {
+        unsafe { GetClassNameW(hwnd, &mut class_name) }.max(0) as usize
+    };
+
+    // A UTF-16 unit never takes more than three bytes in UTF-8
+    let mut utf8 = [0u8; 3 * 256];
+    let mut end = 0;
+    for c in char::decode_utf16(class_name[..len].iter().copied()) {
+        end += c
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+            .encode_utf8(&mut utf8[end..])
+            .len();
+    }
+    f(std::str::from_utf8(&utf8[..end]).unwrap_or_default())
+}
+
+/// Gets the title of a window (empty if it has none)
+pub fn get_window_title(hwnd: HWND) -> String {
+    unsafe {
+        let mut title = [0u16; 512];
+        let len = GetWindowTextW(hwnd, &mut title);
+        String::from_utf16_lossy(&title[..len.max(0) as usize])
+    }
+}
+
+/// Whether `hwnd` still names a window; Windows hands the value of a destroyed
+/// one to new windows before long
+pub fn is_window(hwnd: HWND) -> bool {
+    unsafe { IsWindow(hwnd).as_bool() }
+}
+
+/// Whether a window is shown, not hidden with `ShowWindow(SW_HIDE)`
+pub fn is_window_visible(hwnd: HWND) -> bool {
+    unsafe { IsWindowVisible(hwnd).as_bool() }
+}
+
+/// Whether clicks go through a window to the ones below, like the overlays
+/// that draw FPS counters and chat over games
+pub fn is_window_click_through(hwnd: HWND) -> bool {
+    let style = unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } as u32;
+    style & WS_EX_TRANSPARENT.0 != 0
+}
+
+/// Whether a window is minimized
+pub fn is_window_minimized(hwnd: HWND) -> bool {
+    unsafe { IsIconic(hwnd).as_bool() }
+}
+
+/// Whether DWM cloaks a window: it is on another virtual desktop, or a
+/// suspended UWP app or its own app hid it without minimizing
+pub fn is_window_cloaked(hwnd: HWND) -> bool {
+    let mut cloaked = 0u32;
+    let result = unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as *mut _,
+            std::mem::size_of::<u32>() as u32,
+        )
+    };
+    result.is_ok() && cloaked != 0
+}
+
+/// The virtual desktops of Windows 10 and later, asked through
+/// `IVirtualDesktopManager`
+///
+/// The COM object is created on first use, on the thread asking. Without it
+/// (older Windows, or COM failing) every window counts as on the current
+/// desktop.
+#[derive(Debug, Default)]
+pub struct VirtualDesktops {
+    manager: OnceCell<Option<IVirtualDesktopManager>>,
+}
+
+impl VirtualDesktops {
+    /// Whether `hwnd` is on the virtual desktop being shown (true when unknown)
+    pub fn is_on_current(&self, hwnd: HWND) -> bool {
+        let Some(manager) = self.manager.get_or_init(create_desktop_manager) else {
+            return true;
+        };
+        unsafe { manager.IsWindowOnCurrentVirtualDesktop(hwnd) }.map_or(true, |on| on.as_bool())
+    }
+}
+
+fn create_desktop_manager() -> Option<IVirtualDesktopManager> {
+    unsafe {
+        // Fails harmlessly if this thread already joined COM in another mode
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        CoCreateInstance(&VirtualDesktopManager, None, CLSCTX_ALL).ok()
+    }
+}
+
+/// Gets the ID of the process that owns a window (0 if unknown)
+///
+/// For UWP frames this is the hosted app, not `ApplicationFrameHost.exe`.
+pub fn get_window_process_id(hwnd: HWND) -> u32 {
+    if hwnd.0.is_null() {
+        return 0;
+    }
+
+    with_window_facts(hwnd, |facts| facts.pid)
+}
+
+/// Resolves a UWP `ApplicationFrameWindow` to the `Windows.UI.Core.CoreWindow`
+/// it hosts, returning any other window unchanged
+///
+/// The frame belongs to `ApplicationFrameHost.exe` and its rect includes the
+/// title bar and invisible borders, so the core window is what tells whether
+/// the app is fullscreen. A suspended app's core window may be detached from
+/// the frame, in which case the frame is all there is.
+pub fn resolve_hosted_window(hwnd: HWND) -> HWND {
+    if hwnd.0.is_null() || !with_window_facts(hwnd, |facts| facts.class == UWP_FRAME_CLASS) {
+        return hwnd;
+    }
+    hosted_core_window(hwnd)
+}
+
+/// The core window a UWP frame hosts, or the frame itself without one
+fn hosted_core_window(hwnd: HWND) -> HWND {
+    unsafe {
+        FindWindowExW(
+            hwnd,
+            HWND::default(),
+            &HSTRING::from(UWP_CORE_CLASS),
+            PCWSTR::null(),
+        )
+    }
+    .ok()
+    .filter(|core| !core.0.is_null())
+    .unwrap_or(hwnd)
+}
+
+/// Checks if a window class belongs to the desktop or the taskbar
+pub fn is_shell_class(class: &str) -> bool {
+    matches!(
+        class,
+        "Progman" | "WorkerW" | "Shell_TrayWnd" | "Shell_SecondaryTrayWnd"
+    )
+}
+
+/// Which task switcher the window is, if it is the Alt+Tab switcher or Task View
+pub fn get_task_switcher(hwnd: HWND) -> Option<Switcher> {
+    // Windows switcher class names
+    // "MultitaskingViewFrame" - Windows 10/11 Alt+Tab
+    // "TaskSwitcherWnd" - Older Windows Alt+Tab
+    // "XamlExplorerHostIslandWindow" - Windows 11 Alt+Tab and Task View
+    // "Windows.UI.Core.CoreWindow" - Windows 10 Task View when Explorer owns it;
+    //   UWP apps, Start and Search use the same class from their own processes
+    let (core_window, xaml_host, alt_tab) = with_window_facts(hwnd, |facts| {
+        let class = facts.class.as_str();
+        (
+            class == UWP_CORE_CLASS
+                && facts
+                    .process()
+                    .is_some_and(|owner| owner.eq_ignore_ascii_case("explorer.exe")),
+            class.contains("XamlExplorerHostIslandWindow"),
+            class.contains("MultitaskingView")
+                || class.contains("TaskSwitcher")
+                || class == "ForegroundStaging",
+        )
+    });
+    if core_window {
+        return Some(Switcher::TaskView);
+    }
+    if xaml_host {
+        // Both switchers share the host on Windows 11; only Alt+Tab opens with Alt held
+        let alt_down = unsafe { GetAsyncKeyState(VK_MENU.0 as i32) } as u16 & 0x8000 != 0;
+        return Some(if alt_down {
+            Switcher::AltTab
+        } else {
+            Switcher::TaskView
+        });
+    }
+    alt_tab.then_some(Switcher::AltTab)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    /// A check of a window that matches or covers nothing
+    fn check() -> FullscreenCheck {
+        FullscreenCheck {
+            window_rect: RECT {
+                left: 100,
+                top: 100,
+                right: 900,
+                bottom: 700,
+            },
+            monitor_rect: MONITOR,
+            tolerance: 5,
+            width_match: false,
+            height_match: false,
+            left_match: false,
+            top_match: false,
+            covers_monitor: false,
+            spanned_rect: None,
+        }
+    }
+
+    #[test]
+    fn fullscreen_needs_every_match_or_a_cover() {
+        assert!(!check().is_fullscreen());
+        let exact = FullscreenCheck {
+            width_match: true,
+            height_match: true,
+            left_match: true,
+            top_match: true,
+            ..check()
+        };
+        assert!(exact.is_fullscreen());
+        // The right size in the wrong place is a window, not fullscreen
+        assert!(!FullscreenCheck {
+            left_match: false,
+            ..exact
+        }
+        .is_fullscreen());
+        assert!(FullscreenCheck {
+            covers_monitor: true,
+            ..check()
+        }
+        .is_fullscreen());
+        assert_eq!(exact.lock_rect(), MONITOR);
+    }
+
+    #[test]
+    fn a_spanning_window_locks_to_all_its_monitors() {
+        let both = RECT {
+            right: 3840,
+            ..MONITOR
+        };
+        let spanning = FullscreenCheck {
+            spanned_rect: Some(both),
+            ..check()
+        };
+        assert!(spanning.is_fullscreen());
+        assert_eq!(spanning.lock_rect(), both);
+    }
+}